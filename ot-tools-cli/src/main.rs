@@ -6,16 +6,20 @@ mod bin_files;
 #[doc(hidden)]
 mod operations;
 #[doc(hidden)]
+mod project;
+#[doc(hidden)]
 mod sample_files;
 
-use clap::{command, Command, CommandFactory, Parser, Subcommand};
+use clap::{command, Command, CommandFactory, Parser, Subcommand, ValueHint};
 use clap_complete::{generate, Generator, Shell};
 use env_logger::{Builder, Target};
 use log::LevelFilter;
+use ot_tools_ops::transaction::{apply_plan, undo_last_transaction};
 use std::error::Error;
 use std::fmt::Display;
 use std::io;
 use std::io::Write;
+use std::path::PathBuf;
 
 #[doc(hidden)]
 pub type RBoxErr<T> = Result<T, Box<dyn Error>>;
@@ -53,12 +57,30 @@ enum Commands {
     #[command(subcommand, visible_aliases = &["ops"])]
     Operations(operations::SubCmds),
 
+    #[command(subcommand, visible_aliases = &["proj"])]
+    Project(project::SubCmds),
+
     #[command(subcommand, visible_aliases = &["samples", "s"])]
     SampleFiles(sample_files::SubCmds),
 
     #[command(subcommand, visible_aliases = &["shell", "sh"])]
     ShellCompletion(SubCmds),
 
+    /// Undo the last operation which modified files within a set, restoring files from the
+    /// backups kept in the set's `.ot-tools-backups` directory
+    Undo {
+        /// Directory path of the set
+        #[arg(value_hint = ValueHint::DirPath)]
+        set_dirpath: PathBuf,
+    },
+    /// Apply the changes from a plan previously saved with `--save-plan`. Fails if any of the
+    /// files the plan changes have been modified since the plan was saved.
+    ApplyPlan {
+        /// File path of the saved JSON plan
+        #[arg(value_hint = ValueHint::FilePath)]
+        plan_file_path: PathBuf,
+    },
+
     /// Prints a list of all available commands and a description of what they do
     HelpFull,
 }
@@ -177,8 +199,11 @@ fn main() {
     match Cli::parse().command {
         Commands::BinFiles(x) => bin_files::subcmd_runner(x),
         Commands::Operations(x) => operations::subcmd_runner(x),
+        Commands::Project(x) => project::subcmd_runner(x),
         Commands::SampleFiles(x) => sample_files::subcmd_runner(x),
         Commands::ShellCompletion(x) => cmd_shell_completions(x),
+        Commands::Undo { set_dirpath } => print_err(|| undo_last_transaction(&set_dirpath)),
+        Commands::ApplyPlan { plan_file_path } => print_err(|| apply_plan(&plan_file_path)),
         Commands::HelpFull => cmd_help_full(),
     };
 }
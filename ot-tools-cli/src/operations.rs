@@ -1,7 +1,11 @@
-use clap::Subcommand;
+use crate::RBoxErr;
+use clap::{Args, Subcommand, ValueHint};
+use ot_tools_ops::transaction::{finish_transaction, Transaction};
+use std::path::PathBuf;
 
 mod copy_banks;
 mod list_slots;
+mod merge;
 mod sample_ops;
 mod slot_ops;
 
@@ -12,14 +16,12 @@ pub(crate) enum SubCmds {
     Copy(copy_banks::SubCmds),
     #[command(subcommand, visible_aliases = &["list", "ls", "ll"])]
     ListSlots(list_slots::SubCmds),
-    // ========================================
-    // TODO: Needs testing
-    // #[command(subcommand, visible_aliases = &["slots"])]
-    // SlotOps(slot_ops::SubCmds),
-    // TODO: Needs testing
-    // #[command(subcommand, visible_aliases = &["samples"])]
-    // SampleOps(sample_ops::SubCmds),
-    // ========================================
+    #[command(subcommand)]
+    Merge(merge::SubCmds),
+    #[command(subcommand, visible_aliases = &["slots"])]
+    SlotOps(slot_ops::SubCmds),
+    #[command(subcommand, visible_aliases = &["samples"])]
+    SampleOps(sample_ops::SubCmds),
     // TODO: List entities (bank/part/pattern/arrangement) that have been modified
     // #[command(subcommand, visible_aliases = &["list", "ls", "ll"])]
     // ListNonDefault(list_slots::SubCmds),
@@ -33,7 +35,30 @@ pub(crate) fn subcmd_runner(x: SubCmds) {
     match x {
         SubCmds::Copy(x) => copy_banks::subcmd_runner(x),
         SubCmds::ListSlots(x) => list_slots::subcmd_runner(x),
-        // SubCmds::SlotOps(x) => slot_ops::subcmd_runner(x),
-        // SubCmds::SampleOps(x) => sample_ops::subcmd_runner(x),
+        SubCmds::Merge(x) => merge::subcmd_runner(x),
+        SubCmds::SlotOps(x) => slot_ops::subcmd_runner(x),
+        SubCmds::SampleOps(x) => sample_ops::subcmd_runner(x),
+    }
+}
+
+/// Review the changes an operation will make before making them
+#[derive(Args, Debug, PartialEq)]
+pub(crate) struct PlanArgs {
+    /// Print the planned changes without modifying any files
+    #[clap(short = 'n', long, action)]
+    dry_run: bool,
+    /// Save the planned changes to a JSON file (apply it later with `ot-tools apply-plan`)
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    save_plan: Option<PathBuf>,
+}
+
+impl PlanArgs {
+    /// Saving a plan never applies it -- the saved plan is meant to be reviewed first.
+    pub(crate) fn finish(&self, tx: Transaction) -> RBoxErr<()> {
+        finish_transaction(
+            tx,
+            self.dry_run || self.save_plan.is_some(),
+            self.save_plan.as_deref(),
+        )
     }
 }
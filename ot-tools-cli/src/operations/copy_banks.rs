@@ -1,6 +1,7 @@
+use crate::operations::PlanArgs;
 use crate::print_err;
 use clap::{Subcommand, ValueHint};
-use ot_tools_ops::actions::banks::{batch_copy_banks, copy_bank_by_paths};
+use ot_tools_ops::actions::banks::{batch_copy_banks, stage_copy_bank_by_paths};
 use std::path::PathBuf;
 
 /// Copy sections of a project from one location to another, e.g. banks between projects
@@ -24,6 +25,8 @@ pub(crate) enum SubCmds {
         /// Force overwrite previously modified destination banks (default behaviour is to exit)
         #[clap(short = 'f', long, action)]
         force: bool,
+        #[command(flatten)]
+        plan: PlanArgs,
         // // TODO
         // /// Do not reassign sample slots in destination project (not in use currently!)
         // #[clap(long, action)]
@@ -121,17 +124,18 @@ pub(crate) fn subcmd_runner(x: SubCmds) {
             src_bank_id,
             dest_bank_id,
             force,
+            plan,
             // TODO
             // _no_reassign_slots,
         } => {
             print_err(|| {
-                copy_bank_by_paths(
+                plan.finish(stage_copy_bank_by_paths(
                     &src_project_dirpath,
                     &dest_project_dirpath,
                     src_bank_id,
                     dest_bank_id,
                     force,
-                )
+                )?)
             });
         }
         SubCmds::BankYaml { yaml_file_path } => {
@@ -6,6 +6,7 @@ use ot_tools_ops::actions::parts::{
 };
 use ot_tools_ops::actions::patterns::list_pattern_sample_slot_references;
 use ot_tools_ops::actions::projects::list_project_sample_slots;
+use ot_tools_ops::actions::projects::usage::print_project_slot_usage_report;
 use std::path::PathBuf;
 
 #[derive(Debug, clap::Args, PartialEq, Clone)]
@@ -45,6 +46,16 @@ pub(crate) enum SubCmds {
         #[arg(value_hint = ValueHint::DirPath)]
         project_dirpath: PathBuf,
     },
+    /// Report every static/flex slot in a project with its file and every reference to it in the
+    /// project's banks, plus references to empty slots and slots which are never referenced
+    Usage {
+        /// Directory path of the project
+        #[arg(value_hint = ValueHint::DirPath)]
+        project_dirpath: PathBuf,
+        /// Print the report as JSON instead of a table
+        #[clap(long, action)]
+        json: bool,
+    },
     /// List sample slots assigned in a specific bank of a specific project
     Bank {
         /// Directory path of the project
@@ -95,6 +106,12 @@ pub(crate) fn subcmd_runner(x: SubCmds) {
         SubCmds::Project { project_dirpath } => {
             print_err(|| list_project_sample_slots(&project_dirpath));
         }
+        SubCmds::Usage {
+            project_dirpath,
+            json,
+        } => {
+            print_err(|| print_project_slot_usage_report(&project_dirpath, json));
+        }
         SubCmds::Bank {
             project_dirpath,
             bank_id,
@@ -0,0 +1,113 @@
+use crate::operations::PlanArgs;
+use crate::{print_err, RBoxErr};
+use clap::{Subcommand, ValueHint};
+use ot_tools_ops::actions::merge::{show_data_file_diff, stage_merge_by_paths};
+use std::path::{Path, PathBuf};
+
+/// Show differences between, or merge changes to, bank and project data files
+#[derive(Subcommand, Debug, PartialEq)]
+pub(crate) enum SubCmds {
+    /// Show the fields which differ between two bank / project data files
+    Diff {
+        /// Path to the original bank / project data file
+        #[arg(value_hint = ValueHint::FilePath)]
+        old_file_path: PathBuf,
+        /// Path to the modified bank / project data file
+        #[arg(value_hint = ValueHint::FilePath)]
+        new_file_path: PathBuf,
+    },
+    /// Three-way merge of bank / project data files. Exits with a non-zero status on conflicts.
+    ///
+    /// Can be used as a git merge driver, e.g. in `.git/config`:
+    /// `[merge "octatrack"] driver = ot-tools operations merge files %O %A %B`
+    /// and `*.work merge=octatrack` / `*.strd merge=octatrack` in `.gitattributes`
+    Files {
+        /// Path to the common ancestor data file
+        #[arg(value_hint = ValueHint::FilePath)]
+        base_file_path: PathBuf,
+        /// Path to our version of the data file (merged data is written here by default)
+        #[arg(value_hint = ValueHint::FilePath)]
+        ours_file_path: PathBuf,
+        /// Path to their version of the data file
+        #[arg(value_hint = ValueHint::FilePath)]
+        theirs_file_path: PathBuf,
+        /// Write the merged data file here instead of overwriting our version
+        #[clap(short = 'o', long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        plan: PlanArgs,
+    },
+    /// Three-way merge of the project and bank data files for copies of the same project.
+    /// Exits with a non-zero status on conflicts.
+    Projects {
+        /// Directory path of the common ancestor project
+        #[arg(value_hint = ValueHint::DirPath)]
+        base_project_dirpath: PathBuf,
+        /// Directory path of our version of the project (merged files are written here by default)
+        #[arg(value_hint = ValueHint::DirPath)]
+        ours_project_dirpath: PathBuf,
+        /// Directory path of their version of the project
+        #[arg(value_hint = ValueHint::DirPath)]
+        theirs_project_dirpath: PathBuf,
+        /// Write the merged data files to this (existing) directory instead of our version
+        #[clap(short = 'o', long, value_hint = ValueHint::DirPath)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        plan: PlanArgs,
+    },
+}
+
+/// Exit with a non-zero status when the merge has conflicts or fails, so git knows the merge
+/// needs resolving.
+fn run_merge(base: &Path, ours: &Path, theirs: &Path, output: Option<PathBuf>, plan: PlanArgs) {
+    let mut clean = None;
+    print_err(|| -> RBoxErr<()> {
+        let (tx, x) = stage_merge_by_paths(base, ours, theirs, output.as_deref())?;
+        plan.finish(tx)?;
+        clean = Some(x);
+        Ok(())
+    });
+    match clean {
+        Some(true) => {}
+        Some(false) => std::process::exit(1),
+        None => std::process::exit(2),
+    }
+}
+
+#[doc(hidden)]
+pub(crate) fn subcmd_runner(x: SubCmds) {
+    match x {
+        SubCmds::Diff {
+            old_file_path,
+            new_file_path,
+        } => {
+            print_err(|| show_data_file_diff(&old_file_path, &new_file_path));
+        }
+        SubCmds::Files {
+            base_file_path,
+            ours_file_path,
+            theirs_file_path,
+            output,
+            plan,
+        } => run_merge(
+            &base_file_path,
+            &ours_file_path,
+            &theirs_file_path,
+            output,
+            plan,
+        ),
+        SubCmds::Projects {
+            base_project_dirpath,
+            ours_project_dirpath,
+            theirs_project_dirpath,
+            output,
+            plan,
+        } => run_merge(
+            &base_project_dirpath,
+            &ours_project_dirpath,
+            &theirs_project_dirpath,
+            output,
+            plan,
+        ),
+    }
+}
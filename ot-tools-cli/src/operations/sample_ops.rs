@@ -1,8 +1,7 @@
+use crate::operations::PlanArgs;
 use crate::print_err;
 use clap::{Subcommand, ValueHint};
-use ot_tools_ops::actions::projects::{
-    consolidate_sample_slots_to_audio_pool, consolidate_sample_slots_to_project_pool,
-};
+use ot_tools_ops::actions::projects::stage_consolidate_sample_slots_to_project_pool;
 use std::path::PathBuf;
 
 /// Make changes to sample files used by a project, or contained in the project directory
@@ -13,35 +12,67 @@ pub(crate) enum SubCmds {
         /// Project directory path
         #[arg(value_hint = ValueHint::DirPath)]
         project_dirpath: PathBuf,
+        /// Delete sample files copied out of the set's audio pool when nothing else in the set
+        /// uses them (other projects, or this project's saved state)
+        #[arg(short = 'd', long)]
+        delete_unused_pool_files: bool,
+        #[command(flatten)]
+        plan: PlanArgs,
     },
+    // TODO: Needs testing
+    // /// Transfer sample files used by a project to the set's audio pool directory (will update project sample slots)
+    // Centralise {
+    //     /// Project directory path
+    //     #[arg(value_hint = ValueHint::DirPath)]
+    //     project_dirpath: PathBuf,
+    //     #[command(flatten)]
+    //     plan: PlanArgs,
+    // },
 
-    /// Transfer sample files used by a project to the set's audio pool directory (will update project sample slots)
-    Centralise {
-        /// Project directory path
-        #[arg(value_hint = ValueHint::DirPath)]
-        project_dirpath: PathBuf,
-    },
-
-    /// TODO: Remove any sample files in the project directory that are unused by the project
-    Purge {
-        /// Project directory path
-        #[arg(value_hint = ValueHint::DirPath)]
-        project_dirpath: PathBuf,
-    },
+    // /// Remove any sample files in the project directory that are unused by the project
+    // Purge {
+    //     /// Project directory path
+    //     #[arg(value_hint = ValueHint::DirPath)]
+    //     project_dirpath: PathBuf,
+    //     #[command(flatten)]
+    //     plan: PlanArgs,
+    // },
 }
 
 #[doc(hidden)]
-#[allow(dead_code)] // coming back to it later
 pub(crate) fn subcmd_runner(x: SubCmds) {
     match x {
-        SubCmds::Consolidate { project_dirpath } => {
-            print_err(|| consolidate_sample_slots_to_project_pool(&project_dirpath));
-        }
-        SubCmds::Centralise { project_dirpath } => {
-            print_err(|| consolidate_sample_slots_to_audio_pool(&project_dirpath));
-        }
-        SubCmds::Purge { project_dirpath: _ } => {
-            unimplemented!()
+        // SubCmds::Centralise {
+        //     project_dirpath,
+        //     plan,
+        // } => {
+        //     print_err(|| {
+        //         plan.finish(stage_consolidate_sample_slots_to_audio_pool(
+        //             &project_dirpath.join("project.work"),
+        //         )?)
+        //     });
+        // }
+        // SubCmds::Purge {
+        //     project_dirpath,
+        //     plan,
+        // } => {
+        //     print_err(|| {
+        //         plan.finish(stage_purge_project_pool(
+        //             &project_dirpath.join("project.work"),
+        //         )?)
+        //     });
+        // }
+        SubCmds::Consolidate {
+            project_dirpath,
+            delete_unused_pool_files,
+            plan,
+        } => {
+            print_err(|| {
+                plan.finish(stage_consolidate_sample_slots_to_project_pool(
+                    &project_dirpath.join("project.work"),
+                    delete_unused_pool_files,
+                )?)
+            });
         }
     }
 }
@@ -1,36 +1,119 @@
+use crate::operations::PlanArgs;
 use crate::print_err;
-use clap::{Subcommand, ValueHint};
-use ot_tools_ops::actions::projects::purge_project_pool;
-use ot_tools_ops::actions::projects::slots::cmd_slots_deduplicate;
+use clap::{Subcommand, ValueEnum, ValueHint};
+use ot_tools_ops::actions::projects::relink::stage_relink_missing_sample_files;
+use ot_tools_ops::actions::projects::slots::{
+    stage_cmd_slots_purge, stage_cmd_slots_renumber, SlotRenumberOrder,
+};
 use std::path::PathBuf;
 
 /// Modifying sample slots within an existing project
 #[derive(Subcommand, Debug, PartialEq)]
 pub(crate) enum SubCmds {
-    /// Deduplicate project sample slots based on slot settings (does not remove files, does update bank data)
-    Deduplicate {
-        /// Project directory path to perform de-duplication on
+    // TODO: Needs testing
+    // /// Deduplicate project sample slots based on slot settings (does not remove files, does update bank data)
+    // Deduplicate {
+    //     /// Project directory path to perform de-duplication on
+    //     #[arg(value_hint = ValueHint::DirPath)]
+    //     project_dirpath: PathBuf,
+    //     #[command(flatten)]
+    //     plan: PlanArgs,
+    // },
+    /// Delete project sample slots when not used by any project banks (does not remove files)
+    Purge {
+        /// Project directory path
         #[arg(value_hint = ValueHint::DirPath)]
         project_dirpath: PathBuf,
+        /// Keep slots which are only used by the saved bank state (bankXX.strd files)
+        #[clap(short = 'k', long, action)]
+        keep_strd_refs: bool,
+        #[command(flatten)]
+        plan: PlanArgs,
     },
 
-    /// Delete project sample slots when not used by any project banks
-    Purge {
+    /// Find moved / renamed sample files for slots with missing files (searches the set's audio
+    /// pool and project directories) and update the slot file paths. Ambiguous matches, and matches
+    /// with a path the Octatrack can't load, are reported and left unchanged.
+    Relink {
+        /// Project directory path
+        #[arg(value_hint = ValueHint::DirPath)]
+        project_dirpath: PathBuf,
+        /// YAML samples directory index (`sample-files index --full` output) created before the
+        /// files were moved, used to find files by content
+        #[clap(short = 'i', long, value_hint = ValueHint::FilePath)]
+        index: Option<PathBuf>,
+        #[command(flatten)]
+        plan: PlanArgs,
+    },
+    /// Renumber static and flex sample slots so there are no gaps between them (updates working and
+    /// saved bank data). Parts / p-locks using empty slots are moved to slots which stay empty
+    Renumber {
         /// Project directory path
         #[arg(value_hint = ValueHint::DirPath)]
         project_dirpath: PathBuf,
+        /// Order to renumber slots in
+        #[clap(short = 's', long, value_enum, default_value_t = SortKey::Slot)]
+        sort_by: SortKey,
+        #[command(flatten)]
+        plan: PlanArgs,
     },
 }
 
+/// Order to renumber sample slots in
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SortKey {
+    /// Keep the existing slot order
+    Slot,
+    /// Sample file path
+    Path,
+    /// Number of references in bank data, most used first
+    Usage,
+}
+
+impl From<SortKey> for SlotRenumberOrder {
+    fn from(x: SortKey) -> Self {
+        match x {
+            SortKey::Slot => Self::SlotId,
+            SortKey::Path => Self::Path,
+            SortKey::Usage => Self::Usage,
+        }
+    }
+}
+
 #[doc(hidden)]
-#[allow(dead_code)] // coming back to it later
 pub(crate) fn subcmd_runner(x: SubCmds) {
     match x {
-        SubCmds::Purge { project_dirpath } => {
-            print_err(|| purge_project_pool(&project_dirpath));
+        // SubCmds::Deduplicate {
+        //     project_dirpath,
+        //     plan,
+        // } => {
+        //     print_err(|| plan.finish(stage_cmd_slots_deduplicate(&project_dirpath)?));
+        // }
+        SubCmds::Purge {
+            project_dirpath,
+            keep_strd_refs,
+            plan,
+        } => {
+            print_err(|| plan.finish(stage_cmd_slots_purge(&project_dirpath, keep_strd_refs)?));
+        }
+        SubCmds::Relink {
+            project_dirpath,
+            index,
+            plan,
+        } => {
+            print_err(|| {
+                plan.finish(stage_relink_missing_sample_files(
+                    &project_dirpath,
+                    index.as_deref(),
+                )?)
+            });
         }
-        SubCmds::Deduplicate { project_dirpath } => {
-            print_err(|| cmd_slots_deduplicate(&project_dirpath));
+        SubCmds::Renumber {
+            project_dirpath,
+            sort_by,
+            plan,
+        } => {
+            print_err(|| plan.finish(stage_cmd_slots_renumber(&project_dirpath, sort_by.into())?));
         }
     }
 }
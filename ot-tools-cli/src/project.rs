@@ -0,0 +1,201 @@
+use crate::operations::PlanArgs;
+use crate::print_err;
+use clap::{Subcommand, ValueHint};
+use ot_tools_io::yaml_file_to_type;
+use ot_tools_ops::actions::projects::{
+    archive::{export_project, import_project},
+    create::{stage_create_new_project, YamlNewProjectSpec},
+    paths::print_project_slot_path_checks,
+    settings::{
+        parse_setting_assignment, print_project_settings, print_project_settings_overlay_reports,
+        read_project_settings_overlay, stage_apply_project_settings_overlay_to_projects,
+        stage_set_project_settings,
+    },
+};
+use std::path::PathBuf;
+
+/// Inspect or change whole Octatrack projects
+#[derive(Subcommand, Debug, PartialEq)]
+pub(crate) enum SubCmds {
+    /// Flag sample slot paths the Octatrack can't load, e.g. absolute host paths, paths using
+    /// `\` separators or paths containing unsupported characters
+    CheckPaths {
+        /// Project directory path
+        #[arg(value_hint = ValueHint::DirPath)]
+        project_dirpath: PathBuf,
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Bundle a project and every sample file it uses into a single (tar) archive file
+    Export {
+        /// Project directory path
+        #[arg(value_hint = ValueHint::DirPath)]
+        project_dirpath: PathBuf,
+        /// File path of the archive to create
+        #[arg(value_hint = ValueHint::FilePath)]
+        archive_fpath: PathBuf,
+    },
+
+    /// Unpack a project archive created by `project export` as a new project in a set.
+    /// A number is added to the project name when a project with the same name already exists
+    Import {
+        /// File path of the project archive
+        #[arg(value_hint = ValueHint::FilePath)]
+        archive_fpath: PathBuf,
+        /// Directory path of the set to import the project into
+        #[arg(value_hint = ValueHint::DirPath)]
+        set_dirpath: PathBuf,
+        /// Name for the imported project (defaults to the exported project's name)
+        #[arg(short, long)]
+        name: Option<String>,
+    },
+
+    /// Create a new project in a set, with default data or copied from a template project.
+    /// Will not overwrite an existing directory
+    New {
+        /// Directory path of the set to create the project in
+        #[arg(value_hint = ValueHint::DirPath)]
+        set_dirpath: PathBuf,
+        /// Name of the new project (letters, numbers, '-' or '_', max 12 characters)
+        #[arg(value_hint = ValueHint::Other)]
+        name: String,
+        /// Directory path of a project to use as a template
+        #[arg(short, long, value_hint = ValueHint::DirPath)]
+        template: Option<PathBuf>,
+        /// File path of a YAML spec with the tempo, sample slots and part names to use
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        spec: Option<PathBuf>,
+        #[command(flatten)]
+        plan: PlanArgs,
+    },
+
+    /// Show or change a project's settings (control, mixer and tempo menus)
+    #[command(subcommand)]
+    Settings(SettingsSubCmds),
+}
+
+/// Show or change a project's settings, using the values shown on the device
+#[derive(Subcommand, Debug, PartialEq)]
+pub(crate) enum SettingsSubCmds {
+    /// List every setting key with its current value and the values it accepts
+    Show {
+        /// Project directory path
+        #[arg(value_hint = ValueHint::DirPath)]
+        project_dirpath: PathBuf,
+        /// Print the settings as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Change settings in the project's working file (`project.work`), e.g.
+    /// `mixer.gain_ab=-12 metronome.time_signature=7/8`.
+    /// Nothing is changed unless every setting is valid
+    Set {
+        /// Project directory path
+        #[arg(value_hint = ValueHint::DirPath)]
+        project_dirpath: PathBuf,
+        /// Settings to change, as key=value
+        #[arg(required = true, value_hint = ValueHint::Other)]
+        settings: Vec<String>,
+        #[command(flatten)]
+        plan: PlanArgs,
+    },
+
+    /// Apply a partial settings YAML overlay to every project in a set or compact flash card
+    /// directory, reporting the changes for each project.
+    /// Projects saved by an unsupported OS version are skipped
+    Apply {
+        /// File path of the YAML settings overlay. Overlays use the values stored in the project
+        /// file, not the device values used by `set` (see
+        /// examples/confs/project-settings-overlay.yaml)
+        #[arg(value_hint = ValueHint::FilePath)]
+        overlay_fpath: PathBuf,
+        /// Set or compact flash card directory path
+        #[arg(value_hint = ValueHint::DirPath)]
+        dirpath: PathBuf,
+        /// Print the per-project results as JSON
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        plan: PlanArgs,
+    },
+}
+
+#[doc(hidden)]
+pub(crate) fn subcmd_runner(x: SubCmds) {
+    match x {
+        SubCmds::CheckPaths {
+            project_dirpath,
+            json,
+        } => {
+            print_err(|| print_project_slot_path_checks(&project_dirpath, json));
+        }
+        SubCmds::Export {
+            project_dirpath,
+            archive_fpath,
+        } => {
+            print_err(|| export_project(&project_dirpath, &archive_fpath).map(|_| ()));
+        }
+        SubCmds::Import {
+            archive_fpath,
+            set_dirpath,
+            name,
+        } => {
+            print_err(|| import_project(&archive_fpath, &set_dirpath, name.as_deref()).map(|_| ()));
+        }
+        SubCmds::New {
+            set_dirpath,
+            name,
+            template,
+            spec,
+            plan,
+        } => {
+            print_err(|| {
+                let spec = spec
+                    .map(|x| yaml_file_to_type::<YamlNewProjectSpec>(&x))
+                    .transpose()?;
+                plan.finish(stage_create_new_project(
+                    &set_dirpath,
+                    &name,
+                    template.as_deref(),
+                    spec.as_ref(),
+                )?)
+            });
+        }
+        SubCmds::Settings(SettingsSubCmds::Show {
+            project_dirpath,
+            json,
+        }) => {
+            print_err(|| print_project_settings(&project_dirpath, json));
+        }
+        SubCmds::Settings(SettingsSubCmds::Set {
+            project_dirpath,
+            settings,
+            plan,
+        }) => {
+            print_err(|| {
+                let assignments = settings
+                    .iter()
+                    .map(|x| parse_setting_assignment(x))
+                    .collect::<Result<Vec<_>, _>>()?;
+                plan.finish(stage_set_project_settings(&project_dirpath, &assignments)?)
+            });
+        }
+        SubCmds::Settings(SettingsSubCmds::Apply {
+            overlay_fpath,
+            dirpath,
+            json,
+            plan,
+        }) => {
+            print_err(|| {
+                let overlay = read_project_settings_overlay(&overlay_fpath)?;
+                let (tx, reports) =
+                    stage_apply_project_settings_overlay_to_projects(&dirpath, &overlay)?;
+                print_project_settings_overlay_reports(&reports, json)?;
+                plan.finish(tx)
+            });
+        }
+    }
+}
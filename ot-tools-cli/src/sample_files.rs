@@ -1,12 +1,95 @@
 use crate::print_err;
-use clap::{Subcommand, ValueHint};
+use clap::{Args, Subcommand, ValueEnum, ValueHint};
 use ot_tools_ops::actions::samples::{
-    batch_create_samplechains, create_equally_sliced_sample, create_randomly_sliced_sample,
-    create_samplechains_from_yaml, deconstruct_samplechain_from_paths,
-    deconstruct_samplechains_from_yaml,
+    analyze_audio_files, batch_create_samplechains, convert_audio_files,
+    create_beat_grid_sliced_sample, create_equally_sliced_sample, create_index_samples_dir_full,
+    create_randomly_sliced_sample, create_samplechains_from_yaml, create_transient_sliced_sample,
+    deconstruct_samplechain_from_paths, deconstruct_samplechains_from_yaml, detect_bpm_audio_files,
+    parse_note_division, FileFormatOpts, SliceProcOpts, SliceSnapOpts,
+    DEFAULT_MIN_TEMPO_CONFIDENCE,
 };
+use ot_tools_ops::audio::file::FileFormat;
+use ot_tools_ops::audio::onsets::OnsetOpts;
+use ot_tools_ops::audio::zero_crossings::SnapMode;
 use std::path::PathBuf;
 
+/// Available audio file formats for output audio files
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+pub(crate) enum AudioFileFormat {
+    Wav,
+    Aiff,
+}
+
+impl From<AudioFileFormat> for FileFormat {
+    fn from(x: AudioFileFormat) -> Self {
+        match x {
+            AudioFileFormat::Wav => FileFormat::Wav,
+            AudioFileFormat::Aiff => FileFormat::Aiff,
+        }
+    }
+}
+
+/// Output audio file format options
+#[derive(Args, Debug, PartialEq)]
+pub(crate) struct AudioFormatArgs {
+    /// Format of the output audio files
+    #[arg(long, value_enum)]
+    format: Option<AudioFileFormat>,
+
+    /// Bit depth of the output audio files (16 or 24)
+    #[arg(long, value_hint = ValueHint::Other)]
+    bit_depth: Option<u16>,
+}
+
+impl AudioFormatArgs {
+    fn to_opts(&self) -> Option<FileFormatOpts> {
+        Some(FileFormatOpts {
+            bit_depth: self.bit_depth,
+            format: self.format.map(Into::into),
+        })
+    }
+}
+
+/// Ways of choosing where slice points are moved to
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+pub(crate) enum SliceSnapMode {
+    /// Nearest zero crossing
+    ZeroCrossing,
+    /// Quietest point, better for noisy audio
+    LowEnergy,
+}
+
+impl From<SliceSnapMode> for SnapMode {
+    fn from(x: SliceSnapMode) -> Self {
+        match x {
+            SliceSnapMode::ZeroCrossing => SnapMode::ZeroCrossing,
+            SliceSnapMode::LowEnergy => SnapMode::LowEnergy,
+        }
+    }
+}
+
+/// Slice point snapping options
+#[derive(Args, Debug, PartialEq)]
+pub(crate) struct SliceSnapArgs {
+    /// Move slice start/end points to nearby zero crossings / quiet points, so slices don't
+    /// click when played
+    #[arg(long, value_enum)]
+    snap: Option<SliceSnapMode>,
+
+    /// How far slice points can be moved when snapping, in milliseconds (default 10)
+    #[arg(long, requires = "snap", value_hint = ValueHint::Other)]
+    snap_window_ms: Option<f32>,
+}
+
+impl SliceSnapArgs {
+    fn to_opts(&self) -> Option<SliceSnapOpts> {
+        self.snap.map(|mode| SliceSnapOpts {
+            mode: mode.into(),
+            window_ms: self.snap_window_ms,
+        })
+    }
+}
+
 /// Create sample chains, slice grids and other utilities for audio sample files
 #[derive(Subcommand, Debug, PartialEq)]
 pub(crate) enum SubCmds {
@@ -22,10 +105,18 @@ pub(crate) enum SubCmds {
         #[arg(value_hint = ValueHint::DirPath)]
         out_dir_path: PathBuf,
 
-        /// File paths of wav files to include in the sliced sample chain.
+        /// File paths of WAV/AIFF files to include in the sliced sample chain.
         /// Shell glob patterns work here too.
         #[arg(value_hint = ValueHint::FilePath)]
         wav_file_paths: Vec<PathBuf>,
+
+        /// Output format options (default: 16-bit WAV)
+        #[command(flatten)]
+        audio_format: AudioFormatArgs,
+
+        /// Slice point snapping options
+        #[command(flatten)]
+        snap: SliceSnapArgs,
     },
     /// Create batches of sample chains from a YAML config file
     ChainYaml {
@@ -44,6 +135,10 @@ pub(crate) enum SubCmds {
         /// Directory path where the audio files will be written
         #[arg(value_hint = ValueHint::DirPath)]
         out_dir_path: PathBuf,
+
+        /// Output format options (default: same as the sliced audio file)
+        #[command(flatten)]
+        audio_format: AudioFormatArgs,
     },
     /// Use a YAML config to split batches of sliced samples.
     SplitSlicesYaml {
@@ -60,6 +155,10 @@ pub(crate) enum SubCmds {
         /// How many random slices to create
         #[arg(value_hint = ValueHint::Other)]
         n_slices: usize,
+
+        /// Slice point snapping options
+        #[command(flatten)]
+        snap: SliceSnapArgs,
     },
     /// Create an `.ot` file with linear slice grid from the cli
     GridLinear {
@@ -70,6 +169,96 @@ pub(crate) enum SubCmds {
         /// How many slices to put in the slice grid
         #[arg(value_hint = ValueHint::Other)]
         n_slices: usize,
+
+        /// Slice point snapping options
+        #[command(flatten)]
+        snap: SliceSnapArgs,
+    },
+    /// Create an `.ot` file with a slice at each detected transient (e.g. each hit of a drum
+    /// break) from the cli
+    GridTransient {
+        /// Location of the audio file to detect transients in
+        #[arg(value_hint = ValueHint::FilePath)]
+        wav_file_path: PathBuf,
+
+        /// Detection threshold between 0.0 and 1.0, lower values detect more (quieter) transients
+        #[arg(long, default_value_t = 0.2, value_hint = ValueHint::Other)]
+        threshold: f32,
+
+        /// Minimum time between transients in milliseconds
+        #[arg(long, default_value_t = 50.0, value_hint = ValueHint::Other)]
+        min_gap_ms: f32,
+
+        /// Slice point snapping options
+        #[command(flatten)]
+        snap: SliceSnapArgs,
+    },
+    /// Create an `.ot` file with a slice on each note of a beat grid (e.g. every 1/16 note) from
+    /// the cli
+    GridBeat {
+        /// Location of the audio file to generate a beat grid for
+        #[arg(value_hint = ValueHint::FilePath)]
+        wav_file_path: PathBuf,
+
+        /// Note length of each slice, e.g. '1/16', '1/8T' (triplet) or '1/4D' (dotted)
+        #[arg(value_hint = ValueHint::Other)]
+        division: String,
+
+        /// Tempo of the beat grid. Uses the tempo of an existing `.ot` file for the audio file
+        /// when not set, or detects the tempo if there isn't one
+        #[arg(long, value_hint = ValueHint::Other)]
+        bpm: Option<f32>,
+
+        /// Slice point snapping options
+        #[command(flatten)]
+        snap: SliceSnapArgs,
+    },
+    /// Detect the tempo (BPM) of WAV/AIFF files, optionally writing the tempos into the
+    /// existing `.ot` files next to them
+    DetectBpm {
+        /// Audio files, or directories to search for audio files
+        #[arg(required = true, value_hint = ValueHint::AnyPath)]
+        paths: Vec<PathBuf>,
+
+        /// Update the tempo and trim/loop lengths of the existing `.ot` file for each audio file
+        #[arg(long, default_value_t = false)]
+        write: bool,
+
+        /// Minimum confidence (between 0.0 and 1.0) of a detected tempo for it to be written
+        #[arg(long, default_value_t = DEFAULT_MIN_TEMPO_CONFIDENCE, value_hint = ValueHint::Other)]
+        min_confidence: f32,
+    },
+    /// Measure the peak, true peak, RMS and integrated loudness (LUFS) levels of WAV/AIFF files
+    Analyze {
+        /// Audio files, or directories to search for audio files
+        #[arg(required = true, value_hint = ValueHint::AnyPath)]
+        paths: Vec<PathBuf>,
+    },
+    /// Convert a directory of WAV/AIFF files with any sample rate / bit depth (e.g. 48kHz
+    /// 32-bit float) into Octatrack compatible (44.1kHz 16/24-bit) files, keeping the
+    /// directory structure
+    Convert {
+        /// Directory path of the audio files to convert
+        #[arg(value_hint = ValueHint::DirPath)]
+        in_dir_path: PathBuf,
+
+        /// Directory path where the converted audio files will be written (not within the
+        /// input directory, existing files are never overwritten)
+        #[arg(value_hint = ValueHint::DirPath)]
+        out_dir_path: PathBuf,
+
+        #[command(flatten)]
+        audio_format: AudioFormatArgs,
+    },
+    /// Index the audio files in a directory, with md5 hashes of the file contents
+    /// (e.g. for `operations slot-ops relink --index`)
+    Index {
+        /// Directory path of the samples to index
+        #[arg(value_hint = ValueHint::DirPath)]
+        samples_dir_path: PathBuf,
+        /// File path of the YAML index file to write
+        #[arg(value_hint = ValueHint::FilePath)]
+        yaml_file_path: PathBuf,
     },
     // #[command(subcommand)]
     // Search(SampleSearch),
@@ -82,15 +271,22 @@ pub(crate) fn subcmd_runner(x: SubCmds) {
             chain_name,
             out_dir_path,
             wav_file_paths,
+            audio_format,
+            snap,
         } => {
+            // no detailed options allowed from command line, apart from snapping
+            let audio_opts = snap.to_opts().map(|x| SliceProcOpts {
+                snap: Some(x),
+                ..Default::default()
+            });
             print_err(|| {
                 batch_create_samplechains(
                     &wav_file_paths,
                     &out_dir_path,
                     &chain_name,
-                    None, // no detailed options allowed from command line
-                    None,
                     None,
+                    audio_opts,
+                    audio_format.to_opts(),
                 )
             });
         }
@@ -101,9 +297,17 @@ pub(crate) fn subcmd_runner(x: SubCmds) {
             ot_file_path,
             audio_file_path,
             out_dir_path,
+            audio_format,
         } => {
-            let _ =
-                deconstruct_samplechain_from_paths(&audio_file_path, &ot_file_path, &out_dir_path);
+            print_err(|| {
+                deconstruct_samplechain_from_paths(
+                    &audio_file_path,
+                    &ot_file_path,
+                    &out_dir_path,
+                    audio_format.to_opts(),
+                )
+                .map(|_| ())
+            });
         }
         SubCmds::SplitSlicesYaml { yaml_file_path } => {
             print_err(|| deconstruct_samplechains_from_yaml(&yaml_file_path));
@@ -111,14 +315,68 @@ pub(crate) fn subcmd_runner(x: SubCmds) {
         SubCmds::GridRandom {
             wav_file_path,
             n_slices,
+            snap,
         } => {
-            print_err(|| create_randomly_sliced_sample(&wav_file_path, n_slices));
+            print_err(|| create_randomly_sliced_sample(&wav_file_path, n_slices, snap.to_opts()));
         }
         SubCmds::GridLinear {
             wav_file_path,
             n_slices,
+            snap,
+        } => {
+            print_err(|| create_equally_sliced_sample(&wav_file_path, n_slices, snap.to_opts()));
+        }
+        SubCmds::GridTransient {
+            wav_file_path,
+            threshold,
+            min_gap_ms,
+            snap,
+        } => {
+            let opts = OnsetOpts {
+                threshold,
+                min_gap_ms,
+                ..OnsetOpts::default()
+            };
+            print_err(|| {
+                create_transient_sliced_sample(&wav_file_path, &opts, snap.to_opts()).map(|_| ())
+            });
+        }
+        SubCmds::GridBeat {
+            wav_file_path,
+            division,
+            bpm,
+            snap,
+        } => {
+            print_err(|| {
+                let division = parse_note_division(&division)?;
+                create_beat_grid_sliced_sample(&wav_file_path, bpm, &division, snap.to_opts())
+                    .map(|_| ())
+            });
+        }
+        SubCmds::DetectBpm {
+            paths,
+            write,
+            min_confidence,
+        } => {
+            print_err(|| detect_bpm_audio_files(&paths, write, min_confidence).map(|_| ()));
+        }
+        SubCmds::Analyze { paths } => {
+            print_err(|| analyze_audio_files(&paths).map(|_| ()));
+        }
+        SubCmds::Convert {
+            in_dir_path,
+            out_dir_path,
+            audio_format,
+        } => {
+            print_err(|| {
+                convert_audio_files(&in_dir_path, &out_dir_path, audio_format.to_opts()).map(|_| ())
+            });
+        }
+        SubCmds::Index {
+            samples_dir_path,
+            yaml_file_path,
         } => {
-            print_err(|| create_equally_sliced_sample(&wav_file_path, n_slices));
+            print_err(|| create_index_samples_dir_full(&samples_dir_path, &Some(yaml_file_path)));
         }
     }
 }
@@ -16,6 +16,7 @@ pub mod constants;
 pub mod projects;
 pub mod samples;
 pub mod utils;
+pub mod versions;
 
 #[cfg(test)]
 mod test_utils;
@@ -67,7 +68,7 @@ impl Error for SerdeOctatrackErrors {}
 
 // DO-NOT-DERIVE: Implementation details for each enum are always required.
 /// Trait to convert between Enum option instances and their corresponding value.
-trait OptionEnumValueConvert {
+pub trait OptionEnumValueConvert {
     /// One of the enum types within the `octatrack::options` module.
     type T;
 
@@ -17,6 +17,7 @@ use tempo::TempoMenu;
 use trig_mode_midi_tracks::MidiTrackTrigModes;
 
 use serde::{Deserialize, Serialize};
+use std::{error::Error, ops::RangeInclusive};
 
 use crate::{OptionEnumValueConvert, RBoxErr};
 
@@ -93,6 +94,67 @@ TRIG_MODE_MIDI=0
 [/SETTINGS]
 */
 
+/// Errors from the validated project settings setters.
+#[derive(Debug, PartialEq)]
+pub enum ProjectSettingsErrors {
+    /// Value is outside of the range the device allows for the setting (values are the ones
+    /// shown on the device, not the stored values)
+    OutOfRange {
+        setting: &'static str,
+        value: i64,
+        min: i64,
+        max: i64,
+    },
+    /// Tempo (BPM) is outside of [`tempo::TEMPO_RANGE`]
+    InvalidTempo(f32),
+    /// Metronome time signature denominators can only be 1, 2, 4, 8 or 16
+    InvalidTimeSignatureDenominator(u8),
+}
+impl std::fmt::Display for ProjectSettingsErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::OutOfRange {
+                setting,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "invalid {setting} value {value} - must be between {min} and {max} (inclusive)"
+            ),
+            Self::InvalidTempo(bpm) => write!(
+                f,
+                "invalid tempo {bpm} - must be between {} and {} BPM (inclusive)",
+                tempo::TEMPO_RANGE.start(),
+                tempo::TEMPO_RANGE.end()
+            ),
+            Self::InvalidTimeSignatureDenominator(x) => write!(
+                f,
+                "invalid time signature denominator {x} - must be one of 1, 2, 4, 8 or 16"
+            ),
+        }
+    }
+}
+impl Error for ProjectSettingsErrors {}
+
+/// Check a setting's value is within the range allowed on the device.
+pub(crate) fn check_setting_range<T: Into<i64> + Copy + PartialOrd>(
+    setting: &'static str,
+    value: T,
+    range: RangeInclusive<T>,
+) -> RBoxErr<T> {
+    if !range.contains(&value) {
+        return Err(ProjectSettingsErrors::OutOfRange {
+            setting,
+            value: value.into(),
+            min: (*range.start()).into(),
+            max: (*range.end()).into(),
+        }
+        .into());
+    }
+    Ok(value)
+}
+
 /// Project settings read from a parsed Octatrack Project file
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -5,10 +5,47 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use crate::projects::{
-    options::ProjectMidiChannels, parse_hashmap_string_value, parse_hashmap_string_value_bool,
+    options::ProjectMidiChannels,
+    parse_hashmap_string_value, parse_hashmap_string_value_bool,
+    settings::{check_setting_range, ProjectSettingsErrors},
     FromHashMap,
 };
 use crate::OptionEnumValueConvert;
+use std::ops::RangeInclusive;
+
+/// Range of the input noise gate levels.
+pub const INPUT_GATE_RANGE: RangeInclusive<u8> = 0..=127;
+
+/// Range of the number of reserved track recorders.
+pub const RESERVED_RECORDER_COUNT_RANGE: RangeInclusive<u8> = 0..=8;
+
+/// Range of the reserved track recorder length (sequencer steps).
+pub const RESERVED_RECORDER_LENGTH_RANGE: RangeInclusive<u32> = 1..=64;
+
+/// Range of the metronome time signature numerator.
+pub const METRONOME_NUMERATOR_RANGE: RangeInclusive<u8> = 1..=16;
+
+/// Metronome time signature denominators, indexed by their stored value.
+pub const METRONOME_DENOMINATORS: [u8; 5] = [1, 2, 4, 8, 16];
+
+/// Range of the number of metronome preroll bars.
+pub const METRONOME_PREROLL_RANGE: RangeInclusive<u8> = 0..=16;
+
+/// Range of the metronome cue / main volumes.
+pub const METRONOME_VOLUME_RANGE: RangeInclusive<u8> = 0..=127;
+
+/// Range of the metronome click pitch.
+pub const METRONOME_PITCH_RANGE: RangeInclusive<u8> = 0..=24;
+
+/// Range of the MIDI channels shown on the device.
+pub const MIDI_CHANNEL_RANGE: RangeInclusive<u8> = 1..=16;
+
+/// Range of the `AUDIO CC OUT` / `AUDIO NOTE OUT` settings (1: `INT`, 2: `EXT`, 3: `INT+EXT`).
+pub const MIDI_AUDIO_TRACK_OUT_RANGE: RangeInclusive<u8> = 1..=3;
+
+/// Range of the `AUDIO NOTE IN` setting
+/// (0: `OFF`, 1: `STANDARD`, 2: `FOLLOW TM`, 3: `MAP/TRACK`).
+pub const MIDI_AUDIO_TRACK_NOTE_IN_RANGE: RangeInclusive<u8> = 0..=3;
 
 /// Convenience struct for all data related to the Octatrack Project Settings 'Control' Menu.
 
@@ -115,11 +152,11 @@ impl FromHashMap for AudioControlPage {
 pub struct InputControlPage {
     /// dB level of noise gate for the AB external audio inputs.
     /// See Manual section 8.8 MIXER MENU
-    pub gate_ab: u8, // 127 is default so i assume this is u8? midpoint?
+    pub gate_ab: u8, // 127 is default
 
     /// dB level of noise gate for the CD external audio inputs.
     /// See Manual section 8.8 MIXER MENU
-    pub gate_cd: u8, // 127 is default so i assume this is u8? midpoint?
+    pub gate_cd: u8, // 127 is default
 
     /// See Manual section 8.6.2. INPUT.
     /// Adds a delay to incoming external audio signals. Controlled by the DIR setting on the MIXER page.
@@ -180,7 +217,7 @@ impl FromHashMap for SequencerControlPage {
             )?,
             pattern_change_auto_silence_tracks: parse_hashmap_string_value_bool(
                 hmap,
-                "pattern_change_auto_trig_lfos",
+                "pattern_change_auto_silence_tracks",
                 None,
             )?,
             pattern_change_auto_trig_lfos: parse_hashmap_string_value_bool(
@@ -254,18 +291,20 @@ impl FromHashMap for MemoryControlPage {
 pub struct MetronomeControlPage {
     /// `TIME SIG. NUMER` setting in `PROJECT` -> `CONTROL` -> `METRONOME` UI menu.
     /// Controls the numerator for time signature (the 3 in 3/4).
+    /// Stored 0-indexed, so the default of `3` is a numerator of 4.
     /// See Manual section 8.6.6 METRONOME
-    pub metronome_time_signature: u8, // i'm guessing 3 is actually 4/4? 0-indexed
+    pub metronome_time_signature: u8,
 
     /// `TIME SIG. DENOM` setting in `PROJECT` -> `CONTROL` -> `METRONOME` UI menu.
-    /// Controls the numerator for time signature (the 3 in 3/4).
+    /// Controls the denominator for time signature (the 4 in 3/4).
+    /// Stored as an index into `METRONOME_DENOMINATORS`, so the default of `2` is a denominator of 4.
     /// See Manual section 8.6.6 METRONOME
-    pub metronome_time_signature_denominator: u8, // i'm guessing 2 is actually 4/4? 0-indexed
+    pub metronome_time_signature_denominator: u8,
 
     /// `PREROLL` setting in `PROJECT` -> `CONTROL` -> `METRONOME` UI menu.
     /// How many bars to prerolls with the metronome before playing a pattern.
     /// See Manual section 8.6.6 METRONOME
-    pub metronome_preroll: u8, // what is the maximum for this?
+    pub metronome_preroll: u8,
 
     /// How loud to play the metronome on CUE outputs. Default is 32.
     /// See Manual section 8.6.6 METRONOME
@@ -494,47 +533,47 @@ impl FromHashMap for MidiSyncMidiPage {
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct MidiChannelsMidiPage {
     /// `TRIG CH 1` setting in `PROJECT` -> `CONTROL` -> `MIDI` -> `CHANNELS` UI menu.
-    /// MIDI Channel to send MIDI Trig 1 messages to (1 - 16)
+    /// MIDI Channel to send MIDI Trig 1 messages to (1 - 16, stored 0-indexed)
     /// See manual section 8.7.3 CHANNELS.
     pub midi_trig_ch1: u8,
 
     /// `TRIG CH 2` setting in `PROJECT` -> `CONTROL` -> `MIDI` -> `CHANNELS` UI menu.
-    /// MIDI Channel to send MIDI Trig 2 messages to (1 - 16)
+    /// MIDI Channel to send MIDI Trig 2 messages to (1 - 16, stored 0-indexed)
     /// See manual section 8.7.3 CHANNELS.
     pub midi_trig_ch2: u8,
 
     /// `TRIG CH 3` setting in `PROJECT` -> `CONTROL` -> `MIDI` -> `CHANNELS` UI menu.
-    /// MIDI Channel to send MIDI Trig 3 messages to (1 - 16)
+    /// MIDI Channel to send MIDI Trig 3 messages to (1 - 16, stored 0-indexed)
     /// See manual section 8.7.3 CHANNELS.
     pub midi_trig_ch3: u8,
 
     /// `TRIG CH 4` setting in `PROJECT` -> `CONTROL` -> `MIDI` -> `CHANNELS` UI menu.
-    /// MIDI Channel to send MIDI Trig 4 messages to (1 - 16)
+    /// MIDI Channel to send MIDI Trig 4 messages to (1 - 16, stored 0-indexed)
     /// See manual section 8.7.3 CHANNELS.
     pub midi_trig_ch4: u8,
 
     /// `TRIG CH 5` setting in `PROJECT` -> `CONTROL` -> `MIDI` -> `CHANNELS` UI menu.
-    /// MIDI Channel to send MIDI Trig 5 messages to (1 - 16)
+    /// MIDI Channel to send MIDI Trig 5 messages to (1 - 16, stored 0-indexed)
     /// See manual section 8.7.3 CHANNELS.
     pub midi_trig_ch5: u8,
 
     /// `TRIG CH 6` setting in `PROJECT` -> `CONTROL` -> `MIDI` -> `CHANNELS` UI menu.
-    /// MIDI Channel to send MIDI Trig 6 messages to (1 - 16)
+    /// MIDI Channel to send MIDI Trig 6 messages to (1 - 16, stored 0-indexed)
     /// See manual section 8.7.3 CHANNELS.
     pub midi_trig_ch6: u8,
 
     /// `TRIG CH 7` setting in `PROJECT` -> `CONTROL` -> `MIDI` -> `CHANNELS` UI menu.
-    /// MIDI Channel to send MIDI Trig 7 messages to (1 - 16)
+    /// MIDI Channel to send MIDI Trig 7 messages to (1 - 16, stored 0-indexed)
     /// See manual section 8.7.3 CHANNELS.
     pub midi_trig_ch7: u8,
 
     /// `TRIG CH 8` setting in `PROJECT` -> `CONTROL` -> `MIDI` -> `CHANNELS` UI menu.
-    /// MIDI Channel to send MIDI Trig 8 messages to (1 - 16)
+    /// MIDI Channel to send MIDI Trig 8 messages to (1 - 16, stored 0-indexed)
     /// See manual section 8.7.3 CHANNELS.
     pub midi_trig_ch8: u8,
 
     /// `AUTO CH` setting in `PROJECT` -> `CONTROL` -> `MIDI` -> `CHANNELS` UI menu.
-    /// Auto MIDI Channel (1 - 16)
+    /// Auto MIDI Channel (1 - 16, stored 0-indexed)
     /// See manual section 8.7.3 CHANNELS.
     pub midi_auto_channel: u8,
 }
@@ -558,3 +597,274 @@ impl FromHashMap for MidiChannelsMidiPage {
         })
     }
 }
+
+impl InputControlPage {
+    /// Set the AB input noise gate level (0 to 127).
+    pub fn set_gate_ab(&mut self, x: u8) -> RBoxErr<()> {
+        self.gate_ab = check_setting_range("GATE AB", x, INPUT_GATE_RANGE)?;
+        Ok(())
+    }
+
+    /// Set the CD input noise gate level (0 to 127).
+    pub fn set_gate_cd(&mut self, x: u8) -> RBoxErr<()> {
+        self.gate_cd = check_setting_range("GATE CD", x, INPUT_GATE_RANGE)?;
+        Ok(())
+    }
+}
+
+impl MemoryControlPage {
+    /// Set the number of reserved track recorders (0 to 8).
+    pub fn set_reserved_recorder_count(&mut self, x: u8) -> RBoxErr<()> {
+        self.reserved_recorder_count =
+            check_setting_range("RESERVED RECORDERS", x, RESERVED_RECORDER_COUNT_RANGE)?;
+        Ok(())
+    }
+
+    /// Set the reserved track recorder length in sequencer steps (1 to 64).
+    pub fn set_reserved_recorder_length(&mut self, x: u32) -> RBoxErr<()> {
+        self.reserved_recorder_length =
+            check_setting_range("RESERVE LENGTH", x, RESERVED_RECORDER_LENGTH_RANGE)?;
+        Ok(())
+    }
+}
+
+impl MetronomeControlPage {
+    /// Time signature as shown on the device, e.g. `(4, 4)` for the default 4/4.
+    /// Returns `None` when the stored denominator is unknown.
+    pub fn time_signature(&self) -> Option<(u8, u8)> {
+        let denominator = METRONOME_DENOMINATORS
+            .get(self.metronome_time_signature_denominator as usize)
+            .copied()?;
+        Some((self.metronome_time_signature + 1, denominator))
+    }
+
+    /// Set the time signature, e.g. `(3, 4)` for 3/4.
+    /// Numerators can be 1 to 16, denominators 1, 2, 4, 8 or 16.
+    pub fn set_time_signature(&mut self, numerator: u8, denominator: u8) -> RBoxErr<()> {
+        let numerator =
+            check_setting_range("TIME SIG. NUMER", numerator, METRONOME_NUMERATOR_RANGE)?;
+        let denominator_idx = METRONOME_DENOMINATORS
+            .iter()
+            .position(|x| *x == denominator)
+            .ok_or(ProjectSettingsErrors::InvalidTimeSignatureDenominator(
+                denominator,
+            ))?;
+        self.metronome_time_signature = numerator - 1;
+        self.metronome_time_signature_denominator = denominator_idx as u8;
+        Ok(())
+    }
+
+    /// Set the number of preroll bars (0 to 16).
+    pub fn set_preroll(&mut self, x: u8) -> RBoxErr<()> {
+        self.metronome_preroll = check_setting_range("PREROLL", x, METRONOME_PREROLL_RANGE)?;
+        Ok(())
+    }
+
+    /// Set the metronome volume on the CUE outputs (0 to 127).
+    pub fn set_cue_volume(&mut self, x: u8) -> RBoxErr<()> {
+        self.metronome_cue_volume = check_setting_range("CUE VOLUME", x, METRONOME_VOLUME_RANGE)?;
+        Ok(())
+    }
+
+    /// Set the metronome volume on the MAIN outputs (0 to 127).
+    pub fn set_main_volume(&mut self, x: u8) -> RBoxErr<()> {
+        self.metronome_main_volume = check_setting_range("MAIN VOLUME", x, METRONOME_VOLUME_RANGE)?;
+        Ok(())
+    }
+
+    /// Set the pitch of the metronome clicks (0 to 24).
+    pub fn set_pitch(&mut self, x: u8) -> RBoxErr<()> {
+        self.metronome_pitch = check_setting_range("PITCH", x, METRONOME_PITCH_RANGE)?;
+        Ok(())
+    }
+}
+
+impl MidiControlMidiPage {
+    /// Set `AUDIO CC OUT` (1: `INT`, 2: `EXT`, 3: `INT+EXT`).
+    pub fn set_audio_track_cc_out(&mut self, x: u8) -> RBoxErr<()> {
+        self.midi_audio_track_cc_out =
+            check_setting_range("AUDIO CC OUT", x, MIDI_AUDIO_TRACK_OUT_RANGE)?;
+        Ok(())
+    }
+
+    /// Set `AUDIO NOTE IN` (0: `OFF`, 1: `STANDARD`, 2: `FOLLOW TM`, 3: `MAP/TRACK`).
+    pub fn set_audio_track_note_in(&mut self, x: u8) -> RBoxErr<()> {
+        self.midi_audio_track_note_in =
+            check_setting_range("AUDIO NOTE IN", x, MIDI_AUDIO_TRACK_NOTE_IN_RANGE)?;
+        Ok(())
+    }
+
+    /// Set `AUDIO NOTE OUT` (1: `INT`, 2: `EXT`, 3: `INT+EXT`).
+    pub fn set_audio_track_note_out(&mut self, x: u8) -> RBoxErr<()> {
+        self.midi_audio_track_note_out =
+            check_setting_range("AUDIO NOTE OUT", x, MIDI_AUDIO_TRACK_OUT_RANGE)?;
+        Ok(())
+    }
+}
+
+/// Get a program change channel from a channel number (1 to 16), or `None` for disabled.
+fn to_progchange_channel(setting: &'static str, x: Option<u8>) -> RBoxErr<ProjectMidiChannels> {
+    match x {
+        None => Ok(ProjectMidiChannels::Disabled),
+        Some(x) => ProjectMidiChannels::from_value(
+            &(check_setting_range(setting, x, MIDI_CHANNEL_RANGE)? as i8),
+        ),
+    }
+}
+
+impl MidiSyncMidiPage {
+    /// Set the program change send channel (1 to 16), or disable sending with `None`.
+    /// Also enables / disables `PROG CH SEND` so the two settings stay consistent.
+    pub fn set_progchange_send_channel(&mut self, x: Option<u8>) -> RBoxErr<()> {
+        self.midi_progchange_send_channel = to_progchange_channel("PROG CH SEND CHANNEL", x)?;
+        self.midi_progchange_send = x.is_some();
+        Ok(())
+    }
+
+    /// Set the program change receive channel (1 to 16), or disable receiving with `None`.
+    /// Also enables / disables `PROG CH RECEIVE` so the two settings stay consistent.
+    pub fn set_progchange_receive_channel(&mut self, x: Option<u8>) -> RBoxErr<()> {
+        self.midi_progchange_receive_channel = to_progchange_channel("PROG CH RECEIVE CHANNEL", x)?;
+        self.midi_progchange_receive = x.is_some();
+        Ok(())
+    }
+}
+
+impl MidiChannelsMidiPage {
+    fn trig_channels_mut(&mut self) -> [&mut u8; 8] {
+        [
+            &mut self.midi_trig_ch1,
+            &mut self.midi_trig_ch2,
+            &mut self.midi_trig_ch3,
+            &mut self.midi_trig_ch4,
+            &mut self.midi_trig_ch5,
+            &mut self.midi_trig_ch6,
+            &mut self.midi_trig_ch7,
+            &mut self.midi_trig_ch8,
+        ]
+    }
+
+    /// MIDI channel for a trig (1 to 8) as shown on the device (1 to 16).
+    pub fn trig_channel(&self, trig: u8) -> RBoxErr<u8> {
+        let trig = check_setting_range("TRIG", trig, 1..=8)?;
+        let channels = [
+            self.midi_trig_ch1,
+            self.midi_trig_ch2,
+            self.midi_trig_ch3,
+            self.midi_trig_ch4,
+            self.midi_trig_ch5,
+            self.midi_trig_ch6,
+            self.midi_trig_ch7,
+            self.midi_trig_ch8,
+        ];
+        Ok(channels[trig as usize - 1] + 1)
+    }
+
+    /// Set the MIDI channel (1 to 16) for a trig (1 to 8).
+    pub fn set_trig_channel(&mut self, trig: u8, channel: u8) -> RBoxErr<()> {
+        let trig = check_setting_range("TRIG", trig, 1..=8)?;
+        let channel = check_setting_range("TRIG CH", channel, MIDI_CHANNEL_RANGE)?;
+        *self.trig_channels_mut()[trig as usize - 1] = channel - 1;
+        Ok(())
+    }
+
+    /// `AUTO CH` as shown on the device (1 to 16).
+    pub fn auto_channel(&self) -> u8 {
+        self.midi_auto_channel + 1
+    }
+
+    /// Set `AUTO CH` (1 to 16).
+    pub fn set_auto_channel(&mut self, channel: u8) -> RBoxErr<()> {
+        let channel = check_setting_range("AUTO CH", channel, MIDI_CHANNEL_RANGE)?;
+        self.midi_auto_channel = channel - 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use crate::projects::settings::ProjectSettings;
+
+    mod sequencer {
+        use super::*;
+
+        #[test]
+        fn silence_tracks_parsed_from_own_key() {
+            let hmap: HashMap<String, String> = [
+                ("pattern_change_chain_behavior", "0"),
+                ("pattern_change_auto_silence_tracks", "1"),
+                ("pattern_change_auto_trig_lfos", "0"),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+            let page = SequencerControlPage::from_hashmap(&hmap).unwrap();
+            assert!(page.pattern_change_auto_silence_tracks);
+            assert!(!page.pattern_change_auto_trig_lfos);
+        }
+    }
+
+    mod metronome {
+        use super::*;
+
+        #[test]
+        fn default_time_signature() {
+            let page = ProjectSettings::default().control.metronome;
+            assert_eq!(page.time_signature(), Some((4, 4)));
+        }
+
+        #[test]
+        fn set_time_signature() {
+            let mut page = ProjectSettings::default().control.metronome;
+            page.set_time_signature(7, 8).unwrap();
+            assert_eq!(page.metronome_time_signature, 6);
+            assert_eq!(page.metronome_time_signature_denominator, 3);
+            assert_eq!(page.time_signature(), Some((7, 8)));
+        }
+
+        #[test]
+        fn set_time_signature_invalid_denominator_err() {
+            let mut page = ProjectSettings::default().control.metronome;
+            assert!(page.set_time_signature(4, 3).is_err());
+            assert!(page.set_time_signature(0, 4).is_err());
+            assert_eq!(page.time_signature(), Some((4, 4)));
+        }
+    }
+
+    mod midi {
+        use super::*;
+
+        #[test]
+        fn default_channels() {
+            let page = ProjectSettings::default().control.midi.channels;
+            assert_eq!(page.trig_channel(1).unwrap(), 1);
+            assert_eq!(page.trig_channel(8).unwrap(), 8);
+            assert_eq!(page.auto_channel(), 11);
+        }
+
+        #[test]
+        fn set_trig_channel() {
+            let mut page = ProjectSettings::default().control.midi.channels;
+            page.set_trig_channel(3, 16).unwrap();
+            assert_eq!(page.midi_trig_ch3, 15);
+            assert!(page.set_trig_channel(9, 1).is_err());
+            assert!(page.set_trig_channel(1, 0).is_err());
+        }
+
+        #[test]
+        fn set_progchange_channel_enables() {
+            let mut page = ProjectSettings::default().control.midi.sync;
+            page.set_progchange_send_channel(Some(5)).unwrap();
+            assert!(page.midi_progchange_send);
+            assert_eq!(page.midi_progchange_send_channel, ProjectMidiChannels::Five);
+            page.set_progchange_send_channel(None).unwrap();
+            assert!(!page.midi_progchange_send);
+            assert_eq!(
+                page.midi_progchange_send_channel,
+                ProjectMidiChannels::Disabled
+            );
+        }
+    }
+}
@@ -3,7 +3,17 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::projects::{parse_hashmap_string_value, FromHashMap};
+use crate::projects::{parse_hashmap_string_value, settings::check_setting_range, FromHashMap};
+use crate::RBoxErr;
+
+/// Range of the signed `GAIN` / `LEVEL` values shown on the device.
+pub const MIXER_SIGNED_RANGE: std::ops::RangeInclusive<i8> = -64..=63;
+
+/// Range of the unsigned `DIR` / `MIX` values shown on the device.
+pub const MIXER_UNSIGNED_RANGE: std::ops::RangeInclusive<u8> = 0..=127;
+
+/// Signed gain / level values are stored with this offset added, e.g. `0` is stored as `64`.
+const MIXER_SIGNED_OFFSET: i16 = 64;
 
 /// Global `MIXER` UI menu.
 
@@ -39,7 +49,7 @@ pub struct MixerMenu {
 
     /// Final gain / output level of the cue outputs. -64 to 63 range. 0 is default.
     /// See Manual section 8.8 MIXER MENU
-    pub cue_level: u8, // no idea what params max mins are here
+    pub cue_level: u8,
 }
 
 impl FromHashMap for MixerMenu {
@@ -60,3 +70,122 @@ impl FromHashMap for MixerMenu {
         })
     }
 }
+
+/// Convert a stored gain / level value to the signed value shown on the device.
+fn to_signed(x: u8) -> i8 {
+    (x as i16 - MIXER_SIGNED_OFFSET) as i8
+}
+
+/// Convert a signed gain / level value shown on the device to the stored value.
+fn from_signed(setting: &'static str, x: i8) -> RBoxErr<u8> {
+    let x = check_setting_range(setting, x, MIXER_SIGNED_RANGE)?;
+    Ok((x as i16 + MIXER_SIGNED_OFFSET) as u8)
+}
+
+impl MixerMenu {
+    /// `GAIN AB` as shown on the device (-64 to +63).
+    pub fn gain_ab_signed(&self) -> i8 {
+        to_signed(self.gain_ab)
+    }
+
+    /// Set `GAIN AB` from the value shown on the device (-64 to +63).
+    pub fn set_gain_ab(&mut self, x: i8) -> RBoxErr<()> {
+        self.gain_ab = from_signed("GAIN AB", x)?;
+        Ok(())
+    }
+
+    /// `GAIN CD` as shown on the device (-64 to +63).
+    pub fn gain_cd_signed(&self) -> i8 {
+        to_signed(self.gain_cd)
+    }
+
+    /// Set `GAIN CD` from the value shown on the device (-64 to +63).
+    pub fn set_gain_cd(&mut self, x: i8) -> RBoxErr<()> {
+        self.gain_cd = from_signed("GAIN CD", x)?;
+        Ok(())
+    }
+
+    /// Main output `LEVEL` as shown on the device (-64 to +63).
+    pub fn main_level_signed(&self) -> i8 {
+        to_signed(self.main_level)
+    }
+
+    /// Set the main output `LEVEL` from the value shown on the device (-64 to +63).
+    pub fn set_main_level(&mut self, x: i8) -> RBoxErr<()> {
+        self.main_level = from_signed("MAIN LEVEL", x)?;
+        Ok(())
+    }
+
+    /// Cue output `LEVEL` as shown on the device (-64 to +63).
+    pub fn cue_level_signed(&self) -> i8 {
+        to_signed(self.cue_level)
+    }
+
+    /// Set the cue output `LEVEL` from the value shown on the device (-64 to +63).
+    pub fn set_cue_level(&mut self, x: i8) -> RBoxErr<()> {
+        self.cue_level = from_signed("CUE LEVEL", x)?;
+        Ok(())
+    }
+
+    /// Set `DIR AB` (0 to 127).
+    pub fn set_dir_ab(&mut self, x: u8) -> RBoxErr<()> {
+        self.dir_ab = check_setting_range("DIR AB", x, MIXER_UNSIGNED_RANGE)?;
+        Ok(())
+    }
+
+    /// Set `DIR CD` (0 to 127).
+    pub fn set_dir_cd(&mut self, x: u8) -> RBoxErr<()> {
+        self.dir_cd = check_setting_range("DIR CD", x, MIXER_UNSIGNED_RANGE)?;
+        Ok(())
+    }
+
+    /// Set the headphones `MIX` (0 to 127).
+    pub fn set_phones_mix(&mut self, x: u8) -> RBoxErr<()> {
+        self.phones_mix = check_setting_range("PHONES MIX", x, MIXER_UNSIGNED_RANGE)?;
+        Ok(())
+    }
+
+    /// Set `MAIN TO CUE` (0 to 127).
+    pub fn set_main_to_cue(&mut self, x: u8) -> RBoxErr<()> {
+        self.main_to_cue = check_setting_range("MAIN TO CUE", x, MIXER_UNSIGNED_RANGE)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use crate::projects::settings::ProjectSettings;
+
+    #[test]
+    fn default_gain_is_zero() {
+        let mixer = ProjectSettings::default().mixer;
+        assert_eq!(mixer.gain_ab_signed(), 0);
+        assert_eq!(mixer.main_level_signed(), 0);
+    }
+
+    #[test]
+    fn set_gain_signed_range() {
+        let mut mixer = ProjectSettings::default().mixer;
+        mixer.set_gain_ab(-64).unwrap();
+        assert_eq!(mixer.gain_ab, 0);
+        mixer.set_gain_ab(63).unwrap();
+        assert_eq!(mixer.gain_ab, 127);
+        assert_eq!(mixer.gain_ab_signed(), 63);
+    }
+
+    #[test]
+    fn set_gain_out_of_range_err() {
+        let mut mixer = ProjectSettings::default().mixer;
+        assert!(mixer.set_gain_cd(64).is_err());
+        assert!(mixer.set_gain_cd(-65).is_err());
+        assert_eq!(mixer.gain_cd, 64);
+    }
+
+    #[test]
+    fn set_dir_out_of_range_err() {
+        let mut mixer = ProjectSettings::default().mixer;
+        assert!(mixer.set_dir_ab(128).is_err());
+    }
+}
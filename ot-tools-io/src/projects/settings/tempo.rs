@@ -4,7 +4,23 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::projects::{parse_hashmap_string_value, parse_hashmap_string_value_bool, FromHashMap};
+use crate::projects::{
+    parse_hashmap_string_value, parse_hashmap_string_value_bool, settings::ProjectSettingsErrors,
+    FromHashMap,
+};
+use crate::RBoxErr;
+
+/// Project tempo range (BPM).
+pub const TEMPO_RANGE: std::ops::RangeInclusive<u32> = 30..=300;
+
+/// Check a tempo (BPM) is within [`TEMPO_RANGE`]. Fractional tempos are allowed, as used by
+/// sample attributes files.
+pub fn check_tempo(bpm: f32) -> RBoxErr<f32> {
+    if !(*TEMPO_RANGE.start() as f32..=*TEMPO_RANGE.end() as f32).contains(&bpm) {
+        return Err(ProjectSettingsErrors::InvalidTempo(bpm).into());
+    }
+    Ok(bpm)
+}
 
 /// Global `TEMPO` UI menu.
 
@@ -37,3 +53,38 @@ impl FromHashMap for TempoMenu {
         })
     }
 }
+
+impl TempoMenu {
+    /// Set the project tempo (30 to 300 BPM).
+    pub fn set_tempo(&mut self, bpm: u32) -> RBoxErr<()> {
+        check_tempo(bpm as f32)?;
+        self.tempo = bpm;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::projects::settings::ProjectSettings;
+
+    #[test]
+    fn check_tempo_range() {
+        assert_eq!(check_tempo(30.0).unwrap(), 30.0);
+        assert_eq!(check_tempo(300.0).unwrap(), 300.0);
+        assert_eq!(check_tempo(125.5).unwrap(), 125.5);
+        assert!(check_tempo(29.9).is_err());
+        assert!(check_tempo(300.1).is_err());
+    }
+
+    #[test]
+    fn set_tempo_out_of_range_err() {
+        let mut tempo = ProjectSettings::default().tempo;
+        let err = tempo.set_tempo(301).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ProjectSettingsErrors>(),
+            Some(&ProjectSettingsErrors::InvalidTempo(301.0))
+        );
+        assert_eq!(tempo.tempo, ProjectSettings::default().tempo.tempo);
+    }
+}
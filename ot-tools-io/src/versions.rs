@@ -0,0 +1,324 @@
+//! Octatrack OS versions, used as a gate on which projects ot-tools can write.
+//!
+//! Project files record the OS version that last wrote them, e.g.
+//! `OS_VERSION=R0177     1.40B` (a release identifier followed by the OS version).
+//! The OS version, along with the project file's `VERSION`, decides whether ot-tools can safely
+//! write files for a project or only read them.
+//!
+//! **NOTE**: This is only a gate, not a choice of file layout. The types in this crate are the
+//! OS 1.40 layout, and files written by other OS versions are read with it but never written.
+//! Only the project file is checked, bank and arrangement files are assumed to match the project.
+
+use crate::projects::{metadata::ProjectMetadata, Project};
+use crate::RBoxErr;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// OS versions which ot-tools can write files for.
+pub const READ_WRITE_OS_VERSIONS: [&str; 3] = ["1.40A", "1.40B", "1.40C"];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OsVersionErrors {
+    /// OS version string could not be parsed, e.g. missing the `1.40B` part
+    InvalidOsVersionString(String),
+}
+impl std::fmt::Display for OsVersionErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::InvalidOsVersionString(x) => write!(
+                f,
+                "invalid OS version {x:?} - expected a release id and version, e.g. 'R0177     1.40B'"
+            ),
+        }
+    }
+}
+impl Error for OsVersionErrors {}
+
+/// An Octatrack OS version, e.g. `1.40B`. Versions are ordered by release.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OsVersion {
+    pub major: u8,
+    pub minor: u8,
+    /// Letter suffix for revisions of a release, e.g. `B` for `1.40B`
+    pub revision: Option<char>,
+}
+
+impl OsVersion {
+    pub const fn new(major: u8, minor: u8, revision: Option<char>) -> Self {
+        Self {
+            major,
+            minor,
+            revision,
+        }
+    }
+
+    /// Parse a version string, e.g. `1.40B` or `1.30`.
+    pub fn parse(s: &str) -> RBoxErr<Self> {
+        let err = || OsVersionErrors::InvalidOsVersionString(s.to_string());
+
+        let (major, rest) = s.split_once('.').ok_or_else(err)?;
+        let minor_len = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        let (minor, revision) = rest.split_at(minor_len);
+
+        let mut revision_chars = revision.chars();
+        let revision = match (revision_chars.next(), revision_chars.next()) {
+            (None, _) => None,
+            (Some(c), None) if c.is_ascii_uppercase() => Some(c),
+            _ => return Err(err().into()),
+        };
+
+        Ok(Self {
+            major: major.parse::<u8>().map_err(|_| err())?,
+            minor: minor.parse::<u8>().map_err(|_| err())?,
+            revision,
+        })
+    }
+}
+
+impl std::fmt::Display for OsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{:02}", self.major, self.minor)?;
+        if let Some(c) = self.revision {
+            write!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The OS version string from a project file, e.g. `R0177     1.40B`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OsRelease {
+    /// Release identifier, e.g. `R0177`
+    pub release_id: String,
+    pub version: OsVersion,
+}
+
+impl OsRelease {
+    /// Parse an `OS_VERSION` value from a project file.
+    pub fn parse(s: &str) -> RBoxErr<Self> {
+        let mut parts = s.split_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(release_id), Some(version), None) => Ok(Self {
+                release_id: release_id.to_string(),
+                version: OsVersion::parse(version)?,
+            }),
+            _ => Err(OsVersionErrors::InvalidOsVersionString(s.to_string()).into()),
+        }
+    }
+}
+
+impl ProjectMetadata {
+    /// OS release which last wrote the project.
+    pub fn os_release(&self) -> RBoxErr<OsRelease> {
+        OsRelease::parse(&self.os_version)
+    }
+}
+
+/// What ot-tools can do with files written by an OS version.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionSupport {
+    /// Files can be read and written
+    ReadWrite,
+    /// Files can be read (inspected, exported etc.) but are never written
+    ReadOnly,
+    /// The OS version can't be determined, so files can't be read safely
+    Unsupported,
+}
+
+/// Project file `VERSION` (in the `[META]` section) written by OS 1.40, which matches the types
+/// in this crate.
+pub const PROJECT_VERSION_1_40: u32 = 19;
+
+/// A range of OS versions (inclusive) and what ot-tools can do with the projects they write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OsVersionGate {
+    pub min: OsVersion,
+    pub max: OsVersion,
+    /// Project file `VERSION` the OS versions write. Projects with a different `VERSION` are only
+    /// ever read.
+    pub project_version: u32,
+    pub support: VersionSupport,
+}
+
+/// Support for each known OS version range, checked in order.
+pub const OS_VERSION_GATES: [OsVersionGate; 2] = [
+    OsVersionGate {
+        min: OsVersion::new(1, 40, Some('A')),
+        max: OsVersion::new(1, 40, Some('C')),
+        project_version: PROJECT_VERSION_1_40,
+        support: VersionSupport::ReadWrite,
+    },
+    // other versions are unknown, assume they can be read like 1.40 but don't write anything
+    OsVersionGate {
+        min: OsVersion::new(0, 0, None),
+        max: OsVersion::new(u8::MAX, u8::MAX, Some('Z')),
+        project_version: PROJECT_VERSION_1_40,
+        support: VersionSupport::ReadOnly,
+    },
+];
+
+/// Find the gate for an OS version.
+pub fn get_os_version_gate(version: &OsVersion) -> &'static OsVersionGate {
+    OS_VERSION_GATES
+        .iter()
+        .find(|x| (x.min..=x.max).contains(version))
+        // the last entry covers every version
+        .unwrap_or(&OS_VERSION_GATES[OS_VERSION_GATES.len() - 1])
+}
+
+/// What ot-tools can do with a project, based on the OS version which last wrote it.
+/// Projects with a different project file `VERSION` than their OS version writes can only be read.
+pub fn get_project_version_support(project: &Project) -> VersionSupport {
+    let Ok(release) = project.metadata.os_release() else {
+        return VersionSupport::Unsupported;
+    };
+    let gate = get_os_version_gate(&release.version);
+    if project.metadata.project_version != gate.project_version {
+        return VersionSupport::ReadOnly;
+    }
+    gate.support
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+
+    mod parse {
+        use super::*;
+
+        #[test]
+        fn release() {
+            let release = OsRelease::parse("R0177     1.40B").unwrap();
+            assert_eq!(release.release_id, "R0177");
+            assert_eq!(release.version, OsVersion::new(1, 40, Some('B')));
+        }
+
+        #[test]
+        fn version_without_revision() {
+            assert_eq!(
+                OsVersion::parse("1.30").unwrap(),
+                OsVersion::new(1, 30, None)
+            );
+        }
+
+        #[test]
+        fn invalid() {
+            for x in [
+                "",
+                "1.40B",
+                "R0177",
+                "R0177 1.40b",
+                "R0177 140B",
+                "R0177 1.40BB",
+            ] {
+                assert!(OsRelease::parse(x).is_err(), "{x}");
+            }
+        }
+
+        #[test]
+        fn display_roundtrip() {
+            for x in ["1.40B", "1.30", "1.05A"] {
+                assert_eq!(OsVersion::parse(x).unwrap().to_string(), x);
+            }
+        }
+
+        #[test]
+        fn order() {
+            let v = |x| OsVersion::parse(x).unwrap();
+            assert!(v("1.30C") < v("1.40"));
+            assert!(v("1.40") < v("1.40A"));
+            assert!(v("1.40A") < v("1.40C"));
+            assert!(v("1.40C") < v("1.41"));
+        }
+    }
+
+    mod support {
+        use super::*;
+
+        fn project(os_version: &str) -> Project {
+            let mut project = Project::default();
+            project.metadata.os_version = os_version.to_string();
+            project
+        }
+
+        #[test]
+        fn read_write() {
+            for x in READ_WRITE_OS_VERSIONS {
+                assert_eq!(
+                    get_project_version_support(&project(&format!("R0177     {x}"))),
+                    VersionSupport::ReadWrite,
+                    "{x}"
+                );
+            }
+        }
+
+        #[test]
+        fn read_only() {
+            for x in ["1.25H", "1.30C", "1.39Z", "1.40", "1.40D", "1.41"] {
+                assert_eq!(
+                    get_project_version_support(&project(&format!("R0177     {x}"))),
+                    VersionSupport::ReadOnly,
+                    "{x}"
+                );
+            }
+        }
+
+        #[test]
+        fn unsupported_os_version_string() {
+            assert_eq!(
+                get_project_version_support(&project("1.40B")),
+                VersionSupport::Unsupported
+            );
+        }
+
+        #[test]
+        fn unknown_project_version_read_only() {
+            let mut x = project("R0177     1.40B");
+            x.metadata.project_version = 18;
+            assert_eq!(get_project_version_support(&x), VersionSupport::ReadOnly);
+        }
+
+        #[test]
+        fn default_project_read_write() {
+            assert_eq!(
+                get_project_version_support(&Project::default()),
+                VersionSupport::ReadWrite
+            );
+        }
+    }
+
+    mod gate {
+        use super::*;
+        use crate::read_type_from_bin_file;
+        use std::path::Path;
+
+        #[test]
+        fn default_project_matches_1_40() {
+            let gate = get_os_version_gate(&OsVersion::new(1, 40, Some('B')));
+            assert_eq!(gate, &OS_VERSION_GATES[0]);
+            assert_eq!(
+                Project::default().metadata.project_version,
+                gate.project_version
+            );
+        }
+
+        #[test]
+        fn blank_project_matches_1_40() {
+            let dirpath = Path::new("../data/tests/blank-project");
+            let project =
+                read_type_from_bin_file::<Project>(&dirpath.join("project.work")).unwrap();
+
+            let release = project.metadata.os_release().unwrap();
+            let gate = get_os_version_gate(&release.version);
+            assert_eq!(project.metadata.project_version, gate.project_version);
+            assert_eq!(gate.support, VersionSupport::ReadWrite);
+        }
+
+        #[test]
+        fn unknown_versions_read_only() {
+            let gate = get_os_version_gate(&OsVersion::new(1, 25, Some('H')));
+            assert_eq!(gate.support, VersionSupport::ReadOnly);
+        }
+    }
+}
@@ -4,9 +4,11 @@ use crate::RBoxErr;
 use ot_tools_io::banks::parts::Part;
 use ot_tools_io::banks::patterns::Pattern;
 use ot_tools_io::projects::options::ProjectSampleSlotType;
+use std::collections::HashMap;
 
 pub mod arrangements;
 pub mod banks;
+pub mod merge;
 pub mod parts;
 pub mod patterns;
 pub mod projects;
@@ -41,46 +43,57 @@ pub fn pattern_update_sample_slot_refs(
     sample_type: &ProjectSampleSlotType,
     old: &u8,
     new: &u8,
+) -> RBoxErr<()> {
+    pattern_remap_sample_slot_refs(pattern, sample_type, &HashMap::from([(*old, *new)]))
+}
+
+pub fn part_update_sample_slot_refs(
+    part: &mut Part,
+    sample_type: &ProjectSampleSlotType,
+    old: &u8,
+    new: &u8,
+) -> RBoxErr<()> {
+    part_remap_sample_slot_refs(part, sample_type, &HashMap::from([(*old, *new)]))
+}
+
+/// Change all pattern p-lock references to sample slots in one pass, so that swapping slot
+/// references (`1 -> 2` and `2 -> 1`) doesn't reassign references more than once.
+/// Slot IDs missing from `remap` are left unchanged.
+pub fn pattern_remap_sample_slot_refs(
+    pattern: &mut Pattern,
+    sample_type: &ProjectSampleSlotType,
+    remap: &HashMap<u8, u8>,
 ) -> RBoxErr<()> {
     for audio_track_trigs in pattern.audio_track_trigs.iter_mut() {
         for plock in audio_track_trigs.plocks.iter_mut() {
-            match sample_type {
-                ProjectSampleSlotType::Static => {
-                    if plock.static_slot_id == *old {
-                        plock.static_slot_id = *new;
-                    }
-                }
-                ProjectSampleSlotType::Flex => {
-                    if plock.flex_slot_id == *old {
-                        plock.flex_slot_id = *new;
-                    }
-                }
-                ProjectSampleSlotType::RecorderBuffer => {}
+            let slot_id = match sample_type {
+                ProjectSampleSlotType::Static => &mut plock.static_slot_id,
+                ProjectSampleSlotType::Flex => &mut plock.flex_slot_id,
+                ProjectSampleSlotType::RecorderBuffer => continue,
+            };
+            if let Some(new) = remap.get(slot_id) {
+                *slot_id = *new;
             }
         }
     }
     Ok(())
 }
 
-pub fn part_update_sample_slot_refs(
+/// Change all audio track machine references to sample slots in a part in one pass, see
+/// `pattern_remap_sample_slot_refs`.
+pub fn part_remap_sample_slot_refs(
     part: &mut Part,
     sample_type: &ProjectSampleSlotType,
-    old: &u8,
-    new: &u8,
+    remap: &HashMap<u8, u8>,
 ) -> RBoxErr<()> {
     for audio_track_slots in part.audio_track_machine_slots.iter_mut() {
-        match sample_type {
-            ProjectSampleSlotType::Static => {
-                if audio_track_slots.static_slot_id == *old {
-                    audio_track_slots.static_slot_id = *new;
-                }
-            }
-            ProjectSampleSlotType::Flex => {
-                if audio_track_slots.flex_slot_id == *old {
-                    audio_track_slots.flex_slot_id = *new;
-                }
-            }
-            ProjectSampleSlotType::RecorderBuffer => {}
+        let slot_id = match sample_type {
+            ProjectSampleSlotType::Static => &mut audio_track_slots.static_slot_id,
+            ProjectSampleSlotType::Flex => &mut audio_track_slots.flex_slot_id,
+            ProjectSampleSlotType::RecorderBuffer => continue,
+        };
+        if let Some(new) = remap.get(slot_id) {
+            *slot_id = *new;
         }
     }
 
@@ -7,17 +7,21 @@ pub(crate) mod utils;
 mod yaml;
 
 use crate::{
-    actions::banks::yaml::YamlCopyBankConfig, validate_project_version, OctatoolErrors, RBoxErr,
+    actions::banks::yaml::YamlCopyBankConfig,
+    transaction::{
+        finish_transaction, get_backups_root_for_project, PlannedSlotChange, SlotChangeType,
+        Transaction,
+    },
+    validate_project_version, OctatoolErrors, RBoxErr,
 };
 use itertools::Itertools;
 use ot_tools_io::projects::options::ProjectSampleSlotType;
 use ot_tools_io::{
-    banks::Bank, projects::Project, read_type_from_bin_file, write_type_to_bin_file,
-    yaml_file_to_type, IsDefault,
+    banks::Bank, projects::Project, read_type_from_bin_file, yaml_file_to_type, IsDefault,
 };
 use std::{path::Path, path::PathBuf};
 use utils::{
-    calculate_copy_bank_changes, create_backup_of_work_file, find_sample_slot_refs_in_bank,
+    calculate_copy_bank_changes, find_reused_sample_slots, find_sample_slot_refs_in_bank,
     get_bank_fname_from_id, get_zero_indexed_slots_from_one_indexed, transfer_sample_files,
     BankCopyPathsMeta, BankMeta, BankSlotReferenceType, ProjectMeta,
 };
@@ -74,6 +78,31 @@ pub fn copy_bank_by_paths(
     destination_bank_number: usize,
     force: bool,
 ) -> RBoxErr<()> {
+    let tx = stage_copy_bank_by_paths(
+        source_project_dirpath,
+        destination_project_dirpath,
+        source_bank_number,
+        destination_bank_number,
+        force,
+    )?;
+
+    println!("Writing changes to destination ...");
+    finish_transaction(tx, false, None)?;
+
+    println!("===================================================================================");
+    println!("Bank copy complete.");
+    Ok(())
+}
+
+/// Calculate and stage all the file changes needed to copy a bank (see `copy_bank_by_paths`)
+/// without changing anything on the file system.
+pub fn stage_copy_bank_by_paths(
+    source_project_dirpath: &Path,
+    destination_project_dirpath: &Path,
+    source_bank_number: usize,
+    destination_bank_number: usize,
+    force: bool,
+) -> RBoxErr<Transaction> {
     if !(1..=16).contains(&source_bank_number) || !(1..=16).contains(&destination_bank_number) {
         return Err(Box::new(CliBankErrors::InvalidBankIndex));
     }
@@ -125,9 +154,6 @@ pub fn copy_bank_by_paths(
         return Err(Box::new(OctatoolErrors::PathDoesNotExist));
     }
 
-    create_backup_of_work_file(&destination_meta.project.filepath)?;
-    create_backup_of_work_file(&destination_meta.bank.filepath)?;
-
     let dest_project = read_type_from_bin_file::<Project>(&destination_meta.project.filepath)?;
 
     if !validate_project_version(&dest_project) {
@@ -139,42 +165,68 @@ pub fn copy_bank_by_paths(
     println!("===================================================================================");
     println!("Calculating changes ...");
 
-    let (new_project, new_bank, sample_transfers) =
-        calculate_copy_bank_changes(source_project_dirpath, &src_project, &bank, &dest_project)?;
+    let (new_project, new_bank, sample_transfers) = calculate_copy_bank_changes(
+        source_project_dirpath,
+        &src_project,
+        &bank,
+        destination_project_dirpath,
+        &dest_project,
+    )?;
 
     println!("===================================================================================");
 
     /*
     ================================================================================================
 
-    This is where we begin making changes on the file system. If you want to
-    include some warnings to the user about potentially destructive actions
-    occurring -- __now is the time to do it__!!!
+    Changes are only staged from here on, nothing on the file system changes until the
+    transaction is committed. If you want to include some warnings to the user about potentially
+    destructive actions occurring -- __do it before committing__!!!
 
     ================================================================================================
     */
 
+    let mut tx = Transaction::new(
+        &get_backups_root_for_project(destination_project_dirpath)?,
+        &format!(
+            "copy bank {source_bank_number} from {source_project_dirpath:?} to bank {destination_bank_number} in {destination_project_dirpath:?}"
+        ),
+    );
+
     if !sample_transfers.is_empty() {
-        println!("Copying necessary sample files ...")
+        println!("Staging necessary sample file copies ...")
     } else {
         println!("No sample files need copying.")
     }
 
     transfer_sample_files(
+        &mut tx,
         &sample_transfers,
         source_project_dirpath,
         destination_project_dirpath,
     )?;
 
-    println!("Writing sample slot modifications to destination ...");
-    write_type_to_bin_file::<Project>(&new_project, &destination_meta.project.filepath)?;
-
-    println!("Writing bank modifications to destination ...");
-    write_type_to_bin_file::<Bank>(&new_bank, &destination_meta.bank.filepath)?;
+    println!("Staging sample slot modifications to destination ...");
+    tx.stage_type::<Project>(&new_project, &destination_meta.project.filepath)?;
+
+    println!("Staging bank modifications to destination ...");
+    tx.stage_type::<Bank>(&new_bank, &destination_meta.bank.filepath)?;
+
+    tx.record_project_slot_changes(
+        &destination_meta.project.filepath,
+        &dest_project.slots,
+        &new_project.slots,
+    );
+    for slot in find_reused_sample_slots(&dest_project, &new_project, &new_bank)? {
+        tx.record_slot_change(PlannedSlotChange {
+            project_fpath: destination_meta.project.filepath.clone(),
+            change: SlotChangeType::Reused,
+            sample_type: slot.sample_type,
+            slot_id: slot.slot_id,
+            path: slot.path,
+        });
+    }
 
-    println!("===================================================================================");
-    println!("Bank copy complete.");
-    Ok(())
+    Ok(tx)
 }
 
 /// ### Batched bank copying using a YAML config
@@ -1,4 +1,4 @@
-use crate::actions::banks::copy_bank_by_paths;
+use crate::actions::banks::{copy_bank_by_paths, stage_copy_bank_by_paths};
 use copy_dir;
 use ot_tools_io::banks::parts::AudioTrackMachineSlot;
 use ot_tools_io::banks::Bank;
@@ -100,6 +100,123 @@ mod unit {
     }
 }
 
+mod resolve_dest_sample_fname {
+    use super::*;
+    use std::collections::HashMap;
+    use utils::{resolve_dest_sample_fname, AudioFileHashes};
+
+    fn mock_dirs(test_name: &str) -> (PathBuf, PathBuf) {
+        let base = temp_dir().join("ot-tools-dest-fname").join(test_name);
+        let _ = std::fs::remove_dir_all(&base);
+        let (src, dest) = (base.join("SRC"), base.join("DEST"));
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+        (src, dest)
+    }
+
+    #[test]
+    fn different_file_same_name_renamed() {
+        let (src, dest) = mock_dirs("different_file_same_name_renamed");
+        std::fs::write(src.join("kick.wav"), "src kick").unwrap();
+        std::fs::write(dest.join("kick.wav"), "dest kick").unwrap();
+
+        let mut planned = HashMap::new();
+        let mut hashes = AudioFileHashes::default();
+        let r =
+            resolve_dest_sample_fname(&src.join("kick.wav"), &dest, &[], &mut planned, &mut hashes)
+                .unwrap();
+
+        assert_ne!(r, "kick.wav");
+        assert!(r.starts_with("kick-") && r.ends_with(".wav"));
+    }
+
+    #[test]
+    fn identical_file_same_name_reused() {
+        let (src, dest) = mock_dirs("identical_file_same_name_reused");
+        std::fs::write(src.join("kick.wav"), "kick").unwrap();
+        std::fs::write(dest.join("kick.wav"), "kick").unwrap();
+
+        let r = resolve_dest_sample_fname(
+            &src.join("kick.wav"),
+            &dest,
+            &[],
+            &mut HashMap::new(),
+            &mut AudioFileHashes::default(),
+        )
+        .unwrap();
+
+        assert_eq!(r, "kick.wav");
+    }
+
+    #[test]
+    fn identical_file_different_name_reused() {
+        let (src, dest) = mock_dirs("identical_file_different_name_reused");
+        std::fs::write(src.join("kick.wav"), "kick").unwrap();
+        std::fs::write(dest.join("bd.wav"), "kick").unwrap();
+
+        let r = resolve_dest_sample_fname(
+            &src.join("kick.wav"),
+            &dest,
+            &[dest.join("bd.wav")],
+            &mut HashMap::new(),
+            &mut AudioFileHashes::default(),
+        )
+        .unwrap();
+
+        assert_eq!(r, "bd.wav");
+    }
+
+    #[test]
+    fn identical_file_in_subdir_reused() {
+        let (src, dest) = mock_dirs("identical_file_in_subdir_reused");
+        std::fs::create_dir_all(dest.join("drums")).unwrap();
+        std::fs::write(src.join("kick.wav"), "kick").unwrap();
+        std::fs::write(dest.join("drums").join("bd.wav"), "kick").unwrap();
+
+        let r = resolve_dest_sample_fname(
+            &src.join("kick.wav"),
+            &dest,
+            &[dest.join("drums").join("bd.wav")],
+            &mut HashMap::new(),
+            &mut AudioFileHashes::default(),
+        )
+        .unwrap();
+
+        assert_eq!(r, "drums/bd.wav");
+    }
+
+    #[test]
+    fn planned_files_same_name_renamed() {
+        let (src, dest) = mock_dirs("planned_files_same_name_renamed");
+        std::fs::create_dir_all(src.join("a")).unwrap();
+        std::fs::create_dir_all(src.join("b")).unwrap();
+        std::fs::write(src.join("a").join("kick.wav"), "kick a").unwrap();
+        std::fs::write(src.join("b").join("kick.wav"), "kick b").unwrap();
+
+        let mut planned = HashMap::new();
+        let mut hashes = AudioFileHashes::default();
+        let first = resolve_dest_sample_fname(
+            &src.join("a").join("kick.wav"),
+            &dest,
+            &[],
+            &mut planned,
+            &mut hashes,
+        )
+        .unwrap();
+        let second = resolve_dest_sample_fname(
+            &src.join("b").join("kick.wav"),
+            &dest,
+            &[],
+            &mut planned,
+            &mut hashes,
+        )
+        .unwrap();
+
+        assert_eq!(first, "kick.wav");
+        assert_ne!(second, "kick.wav");
+    }
+}
+
 mod integration {
 
     use super::*;
@@ -215,7 +332,8 @@ mod integration {
             println!("MODIFIED PROJ SLOTS: {:#?}", modifiedproj.slots);
             println!("VALID PROJ SLOTS: {:#?}", valid_destproj.slots);
 
-            // sample slots should all match
+            // sample slots should all match (and no slots should be missing / added twice)
+            assert_eq!(valid_destproj.slots.len(), modifiedproj.slots.len());
             for (valid_slot, modifiedslot) in
                 valid_destproj.slots.iter().zip(modifiedproj.slots.iter())
             {
@@ -354,6 +472,52 @@ mod integration {
             assert!(r.is_ok());
         }
 
+        #[test]
+        fn staged_copy_plans_without_changes() {
+            #[cfg(target_os = "windows")]
+            let test_name = "copy2empty\\staged_plan".to_string();
+            #[cfg(not(target_os = "windows"))]
+            let test_name = "int/staged_plan".to_string();
+
+            let paths = mock_dirs(&test_name);
+
+            let mut srcproj = Project::default();
+            let destproj = Project::default();
+            let mut srcbank = Bank::default();
+
+            srcproj.slots.push(
+                ProjectSampleSlot::new(
+                    ProjectSampleSlotType::Static,
+                    1,
+                    PathBuf::from("../AUDIO/first-0.wav"),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap(),
+            );
+            srcbank.patterns[0].audio_track_trigs[0].plocks.0[0].static_slot_id = 0;
+            write_mock_data_files(&paths, &srcproj, &srcbank, &destproj);
+
+            let tx =
+                stage_copy_bank_by_paths(&paths.inproject, &paths.outproject, 1, 1, false).unwrap();
+            let plan = tx.plan().unwrap();
+
+            assert_eq!(plan.summary.slots_created, 1);
+            assert!(plan.summary.files_copied >= 1);
+            assert_eq!(plan.summary.files_written, 2);
+            assert!(!paths.outproject.join("first-0.wav").exists());
+            assert_eq!(
+                read_type_from_bin_file::<Project>(&paths.outproject.join("project.work")).unwrap(),
+                destproj
+            );
+
+            tear_down_dirs(&test_name);
+        }
+
         mod static_slots {
             use std::cmp::Ordering;
 
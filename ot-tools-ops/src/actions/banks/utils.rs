@@ -1,9 +1,10 @@
-use chrono;
-
+use crate::actions::projects::paths::{get_device_slot_path, to_device_slot_path};
 use crate::actions::{
     banks::CliBankErrors, part_update_sample_slot_refs, pattern_update_sample_slot_refs,
 };
-use crate::{OctatoolErrors, RBoxErr};
+use crate::audio::utils::scan_dir_path_for_audio_files;
+use crate::utils::get_md5_hash_from_path;
+use crate::{transaction::Transaction, OctatoolErrors, RBoxErr};
 use itertools::Itertools;
 use ot_tools_io::samples::options::{
     SampleAttributeLoopMode, SampleAttributeTimestrechMode, SampleAttributeTrigQuantizationMode,
@@ -13,7 +14,12 @@ use ot_tools_io::{
     projects::{options::ProjectSampleSlotType, slots::ProjectSampleSlot, Project},
 };
 use std::{
-    array::from_fn, cmp::PartialEq, collections::HashSet, ffi::OsStr, path::Path, path::PathBuf,
+    array::from_fn,
+    cmp::PartialEq,
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    path::Path,
+    path::PathBuf,
 };
 
 type SlotType = ProjectSampleSlotType;
@@ -128,25 +134,6 @@ fn check_file_exists(path: &Path) -> RBoxErr<()> {
     Ok(())
 }
 
-/// Copy a given `*.work` file with the file extension suffixed to `*.work_ot-tools-cli_UtcTimestamp`
-pub(crate) fn create_backup_of_work_file(path: &Path) -> RBoxErr<()> {
-    match check_file_exists(path) {
-        Ok(_) => {
-            let datetime = chrono::Utc::now().timestamp();
-            let mut backup_filepath = path.to_path_buf();
-            backup_filepath.set_extension(format!["work_ot-tools-cli_{:?}", datetime]);
-            println!("Creating working file backup: {backup_filepath:?}");
-            let _ = std::fs::copy(path, backup_filepath)?;
-            Ok(())
-        }
-        // passes through the validation error
-        Err(e) => {
-            eprintln!("Could not create backup of file: path={path:?}");
-            Err(e)
-        }
-    }
-}
-
 /// Given a project directory path, check the `project.work` file exists and return the file path for it
 fn resolve_project_work_file_from_project_dirpath(dirpath: &Path) -> RBoxErr<PathBuf> {
     let project_fpath = dirpath.join("project.work");
@@ -322,6 +309,34 @@ pub(crate) fn find_sample_slot_refs_in_bank(
         .collect::<HashSet<BankSlotReference>>())
 }
 
+/// Find the (1-indexed) destination project sample slots which existed before a bank copy, are
+/// unchanged after it, and are referenced by the copied bank data.
+pub(crate) fn find_reused_sample_slots(
+    old_project: &Project,
+    new_project: &Project,
+    new_bank: &Bank,
+) -> RBoxErr<Vec<Slot>> {
+    let refs = find_sample_slot_refs_in_bank(
+        &get_zero_indexed_slots_from_one_indexed(&new_project.slots)?,
+        new_bank,
+    )?;
+
+    Ok(new_project
+        .slots
+        .iter()
+        .filter(|slot| old_project.slots.contains(slot))
+        .filter(|slot| {
+            refs.iter().any(|r| {
+                r.reference_type == BankSlotReferenceType::Active
+                    && r.sample_type == slot.sample_type
+                    && r.slot_id + 1 == slot.slot_id
+            })
+        })
+        .cloned()
+        .sorted_by_key(|x| (x.sample_type.clone() as u8, x.slot_id))
+        .collect())
+}
+
 /// Get the file path of a sample's `.ot` file as a new/cloned `PathBuf`, given the audio file path,
 /// (change fle extension to `.ot`). Note: These files are not guaranteed to exist.
 fn resolve_otfile_fpath_from_audio_fpath(path: &Path) -> PathBuf {
@@ -385,6 +400,136 @@ pub(crate) fn find_sample_slot_settings_match(candidate: &Slot, slots: &[Slot])
         .cloned()
 }
 
+/// Cache of audio file content hashes, as hashing every sample file involved in a copy more than
+/// once would be slow.
+#[derive(Default)]
+pub(crate) struct AudioFileHashes(HashMap<PathBuf, Option<String>>);
+
+impl AudioFileHashes {
+    /// md5 hash of a file's contents, `None` when the file doesn't exist or can't be read.
+    pub(crate) fn get(&mut self, path: &Path) -> Option<String> {
+        self.0
+            .entry(path.to_path_buf())
+            .or_insert_with(|| get_md5_hash_from_path(path).ok())
+            .clone()
+    }
+}
+
+/// Find a match for a given sample slot based on the SETTINGS of the sample slot and the CONTENT
+/// of the slot's audio file, i.e. do not match on `slot_id` or `path`.
+///
+/// Slot paths are resolved relative to each slot's project directory. Slots with missing audio
+/// files never match.
+pub(crate) fn find_sample_slot_content_match(
+    candidate: &Slot,
+    candidate_dirpath: &Path,
+    slots: &[Slot],
+    slots_dirpath: &Path,
+    hashes: &mut AudioFileHashes,
+) -> Option<Slot> {
+    let candidate_hash = hashes.get(&candidate_dirpath.join(&candidate.path))?;
+
+    slots
+        .iter()
+        .filter(|x| {
+            x.sample_type == candidate.sample_type
+                && x.trim_bars_x100 == candidate.trim_bars_x100
+                && x.trig_quantization_mode == candidate.trig_quantization_mode
+                && x.timestrech_mode == candidate.timestrech_mode
+                && x.loop_mode == candidate.loop_mode
+                && x.gain == candidate.gain
+                && x.bpm == candidate.bpm
+        })
+        // first one in ascending order
+        .sorted_by(|x, y| Ord::cmp(&x.slot_id, &y.slot_id))
+        .find(|x| hashes.get(&slots_dirpath.join(&x.path)).as_ref() == Some(&candidate_hash))
+        .cloned()
+}
+
+/// Work out the file name a source sample file will have in the destination project directory.
+///
+/// - an identical file (by content) already in the destination, or already planned to be copied
+///   there, is reused whatever its name. Files in subdirectories of the destination are returned
+///   as a path relative to the destination directory, e.g. `drums/kick.wav`
+/// - otherwise the source file name is used, unless a different file already has that name, in
+///   which case the name is suffixed with part of the content hash, e.g. `kick-a1B2c3D4.wav`
+///
+/// `planned` tracks the names (and content hashes) of files already allocated during this copy.
+pub(crate) fn resolve_dest_sample_fname(
+    src_audio_fpath: &Path,
+    dest_dirpath: &Path,
+    dest_audio_fpaths: &[PathBuf],
+    planned: &mut HashMap<String, String>,
+    hashes: &mut AudioFileHashes,
+) -> RBoxErr<String> {
+    let Some(src_hash) = hashes.get(src_audio_fpath) else {
+        return Err(Box::new(OctatoolErrors::PathDoesNotExist));
+    };
+
+    if let Some((name, _)) = planned.iter().find(|(_, hash)| **hash == src_hash) {
+        return Ok(name.clone());
+    }
+    if let Some(existing) = dest_audio_fpaths
+        .iter()
+        .find(|x| hashes.get(x).as_ref() == Some(&src_hash))
+    {
+        let name = get_device_slot_path(dest_dirpath, existing)
+            .to_string_lossy()
+            .to_string();
+        planned.insert(name.clone(), src_hash);
+        return Ok(name);
+    }
+
+    resolve_free_sample_fname(src_audio_fpath, &src_hash, dest_dirpath, planned, hashes)
+}
+
+/// Work out a file name for a source sample file in a destination directory which doesn't clash
+/// with a different file, either already in the destination or planned to be written there.
+///
+/// The source file name is used when it is free (or the file there is identical), otherwise the
+/// name is suffixed with part of the content hash, e.g. `kick-a1B2c3D4.wav`, then a counter.
+pub(crate) fn resolve_free_sample_fname(
+    src_audio_fpath: &Path,
+    src_hash: &str,
+    dest_dirpath: &Path,
+    planned: &mut HashMap<String, String>,
+    hashes: &mut AudioFileHashes,
+) -> RBoxErr<String> {
+    let fname = resolve_fname_and_fext_from_path(src_audio_fpath)?;
+    let stem = src_audio_fpath
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default();
+    let ext = src_audio_fpath
+        .extension()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default();
+    let suffix = src_hash
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(8)
+        .collect::<String>();
+
+    let mut candidate = fname;
+    let mut n_attempts = 0;
+    loop {
+        let is_free = planned
+            .get(&candidate)
+            .is_none_or(|planned_hash| planned_hash == src_hash);
+        let dest_hash = hashes.get(&dest_dirpath.join(&candidate));
+        if is_free && dest_hash.as_deref().is_none_or(|hash| hash == src_hash) {
+            planned.insert(candidate.clone(), src_hash.to_string());
+            return Ok(candidate);
+        }
+        n_attempts += 1;
+        candidate = if n_attempts == 1 {
+            format!("{stem}-{suffix}.{ext}")
+        } else {
+            format!("{stem}-{suffix}-{n_attempts}.{ext}")
+        };
+    }
+}
+
 /// Create a new sample slot based on another sample slot.
 /// Always copies the slot's settings and, depending on options provided, maybe
 /// copies: the path and/or slot_id.
@@ -556,6 +701,7 @@ pub fn calculate_copy_bank_changes(
     src_project_dirpath: &Path,
     src_project: &Project,
     src_bank: &Bank,
+    dest_project_dirpath: &Path,
     dest_project: &Project,
 ) -> RBoxErr<(Project, Bank, Vec<SlotFileCopy>)> {
     println!("Calculating destination project slot changes ...");
@@ -612,12 +758,30 @@ pub fn calculate_copy_bank_changes(
     let bank_slot_refs =
         find_sample_slot_refs_in_bank(&deduped_src_zero_indexed_slots, &deduped_bank)?;
 
+    // destination slots with the same settings and identical audio file content as source slots
+    let mut hashes = AudioFileHashes::default();
+    let dest_slot_matches: HashMap<(SlotType, u8), Slot> = deduped_src_zero_indexed_slots
+        .iter()
+        .filter_map(|src| {
+            find_sample_slot_content_match(
+                src,
+                src_project_dirpath,
+                &dest_zero_indexed_slots,
+                dest_project_dirpath,
+                &mut hashes,
+            )
+            .map(|dest| ((src.sample_type.clone(), src.slot_id), dest))
+        })
+        .collect();
+    let find_dest_slot_match =
+        |src: &Slot| dest_slot_matches.get(&(src.sample_type.clone(), src.slot_id));
+
     // the set of source slots where
     // - the slot can be mapped onto existing destination slots
     // - the slot is referenced in the bank we are going to copy
     let src_slots_reuses = deduped_src_zero_indexed_slots
         .iter()
-        .filter(|src| find_sample_slot_settings_match(src, &dest_zero_indexed_slots).is_some())
+        .filter(|src| find_dest_slot_match(src).is_some())
         .cloned()
         .map(|src| SlotsSlotReference {
             sample_type: src.sample_type,
@@ -632,7 +796,7 @@ pub fn calculate_copy_bank_changes(
     // - the slot is referenced in the bank we are going to copy
     let src_slots_inserts = deduped_src_zero_indexed_slots
         .iter()
-        .filter(|src| find_sample_slot_settings_match(src, &dest_zero_indexed_slots).is_none())
+        .filter(|src| find_dest_slot_match(src).is_none())
         .cloned()
         .map(|src| SlotsSlotReference {
             sample_type: src.sample_type,
@@ -718,8 +882,7 @@ pub fn calculate_copy_bank_changes(
                 .find(|y| x.slot_id == y.slot_id && x.sample_type == y.sample_type)
                 .unwrap(); // TODO: eww
 
-            let new_slot =
-                find_sample_slot_settings_match(src_slot, &dest_zero_indexed_slots).unwrap();
+            let new_slot = find_dest_slot_match(src_slot).unwrap();
 
             SampleSlotOperation {
                 src_slot: src_slot.clone(),
@@ -729,9 +892,13 @@ pub fn calculate_copy_bank_changes(
         })
         .collect::<HashSet<_>>();
 
+    // files are matched by content so different files with the same name don't collide
+    let dest_audio_fpaths = scan_dir_path_for_audio_files(&dest_project_dirpath.to_path_buf())?;
+    let mut planned_dest_fnames: HashMap<String, String> = HashMap::new();
+
     let active_insert_ops = src_slots_inserts
         .into_iter()
-        .sorted_by_key(|x| x.slot_id)
+        .sorted_by_key(|x| (x.sample_type.clone() as u8, x.slot_id))
         .map(|x| {
             let src_slot = deduped_src_zero_indexed_slots
                 .iter()
@@ -739,8 +906,15 @@ pub fn calculate_copy_bank_changes(
                 .unwrap(); // TODO: eww
 
             let src_path_audio_abs = &src_project_dirpath.join(&src_slot.path);
+            let dest_fname = resolve_dest_sample_fname(
+                src_path_audio_abs,
+                dest_project_dirpath,
+                &dest_audio_fpaths,
+                &mut planned_dest_fnames,
+                &mut hashes,
+            )?;
             let audio_fpath_rel_dest =
-                PathBuf::from(&resolve_fname_and_fext_from_path(src_path_audio_abs).unwrap());
+                to_device_slot_path(dest_project_dirpath, &dest_project_dirpath.join(dest_fname))?;
             let dest_slot_id = match src_slot.sample_type {
                 SlotType::Static => free_static
                     .pop()
@@ -759,13 +933,13 @@ pub fn calculate_copy_bank_changes(
             )
             .expect("Unable to create remapped sample slot.");
 
-            SampleSlotOperation {
+            Ok(SampleSlotOperation {
                 src_slot: src_slot.clone(),
                 dest_slot,
                 op_type: SampleSlotOperationType::NewSlot,
-            }
+            })
         })
-        .collect::<HashSet<_>>();
+        .collect::<RBoxErr<HashSet<_>>>()?;
 
     // TODO: This seems like a good cut point. "Get required operations" ... but need the zero
     //       indexed & deduplicated project data/bank data to continue... so maybe it isn't...
@@ -798,7 +972,7 @@ pub fn calculate_copy_bank_changes(
             .iter()
             .filter(|x| {
                 x.op_type == SampleSlotOperationType::NewSlot
-                    && x.src_slot.sample_type == SlotType::Static
+                    && x.src_slot.sample_type == SlotType::Flex
             })
             .cloned()
             .map(|x| x.dest_slot)
@@ -834,30 +1008,36 @@ pub fn calculate_copy_bank_changes(
     Ok((new_project, deduped_bank, sample_transfers))
 }
 
-/// Transfer sample files (audio and `.ot` files) related to `NewSlot` operations.
+/// Stage transfers of sample files (audio and `.ot` files) related to `NewSlot` operations.
 ///
 /// This function uses the destination sample slot in a `SampleSlotOperation` to get the
 /// destination file path.
 pub(crate) fn transfer_sample_files(
+    tx: &mut Transaction,
     transfers: &[SlotFileCopy],
     src_dirpath: &Path,
     dest_dirpath: &Path,
 ) -> RBoxErr<()> {
+    // several slots can share one sample file
+    let mut staged: HashSet<PathBuf> = HashSet::new();
+
     for transfer in transfers {
         let src_path_audio_abs = &src_dirpath.to_path_buf().join(&transfer.from.path);
         let src_ot_filepath_abs = resolve_otfile_fpath_from_audio_fpath(src_path_audio_abs);
 
-        let dest_filename_and_ext = resolve_fname_and_fext_from_path(&transfer.to.path)?;
-        let dest_path_audio_abs = dest_dirpath.join(dest_filename_and_ext);
+        let dest_path_audio_abs = dest_dirpath.join(&transfer.to.path);
         let dest_ot_filepath_abs = resolve_otfile_fpath_from_audio_fpath(&dest_path_audio_abs);
 
         println!(
             "Checking for audio file copy: {:?} -> {:?}",
             src_path_audio_abs, dest_path_audio_abs,
         );
+        if !staged.insert(dest_path_audio_abs.clone()) {
+            continue;
+        }
         if dest_path_audio_abs.exists() {
             println!(
-                "File exists in destination, skipping copy: {:?} -> {:?}",
+                "Identical file exists in destination, skipping copy: {:?} -> {:?}",
                 src_path_audio_abs, dest_path_audio_abs,
             );
         } else {
@@ -865,7 +1045,7 @@ pub(crate) fn transfer_sample_files(
                 "Transferring audio file: {:?} -> {:?}",
                 src_path_audio_abs, dest_path_audio_abs
             );
-            std::fs::copy(src_path_audio_abs, dest_path_audio_abs)?;
+            tx.stage_copy(src_path_audio_abs, &dest_path_audio_abs)?;
         }
 
         println!(
@@ -879,7 +1059,7 @@ pub(crate) fn transfer_sample_files(
                 "Transferring OT file: {:?} -> {:?}",
                 src_ot_filepath_abs, dest_ot_filepath_abs
             );
-            std::fs::copy(src_ot_filepath_abs, dest_ot_filepath_abs)?;
+            tx.stage_copy(&src_ot_filepath_abs, &dest_ot_filepath_abs)?;
         } else {
             println!(
                 "OT file doesn't exist in source, skipping copy: {:?}",
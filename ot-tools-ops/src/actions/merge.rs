@@ -0,0 +1,802 @@
+//! Semantic diffs and three-way merges of `Bank` and `Project` data files.
+//!
+//! Data is compared via the serde representation of each type, so changes are tracked per field
+//! (e.g. `patterns[3].audio_track_trigs[0].plocks[12].static_slot_id`) instead of per byte.
+//!
+//! A couple of rules for what counts as a 'field':
+//! - Arrays of plain numbers (header bytes, part names, trig masks etc.) are treated as a single
+//!   field. Merging these byte-by-byte could create values neither side wrote.
+//! - Project sample slots are matched by their sample type and slot ID, not their position in the
+//!   list, so slots added on different cards do not clash with each other.
+//! - Bank checksums are never reported as conflicts and always take the 'ours' value, as there is
+//!   no way to calculate them yet (same as `Bank::is_default`).
+//!
+//! Conflicting fields keep the 'ours' value in the merged data, which is also what git expects
+//! from a merge driver (results are written over `%A`).
+//!
+//! Merged files are written with a `Transaction`, so merges can be dry run / saved as plans, and
+//! the files they replace are backed up (into the set directory of a merged project directory, or
+//! the directory containing a merged data file).
+
+use crate::transaction::{finish_transaction, get_backups_root_for_project, Transaction};
+use crate::{OctatoolErrors, RBoxErr};
+use ot_tools_io::{banks::Bank, projects::Project, read_bin_file, read_type_from_bin_file};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum MergeErrors {
+    /// File contents are not a recognised Octatrack data file which can be merged
+    UnknownFileType,
+    /// The base / ours / theirs files are different types of data files
+    MismatchedFileTypes,
+}
+impl Display for MergeErrors {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Self::UnknownFileType => write!(
+                f,
+                "file is not a recognised Octatrack data file (only banks and projects can be merged)"
+            ),
+            Self::MismatchedFileTypes => write!(
+                f,
+                "files being merged must all be the same type of data file"
+            ),
+        }
+    }
+}
+impl std::error::Error for MergeErrors {}
+
+/// Types of Octatrack data file which can be diffed / merged.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MergeFileType {
+    Bank,
+    Project,
+}
+
+impl MergeFileType {
+    /// Work out the type of data file from its contents.
+    ///
+    /// File names can't be relied on here: git passes temporary file names to merge drivers.
+    pub fn from_path(path: &Path) -> RBoxErr<Self> {
+        let bytes = read_bin_file(path)?;
+        if bytes.len() > 16 && &bytes[0..4] == b"FORM" && &bytes[8..16] == b"DPS1BANK" {
+            return Ok(Self::Bank);
+        }
+        if String::from_utf8_lossy(&bytes).contains("TYPE=OCTATRACK DPS-1 PROJECT") {
+            return Ok(Self::Project);
+        }
+        Err(MergeErrors::UnknownFileType.into())
+    }
+}
+
+/// A field which has a different value between two versions of the same data.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldChange {
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl Display for FieldChange {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path,
+            fmt_opt_value(&self.old),
+            fmt_opt_value(&self.new)
+        )
+    }
+}
+
+/// A field which was changed to different values in both 'ours' and 'theirs'.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MergeConflict {
+    pub path: String,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+impl Display for MergeConflict {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: base={} ours={} theirs={}",
+            self.path,
+            fmt_opt_value(&self.base),
+            fmt_opt_value(&self.ours),
+            fmt_opt_value(&self.theirs)
+        )
+    }
+}
+
+fn fmt_opt_value(v: &Option<Value>) -> String {
+    match v {
+        Some(x) => x.to_string(),
+        None => "<none>".to_string(),
+    }
+}
+
+fn join_key(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn is_numeric_array(v: &[Value]) -> bool {
+    v.iter().all(|x| x.is_number())
+}
+
+/// Project sample slots are identified by their type and ID, not their position in the list.
+fn is_keyed_slot_array(path: &str) -> bool {
+    path == "slots"
+}
+
+fn slot_key(v: &Value) -> String {
+    format!(
+        "{}:{}",
+        v["sample_type"].as_str().unwrap_or_default(),
+        v["slot_id"]
+    )
+}
+
+/// Ordered list of slot keys present in any of the provided slot arrays.
+fn slot_keys(arrays: &[&[Value]]) -> Vec<String> {
+    let mut keys: Vec<String> = vec![];
+    for arr in arrays {
+        for k in arr.iter().map(slot_key) {
+            if !keys.contains(&k) {
+                keys.push(k);
+            }
+        }
+    }
+    keys
+}
+
+fn find_slot<'a>(arr: &'a [Value], key: &str) -> Option<&'a Value> {
+    arr.iter().find(|x| slot_key(x) == key)
+}
+
+fn diff_values(path: &str, old: Option<&Value>, new: Option<&Value>, out: &mut Vec<FieldChange>) {
+    if old == new {
+        return;
+    }
+    match (old, new) {
+        (Some(Value::Object(o)), Some(Value::Object(n))) => {
+            for (k, v) in o {
+                diff_values(&join_key(path, k), Some(v), n.get(k), out);
+            }
+            for (k, v) in n.iter().filter(|(k, _)| !o.contains_key(*k)) {
+                diff_values(&join_key(path, k), None, Some(v), out);
+            }
+        }
+        (Some(Value::Array(o)), Some(Value::Array(n))) if is_keyed_slot_array(path) => {
+            for key in slot_keys(&[o, n]) {
+                diff_values(
+                    &format!("{path}[{key}]"),
+                    find_slot(o, &key),
+                    find_slot(n, &key),
+                    out,
+                );
+            }
+        }
+        (Some(Value::Array(o)), Some(Value::Array(n)))
+            if o.len() == n.len() && !is_numeric_array(o) =>
+        {
+            for (i, (a, b)) in o.iter().zip(n.iter()).enumerate() {
+                diff_values(&format!("{path}[{i}]"), Some(a), Some(b), out);
+            }
+        }
+        _ => out.push(FieldChange {
+            path: path.to_string(),
+            old: old.cloned(),
+            new: new.cloned(),
+        }),
+    }
+}
+
+fn merge_values(
+    path: &str,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<MergeConflict>,
+) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+
+    match (base, ours, theirs) {
+        (Some(Value::Object(b)), Some(Value::Object(o)), Some(Value::Object(t))) => {
+            let mut merged = Map::new();
+            let keys = o.keys().chain(t.keys().filter(|k| !o.contains_key(*k)));
+            for k in keys {
+                let v = merge_values(&join_key(path, k), b.get(k), o.get(k), t.get(k), conflicts);
+                if let Some(v) = v {
+                    merged.insert(k.clone(), v);
+                }
+            }
+            Some(Value::Object(merged))
+        }
+        (Some(Value::Array(b)), Some(Value::Array(o)), Some(Value::Array(t)))
+            if is_keyed_slot_array(path) =>
+        {
+            let merged = slot_keys(&[o, t, b])
+                .iter()
+                .filter_map(|key| {
+                    merge_values(
+                        &format!("{path}[{key}]"),
+                        find_slot(b, key),
+                        find_slot(o, key),
+                        find_slot(t, key),
+                        conflicts,
+                    )
+                })
+                .collect();
+            Some(Value::Array(merged))
+        }
+        (Some(Value::Array(b)), Some(Value::Array(o)), Some(Value::Array(t)))
+            if b.len() == o.len() && o.len() == t.len() && !is_numeric_array(o) =>
+        {
+            let merged = (0..o.len())
+                .filter_map(|i| {
+                    merge_values(
+                        &format!("{path}[{i}]"),
+                        Some(&b[i]),
+                        Some(&o[i]),
+                        Some(&t[i]),
+                        conflicts,
+                    )
+                })
+                .collect();
+            Some(Value::Array(merged))
+        }
+        _ => {
+            if path != "checksum" {
+                conflicts.push(MergeConflict {
+                    path: path.to_string(),
+                    base: base.cloned(),
+                    ours: ours.cloned(),
+                    theirs: theirs.cloned(),
+                });
+            }
+            ours.cloned()
+        }
+    }
+}
+
+/// Get a list of all fields which differ between two instances of the same type.
+pub fn diff_types<T: Serialize>(old: &T, new: &T) -> RBoxErr<Vec<FieldChange>> {
+    let old = serde_json::to_value(old)?;
+    let new = serde_json::to_value(new)?;
+
+    let mut changes = vec![];
+    diff_values("", Some(&old), Some(&new), &mut changes);
+    Ok(changes)
+}
+
+/// Three-way merge of `ours` and `theirs` with a common ancestor `base`.
+///
+/// Returns the merged data along with any fields which could not be merged automatically.
+pub fn merge_types<T>(base: &T, ours: &T, theirs: &T) -> RBoxErr<(T, Vec<MergeConflict>)>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let base = serde_json::to_value(base)?;
+    let ours = serde_json::to_value(ours)?;
+    let theirs = serde_json::to_value(theirs)?;
+
+    let mut conflicts = vec![];
+    let merged = merge_values("", Some(&base), Some(&ours), Some(&theirs), &mut conflicts)
+        .ok_or(OctatoolErrors::Unknown)?;
+
+    Ok((serde_json::from_value::<T>(merged)?, conflicts))
+}
+
+fn merge_type_files<T>(
+    base_fpath: &Path,
+    ours_fpath: &Path,
+    theirs_fpath: &Path,
+    out_fpath: &Path,
+    tx: &mut Transaction,
+) -> RBoxErr<Vec<MergeConflict>>
+where
+    T: Serialize + DeserializeOwned + ot_tools_io::Decode + ot_tools_io::Encode,
+{
+    let (merged, conflicts) = merge_types::<T>(
+        &read_type_from_bin_file::<T>(base_fpath)?,
+        &read_type_from_bin_file::<T>(ours_fpath)?,
+        &read_type_from_bin_file::<T>(theirs_fpath)?,
+    )?;
+    tx.stage_type::<T>(&merged, out_fpath)?;
+    Ok(conflicts)
+}
+
+/// Three-way merge of bank or project data files, staging a write of the merged data to
+/// `out_fpath`.
+///
+/// The merged file is always staged, with conflicting fields keeping the 'ours' value.
+/// Returns any conflicts found.
+pub fn merge_data_files(
+    base_fpath: &Path,
+    ours_fpath: &Path,
+    theirs_fpath: &Path,
+    out_fpath: &Path,
+    tx: &mut Transaction,
+) -> RBoxErr<Vec<MergeConflict>> {
+    let file_type = MergeFileType::from_path(base_fpath)?;
+    if MergeFileType::from_path(ours_fpath)? != file_type
+        || MergeFileType::from_path(theirs_fpath)? != file_type
+    {
+        return Err(MergeErrors::MismatchedFileTypes.into());
+    }
+
+    match file_type {
+        MergeFileType::Bank => {
+            merge_type_files::<Bank>(base_fpath, ours_fpath, theirs_fpath, out_fpath, tx)
+        }
+        MergeFileType::Project => {
+            merge_type_files::<Project>(base_fpath, ours_fpath, theirs_fpath, out_fpath, tx)
+        }
+    }
+}
+
+/// Get the list of changed fields between two bank or project data files.
+pub fn diff_data_files(old_fpath: &Path, new_fpath: &Path) -> RBoxErr<Vec<FieldChange>> {
+    let file_type = MergeFileType::from_path(old_fpath)?;
+    if MergeFileType::from_path(new_fpath)? != file_type {
+        return Err(MergeErrors::MismatchedFileTypes.into());
+    }
+
+    match file_type {
+        MergeFileType::Bank => diff_types(
+            &read_type_from_bin_file::<Bank>(old_fpath)?,
+            &read_type_from_bin_file::<Bank>(new_fpath)?,
+        ),
+        MergeFileType::Project => diff_types(
+            &read_type_from_bin_file::<Project>(old_fpath)?,
+            &read_type_from_bin_file::<Project>(new_fpath)?,
+        ),
+    }
+}
+
+/// Project and bank data file names which are merged for a project directory.
+fn project_dir_merge_fnames() -> Vec<String> {
+    let mut fnames = vec![];
+    for ext in ["work", "strd"] {
+        fnames.push(format!("project.{ext}"));
+        for bank_id in 1..=16 {
+            fnames.push(format!("bank{bank_id:0>2}.{ext}"));
+        }
+    }
+    fnames
+}
+
+/// Three-way merge of all project and bank data files for project directories, staging writes of
+/// the merged files to `out_dirpath` (which can be the same as `ours_dirpath`).
+///
+/// Files which do not exist in all three directories are skipped.
+/// Arrangement files are not merged.
+pub fn merge_project_dirs(
+    base_dirpath: &Path,
+    ours_dirpath: &Path,
+    theirs_dirpath: &Path,
+    out_dirpath: &Path,
+    tx: &mut Transaction,
+) -> RBoxErr<Vec<(PathBuf, Vec<MergeConflict>)>> {
+    for dirpath in [base_dirpath, ours_dirpath, theirs_dirpath, out_dirpath] {
+        if !dirpath.is_dir() {
+            return Err(OctatoolErrors::PathIsNotADirectory.into());
+        }
+    }
+
+    let mut results = vec![];
+    for fname in project_dir_merge_fnames() {
+        let fpaths = [
+            base_dirpath.join(&fname),
+            ours_dirpath.join(&fname),
+            theirs_dirpath.join(&fname),
+        ];
+        if !fpaths.iter().all(|x| x.exists()) {
+            continue;
+        }
+        let out_fpath = out_dirpath.join(&fname);
+        let conflicts = merge_data_files(&fpaths[0], &fpaths[1], &fpaths[2], &out_fpath, tx)?;
+        results.push((out_fpath, conflicts));
+    }
+
+    Ok(results)
+}
+
+/// Print the list of changed fields between two bank or project data files.
+pub fn show_data_file_diff(old_fpath: &Path, new_fpath: &Path) -> RBoxErr<()> {
+    let changes = diff_data_files(old_fpath, new_fpath)?;
+    for change in &changes {
+        println!("{change}");
+    }
+    println!("Changed fields: {}", changes.len());
+    Ok(())
+}
+
+/// Backups for a merged data file are kept in the directory containing it. Git runs merge drivers
+/// with `%A` as a temporary file in the repository root, so going up a directory (as for project
+/// directories) would write backups outside of the repository.
+fn get_backups_root_for_file(fpath: &Path) -> RBoxErr<PathBuf> {
+    match fpath.parent() {
+        Some(x) if !x.as_os_str().is_empty() => Ok(std::fs::canonicalize(x)?),
+        _ => Ok(std::fs::canonicalize(".")?),
+    }
+}
+
+/// Merge either three bank / project data files, or three project directories, printing any
+/// conflicts.
+///
+/// Returns `true` when the merge completed without conflicts.
+pub fn merge_by_paths(
+    base_path: &Path,
+    ours_path: &Path,
+    theirs_path: &Path,
+    out_path: Option<&Path>,
+) -> RBoxErr<bool> {
+    let (tx, clean) = stage_merge_by_paths(base_path, ours_path, theirs_path, out_path)?;
+    finish_transaction(tx, false, None)?;
+    Ok(clean)
+}
+
+/// Stage a merge (see `merge_by_paths`) without changing anything on the file system, printing
+/// any conflicts.
+///
+/// Returns the transaction, and `true` when the merge completed without conflicts.
+pub fn stage_merge_by_paths(
+    base_path: &Path,
+    ours_path: &Path,
+    theirs_path: &Path,
+    out_path: Option<&Path>,
+) -> RBoxErr<(Transaction, bool)> {
+    let out_path = out_path.unwrap_or(ours_path);
+    let description = format!("merge {ours_path:?} and {theirs_path:?} into {out_path:?}");
+
+    let (tx, results) = if base_path.is_dir() {
+        let mut tx = Transaction::new(&get_backups_root_for_project(out_path)?, &description);
+        let results = merge_project_dirs(base_path, ours_path, theirs_path, out_path, &mut tx)?;
+        (tx, results)
+    } else {
+        let mut tx = Transaction::new(&get_backups_root_for_file(out_path)?, &description);
+        let conflicts = merge_data_files(base_path, ours_path, theirs_path, out_path, &mut tx)?;
+        (tx, vec![(out_path.to_path_buf(), conflicts)])
+    };
+
+    let mut clean = true;
+    for (fpath, conflicts) in results {
+        if conflicts.is_empty() {
+            continue;
+        }
+        clean = false;
+        eprintln!("CONFLICTS in {fpath:?} (kept 'ours' values):");
+        for conflict in conflicts {
+            eprintln!("    {conflict}");
+        }
+    }
+
+    Ok((tx, clean))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+
+    mod diff {
+        use super::*;
+
+        #[test]
+        fn no_changes() {
+            let bank = Bank::default();
+            assert!(diff_types(&bank, &bank).unwrap().is_empty());
+        }
+
+        #[test]
+        fn plock_change_path() {
+            let old = Bank::default();
+            let mut new = Bank::default();
+            new.patterns[3].audio_track_trigs[0].plocks[12].static_slot_id = 5;
+
+            let changes = diff_types(&old, &new).unwrap();
+            assert_eq!(changes.len(), 1);
+            assert_eq!(
+                changes[0].path,
+                "patterns[3].audio_track_trigs[0].plocks[12].static_slot_id"
+            );
+            assert_eq!(changes[0].new, Some(Value::from(5)));
+        }
+
+        #[test]
+        fn part_name_is_single_field() {
+            let old = Bank::default();
+            let mut new = Bank::default();
+            new.part_names[0] = [0x4b, 0x49, 0x43, 0x4b, 0x00, 0x00, 0x00];
+
+            let changes = diff_types(&old, &new).unwrap();
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].path, "part_names[0]");
+        }
+
+        #[test]
+        fn project_slot_added() {
+            let old = Project::default();
+            let mut new = Project::default();
+            let mut slot = new.slots[0].clone();
+            slot.slot_id = 1;
+            slot.sample_type = ot_tools_io::projects::options::ProjectSampleSlotType::Static;
+            new.slots.insert(0, slot);
+
+            let changes = diff_types(&old, &new).unwrap();
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].path, "slots[Static:1]");
+            assert_eq!(changes[0].old, None);
+        }
+    }
+
+    mod merge {
+        use super::*;
+        use ot_tools_io::projects::options::ProjectSampleSlotType;
+
+        #[test]
+        fn non_overlapping_bank_edits() {
+            let base = Bank::default();
+            let mut ours = Bank::default();
+            let mut theirs = Bank::default();
+            ours.patterns[0].audio_track_trigs[0].plocks[0].static_slot_id = 1;
+            theirs.parts.unsaved[1].audio_track_machine_slots[2].flex_slot_id = 9;
+
+            let (merged, conflicts) = merge_types(&base, &ours, &theirs).unwrap();
+            assert!(conflicts.is_empty());
+            assert_eq!(
+                merged.patterns[0].audio_track_trigs[0].plocks[0].static_slot_id,
+                1
+            );
+            assert_eq!(
+                merged.parts.unsaved[1].audio_track_machine_slots[2].flex_slot_id,
+                9
+            );
+        }
+
+        #[test]
+        fn same_edit_both_sides() {
+            let base = Bank::default();
+            let mut ours = Bank::default();
+            ours.patterns[2].audio_track_trigs[7].plocks[63].flex_slot_id = 4;
+            let theirs = ours.clone();
+
+            let (merged, conflicts) = merge_types(&base, &ours, &theirs).unwrap();
+            assert!(conflicts.is_empty());
+            assert_eq!(merged, ours);
+        }
+
+        #[test]
+        fn conflicting_edit_keeps_ours() {
+            let base = Bank::default();
+            let mut ours = Bank::default();
+            let mut theirs = Bank::default();
+            ours.patterns[0].audio_track_trigs[0].plocks[0].static_slot_id = 1;
+            theirs.patterns[0].audio_track_trigs[0].plocks[0].static_slot_id = 2;
+
+            let (merged, conflicts) = merge_types(&base, &ours, &theirs).unwrap();
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(
+                conflicts[0].path,
+                "patterns[0].audio_track_trigs[0].plocks[0].static_slot_id"
+            );
+            assert_eq!(
+                merged.patterns[0].audio_track_trigs[0].plocks[0].static_slot_id,
+                1
+            );
+        }
+
+        #[test]
+        fn checksum_never_conflicts() {
+            let base = Bank::default();
+            let mut ours = Bank::default();
+            let mut theirs = Bank::default();
+            ours.checksum = [1, 2];
+            theirs.checksum = [3, 4];
+
+            let (merged, conflicts) = merge_types(&base, &ours, &theirs).unwrap();
+            assert!(conflicts.is_empty());
+            assert_eq!(merged.checksum, [1, 2]);
+        }
+
+        #[test]
+        fn project_slots_added_on_both_sides() {
+            let base = Project::default();
+            let mut ours = Project::default();
+            let mut theirs = Project::default();
+
+            let mut slot = base.slots[0].clone();
+            slot.sample_type = ProjectSampleSlotType::Static;
+            slot.slot_id = 1;
+            slot.path = PathBuf::from("../AUDIO/ours.wav");
+            ours.slots.push(slot.clone());
+
+            slot.slot_id = 2;
+            slot.path = PathBuf::from("../AUDIO/theirs.wav");
+            theirs.slots.push(slot);
+            theirs.settings.tempo.tempo = 130;
+
+            let (merged, conflicts) = merge_types(&base, &ours, &theirs).unwrap();
+            assert!(conflicts.is_empty());
+            assert_eq!(merged.slots.len(), base.slots.len() + 2);
+            assert_eq!(merged.settings.tempo.tempo, 130);
+        }
+
+        #[test]
+        fn project_same_slot_different_paths() {
+            let base = Project::default();
+            let mut ours = Project::default();
+            let mut theirs = Project::default();
+
+            let mut slot = base.slots[0].clone();
+            slot.sample_type = ProjectSampleSlotType::Flex;
+            slot.slot_id = 1;
+            slot.path = PathBuf::from("../AUDIO/ours.wav");
+            ours.slots.push(slot.clone());
+            slot.path = PathBuf::from("../AUDIO/theirs.wav");
+            theirs.slots.push(slot);
+
+            let (_, conflicts) = merge_types(&base, &ours, &theirs).unwrap();
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].path, "slots[Flex:1]");
+        }
+    }
+
+    mod file_type {
+        use super::*;
+
+        #[test]
+        fn bank() {
+            let fpath = PathBuf::from("../data/tests/blank-project/bank01.work");
+            assert_eq!(
+                MergeFileType::from_path(&fpath).unwrap(),
+                MergeFileType::Bank
+            );
+        }
+
+        #[test]
+        fn project() {
+            let fpath = PathBuf::from("../data/tests/blank-project/project.work");
+            assert_eq!(
+                MergeFileType::from_path(&fpath).unwrap(),
+                MergeFileType::Project
+            );
+        }
+
+        #[test]
+        fn arrangement_err() {
+            let fpath = PathBuf::from("../data/tests/blank-project/arr01.work");
+            assert!(MergeFileType::from_path(&fpath).is_err());
+        }
+    }
+
+    // mock files written to temp dir, with backups written to the temp dir too
+    #[cfg(not(target_os = "windows"))]
+    mod files {
+        use super::*;
+        use ot_tools_io::write_type_to_bin_file;
+        use std::fs::{create_dir_all, remove_dir_all};
+
+        /// Temp 'set' directory, with an empty 'project' directory within it.
+        fn test_dir(name: &str) -> PathBuf {
+            let dirpath = std::env::temp_dir().join(name);
+            if dirpath.exists() {
+                remove_dir_all(&dirpath).unwrap();
+            }
+            create_dir_all(dirpath.join("project")).unwrap();
+            dirpath
+        }
+
+        /// Bank files where ours and theirs change different fields.
+        fn write_bank_files(dirpath: &Path) -> [PathBuf; 3] {
+            let base = Bank::default();
+            let mut ours = Bank::default();
+            let mut theirs = Bank::default();
+            ours.patterns[1].audio_track_trigs[1].plocks[1].static_slot_id = 7;
+            theirs.patterns[2].audio_track_trigs[2].plocks[2].flex_slot_id = 8;
+
+            let fpaths = ["base", "ours", "theirs"].map(|x| dirpath.join("project").join(x));
+            write_type_to_bin_file(&base, &fpaths[0]).unwrap();
+            write_type_to_bin_file(&ours, &fpaths[1]).unwrap();
+            write_type_to_bin_file(&theirs, &fpaths[2]).unwrap();
+            fpaths
+        }
+
+        #[test]
+        fn merge_bank_files() {
+            let dirpath = test_dir("ot-tools-ops-merge-bank-files");
+            let fpaths = write_bank_files(&dirpath);
+
+            let clean = merge_by_paths(&fpaths[0], &fpaths[1], &fpaths[2], None).unwrap();
+            let merged = read_type_from_bin_file::<Bank>(&fpaths[1]).unwrap();
+            let backed_up = dirpath
+                .join("project")
+                .join(crate::transaction::BACKUPS_DIRNAME)
+                .is_dir();
+            let _ = remove_dir_all(&dirpath);
+
+            assert!(clean);
+            assert!(backed_up);
+            assert_eq!(
+                merged.patterns[1].audio_track_trigs[1].plocks[1].static_slot_id,
+                7
+            );
+            assert_eq!(
+                merged.patterns[2].audio_track_trigs[2].plocks[2].flex_slot_id,
+                8
+            );
+        }
+
+        #[test]
+        fn stage_merge_bank_files_unchanged() {
+            let dirpath = test_dir("ot-tools-ops-merge-bank-files-staged");
+            let fpaths = write_bank_files(&dirpath);
+            let before = read_bin_file(&fpaths[1]).unwrap();
+
+            let (tx, clean) =
+                stage_merge_by_paths(&fpaths[0], &fpaths[1], &fpaths[2], None).unwrap();
+            let plan = tx.plan().unwrap();
+            let after = read_bin_file(&fpaths[1]).unwrap();
+            let _ = remove_dir_all(&dirpath);
+
+            assert!(clean);
+            assert_eq!(plan.summary.files_written, 1);
+            assert_eq!(plan.files[0].target(), fpaths[1]);
+            assert_eq!(before, after);
+        }
+
+        // git runs merge drivers from the repository root, with `%A` a bare temp file name there
+        #[test]
+        fn merge_bare_file_names_backups_in_same_dir() {
+            let fpaths = ["base", "ours", "theirs"]
+                .map(|x| PathBuf::from(format!(".ot-tools-ops-merge-bare-{x}")));
+            write_type_to_bin_file(&Bank::default(), &fpaths[0]).unwrap();
+            write_type_to_bin_file(&Bank::default(), &fpaths[1]).unwrap();
+            write_type_to_bin_file(&Bank::default(), &fpaths[2]).unwrap();
+
+            let r = stage_merge_by_paths(&fpaths[0], &fpaths[1], &fpaths[2], None);
+            for fpath in &fpaths {
+                let _ = std::fs::remove_file(fpath);
+            }
+
+            let (tx, clean) = r.unwrap();
+            assert!(clean);
+            assert_eq!(
+                tx.plan().unwrap().backups_root,
+                std::env::current_dir().unwrap()
+            );
+        }
+
+        #[test]
+        fn merge_unmodified_project_dirs() {
+            let dirpath = test_dir("ot-tools-ops-merge-project-dirs");
+            let out_dirpath = dirpath.join("project");
+
+            let project = PathBuf::from("../data/tests/blank-project");
+            let (tx, clean) =
+                stage_merge_by_paths(&project, &project, &project, Some(&out_dirpath)).unwrap();
+            tx.commit().unwrap();
+            let merged_exists = out_dirpath.join("project.work").exists();
+            let _ = remove_dir_all(&dirpath);
+
+            assert!(merged_exists);
+            assert!(clean);
+        }
+    }
+}
@@ -1,25 +1,38 @@
+pub mod archive;
+pub mod create;
+pub mod paths;
+pub mod relink;
+pub mod settings;
 pub mod slots;
+pub mod usage;
 
+use crate::actions::banks::utils::{
+    resolve_fname_and_fext_from_path, resolve_free_sample_fname, AudioFileHashes,
+};
+use crate::actions::projects::paths::to_device_slot_path;
 use crate::audio::utils::scan_dir_path_for_audio_files;
-use crate::{validate_project_version, OctatoolErrors, RBoxErr};
+use crate::transaction::{finish_transaction, get_backups_root_for_project, Transaction};
+use crate::{validate_project_version, validate_project_version_readable, OctatoolErrors, RBoxErr};
 
 use ot_tools_io::{
     projects::{slots::ProjectSampleSlot, Project},
-    read_type_from_bin_file, write_type_to_bin_file,
+    read_type_from_bin_file,
 };
 
 use itertools::Itertools;
 use std::{
+    collections::{HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
 };
+use walkdir::WalkDir;
 
 /// List all the sample slots within an Octatrack Project, given a path to a Project data file
 pub fn list_project_sample_slots(path: &Path) -> RBoxErr<()> {
     let project = read_type_from_bin_file::<Project>(path).expect("Could not load project file");
 
-    if !validate_project_version(&project) {
-        return Err(OctatoolErrors::InvalidOsVersion.into());
+    if !validate_project_version_readable(&project) {
+        return Err(OctatoolErrors::UnsupportedOsVersion.into());
     };
 
     let slots = project
@@ -35,141 +48,275 @@ pub fn list_project_sample_slots(path: &Path) -> RBoxErr<()> {
 }
 
 /// Copy sample files for project sample slots to the project set's audio pool directory,
-/// updating the project sample slot location.
-// TODO: What about file name duplicates...
+/// updating the project sample slot location (e.g. `../AUDIO/kick.wav`).
 pub fn consolidate_sample_slots_to_audio_pool(project_file_path: &Path) -> RBoxErr<()> {
+    finish_transaction(
+        stage_consolidate_sample_slots_to_audio_pool(project_file_path)?,
+        false,
+        None,
+    )
+}
+
+/// Stage the changes for consolidating sample slots to the set audio pool without changing anything on the file system.
+///
+/// Sample files already in the audio pool are reused when their contents match. A different file
+/// with the same name gets a new name (same as `stage_consolidate_sample_slots_to_project_pool`),
+/// e.g. `kick-a1B2c3D4.wav`.
+pub fn stage_consolidate_sample_slots_to_audio_pool(
+    project_file_path: &Path,
+) -> RBoxErr<Transaction> {
     let abs_project_fp = fs::canonicalize(project_file_path)?;
 
     let project_dir_path = abs_project_fp
         .parent()
-        .unwrap_or_else(|| panic!("Cannot find project directory from project file path."));
+        .ok_or(OctatoolErrors::PathIsNotADirectory)?;
 
     let audio_pool_path = project_dir_path
-        .to_path_buf()
         .parent()
-        .unwrap_or_else(|| panic!("Cannot find set directory from project file path."))
+        .ok_or(OctatoolErrors::PathIsNotASet)?
         .join("AUDIO");
 
-    let mut project =
-        read_type_from_bin_file::<Project>(project_file_path).expect("Could not load project file");
+    let mut project = read_type_from_bin_file::<Project>(project_file_path)?;
 
     if !validate_project_version(&project) {
         return Err(OctatoolErrors::InvalidOsVersion.into());
     };
 
+    let mut tx = Transaction::new(
+        &get_backups_root_for_project(project_dir_path)?,
+        &format!("consolidate sample slots to the audio pool for {abs_project_fp:?}"),
+    );
+
     let mut slots: Vec<ProjectSampleSlot> = project
         .slots
+        .clone()
         .into_iter()
-        .sorted_by(|x, y| Ord::cmp(&x.slot_id, &y.slot_id))
+        .sorted_by_key(|x| (x.sample_type.clone() as u8, x.slot_id))
         .collect();
 
+    let mut planned: HashMap<String, String> = HashMap::new();
+    let mut hashes = AudioFileHashes::default();
+    let mut copied: HashSet<PathBuf> = HashSet::new();
+
     for slot in slots.iter_mut() {
         // recording buffer slots can have an empty path field
-        if slot.path != PathBuf::from("") {
-            let audio_fname = slot
-                .path
-                .file_name()
-                .unwrap_or_else(|| panic!("Could not resolve filename for audio file."))
-                .to_str()
-                .unwrap_or_else(|| panic!("Could not convert filename into string."))
-                .to_string();
-
-            let audio_fpath = project_dir_path
-                .join(&slot.path)
-                .to_path_buf()
-                .canonicalize()
-                .expect("Could not get abslute path for sample slot.");
-
-            let new_audio_path = audio_pool_path.join(&audio_fname);
-
-            let _ = fs::copy(&audio_fpath, &new_audio_path)?;
-
-            let mut ot_filepath = audio_fpath.clone();
-            ot_filepath.set_extension("ot");
+        if slot.path == Path::new("") {
+            continue;
+        }
 
+        let audio_fpath = project_dir_path.join(&slot.path).canonicalize()?;
+        let Some(src_hash) = hashes.get(&audio_fpath) else {
+            return Err(Box::new(OctatoolErrors::PathDoesNotExist));
+        };
+        let fname = resolve_free_sample_fname(
+            &audio_fpath,
+            &src_hash,
+            &audio_pool_path,
+            &mut planned,
+            &mut hashes,
+        )?;
+        let new_audio_path = audio_pool_path.join(&fname);
+
+        if !new_audio_path.exists() && copied.insert(new_audio_path.clone()) {
+            tx.stage_copy(&audio_fpath, &new_audio_path)?;
+
+            let ot_filepath = audio_fpath.with_extension("ot");
             if ot_filepath.exists() {
-                let mut new_otfile_path = audio_pool_path.join(&audio_fname);
-                new_otfile_path.set_extension("ot");
-
-                let _ = fs::copy(&ot_filepath, &new_otfile_path)?;
+                tx.stage_copy(&ot_filepath, &new_audio_path.with_extension("ot"))?;
             }
-
-            slot.path = new_audio_path;
         }
+
+        slot.path = to_device_slot_path(project_dir_path, &new_audio_path)?;
     }
 
+    tx.record_project_slot_changes(&abs_project_fp, &project.slots, &slots);
     project.slots = slots;
-    write_type_to_bin_file::<Project>(&project, &abs_project_fp)
-        .expect("Could not save project file");
+    tx.stage_type::<Project>(&project, &abs_project_fp)?;
 
-    Ok(())
+    Ok(tx)
 }
 
-/// Copy sample files for project sample slots to the project set's audio pool directory,
-/// updating the project sample slot file path location -- does not reassign slots!.
-// TODO: What about file name duplicates...
-pub fn consolidate_sample_slots_to_project_pool(project_file_path: &Path) -> RBoxErr<()> {
-    let abs_project_fp = fs::canonicalize(project_file_path)?;
+/// Copy sample files for project sample slots to the project directory, updating the project
+/// sample slot file path location -- does not reassign slots!
+///
+/// When `delete_unused_pool_files` is set, sample files copied out of the set's audio pool are
+/// deleted from the audio pool if nothing else in the set uses them (other projects, or the
+/// saved / working state of this project which isn't being consolidated).
+pub fn consolidate_sample_slots_to_project_pool(
+    project_file_path: &Path,
+    delete_unused_pool_files: bool,
+) -> RBoxErr<()> {
+    finish_transaction(
+        stage_consolidate_sample_slots_to_project_pool(
+            project_file_path,
+            delete_unused_pool_files,
+        )?,
+        false,
+        None,
+    )
+}
 
-    println!("{:#?}", abs_project_fp);
+/// Stage the changes for consolidating sample slots to the project pool without changing anything on the file system.
+///
+/// Sample slot paths are rewritten relative to the project directory. Slots are handled in
+/// slot type then slot ID order, so when a different sample file with the same name is already
+/// in the project directory (or was copied there for an earlier slot) the new file name is
+/// always the same, e.g. `kick-a1B2c3D4.wav`.
+pub fn stage_consolidate_sample_slots_to_project_pool(
+    project_file_path: &Path,
+    delete_unused_pool_files: bool,
+) -> RBoxErr<Transaction> {
+    let abs_project_fp = fs::canonicalize(project_file_path)?;
 
     let project_dir_path = abs_project_fp
         .parent()
-        .unwrap_or_else(|| panic!("Cannot find project directory from project file path."));
+        .ok_or(OctatoolErrors::PathIsNotADirectory)?;
 
-    println!("{:#?}", project_dir_path);
+    let set_dir_path = project_dir_path
+        .parent()
+        .ok_or(OctatoolErrors::PathIsNotASet)?;
 
-    let mut project =
-        read_type_from_bin_file::<Project>(project_file_path).expect("Could not load project file");
+    let mut project = read_type_from_bin_file::<Project>(project_file_path)?;
 
     if !validate_project_version(&project) {
         return Err(OctatoolErrors::InvalidOsVersion.into());
     };
 
+    let mut tx = Transaction::new(
+        &get_backups_root_for_project(project_dir_path)?,
+        &format!("consolidate sample slots to the project pool for {abs_project_fp:?}"),
+    );
+
     let mut slots: Vec<ProjectSampleSlot> = project
         .slots
+        .clone()
         .into_iter()
-        .sorted_by(|x, y| Ord::cmp(&x.slot_id, &y.slot_id))
+        .sorted_by_key(|x| (x.sample_type.clone() as u8, x.slot_id))
         .collect();
 
+    let mut planned: HashMap<String, String> = HashMap::new();
+    let mut hashes = AudioFileHashes::default();
+    let mut copied: HashSet<PathBuf> = HashSet::new();
+    let mut pool_fpaths: Vec<PathBuf> = vec![];
+
     for slot in slots.iter_mut() {
         // recording buffer slots can have an empty path field
-        if slot.path != PathBuf::from("") {
-            let audio_fname = slot
-                .path
-                .file_name()
-                .unwrap_or_else(|| panic!("Could not resolve filename for audio file."))
-                .to_str()
-                .unwrap_or_else(|| panic!("Could not convert filename into string."))
-                .to_string();
-
-            let audio_fpath = project_dir_path.join(&slot.path);
-            println!("{:#?}", audio_fpath);
-            // must be relative to project file!
-            let new_audio_path = project_dir_path.join(&audio_fname);
-            println!("{:#?}", audio_fpath);
-
-            let _ = fs::copy(&audio_fpath, &new_audio_path)?;
-
-            let mut ot_filepath = audio_fpath.clone();
-            ot_filepath.set_extension("ot");
+        if slot.path == Path::new("") {
+            continue;
+        }
 
-            if ot_filepath.exists() {
-                let mut new_otfile_path = project_dir_path.join(&audio_fname);
-                new_otfile_path.set_extension("ot");
+        let audio_fpath = project_dir_path.join(&slot.path).canonicalize()?;
+
+        let fname = if audio_fpath.parent() == Some(project_dir_path) {
+            resolve_fname_and_fext_from_path(&audio_fpath)?
+        } else {
+            let Some(src_hash) = hashes.get(&audio_fpath) else {
+                return Err(Box::new(OctatoolErrors::PathDoesNotExist));
+            };
+            let fname = resolve_free_sample_fname(
+                &audio_fpath,
+                &src_hash,
+                project_dir_path,
+                &mut planned,
+                &mut hashes,
+            )?;
+            let new_audio_path = project_dir_path.join(&fname);
+
+            if !new_audio_path.exists() && copied.insert(new_audio_path.clone()) {
+                tx.stage_copy(&audio_fpath, &new_audio_path)?;
+
+                let ot_filepath = audio_fpath.with_extension("ot");
+                if ot_filepath.exists() {
+                    tx.stage_copy(&ot_filepath, &new_audio_path.with_extension("ot"))?;
+                }
+            }
 
-                let _ = fs::copy(&ot_filepath, &new_otfile_path)?;
+            if audio_fpath.starts_with(set_dir_path.join("AUDIO"))
+                && !pool_fpaths.contains(&audio_fpath)
+            {
+                pool_fpaths.push(audio_fpath.clone());
             }
+            fname
+        };
+
+        slot.path = to_device_slot_path(project_dir_path, &project_dir_path.join(fname))?;
+    }
+
+    if delete_unused_pool_files && !pool_fpaths.is_empty() {
+        let used_elsewhere =
+            get_sample_fpaths_used_by_other_projects(set_dir_path, &abs_project_fp)?;
+
+        for pool_fpath in pool_fpaths.iter().filter(|x| !used_elsewhere.contains(*x)) {
+            tx.stage_remove(pool_fpath)?;
 
-            slot.path = new_audio_path;
+            let ot_filepath = pool_fpath.with_extension("ot");
+            if ot_filepath.exists() {
+                tx.stage_remove(&ot_filepath)?;
+            }
         }
     }
 
+    tx.record_project_slot_changes(&abs_project_fp, &project.slots, &slots);
     project.slots = slots;
-    write_type_to_bin_file::<Project>(&project, &abs_project_fp)
-        .expect("Could not save project file");
+    tx.stage_type::<Project>(&project, &abs_project_fp)?;
 
-    Ok(())
+    Ok(tx)
+}
+
+/// Absolute paths of the sample files loaded into sample slots by every project in a set, apart
+/// from the project file `exclude_project_fpath` (absolute). Both the working and saved project
+/// files are checked, so the other state of the excluded project is still included. Missing
+/// sample files are ignored.
+fn get_sample_fpaths_used_by_other_projects(
+    set_dirpath: &Path,
+    exclude_project_fpath: &Path,
+) -> RBoxErr<HashSet<PathBuf>> {
+    let mut used = HashSet::new();
+
+    for entry in fs::read_dir(set_dirpath)? {
+        let dirpath = entry?.path().canonicalize()?;
+        if !dirpath.is_dir() {
+            continue;
+        }
+
+        for project_fpath in [dirpath.join("project.work"), dirpath.join("project.strd")] {
+            if !project_fpath.exists() || project_fpath == exclude_project_fpath {
+                continue;
+            }
+            let project = read_type_from_bin_file::<Project>(&project_fpath)?;
+            used.extend(
+                project
+                    .slots
+                    .iter()
+                    .filter(|x| x.path != Path::new(""))
+                    .filter_map(|x| dirpath.join(&x.path).canonicalize().ok()),
+            );
+        }
+    }
+
+    Ok(used)
+}
+
+/// Find every project directory (directories with a `project.work` file) in a set or compact
+/// flash card directory tree, in path order. Hidden directories, e.g. backups, are skipped.
+pub fn find_project_dirpaths(root_dirpath: &Path) -> RBoxErr<Vec<PathBuf>> {
+    if !root_dirpath.is_dir() {
+        return Err(OctatoolErrors::PathIsNotADirectory.into());
+    }
+
+    let mut dirpaths = vec![];
+    for entry in WalkDir::new(root_dirpath)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|x| x.depth() == 0 || !x.file_name().to_string_lossy().starts_with('.'))
+    {
+        let entry = entry?;
+        if entry.file_type().is_dir() && entry.path().join("project.work").is_file() {
+            dirpaths.push(entry.path().to_path_buf());
+        }
+    }
+
+    Ok(dirpaths)
 }
 
 /// Remove audio sample files from the project directory which
@@ -177,59 +324,65 @@ pub fn consolidate_sample_slots_to_project_pool(project_file_path: &Path) -> RBo
 /// No such feature exists for a set audio pool, as the set audio pool is
 /// supposed to have a bunch of possible samples available which may not be in use.
 pub fn purge_project_pool(project_file_path: &Path) -> RBoxErr<()> {
+    finish_transaction(stage_purge_project_pool(project_file_path)?, false, None)
+}
+
+/// Stage the removal of unused project pool sample files without changing anything on the
+/// file system.
+pub fn stage_purge_project_pool(project_file_path: &Path) -> RBoxErr<Transaction> {
     let abs_project_fp = fs::canonicalize(project_file_path)?;
 
     let project_dir_path = abs_project_fp
         .parent()
-        .unwrap_or_else(|| panic!("Cannot find project directory from project file path."));
+        .ok_or(OctatoolErrors::PathIsNotADirectory)?;
 
-    let project =
-        read_type_from_bin_file::<Project>(&abs_project_fp).expect("Could not load project file");
+    let project = read_type_from_bin_file::<Project>(&abs_project_fp)?;
 
     if !validate_project_version(&project) {
         return Err(OctatoolErrors::InvalidOsVersion.into());
     };
 
+    let mut tx = Transaction::new(
+        &get_backups_root_for_project(project_dir_path)?,
+        &format!("purge unused sample files from the project pool for {abs_project_fp:?}"),
+    );
+
     let sample_file_slots: Vec<ProjectSampleSlot> = project
         .slots
         .into_iter()
         .sorted_by(|x, y| Ord::cmp(&x.slot_id, &y.slot_id))
-        .filter(|x| x.path != PathBuf::from(""))
+        .filter(|x| x.path != Path::new(""))
         .collect();
 
     let slot_paths: Vec<PathBuf> = sample_file_slots
         .into_iter()
-        .map(|x| {
-            project_dir_path
-                .join(x.path)
-                .to_path_buf()
-                .canonicalize()
-                .expect("Could not get absolute path for sample slot.")
-        })
-        .collect();
+        .map(|x| project_dir_path.join(x.path).canonicalize())
+        .collect::<Result<_, _>>()?;
     let samples: Vec<PathBuf> = scan_dir_path_for_audio_files(&project_dir_path.to_path_buf())?;
 
     for sample in samples {
         if !slot_paths.contains(&sample) {
-            fs::remove_file(&sample)?;
+            tx.stage_remove(&sample)?;
 
             let mut ot_filepath = sample.clone();
             ot_filepath.set_extension("ot");
 
             if ot_filepath.exists() {
-                fs::remove_file(&ot_filepath)?;
+                tx.stage_remove(&ot_filepath)?;
             }
         }
     }
 
-    Ok(())
+    Ok(tx)
 }
 
 #[cfg(test)]
 #[allow(unused_imports)]
 mod test {
     use super::*;
-    use ot_tools_io::yaml_file_to_bin_file;
+    use crate::utils::get_md5_hash_from_path;
+    use ot_tools_io::projects::options::ProjectSampleSlotType;
+    use ot_tools_io::{write_type_to_bin_file, yaml_file_to_bin_file};
 
     #[test]
     fn test_list_sample_slots_ok() {
@@ -308,6 +461,104 @@ mod test {
         assert!(test_dir_path.join("PROJECT/fourth-0.wav").exists());
         assert!(test_dir_path.join("PROJECT/fifth-0.wav").exists());
         assert!(test_dir_path.join("PROJECT/fifth-0.ot").exists());
+
+        let project =
+            read_type_from_bin_file::<Project>(&test_dir_path.join("PROJECT/project.work"))
+                .unwrap();
+        let paths: Vec<PathBuf> = project
+            .slots
+            .iter()
+            .filter(|x| x.path != Path::new(""))
+            .sorted_by_key(|x| x.slot_id)
+            .map(|x| x.path.clone())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "../AUDIO/first-0.wav",
+                "../AUDIO/second-0.wav",
+                "../AUDIO/third-0.wav",
+                "../AUDIO/fourth-0.wav",
+                "../AUDIO/fifth-0.wav"
+            ]
+            .map(PathBuf::from)
+            .to_vec()
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_consolidate_sslots_audio_pool_name_collision() {
+        let base_path =
+            PathBuf::from("../data/tests/projects/sample-slots/consolidation/to_audio_pool/");
+
+        let test_dir_path = std::env::temp_dir().join("ot_consolidate_audio_pool_collision");
+
+        if test_dir_path.exists() {
+            let _ = fs::remove_dir_all(&test_dir_path);
+        };
+
+        make_sslot_mock_set_dir(&test_dir_path);
+
+        // different audio with the same name as a project sample file
+        let other_fpath = test_dir_path.join("AUDIO/fourth-0.wav");
+        let _ = fs::copy(
+            PathBuf::from("./../data/tests/samples/chains/create/default-1-samples/valid-1.wav"),
+            &other_fpath,
+        );
+        let other_hash = get_md5_hash_from_path(&other_fpath).unwrap();
+
+        yaml_file_to_bin_file::<Project>(
+            &base_path.join("init/project.yaml"),
+            &test_dir_path.join("PROJECT/project.work"),
+        )
+        .unwrap();
+
+        consolidate_sample_slots_to_audio_pool(&test_dir_path.join("PROJECT/project.work"))
+            .unwrap();
+
+        let project =
+            read_type_from_bin_file::<Project>(&test_dir_path.join("PROJECT/project.work"))
+                .unwrap();
+        let fourth = project.slots.iter().find(|x| x.slot_id == 4).unwrap();
+
+        assert!(fourth
+            .path
+            .to_str()
+            .unwrap()
+            .starts_with("../AUDIO/fourth-0-"));
+        assert_eq!(get_md5_hash_from_path(&other_fpath).unwrap(), other_hash);
+        assert_eq!(
+            get_md5_hash_from_path(&test_dir_path.join("PROJECT").join(&fourth.path)).unwrap(),
+            get_md5_hash_from_path(&test_dir_path.join("PROJECT/fourth-0.wav")).unwrap(),
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_consolidate_sslots_missing_sample_err() {
+        let base_path =
+            PathBuf::from("../data/tests/projects/sample-slots/consolidation/to_audio_pool/");
+
+        let test_dir_path = std::env::temp_dir().join("ot_consolidate_missing_sample");
+
+        if test_dir_path.exists() {
+            let _ = fs::remove_dir_all(&test_dir_path);
+        };
+
+        make_sslot_mock_set_dir(&test_dir_path);
+        let _ = fs::remove_file(test_dir_path.join("PROJECT/fifth-0.wav"));
+
+        yaml_file_to_bin_file::<Project>(
+            &base_path.join("init/project.yaml"),
+            &test_dir_path.join("PROJECT/project.work"),
+        )
+        .unwrap();
+
+        let project_fpath = test_dir_path.join("PROJECT/project.work");
+        assert!(stage_consolidate_sample_slots_to_audio_pool(&project_fpath).is_err());
+        assert!(stage_consolidate_sample_slots_to_project_pool(&project_fpath, false).is_err());
+        assert!(stage_purge_project_pool(&project_fpath).is_err());
     }
 
     // fails due to paths on windows
@@ -337,8 +588,10 @@ mod test {
         )
         .unwrap();
 
-        let r =
-            consolidate_sample_slots_to_project_pool(&test_dir_path.join("PROJECT/project.work"));
+        let r = consolidate_sample_slots_to_project_pool(
+            &test_dir_path.join("PROJECT/project.work"),
+            false,
+        );
 
         assert!(r.is_ok());
 
@@ -352,6 +605,194 @@ mod test {
         assert!(test_dir_path.join("PROJECT/fourth-0.wav").exists());
         assert!(test_dir_path.join("PROJECT/fifth-0.wav").exists());
         assert!(test_dir_path.join("PROJECT/fifth-0.ot").exists());
+        assert!(test_dir_path.join("PROJECT/third-0.ot").exists());
+
+        let project =
+            read_type_from_bin_file::<Project>(&test_dir_path.join("PROJECT/project.work"))
+                .unwrap();
+        let paths: Vec<PathBuf> = project
+            .slots
+            .iter()
+            .filter(|x| x.path != Path::new(""))
+            .sorted_by_key(|x| x.slot_id)
+            .map(|x| x.path.clone())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "first-0.wav",
+                "second-0.wav",
+                "third-0.wav",
+                "fourth-0.wav",
+                "fifth-0.wav"
+            ]
+            .map(PathBuf::from)
+            .to_vec()
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_consolidate_sslots_project_pool_name_collision() {
+        let base_path =
+            PathBuf::from("./../data/tests/projects/sample-slots/consolidation/to_project_pool/");
+
+        let test_dir_path = std::env::temp_dir().join("ot_consolidate_project_pool_collision");
+
+        if test_dir_path.exists() {
+            let _ = fs::remove_dir_all(&test_dir_path);
+        };
+
+        make_sslot_mock_set_dir(&test_dir_path);
+
+        // different audio with the same name as a file in the audio pool
+        let _ = fs::copy(
+            PathBuf::from("./../data/tests/samples/chains/create/default-1-samples/valid-1.wav"),
+            test_dir_path.join("PROJECT/first-0.wav"),
+        );
+
+        yaml_file_to_bin_file::<Project>(
+            &base_path.join("init/project.yaml"),
+            &test_dir_path.join("PROJECT/project.work"),
+        )
+        .unwrap();
+
+        let tx = stage_consolidate_sample_slots_to_project_pool(
+            &test_dir_path.join("PROJECT/project.work"),
+            false,
+        )
+        .unwrap();
+        let again = stage_consolidate_sample_slots_to_project_pool(
+            &test_dir_path.join("PROJECT/project.work"),
+            false,
+        )
+        .unwrap();
+        assert_eq!(tx.plan().unwrap().slots, again.plan().unwrap().slots);
+
+        finish_transaction(tx, false, None).unwrap();
+
+        let project =
+            read_type_from_bin_file::<Project>(&test_dir_path.join("PROJECT/project.work"))
+                .unwrap();
+        let first = project.slots.iter().find(|x| x.slot_id == 1).unwrap();
+
+        assert_ne!(first.path, PathBuf::from("first-0.wav"));
+        assert!(first.path.to_str().unwrap().starts_with("first-0-"));
+        assert_eq!(first.path.parent(), Some(Path::new("")));
+        assert_eq!(
+            get_md5_hash_from_path(&test_dir_path.join("PROJECT").join(&first.path)).unwrap(),
+            get_md5_hash_from_path(&test_dir_path.join("AUDIO/first-0.wav")).unwrap(),
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_consolidate_sslots_project_pool_delete_unused() {
+        let base_path =
+            PathBuf::from("./../data/tests/projects/sample-slots/consolidation/to_project_pool/");
+
+        let test_dir_path = std::env::temp_dir().join("ot_consolidate_project_pool_delete");
+
+        if test_dir_path.exists() {
+            let _ = fs::remove_dir_all(&test_dir_path);
+        };
+
+        make_sslot_mock_set_dir(&test_dir_path);
+
+        yaml_file_to_bin_file::<Project>(
+            &base_path.join("init/project.yaml"),
+            &test_dir_path.join("PROJECT/project.work"),
+        )
+        .unwrap();
+
+        // another project in the set still uses one of the audio pool samples
+        let _ = fs::create_dir(test_dir_path.join("OTHER"));
+        let mut other = Project::default();
+        other.slots.push(
+            ProjectSampleSlot::new(
+                ProjectSampleSlotType::Static,
+                1,
+                PathBuf::from("../AUDIO/second-0.wav"),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap(),
+        );
+        write_type_to_bin_file::<Project>(&other, &test_dir_path.join("OTHER/project.work"))
+            .unwrap();
+
+        let r = consolidate_sample_slots_to_project_pool(
+            &test_dir_path.join("PROJECT/project.work"),
+            true,
+        );
+
+        assert!(r.is_ok());
+
+        assert!(test_dir_path.join("PROJECT/first-0.wav").exists());
+        assert!(test_dir_path.join("PROJECT/second-0.wav").exists());
+        assert!(test_dir_path.join("PROJECT/third-0.wav").exists());
+        assert!(test_dir_path.join("PROJECT/third-0.ot").exists());
+        assert!(!test_dir_path.join("AUDIO/first-0.wav").exists());
+        assert!(test_dir_path.join("AUDIO/second-0.wav").exists());
+        assert!(!test_dir_path.join("AUDIO/third-0.wav").exists());
+        assert!(!test_dir_path.join("AUDIO/third-0.ot").exists());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_consolidate_sslots_project_pool_delete_unused_keeps_strd() {
+        let base_path =
+            PathBuf::from("./../data/tests/projects/sample-slots/consolidation/to_project_pool/");
+
+        let test_dir_path = std::env::temp_dir().join("ot_consolidate_project_pool_delete_strd");
+
+        if test_dir_path.exists() {
+            let _ = fs::remove_dir_all(&test_dir_path);
+        };
+
+        make_sslot_mock_set_dir(&test_dir_path);
+
+        yaml_file_to_bin_file::<Project>(
+            &base_path.join("init/project.yaml"),
+            &test_dir_path.join("PROJECT/project.work"),
+        )
+        .unwrap();
+
+        // the saved project state is the only other reference to an audio pool sample
+        let mut strd = Project::default();
+        strd.slots.push(
+            ProjectSampleSlot::new(
+                ProjectSampleSlotType::Static,
+                1,
+                PathBuf::from("../AUDIO/first-0.wav"),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap(),
+        );
+        write_type_to_bin_file::<Project>(&strd, &test_dir_path.join("PROJECT/project.strd"))
+            .unwrap();
+
+        let r = consolidate_sample_slots_to_project_pool(
+            &test_dir_path.join("PROJECT/project.work"),
+            true,
+        );
+
+        assert!(r.is_ok());
+
+        assert!(test_dir_path.join("PROJECT/first-0.wav").exists());
+        assert!(test_dir_path.join("AUDIO/first-0.wav").exists());
+        assert!(!test_dir_path.join("AUDIO/second-0.wav").exists());
+
+        let _ = fs::remove_dir_all(&test_dir_path);
     }
 
     #[cfg(not(target_os = "windows"))]
@@ -0,0 +1,485 @@
+//! Export a project as a self-contained (tar) archive which can be imported into another set.
+//!
+//! Archive layout:
+//! ```text
+//! manifest.json
+//! <PROJECT>/project.work
+//! <PROJECT>/bank01.work
+//! ...
+//! <PROJECT>/kick.wav
+//! <PROJECT>/kick.ot
+//! ```
+//! Every sample file used by the project (whether in the set's audio pool or the project
+//! directory) is stored in the project directory of the archive, and sample slot paths in the
+//! archived project files are rewritten to match.
+
+use crate::actions::banks::utils::{resolve_free_sample_fname, AudioFileHashes};
+use crate::actions::projects::paths::to_device_slot_path;
+use crate::utils::get_md5_hash_from_path;
+use crate::{validate_project_version_readable, OctatoolErrors, RBoxErr};
+use itertools::Itertools;
+use ot_tools_io::projects::{options::ProjectSampleSlotType, Project};
+use ot_tools_io::{read_type_from_bin_file, serialize_bin_from_type};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Version of the archive layout / manifest written by `export_project`.
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_FNAME: &str = "manifest.json";
+
+#[derive(Debug)]
+pub enum ProjectArchiveErrors {
+    MissingSampleFile(PathBuf),
+    MissingManifest,
+    UnsupportedFormatVersion(u32),
+    InvalidArchivePath(PathBuf),
+    ChecksumMismatch(PathBuf),
+}
+impl std::fmt::Display for ProjectArchiveErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::MissingSampleFile(path) => {
+                write!(f, "sample file used by the project is missing: {path:?}")
+            }
+            Self::MissingManifest => write!(f, "archive has no {MANIFEST_FNAME} file"),
+            Self::UnsupportedFormatVersion(v) => write!(
+                f,
+                "unsupported archive format version {v} (supported: {ARCHIVE_FORMAT_VERSION})"
+            ),
+            Self::InvalidArchivePath(path) => write!(f, "invalid file path in archive: {path:?}"),
+            Self::ChecksumMismatch(path) => {
+                write!(
+                    f,
+                    "sample file does not match the archive manifest: {path:?}"
+                )
+            }
+        }
+    }
+}
+impl std::error::Error for ProjectArchiveErrors {}
+
+/// A sample file stored in a project archive.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ArchivedSample {
+    /// Sample slot path in the exported project
+    pub original_path: PathBuf,
+    /// File name within the archived project directory
+    pub archive_fname: String,
+    /// md5 hash of the audio file (see `get_md5_hash_from_path`)
+    pub md5: String,
+    /// Whether the sample's `.ot` attributes file is included
+    pub attributes_file: bool,
+}
+
+/// Describes the contents of a project archive, stored in the archive as `manifest.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProjectArchiveManifest {
+    pub format_version: u32,
+    pub project_name: String,
+    pub os_version: String,
+    /// Project data files (`project.work`, `bank01.strd` etc.)
+    pub data_files: Vec<String>,
+    pub samples: Vec<ArchivedSample>,
+}
+
+/// Project data files in a project directory (`*.work` and `*.strd` files), sorted by name.
+fn list_project_data_fnames(project_dirpath: &Path) -> RBoxErr<Vec<String>> {
+    let mut fnames = vec![];
+    for entry in fs::read_dir(project_dirpath)? {
+        let path = entry?.path();
+        let is_data_file = path.extension().is_some_and(|x| x == "work" || x == "strd");
+        if path.is_file() && is_data_file {
+            if let Some(fname) = path.file_name().and_then(|x| x.to_str()) {
+                fnames.push(fname.to_string());
+            }
+        }
+    }
+    fnames.sort();
+    Ok(fnames)
+}
+
+fn append_bytes(
+    builder: &mut tar::Builder<File>,
+    archive_path: &Path,
+    bytes: &[u8],
+) -> RBoxErr<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(bytes.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, archive_path, bytes)?;
+    Ok(())
+}
+
+/// Export a project, and every sample file it uses, to a single tar archive at `archive_fpath`.
+///
+/// Samples are stored next to the project files with slot paths rewritten to be project-local.
+/// Different sample files with the same name are renamed (see `resolve_free_sample_fname`).
+pub fn export_project(
+    project_dirpath: &Path,
+    archive_fpath: &Path,
+) -> RBoxErr<ProjectArchiveManifest> {
+    let project_dirpath = fs::canonicalize(project_dirpath)?;
+    let project_name = project_dirpath
+        .file_name()
+        .and_then(|x| x.to_str())
+        .ok_or(OctatoolErrors::PathIsNotADirectory)?
+        .to_string();
+
+    let work_project = read_type_from_bin_file::<Project>(&project_dirpath.join("project.work"))?;
+    if !validate_project_version_readable(&work_project) {
+        return Err(OctatoolErrors::UnsupportedOsVersion.into());
+    };
+
+    let data_fnames = list_project_data_fnames(&project_dirpath)?;
+
+    // sample files used by either the working or saved project
+    let mut projects: Vec<(String, Project)> = vec![("project.work".to_string(), work_project)];
+    if data_fnames.contains(&"project.strd".to_string()) {
+        let strd_project =
+            read_type_from_bin_file::<Project>(&project_dirpath.join("project.strd"))?;
+        projects.push(("project.strd".to_string(), strd_project));
+    }
+
+    let mut hashes = AudioFileHashes::default();
+    let mut planned: HashMap<String, String> = HashMap::new();
+    let mut archived: HashMap<PathBuf, ArchivedSample> = HashMap::new();
+    let mut samples: Vec<(PathBuf, ArchivedSample)> = vec![];
+
+    for (_, project) in projects.iter_mut() {
+        for slot in project
+            .slots
+            .iter_mut()
+            .filter(|x| x.sample_type != ProjectSampleSlotType::RecorderBuffer)
+            .filter(|x| x.path != Path::new(""))
+            .sorted_by_key(|x| (x.sample_type.clone() as u8, x.slot_id))
+        {
+            let audio_fpath = project_dirpath
+                .join(&slot.path)
+                .canonicalize()
+                .map_err(|_| ProjectArchiveErrors::MissingSampleFile(slot.path.clone()))?;
+
+            if !archived.contains_key(&audio_fpath) {
+                let Some(md5) = hashes.get(&audio_fpath) else {
+                    return Err(ProjectArchiveErrors::MissingSampleFile(slot.path.clone()).into());
+                };
+                let archive_fname = resolve_free_sample_fname(
+                    &audio_fpath,
+                    &md5,
+                    &project_dirpath,
+                    &mut planned,
+                    &mut hashes,
+                )?;
+                let sample = ArchivedSample {
+                    original_path: slot.path.clone(),
+                    archive_fname,
+                    md5,
+                    attributes_file: audio_fpath.with_extension("ot").is_file(),
+                };
+                archived.insert(audio_fpath.clone(), sample.clone());
+                samples.push((audio_fpath.clone(), sample));
+            }
+
+            slot.path = to_device_slot_path(
+                &project_dirpath,
+                &project_dirpath.join(&archived[&audio_fpath].archive_fname),
+            )?;
+        }
+    }
+
+    let manifest = ProjectArchiveManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        project_name: project_name.clone(),
+        os_version: projects[0].1.metadata.os_version.clone(),
+        data_files: data_fnames.clone(),
+        samples: samples.iter().map(|(_, x)| x.clone()).collect(),
+    };
+
+    let archive_project_dirpath = PathBuf::from(&project_name);
+    let mut builder = tar::Builder::new(File::create(archive_fpath)?);
+
+    append_bytes(
+        &mut builder,
+        Path::new(MANIFEST_FNAME),
+        serde_json::to_string_pretty(&manifest)?.as_bytes(),
+    )?;
+
+    for fname in &data_fnames {
+        let archive_path = archive_project_dirpath.join(fname);
+        match projects.iter().find(|(x, _)| x == fname) {
+            Some((_, project)) => append_bytes(
+                &mut builder,
+                &archive_path,
+                &serialize_bin_from_type::<Project>(project)?,
+            )?,
+            None => builder.append_path_with_name(project_dirpath.join(fname), &archive_path)?,
+        }
+    }
+
+    for (audio_fpath, sample) in &samples {
+        let archive_path = archive_project_dirpath.join(&sample.archive_fname);
+        builder.append_path_with_name(audio_fpath, &archive_path)?;
+        if sample.attributes_file {
+            builder.append_path_with_name(
+                audio_fpath.with_extension("ot"),
+                archive_path.with_extension("ot"),
+            )?;
+        }
+    }
+
+    builder.into_inner()?;
+
+    println!(
+        "Exported project {project_name:?} with {} sample files to {archive_fpath:?}",
+        samples.len()
+    );
+
+    Ok(manifest)
+}
+
+/// Read the manifest from a project archive.
+pub fn read_project_archive_manifest(archive_fpath: &Path) -> RBoxErr<ProjectArchiveManifest> {
+    let mut archive = tar::Archive::new(File::open(archive_fpath)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()? == Path::new(MANIFEST_FNAME) {
+            let mut s = String::new();
+            entry.read_to_string(&mut s)?;
+            let manifest: ProjectArchiveManifest = serde_json::from_str(&s)?;
+            if manifest.format_version != ARCHIVE_FORMAT_VERSION {
+                return Err(ProjectArchiveErrors::UnsupportedFormatVersion(
+                    manifest.format_version,
+                )
+                .into());
+            }
+            return Ok(manifest);
+        }
+    }
+    Err(ProjectArchiveErrors::MissingManifest.into())
+}
+
+/// Get a project directory name in the set which isn't used yet, e.g. `MYPROJECT-2` when
+/// `MYPROJECT` already exists.
+fn resolve_free_project_dirname(set_dirpath: &Path, name: &str) -> String {
+    let mut candidate = name.to_string();
+    let mut n = 1;
+    while set_dirpath.join(&candidate).exists() {
+        n += 1;
+        candidate = format!("{name}-{n}");
+    }
+    candidate
+}
+
+fn unpack_project_archive(
+    archive_fpath: &Path,
+    manifest: &ProjectArchiveManifest,
+    project_dirpath: &Path,
+) -> RBoxErr<()> {
+    let mut archive = tar::Archive::new(File::open(archive_fpath)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        if path == Path::new(MANIFEST_FNAME) {
+            continue;
+        }
+
+        // only plain files within the archived project directory, nothing that could be
+        // written outside of the new project directory
+        let components = path.components().collect_vec();
+        let fname = match components.as_slice() {
+            [Component::Normal(dirname), Component::Normal(fname)]
+                if *dirname == manifest.project_name.as_str() =>
+            {
+                fname.to_owned()
+            }
+            _ => return Err(ProjectArchiveErrors::InvalidArchivePath(path).into()),
+        };
+        if !entry.header().entry_type().is_file() {
+            return Err(ProjectArchiveErrors::InvalidArchivePath(path).into());
+        }
+        entry.unpack(project_dirpath.join(fname))?;
+    }
+
+    for sample in &manifest.samples {
+        let fpath = project_dirpath.join(&sample.archive_fname);
+        if get_md5_hash_from_path(&fpath).ok().as_ref() != Some(&sample.md5) {
+            return Err(ProjectArchiveErrors::ChecksumMismatch(fpath).into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Import a project archive created with `export_project` into the set at `set_dirpath`,
+/// returning the new project's directory path.
+///
+/// The project is imported as `project_name` (or the exported project's name when `None`). When
+/// a directory with that name already exists in the set, the name is suffixed with a number,
+/// e.g. `MYPROJECT-2`. Nothing existing in the set is modified.
+pub fn import_project(
+    archive_fpath: &Path,
+    set_dirpath: &Path,
+    project_name: Option<&str>,
+) -> RBoxErr<PathBuf> {
+    if !set_dirpath.join("AUDIO").is_dir() {
+        return Err(OctatoolErrors::PathIsNotASet.into());
+    }
+
+    let manifest = read_project_archive_manifest(archive_fpath)?;
+    let name = project_name.unwrap_or(&manifest.project_name);
+    if Path::new(name).components().collect_vec() != [Component::Normal(name.as_ref())] {
+        return Err(ProjectArchiveErrors::InvalidArchivePath(PathBuf::from(name)).into());
+    }
+
+    let project_dirpath = set_dirpath.join(resolve_free_project_dirname(set_dirpath, name));
+    fs::create_dir(&project_dirpath)?;
+
+    if let Err(e) = unpack_project_archive(archive_fpath, &manifest, &project_dirpath) {
+        let _ = fs::remove_dir_all(&project_dirpath);
+        return Err(e);
+    }
+
+    println!(
+        "Imported project {:?} with {} sample files to {project_dirpath:?}",
+        manifest.project_name,
+        manifest.samples.len()
+    );
+
+    Ok(project_dirpath)
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use ot_tools_io::yaml_file_to_bin_file;
+
+    /// Set with a project using samples from both the audio pool and the project directory,
+    /// including two different samples named `kick.wav`.
+    fn make_mock_set(set_dirpath: &Path) {
+        if set_dirpath.exists() {
+            let _ = fs::remove_dir_all(set_dirpath);
+        }
+        fs::create_dir_all(set_dirpath.join("AUDIO")).unwrap();
+        fs::create_dir_all(set_dirpath.join("PROJECT")).unwrap();
+
+        let misc = PathBuf::from("../data/tests/misc");
+        let other_wav =
+            PathBuf::from("../data/tests/samples/chains/create/default-1-samples/valid-1.wav");
+        for (src, dest) in [
+            (misc.join("test.wav"), "AUDIO/first-0.wav"),
+            (misc.join("test.wav"), "AUDIO/second-0.wav"),
+            (misc.join("pair.wav"), "AUDIO/third-0.wav"),
+            (misc.join("pair.ot"), "AUDIO/third-0.ot"),
+            (other_wav.clone(), "PROJECT/fourth-0.wav"),
+            (other_wav, "PROJECT/fifth-0.wav"),
+            (misc.join("pair.ot"), "PROJECT/fifth-0.ot"),
+        ] {
+            fs::copy(src, set_dirpath.join(dest)).unwrap();
+        }
+
+        yaml_file_to_bin_file::<Project>(
+            Path::new(
+                "../data/tests/projects/sample-slots/consolidation/to_audio_pool/init/project.yaml",
+            ),
+            &set_dirpath.join("PROJECT/project.work"),
+        )
+        .unwrap();
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn export_then_import() {
+        let test_dirpath = std::env::temp_dir().join("ot_project_archive");
+        let src_set = test_dirpath.join("SRC");
+        let dest_set = test_dirpath.join("DEST");
+        make_mock_set(&src_set);
+        make_mock_set(&dest_set);
+
+        let archive_fpath = test_dirpath.join("project.tar");
+        let manifest = export_project(&src_set.join("PROJECT"), &archive_fpath).unwrap();
+
+        assert_eq!(manifest.project_name, "PROJECT");
+        assert_eq!(manifest.data_files, vec!["project.work".to_string()]);
+        // static slots first, then flex slots
+        assert_eq!(
+            manifest
+                .samples
+                .iter()
+                .map(|x| x.archive_fname.as_str())
+                .collect_vec(),
+            vec![
+                "first-0.wav",
+                "second-0.wav",
+                "fourth-0.wav",
+                "third-0.wav",
+                "fifth-0.wav"
+            ]
+        );
+
+        // name collision with the existing project in the destination set
+        let imported = import_project(&archive_fpath, &dest_set, None).unwrap();
+        assert_eq!(imported, dest_set.join("PROJECT-2"));
+
+        let project = read_type_from_bin_file::<Project>(&imported.join("project.work")).unwrap();
+        for slot in project.slots.iter().filter(|x| x.path != Path::new("")) {
+            assert_eq!(slot.path.parent(), Some(Path::new("")));
+            assert!(imported.join(&slot.path).is_file());
+        }
+        assert!(imported.join("third-0.ot").is_file());
+        assert!(imported.join("fifth-0.ot").is_file());
+
+        // existing project untouched
+        let existing =
+            read_type_from_bin_file::<Project>(&dest_set.join("PROJECT/project.work")).unwrap();
+        assert!(existing
+            .slots
+            .iter()
+            .any(|x| x.path == Path::new("../AUDIO/first-0.wav")));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn export_renames_colliding_sample_names() {
+        let test_dirpath = std::env::temp_dir().join("ot_project_archive_collision");
+        let set = test_dirpath.join("SET");
+        make_mock_set(&set);
+
+        // a different sample with the same name as a sample in the audio pool
+        fs::copy(
+            "../data/tests/samples/chains/create/default-1-samples/valid-1.wav",
+            set.join("PROJECT/first-0.wav"),
+        )
+        .unwrap();
+        let mut project =
+            read_type_from_bin_file::<Project>(&set.join("PROJECT/project.work")).unwrap();
+        let slot = project.slots.iter_mut().find(|x| x.slot_id == 4).unwrap();
+        slot.path = PathBuf::from("first-0.wav");
+        ot_tools_io::write_type_to_bin_file(&project, &set.join("PROJECT/project.work")).unwrap();
+
+        let manifest = export_project(&set.join("PROJECT"), &test_dirpath.join("a.tar")).unwrap();
+        let fnames = manifest
+            .samples
+            .iter()
+            .map(|x| x.archive_fname.clone())
+            .collect_vec();
+
+        assert_eq!(fnames.iter().unique().count(), fnames.len());
+        assert!(fnames[0].starts_with("first-0-"));
+        assert!(fnames.contains(&"first-0.wav".to_string()));
+    }
+
+    #[test]
+    fn import_not_a_set_err() {
+        let r = import_project(
+            Path::new("missing.tar"),
+            Path::new("../data/tests/misc"),
+            None,
+        );
+        assert!(r.is_err());
+    }
+}
@@ -0,0 +1,414 @@
+//! Create new projects within a set, either from defaults or from a template project, with
+//! optional changes from a YAML spec.
+
+use crate::actions::projects::paths::{get_device_slot_path, validate_slot_path};
+use crate::transaction::{finish_transaction, Transaction};
+use crate::{validate_project_version, OctatoolErrors, RBoxErr};
+use ot_tools_io::{
+    arrangements::ArrangementFile,
+    banks::Bank,
+    projects::{options::ProjectSampleSlotType, slots::ProjectSampleSlot, Project},
+    read_type_from_bin_file,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Longest project name the device's naming screen allows.
+pub const MAX_PROJECT_NAME_LEN: usize = 12;
+
+/// Longest part name the device allows.
+pub const MAX_PART_NAME_LEN: usize = 7;
+
+#[derive(Debug)]
+pub enum NewProjectErrors {
+    InvalidProjectName(String),
+    ProjectExists(PathBuf),
+    InvalidBankId(usize),
+    InvalidPartName(String),
+    TooManyPartNames(usize),
+}
+impl std::fmt::Display for NewProjectErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::InvalidProjectName(name) => write!(
+                f,
+                "invalid project name {name:?} - names must be 1-{MAX_PROJECT_NAME_LEN} characters long and only use letters, numbers, '-' or '_'"
+            ),
+            Self::ProjectExists(path) => write!(f, "project directory already exists: {path:?}"),
+            Self::InvalidBankId(id) => write!(
+                f,
+                "Invalid bank number {id} - only numbers between 1-16 (inclusive) can be provided"
+            ),
+            Self::InvalidPartName(name) => write!(
+                f,
+                "invalid part name {name:?} - names must be 1-{MAX_PART_NAME_LEN} ASCII characters long"
+            ),
+            Self::TooManyPartNames(n) => {
+                write!(f, "{n} part names provided for a bank with 4 parts")
+            }
+        }
+    }
+}
+impl std::error::Error for NewProjectErrors {}
+
+/// Sample slot to load in a new project. `path` is relative to the new project's directory,
+/// e.g. `../AUDIO/kick.wav`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct YamlNewProjectSlot {
+    pub sample_type: ProjectSampleSlotType,
+    pub slot_id: u8,
+    pub path: PathBuf,
+    pub gain: Option<i8>,
+    pub bpm: Option<u16>,
+}
+
+/// Part names for one of a new project's banks.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct YamlNewProjectPartNames {
+    pub bank_id: usize,
+    pub names: Vec<String>,
+}
+
+/// Changes to make to a new project, e.g.
+/// ```yaml
+/// tempo: 125
+/// slots:
+///   - sample_type: Static
+///     slot_id: 1
+///     path: "../AUDIO/kick.wav"
+/// part_names:
+///   - bank_id: 1
+///     names: ["INTRO", "VERSE", "CHORUS", "OUTRO"]
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct YamlNewProjectSpec {
+    pub tempo: Option<u32>,
+    #[serde(default)]
+    pub slots: Vec<YamlNewProjectSlot>,
+    #[serde(default)]
+    pub part_names: Vec<YamlNewProjectPartNames>,
+}
+
+/// Check a project name can be used on the device.
+pub fn validate_project_name(name: &str) -> Result<(), NewProjectErrors> {
+    let valid_chars = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if name.is_empty() || name.len() > MAX_PROJECT_NAME_LEN || !valid_chars {
+        return Err(NewProjectErrors::InvalidProjectName(name.to_string()));
+    }
+    Ok(())
+}
+
+fn part_name_bytes(name: &str) -> Result<[u8; MAX_PART_NAME_LEN], NewProjectErrors> {
+    if name.is_empty() || name.len() > MAX_PART_NAME_LEN || !name.is_ascii() {
+        return Err(NewProjectErrors::InvalidPartName(name.to_string()));
+    }
+    let mut bytes = [0_u8; MAX_PART_NAME_LEN];
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    Ok(bytes)
+}
+
+/// Apply the changes from a YAML spec to a new project's data.
+fn apply_new_project_spec(
+    spec: &YamlNewProjectSpec,
+    project: &mut Project,
+    banks: &mut [Bank],
+) -> RBoxErr<()> {
+    if let Some(tempo) = spec.tempo {
+        project.settings.tempo.set_tempo(tempo)?;
+    }
+
+    for slot in &spec.slots {
+        validate_slot_path(&slot.path)?;
+        let new_slot = ProjectSampleSlot::new(
+            slot.sample_type.clone(),
+            slot.slot_id,
+            slot.path.clone(),
+            None,
+            None,
+            None,
+            None,
+            slot.gain,
+            slot.bpm,
+        )?;
+        project
+            .slots
+            .retain(|x| !(x.sample_type == slot.sample_type && x.slot_id == slot.slot_id));
+        project.slots.push(new_slot);
+    }
+    project
+        .slots
+        .sort_by_key(|x| (x.sample_type.clone() as u8, x.slot_id));
+
+    for part_names in &spec.part_names {
+        if !(1..=banks.len()).contains(&part_names.bank_id) {
+            return Err(NewProjectErrors::InvalidBankId(part_names.bank_id).into());
+        }
+        if part_names.names.len() > 4 {
+            return Err(NewProjectErrors::TooManyPartNames(part_names.names.len()).into());
+        }
+        let bank = &mut banks[part_names.bank_id - 1];
+        for (idx, name) in part_names.names.iter().enumerate() {
+            bank.part_names[idx] = part_name_bytes(name)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Create a new project called `name` within the set at `set_dirpath`.
+/// See `stage_create_new_project`.
+pub fn create_new_project(
+    set_dirpath: &Path,
+    name: &str,
+    template_dirpath: Option<&Path>,
+    spec: Option<&YamlNewProjectSpec>,
+) -> RBoxErr<()> {
+    finish_transaction(
+        stage_create_new_project(set_dirpath, name, template_dirpath, spec)?,
+        false,
+        None,
+    )
+}
+
+/// Stage creating a new project called `name` within the set at `set_dirpath`, without changing
+/// anything on the file system.
+///
+/// The new project's working data files are either defaults, or copied from the working data
+/// files of `template_dirpath`. Template sample slot paths are rewritten relative to the new
+/// project directory. Changes from `spec` are applied on top.
+///
+/// Never overwrites an existing directory.
+pub fn stage_create_new_project(
+    set_dirpath: &Path,
+    name: &str,
+    template_dirpath: Option<&Path>,
+    spec: Option<&YamlNewProjectSpec>,
+) -> RBoxErr<Transaction> {
+    validate_project_name(name)?;
+
+    if !set_dirpath.join("AUDIO").is_dir() {
+        return Err(OctatoolErrors::PathIsNotASet.into());
+    }
+    let set_dirpath = fs::canonicalize(set_dirpath)?;
+    let project_dirpath = set_dirpath.join(name);
+    if project_dirpath.exists() {
+        return Err(NewProjectErrors::ProjectExists(project_dirpath).into());
+    }
+
+    let mut tx = Transaction::new(
+        &set_dirpath,
+        &format!("create new project {project_dirpath:?}"),
+    );
+
+    let (mut project, mut banks, arrangements) = match template_dirpath {
+        None => (
+            Project::default(),
+            (0..16).map(|_| Bank::default()).collect::<Vec<_>>(),
+            (0..8)
+                .map(|_| ArrangementFile::default())
+                .collect::<Vec<_>>(),
+        ),
+        Some(template_dirpath) => {
+            let template_dirpath = fs::canonicalize(template_dirpath)?;
+            let mut project =
+                read_type_from_bin_file::<Project>(&template_dirpath.join("project.work"))?;
+            if !validate_project_version(&project) {
+                return Err(OctatoolErrors::InvalidOsVersion.into());
+            };
+
+            for slot in project.slots.iter_mut() {
+                if slot.path == Path::new("") {
+                    continue;
+                }
+                // missing sample files keep their existing path
+                if let Ok(audio_fpath) = template_dirpath.join(&slot.path).canonicalize() {
+                    slot.path = get_device_slot_path(&project_dirpath, &audio_fpath);
+                }
+                validate_slot_path(&slot.path)?;
+            }
+
+            let banks = (1..=16)
+                .map(|i| {
+                    read_type_from_bin_file::<Bank>(
+                        &template_dirpath.join(format!("bank{i:0>2}.work")),
+                    )
+                })
+                .collect::<RBoxErr<Vec<_>>>()?;
+            let arrangements = (1..=8)
+                .map(|i| {
+                    read_type_from_bin_file::<ArrangementFile>(
+                        &template_dirpath.join(format!("arr{i:0>2}.work")),
+                    )
+                })
+                .collect::<RBoxErr<Vec<_>>>()?;
+
+            (project, banks, arrangements)
+        }
+    };
+
+    if let Some(spec) = spec {
+        apply_new_project_spec(spec, &mut project, &mut banks)?;
+    }
+
+    tx.stage_type::<Project>(&project, &project_dirpath.join("project.work"))?;
+    for (i, bank) in (1..).zip(banks.iter()) {
+        tx.stage_type::<Bank>(bank, &project_dirpath.join(format!("bank{i:0>2}.work")))?;
+    }
+    for (i, arrangement) in (1..).zip(arrangements.iter()) {
+        tx.stage_type::<ArrangementFile>(
+            arrangement,
+            &project_dirpath.join(format!("arr{i:0>2}.work")),
+        )?;
+    }
+    tx.record_project_slot_changes(&project_dirpath.join("project.work"), &[], &project.slots);
+
+    Ok(tx)
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+
+    fn mock_set(name: &str) -> PathBuf {
+        let set_dirpath = std::env::temp_dir().join(format!("ot_new_project_{name}"));
+        if set_dirpath.exists() {
+            let _ = fs::remove_dir_all(&set_dirpath);
+        }
+        fs::create_dir_all(set_dirpath.join("AUDIO")).unwrap();
+        set_dirpath
+    }
+
+    mod name {
+        use super::*;
+
+        #[test]
+        fn valid() {
+            for name in ["A", "MY_PROJECT-1", "abcdefghijkl"] {
+                assert!(validate_project_name(name).is_ok(), "{name}");
+            }
+        }
+
+        #[test]
+        fn invalid() {
+            for name in [
+                "",
+                "abcdefghijklm",
+                "MY PROJECT",
+                "../SET",
+                "PROJ/ECT",
+                "PRÖJECT",
+            ] {
+                assert!(validate_project_name(name).is_err(), "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let set_dirpath = mock_set("defaults");
+        create_new_project(&set_dirpath, "NEW", None, None).unwrap();
+
+        let project_dirpath = set_dirpath.join("NEW");
+        let project = read_type_from_bin_file::<Project>(&project_dirpath.join("project.work"));
+        assert_eq!(project.unwrap(), Project::default());
+        assert!(project_dirpath.join("bank16.work").is_file());
+        assert!(project_dirpath.join("arr08.work").is_file());
+    }
+
+    #[test]
+    fn existing_dir_err() {
+        let set_dirpath = mock_set("existing");
+        fs::create_dir(set_dirpath.join("NEW")).unwrap();
+        let r = stage_create_new_project(&set_dirpath, "NEW", None, None);
+        assert!(r.is_err());
+        assert_eq!(fs::read_dir(set_dirpath.join("NEW")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn from_spec() {
+        let set_dirpath = mock_set("spec");
+        let spec: YamlNewProjectSpec = ot_tools_io::deserialize_yaml_to_type(
+            r#"
+tempo: 125
+slots:
+  - sample_type: Static
+    slot_id: 3
+    path: "../AUDIO/kick.wav"
+part_names:
+  - bank_id: 2
+    names: ["INTRO", "VERSE"]
+"#,
+        )
+        .unwrap();
+        create_new_project(&set_dirpath, "NEW", None, Some(&spec)).unwrap();
+
+        let project_dirpath = set_dirpath.join("NEW");
+        let project =
+            read_type_from_bin_file::<Project>(&project_dirpath.join("project.work")).unwrap();
+        let bank = read_type_from_bin_file::<Bank>(&project_dirpath.join("bank02.work")).unwrap();
+
+        assert_eq!(project.settings.tempo.tempo, 125);
+        assert!(project.slots.iter().any(|x| x.slot_id == 3
+            && x.sample_type == ProjectSampleSlotType::Static
+            && x.path == Path::new("../AUDIO/kick.wav")));
+        assert_eq!(&bank.part_names[0], b"INTRO\0\0");
+        assert_eq!(&bank.part_names[1], b"VERSE\0\0");
+        assert_eq!(&bank.part_names[2], &Bank::default().part_names[2]);
+    }
+
+    #[test]
+    fn invalid_spec_writes_nothing() {
+        let set_dirpath = mock_set("bad_spec");
+        let spec = YamlNewProjectSpec {
+            tempo: Some(301),
+            ..Default::default()
+        };
+        assert!(create_new_project(&set_dirpath, "NEW", None, Some(&spec)).is_err());
+        assert!(!set_dirpath.join("NEW").exists());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn from_template() {
+        let set_dirpath = mock_set("template");
+        copy_dir::copy_dir("../data/tests/blank-project", set_dirpath.join("TEMPLATE")).unwrap();
+        let template_fpath = set_dirpath.join("TEMPLATE/project.work");
+        let mut template = read_type_from_bin_file::<Project>(&template_fpath).unwrap();
+        fs::write(set_dirpath.join("TEMPLATE/kick.wav"), b"kick").unwrap();
+        template.slots.push(
+            ProjectSampleSlot::new(
+                ProjectSampleSlotType::Flex,
+                1,
+                PathBuf::from("kick.wav"),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap(),
+        );
+        ot_tools_io::write_type_to_bin_file(&template, &template_fpath).unwrap();
+
+        create_new_project(
+            &set_dirpath,
+            "NEW",
+            Some(&set_dirpath.join("TEMPLATE")),
+            None,
+        )
+        .unwrap();
+
+        let project =
+            read_type_from_bin_file::<Project>(&set_dirpath.join("NEW/project.work")).unwrap();
+        assert!(project
+            .slots
+            .iter()
+            .any(|x| x.path == Path::new("../TEMPLATE/kick.wav")));
+    }
+}
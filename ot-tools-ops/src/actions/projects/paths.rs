@@ -0,0 +1,299 @@
+//! Sample slot paths as the Octatrack expects them in project files: relative to the project
+//! directory with `/` separators, e.g. `../AUDIO/kick.wav` for a sample in the set's audio pool.
+
+use crate::utils::get_relative_path;
+use crate::{OctatoolErrors, RBoxErr};
+use itertools::Itertools;
+use ot_tools_io::{
+    projects::{options::ProjectSampleSlotType, Project},
+    read_type_from_bin_file,
+};
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+
+/// Longest sample slot path the Octatrack will load (the FAT32 long file name limit).
+pub const MAX_SLOT_PATH_LEN: usize = 255;
+
+/// Characters which either can't be used in FAT32 file names, or break parsing of project files
+/// (`=`). Backslashes are handled separately as host specific path separators.
+const INVALID_SLOT_PATH_CHARS: [char; 8] = ['"', '*', ':', '<', '>', '?', '|', '='];
+
+/// Something about a sample slot path which stops the Octatrack from loading the sample.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum SlotPathIssue {
+    /// Absolute path on the host machine, e.g. `/home/me/set/AUDIO/kick.wav` or `C:\kick.wav`
+    Absolute,
+    /// Uses Windows `\` path separators
+    HostSeparator,
+    /// Relative path leading outside of the set directory
+    OutsideSet,
+    /// Path is longer than `MAX_SLOT_PATH_LEN` characters
+    TooLong(usize),
+    InvalidCharacter(char),
+    /// The Octatrack only supports ASCII characters in file names
+    NonAscii(char),
+}
+
+impl std::fmt::Display for SlotPathIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Absolute => write!(f, "path is an absolute host path"),
+            Self::HostSeparator => write!(f, "path uses '\\' separators"),
+            Self::OutsideSet => write!(f, "path leads outside of the set directory"),
+            Self::TooLong(len) => {
+                write!(f, "path is {len} characters long (max {MAX_SLOT_PATH_LEN})")
+            }
+            Self::InvalidCharacter(c) => write!(f, "path contains invalid character {c:?}"),
+            Self::NonAscii(c) => write!(f, "path contains non-ASCII character {c:?}"),
+        }
+    }
+}
+impl std::error::Error for SlotPathIssue {}
+
+/// Find everything wrong with a sample slot path as it is written in a project file.
+/// Empty paths (unused slots and recorder buffers) are fine.
+pub fn check_slot_path(path: &Path) -> Vec<SlotPathIssue> {
+    let s = path.to_string_lossy();
+    let mut issues = vec![];
+
+    let bytes = s.as_bytes();
+    let has_drive_prefix = bytes.len() > 1 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    let is_absolute =
+        s.starts_with('/') || s.starts_with('\\') || has_drive_prefix || path.is_absolute();
+
+    if is_absolute {
+        issues.push(SlotPathIssue::Absolute);
+    }
+    if s.contains('\\') {
+        issues.push(SlotPathIssue::HostSeparator);
+    }
+    if !is_absolute {
+        // the project directory is depth 0, the set directory is depth -1
+        let mut depth: i32 = 0;
+        for part in s.split(['/', '\\']) {
+            match part {
+                "" | "." => {}
+                ".." => depth -= 1,
+                _ => depth += 1,
+            }
+            if depth < -1 {
+                issues.push(SlotPathIssue::OutsideSet);
+                break;
+            }
+        }
+    }
+    let len = s.chars().count();
+    if len > MAX_SLOT_PATH_LEN {
+        issues.push(SlotPathIssue::TooLong(len));
+    }
+    for c in s.chars().unique() {
+        if !c.is_ascii() {
+            issues.push(SlotPathIssue::NonAscii(c));
+        } else if c.is_ascii_control() || INVALID_SLOT_PATH_CHARS.contains(&c) {
+            issues.push(SlotPathIssue::InvalidCharacter(c));
+        }
+    }
+
+    issues
+}
+
+/// Check a sample slot path can be loaded by the Octatrack, returning the first issue found.
+pub fn validate_slot_path(path: &Path) -> Result<(), SlotPathIssue> {
+    match check_slot_path(path).into_iter().next() {
+        Some(issue) => Err(issue),
+        None => Ok(()),
+    }
+}
+
+/// Get the sample slot path for a sample file, i.e. the path relative to the project directory
+/// with `/` separators whatever the host OS.
+///
+/// Both paths should be absolute (e.g. canonicalized), no file system access is done here.
+pub fn get_device_slot_path(project_dirpath: &Path, sample_fpath: &Path) -> PathBuf {
+    let parts = get_relative_path(project_dirpath, sample_fpath)
+        .components()
+        .map(|x| match x {
+            Component::ParentDir => "..".to_string(),
+            x => x.as_os_str().to_string_lossy().to_string(),
+        })
+        .collect_vec();
+    PathBuf::from(parts.join("/"))
+}
+
+/// Get a validated sample slot path for a sample file (see `get_device_slot_path`).
+pub fn to_device_slot_path(project_dirpath: &Path, sample_fpath: &Path) -> RBoxErr<PathBuf> {
+    let path = get_device_slot_path(project_dirpath, sample_fpath);
+    validate_slot_path(&path)?;
+    Ok(path)
+}
+
+/// A sample slot with a path the Octatrack can't load.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SlotPathCheck {
+    pub project_file: PathBuf,
+    pub sample_type: ProjectSampleSlotType,
+    pub slot_id: u8,
+    pub path: PathBuf,
+    pub issues: Vec<SlotPathIssue>,
+}
+
+/// Check the sample slot paths in a project's working and saved project files.
+pub fn check_project_slot_paths(project_dirpath: &Path) -> RBoxErr<Vec<SlotPathCheck>> {
+    if !project_dirpath.is_dir() {
+        return Err(OctatoolErrors::PathIsNotADirectory.into());
+    }
+
+    let mut checks = vec![];
+    for project_fpath in [
+        project_dirpath.join("project.work"),
+        project_dirpath.join("project.strd"),
+    ]
+    .into_iter()
+    .filter(|x| x.exists())
+    {
+        let project = read_type_from_bin_file::<Project>(&project_fpath)?;
+        for slot in project
+            .slots
+            .iter()
+            .sorted_by_key(|x| (x.sample_type.clone() as u8, x.slot_id))
+        {
+            let issues = check_slot_path(&slot.path);
+            if !issues.is_empty() {
+                checks.push(SlotPathCheck {
+                    project_file: project_fpath.clone(),
+                    sample_type: slot.sample_type.clone(),
+                    slot_id: slot.slot_id,
+                    path: slot.path.clone(),
+                    issues,
+                });
+            }
+        }
+    }
+
+    Ok(checks)
+}
+
+/// Print any sample slot path issues in a project as a list, or as JSON.
+pub fn print_project_slot_path_checks(project_dirpath: &Path, json: bool) -> RBoxErr<()> {
+    let checks = check_project_slot_paths(project_dirpath)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+        return Ok(());
+    }
+
+    for check in &checks {
+        println!(
+            "{:?} {:?} slot {}: {}",
+            check.project_file.file_name().unwrap_or_default(),
+            check.sample_type,
+            check.slot_id,
+            check.path.display(),
+        );
+        for issue in &check.issues {
+            println!("    {issue}");
+        }
+    }
+    println!("Sample slots with invalid paths: {}", checks.len());
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+
+    mod check {
+        use super::*;
+
+        #[test]
+        fn relative_paths_ok() {
+            for path in ["", "kick.wav", "./kick.wav", "../AUDIO/drums/kick.wav"] {
+                assert_eq!(check_slot_path(Path::new(path)), vec![], "{path}");
+            }
+        }
+
+        #[test]
+        fn absolute_unix() {
+            assert_eq!(
+                check_slot_path(Path::new("/home/me/set/AUDIO/kick.wav")),
+                vec![SlotPathIssue::Absolute]
+            );
+        }
+
+        #[test]
+        fn absolute_windows() {
+            assert_eq!(
+                check_slot_path(Path::new("C:\\set\\AUDIO\\kick.wav")),
+                vec![
+                    SlotPathIssue::Absolute,
+                    SlotPathIssue::HostSeparator,
+                    SlotPathIssue::InvalidCharacter(':'),
+                ]
+            );
+        }
+
+        #[test]
+        fn outside_set() {
+            assert_eq!(
+                check_slot_path(Path::new("../../OTHERSET/AUDIO/kick.wav")),
+                vec![SlotPathIssue::OutsideSet]
+            );
+            assert_eq!(
+                check_slot_path(Path::new("../OTHERPROJECT/kick.wav")),
+                vec![]
+            );
+        }
+
+        #[test]
+        fn too_long() {
+            let path = format!("../AUDIO/{}.wav", "a".repeat(MAX_SLOT_PATH_LEN));
+            assert_eq!(
+                check_slot_path(Path::new(&path)),
+                vec![SlotPathIssue::TooLong(MAX_SLOT_PATH_LEN + 13)]
+            );
+        }
+
+        #[test]
+        fn invalid_characters() {
+            assert_eq!(
+                check_slot_path(Path::new("../AUDIO/kick=1?é.wav")),
+                vec![
+                    SlotPathIssue::InvalidCharacter('='),
+                    SlotPathIssue::InvalidCharacter('?'),
+                    SlotPathIssue::NonAscii('é'),
+                ]
+            );
+        }
+    }
+
+    mod device_path {
+        use super::*;
+
+        #[test]
+        fn audio_pool() {
+            let r = to_device_slot_path(
+                Path::new("/set/PROJECT"),
+                Path::new("/set/AUDIO/drums/kick.wav"),
+            );
+            assert_eq!(r.unwrap(), PathBuf::from("../AUDIO/drums/kick.wav"));
+        }
+
+        #[test]
+        fn project_dir() {
+            let r = to_device_slot_path(
+                Path::new("/set/PROJECT"),
+                Path::new("/set/PROJECT/kick.wav"),
+            );
+            assert_eq!(r.unwrap(), PathBuf::from("kick.wav"));
+        }
+
+        #[test]
+        fn outside_set_err() {
+            let r = to_device_slot_path(Path::new("/set/PROJECT"), Path::new("/samples/kick.wav"));
+            assert!(r.is_err());
+        }
+    }
+}
@@ -0,0 +1,578 @@
+//! Relink project sample slots whose sample files have been moved or renamed.
+
+use crate::actions::projects::paths::{get_device_slot_path, validate_slot_path, SlotPathIssue};
+use crate::actions::samples::yaml::samplesdir::SamplesDirIndexFull;
+use crate::audio::utils::scan_dir_path_for_audio_files;
+use crate::transaction::{finish_transaction, get_backups_root_for_project, Transaction};
+use crate::utils::get_md5_hash_from_path;
+use crate::{validate_project_version, OctatoolErrors, RBoxErr};
+use itertools::Itertools;
+use ot_tools_io::projects::{options::ProjectSampleSlotType, slots::ProjectSampleSlot, Project};
+use ot_tools_io::{read_type_from_bin_file, yaml_file_to_type};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Maximum edit distance between normalised file stems for a 'similar name' match.
+const MAX_SIMILAR_NAME_DISTANCE: usize = 3;
+
+/// How a candidate sample file was matched to a missing sample file
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RelinkMatchKind {
+    /// Same file name (and extension)
+    Name,
+    /// Same md5 hash as the missing file, according to a samples directory index
+    ContentHash,
+    /// File name stems differ by only a few characters (ignoring case and punctuation)
+    SimilarName,
+}
+
+/// Outcome of searching for a missing sample file
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RelinkStatus {
+    /// A single matching file was found, the slot will point at it
+    Relinked {
+        kind: RelinkMatchKind,
+        new_path: PathBuf,
+    },
+    /// More than one file matched equally well, the slot is left unchanged
+    Ambiguous {
+        kind: RelinkMatchKind,
+        candidates: Vec<PathBuf>,
+    },
+    /// A single matching file was found, but the Octatrack can't load it from its path, the slot
+    /// is left unchanged
+    InvalidPath {
+        kind: RelinkMatchKind,
+        new_path: PathBuf,
+        issue: SlotPathIssue,
+    },
+    NotFound,
+}
+
+/// A project sample slot whose sample file does not exist. Slot IDs are 1-indexed, paths are
+/// relative to the project directory.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SlotRelink {
+    pub sample_type: ProjectSampleSlotType,
+    pub slot_id: u8,
+    pub old_path: PathBuf,
+    pub status: RelinkStatus,
+}
+
+impl std::fmt::Display for SlotRelink {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} slot {} {:?}: ",
+            self.sample_type, self.slot_id, self.old_path
+        )?;
+        match &self.status {
+            RelinkStatus::Relinked { kind, new_path } => {
+                write!(f, "relinked to {new_path:?} (matched by {kind:?})")
+            }
+            RelinkStatus::Ambiguous { kind, candidates } => {
+                write!(f, "AMBIGUOUS (matched by {kind:?}), candidates:")?;
+                for candidate in candidates {
+                    write!(f, "\n        {candidate:?}")?;
+                }
+                Ok(())
+            }
+            RelinkStatus::InvalidPath {
+                kind,
+                new_path,
+                issue,
+            } => write!(f, "SKIPPED, matched {new_path:?} (by {kind:?}) but {issue}"),
+            RelinkStatus::NotFound => write!(f, "NOT FOUND"),
+        }
+    }
+}
+
+/// Lexically resolve `.` and `..` components of a path (does not touch the file system, so works
+/// for files which no longer exist).
+fn normalise_path(path: &Path) -> PathBuf {
+    let mut normalised = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalised.pop();
+            }
+            c => normalised.push(c),
+        }
+    }
+    normalised
+}
+
+/// Lowercase file stem with everything except letters and numbers removed.
+fn normalised_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|x| x.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// Levenshtein edit distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect_vec();
+    let mut prev = (0..=b.len()).collect_vec();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+/// Candidate sample files for relinking, with md5 hashes calculated only when needed.
+struct RelinkCandidates {
+    paths: Vec<PathBuf>,
+    hashes: Option<Vec<Option<String>>>,
+}
+
+impl RelinkCandidates {
+    fn by_name(&self, missing: &Path) -> Vec<PathBuf> {
+        self.paths
+            .iter()
+            .filter(|x| x.file_name() == missing.file_name())
+            .cloned()
+            .collect()
+    }
+
+    fn by_hash(&mut self, md5s: &[String]) -> Vec<PathBuf> {
+        let hashes = self.hashes.get_or_insert_with(|| {
+            self.paths
+                .iter()
+                .map(|x| get_md5_hash_from_path(x).ok())
+                .collect()
+        });
+        self.paths
+            .iter()
+            .zip(hashes.iter())
+            .filter(|(_, hash)| hash.as_ref().is_some_and(|h| md5s.contains(h)))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    fn by_similar_name(&self, missing: &Path) -> Vec<PathBuf> {
+        let stem = normalised_stem(missing);
+        let distances = self
+            .paths
+            .iter()
+            .map(|x| (x, edit_distance(&stem, &normalised_stem(x))))
+            .filter(|(_, d)| *d <= MAX_SIMILAR_NAME_DISTANCE && *d < stem.len())
+            .collect_vec();
+
+        let Some(min) = distances.iter().map(|(_, d)| *d).min() else {
+            return vec![];
+        };
+        distances
+            .into_iter()
+            .filter(|(_, d)| *d == min)
+            .map(|(x, _)| x.clone())
+            .collect()
+    }
+}
+
+/// Find the md5 hashes a samples directory index has recorded for a missing sample file.
+/// Entries are matched on the full (normalised) path, falling back to the file name.
+fn find_indexed_md5s(index: &SamplesDirIndexFull, missing_abs: &Path) -> Vec<String> {
+    let by_path = index
+        .samples
+        .iter()
+        .filter(|x| normalise_path(&x.path) == missing_abs)
+        .map(|x| x.md5.clone())
+        .collect_vec();
+    if !by_path.is_empty() {
+        return by_path;
+    }
+    index
+        .samples
+        .iter()
+        .filter(|x| x.path.file_name() == missing_abs.file_name())
+        .map(|x| x.md5.clone())
+        .unique()
+        .collect()
+}
+
+/// Choose a single match from a list of candidates, preferring candidates which also have a
+/// matching md5 hash when there are several.
+fn resolve_candidates(
+    kind: RelinkMatchKind,
+    candidates: Vec<PathBuf>,
+    hash_matches: &[PathBuf],
+) -> Option<RelinkStatus> {
+    match candidates.len() {
+        0 => None,
+        1 => Some(RelinkStatus::Relinked {
+            kind,
+            new_path: candidates[0].clone(),
+        }),
+        _ => {
+            let narrowed = candidates
+                .iter()
+                .filter(|x| hash_matches.contains(x))
+                .cloned()
+                .collect_vec();
+            if narrowed.len() == 1 {
+                Some(RelinkStatus::Relinked {
+                    kind,
+                    new_path: narrowed[0].clone(),
+                })
+            } else {
+                Some(RelinkStatus::Ambiguous { kind, candidates })
+            }
+        }
+    }
+}
+
+/// Search for the sample files of project sample slots which don't exist, returning new
+/// (relative) paths for slots with a single match.
+///
+/// `project_dirpath` and `search_paths` must be absolute. Missing files are matched by exact file
+/// name first, then by md5 hash (only if `index` has an entry for the missing file) and finally by
+/// similar file names.
+pub fn find_missing_sample_relinks(
+    project_dirpath: &Path,
+    slots: &[ProjectSampleSlot],
+    search_paths: &[PathBuf],
+    index: Option<&SamplesDirIndexFull>,
+) -> RBoxErr<Vec<SlotRelink>> {
+    let mut candidates = RelinkCandidates {
+        paths: search_paths
+            .iter()
+            .map(scan_dir_path_for_audio_files)
+            .flatten_ok()
+            .collect::<RBoxErr<Vec<PathBuf>>>()?
+            .into_iter()
+            .unique()
+            .collect(),
+        hashes: None,
+    };
+
+    let mut relinks = vec![];
+    for slot in slots
+        .iter()
+        .filter(|x| x.sample_type != ProjectSampleSlotType::RecorderBuffer)
+        .filter(|x| x.path != Path::new(""))
+        .sorted_by_key(|x| (x.sample_type.clone() as u8, x.slot_id))
+    {
+        let missing_abs = normalise_path(&project_dirpath.join(&slot.path));
+        if missing_abs.exists() {
+            continue;
+        }
+
+        let md5s = index
+            .map(|x| find_indexed_md5s(x, &missing_abs))
+            .unwrap_or_default();
+        let hash_matches = if md5s.is_empty() {
+            vec![]
+        } else {
+            candidates.by_hash(&md5s)
+        };
+
+        let status = resolve_candidates(
+            RelinkMatchKind::Name,
+            candidates.by_name(&missing_abs),
+            &hash_matches,
+        )
+        .or_else(|| resolve_candidates(RelinkMatchKind::ContentHash, hash_matches.clone(), &[]))
+        .or_else(|| {
+            resolve_candidates(
+                RelinkMatchKind::SimilarName,
+                candidates.by_similar_name(&missing_abs),
+                &hash_matches,
+            )
+        })
+        .unwrap_or(RelinkStatus::NotFound);
+
+        let relative = |x: PathBuf| get_device_slot_path(project_dirpath, &x);
+        let status = match status {
+            RelinkStatus::Relinked { kind, new_path } => {
+                let new_path = relative(new_path);
+                match validate_slot_path(&new_path) {
+                    Ok(()) => RelinkStatus::Relinked { kind, new_path },
+                    Err(issue) => RelinkStatus::InvalidPath {
+                        kind,
+                        new_path,
+                        issue,
+                    },
+                }
+            }
+            RelinkStatus::Ambiguous { kind, candidates } => RelinkStatus::Ambiguous {
+                kind,
+                candidates: candidates.into_iter().map(relative).collect(),
+            },
+            status => status,
+        };
+
+        relinks.push(SlotRelink {
+            sample_type: slot.sample_type.clone(),
+            slot_id: slot.slot_id,
+            old_path: slot.path.clone(),
+            status,
+        });
+    }
+
+    Ok(relinks)
+}
+
+/// Relink project sample slots whose sample files are missing, searching the set directory (the
+/// set's audio pool and all project directories) for the moved / renamed files.
+///
+/// Slots with ambiguous matches, or matches with a path the Octatrack can't load, are reported and
+/// left unchanged. `index_fpath` is an optional YAML samples directory index (with md5 hashes)
+/// created before the files were moved, used to match files by content.
+pub fn relink_missing_sample_files(
+    project_dirpath: &Path,
+    index_fpath: Option<&Path>,
+) -> RBoxErr<()> {
+    finish_transaction(
+        stage_relink_missing_sample_files(project_dirpath, index_fpath)?,
+        false,
+        None,
+    )
+}
+
+/// Stage relinking of missing sample files (see `relink_missing_sample_files`) without changing
+/// anything on the file system. The relink report is printed.
+pub fn stage_relink_missing_sample_files(
+    project_dirpath: &Path,
+    index_fpath: Option<&Path>,
+) -> RBoxErr<Transaction> {
+    let project_dirpath = fs::canonicalize(project_dirpath)?;
+    let project_fpath = project_dirpath.join("project.work");
+    let set_dirpath = project_dirpath
+        .parent()
+        .ok_or(OctatoolErrors::PathIsNotASet)?
+        .to_path_buf();
+
+    let project = read_type_from_bin_file::<Project>(&project_fpath)?;
+    if !validate_project_version(&project) {
+        return Err(OctatoolErrors::InvalidOsVersion.into());
+    };
+
+    let index = match index_fpath {
+        Some(fpath) => Some(yaml_file_to_type::<SamplesDirIndexFull>(fpath)?),
+        None => None,
+    };
+
+    let relinks = find_missing_sample_relinks(
+        &project_dirpath,
+        &project.slots,
+        &[set_dirpath],
+        index.as_ref(),
+    )?;
+
+    println!("Missing sample files: {}", relinks.len());
+    for relink in &relinks {
+        println!("    {relink}");
+    }
+
+    let new_paths: HashMap<(ProjectSampleSlotType, u8), PathBuf> = relinks
+        .iter()
+        .filter_map(|x| match &x.status {
+            RelinkStatus::Relinked { new_path, .. } => {
+                Some(((x.sample_type.clone(), x.slot_id), new_path.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut new_project = project.clone();
+    for slot in new_project.slots.iter_mut() {
+        if let Some(new_path) = new_paths.get(&(slot.sample_type.clone(), slot.slot_id)) {
+            slot.path = new_path.clone();
+        }
+    }
+
+    let mut tx = Transaction::new(
+        &get_backups_root_for_project(&project_dirpath)?,
+        &format!("relink missing sample files in {project_dirpath:?}"),
+    );
+    if new_project.slots != project.slots {
+        tx.stage_type::<Project>(&new_project, &project_fpath)?;
+        tx.record_project_slot_changes(&project_fpath, &project.slots, &new_project.slots);
+    }
+
+    Ok(tx)
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use crate::actions::samples::yaml::samplesdir::SamplesDirAudioFile;
+    use std::env::temp_dir;
+
+    fn slot(slot_id: u8, path: &str) -> ProjectSampleSlot {
+        ProjectSampleSlot::new(
+            ProjectSampleSlotType::Static,
+            slot_id,
+            PathBuf::from(path),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("kick", "kick"), 0);
+        assert_eq!(edit_distance("kick", "kick2"), 1);
+        assert_eq!(edit_distance("kick", "snare"), 5);
+    }
+
+    #[test]
+    fn normalised_stems() {
+        assert_eq!(normalised_stem(Path::new("a/Kick_01 (v2).wav")), "kick01v2");
+    }
+
+    /// Test wav file with different content to `misc/test.wav`
+    const OTHER_WAV: &str = "samples/chains/create/default-1-samples/valid-1.wav";
+
+    /// Creates `SET/PROJECT` and `SET/AUDIO` with copies of the test wav files in the audio pool.
+    fn mock_set(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let set = temp_dir().join("ot-tools-relink").join(test_name);
+        let _ = fs::remove_dir_all(&set);
+        fs::create_dir_all(set.join("PROJECT")).unwrap();
+        fs::create_dir_all(set.join("AUDIO").join("moved")).unwrap();
+        for (src, dest) in files {
+            fs::copy(
+                PathBuf::from("../data/tests").join(src),
+                set.join("AUDIO").join(dest),
+            )
+            .unwrap();
+        }
+        fs::canonicalize(set).unwrap()
+    }
+
+    #[test]
+    fn relink_by_name_hash_and_similar() {
+        let set = mock_set(
+            "relink_by_name_hash_and_similar",
+            &[
+                ("misc/test.wav", "moved/kick.wav"),
+                (OTHER_WAV, "renamed-snare.wav"),
+                ("misc/test.wav", "Hat 01.wav"),
+                ("misc/test.wav", "moved/dupe.wav"),
+                ("misc/test.wav", "dupe.wav"),
+            ],
+        );
+        let project = set.join("PROJECT");
+        let slots = vec![
+            slot(1, "../AUDIO/kick.wav"),
+            slot(2, "../AUDIO/snare.wav"),
+            slot(3, "../AUDIO/hat_1.wav"),
+            slot(4, "../AUDIO/other/dupe.wav"),
+            slot(5, "../AUDIO/nothing-like-it-at-all.wav"),
+        ];
+        let index = SamplesDirIndexFull {
+            dirpath: set.join("AUDIO"),
+            samples: vec![SamplesDirAudioFile {
+                md5: get_md5_hash_from_path(&set.join("AUDIO").join("renamed-snare.wav")).unwrap(),
+                path: set.join("AUDIO").join("snare.wav"),
+                name: "snare".to_string(),
+            }],
+        };
+
+        let relinks =
+            find_missing_sample_relinks(&project, &slots, std::slice::from_ref(&set), Some(&index))
+                .unwrap();
+        let statuses = relinks.into_iter().map(|x| x.status).collect_vec();
+
+        assert_eq!(
+            statuses,
+            vec![
+                RelinkStatus::Relinked {
+                    kind: RelinkMatchKind::Name,
+                    new_path: PathBuf::from("../AUDIO/moved/kick.wav"),
+                },
+                RelinkStatus::Relinked {
+                    kind: RelinkMatchKind::ContentHash,
+                    new_path: PathBuf::from("../AUDIO/renamed-snare.wav"),
+                },
+                RelinkStatus::Relinked {
+                    kind: RelinkMatchKind::SimilarName,
+                    new_path: PathBuf::from("../AUDIO/Hat 01.wav"),
+                },
+                RelinkStatus::Ambiguous {
+                    kind: RelinkMatchKind::Name,
+                    candidates: vec![
+                        PathBuf::from("../AUDIO/dupe.wav"),
+                        PathBuf::from("../AUDIO/moved/dupe.wav"),
+                    ],
+                },
+                RelinkStatus::NotFound,
+            ]
+        );
+
+        let _ = fs::remove_dir_all(set);
+    }
+
+    #[test]
+    fn existing_files_not_relinked() {
+        let set = mock_set(
+            "existing_files_not_relinked",
+            &[("misc/test.wav", "kick.wav")],
+        );
+        let relinks = find_missing_sample_relinks(
+            &set.join("PROJECT"),
+            &[slot(1, "../AUDIO/kick.wav")],
+            std::slice::from_ref(&set),
+            None,
+        )
+        .unwrap();
+        assert_eq!(relinks, vec![]);
+        let _ = fs::remove_dir_all(set);
+    }
+
+    #[test]
+    fn invalid_path_skipped() {
+        let set = mock_set(
+            "invalid_path_skipped",
+            &[
+                ("misc/test.wav", "moved/kick=1.wav"),
+                ("misc/test.wav", "moved/snare.wav"),
+            ],
+        );
+        let relinks = find_missing_sample_relinks(
+            &set.join("PROJECT"),
+            &[
+                slot(1, "../AUDIO/kick=1.wav"),
+                slot(2, "../AUDIO/snare.wav"),
+            ],
+            std::slice::from_ref(&set),
+            None,
+        )
+        .unwrap();
+        let statuses = relinks.into_iter().map(|x| x.status).collect_vec();
+        assert_eq!(
+            statuses,
+            vec![
+                RelinkStatus::InvalidPath {
+                    kind: RelinkMatchKind::Name,
+                    new_path: PathBuf::from("../AUDIO/moved/kick=1.wav"),
+                    issue: SlotPathIssue::InvalidCharacter('='),
+                },
+                RelinkStatus::Relinked {
+                    kind: RelinkMatchKind::Name,
+                    new_path: PathBuf::from("../AUDIO/moved/snare.wav"),
+                },
+            ]
+        );
+        let _ = fs::remove_dir_all(set);
+    }
+}
@@ -0,0 +1,1045 @@
+//! Change project settings (`PROJECT` -> `CONTROL`, `MIXER` and `TEMPO` menus) by key, using
+//! the values shown on the device, e.g. `mixer.gain_ab=-12` or `metronome.time_signature=7/8`.
+
+use crate::actions::projects::find_project_dirpaths;
+use crate::transaction::{finish_transaction, get_backups_root_for_project, Transaction};
+use crate::{validate_project_version, OctatoolErrors, RBoxErr};
+use ot_tools_io::{
+    projects::{options::ProjectMidiChannels, settings::ProjectSettings, Project},
+    read_type_from_bin_file, yaml_file_to_type, OptionEnumValueConvert,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum ProjectSettingErrors {
+    /// No setting with this key
+    UnknownKey(String),
+    /// Value can't be parsed for the setting
+    InvalidValue {
+        key: String,
+        value: String,
+        expected: &'static str,
+    },
+    /// Assignment is not in `key=value` form
+    InvalidAssignment(String),
+    /// Settings overlay has a key which isn't a project setting
+    UnknownOverlayKey(String),
+    /// Settings overlay has a value where a group of settings is expected (or the other way
+    /// around)
+    InvalidOverlayValue(String),
+}
+impl std::fmt::Display for ProjectSettingErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::UnknownKey(key) => write!(
+                f,
+                "unknown project setting {key:?} - run `project settings show` to list the settings"
+            ),
+            Self::InvalidValue {
+                key,
+                value,
+                expected,
+            } => write!(f, "invalid value {value:?} for {key} - expected {expected}"),
+            Self::InvalidAssignment(x) => {
+                write!(
+                    f,
+                    "invalid setting {x:?} - settings must be written as key=value"
+                )
+            }
+            Self::UnknownOverlayKey(key) => {
+                write!(f, "unknown project setting {key:?} in settings overlay")
+            }
+            Self::InvalidOverlayValue(key) => {
+                if key.is_empty() {
+                    write!(f, "settings overlay must be a mapping of project settings")
+                } else {
+                    write!(f, "invalid value for {key:?} in settings overlay")
+                }
+            }
+        }
+    }
+}
+impl std::error::Error for ProjectSettingErrors {}
+
+/// A project setting which can be changed by key.
+struct SettingKey {
+    key: &'static str,
+    /// Values accepted by `set`, shown in listings and errors
+    values: &'static str,
+    get: fn(&ProjectSettings) -> String,
+    set: fn(&mut ProjectSettings, &str) -> RBoxErr<()>,
+}
+
+const BOOL_VALUES: &str = "on/off";
+const SIGNED_LEVEL_VALUES: &str = "-64 to 63";
+const UNSIGNED_LEVEL_VALUES: &str = "0 to 127";
+const MIDI_CHANNEL_VALUES: &str = "1 to 16";
+const PROGCHANGE_CHANNEL_VALUES: &str = "off, 1 to 16";
+const MIDI_OUT_VALUES: &str = "int, ext, int+ext";
+const MIDI_NOTE_IN_VALUES: &str = "off, standard, follow_tm, map_track";
+
+const MIDI_OUT_NAMES: [(&str, u8); 3] = [("int", 1), ("ext", 2), ("int+ext", 3)];
+const MIDI_NOTE_IN_NAMES: [(&str, u8); 4] = [
+    ("off", 0),
+    ("standard", 1),
+    ("follow_tm", 2),
+    ("map_track", 3),
+];
+
+/// Error for a value which can't be used for a setting, listing the values it accepts.
+fn invalid_value(key: &str, value: &str) -> ProjectSettingErrors {
+    let expected = SETTING_KEYS
+        .iter()
+        .find(|x| x.key == key)
+        .map(|x| x.values)
+        .unwrap_or_default();
+    ProjectSettingErrors::InvalidValue {
+        key: key.to_string(),
+        value: value.to_string(),
+        expected,
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> RBoxErr<bool> {
+    match value.to_lowercase().as_str() {
+        "on" | "true" | "yes" | "1" => Ok(true),
+        "off" | "false" | "no" | "0" => Ok(false),
+        _ => Err(invalid_value(key, value).into()),
+    }
+}
+
+fn parse_num<T: std::str::FromStr>(key: &str, value: &str) -> RBoxErr<T> {
+    value
+        .trim_start_matches('+')
+        .parse::<T>()
+        .map_err(|_| invalid_value(key, value).into())
+}
+
+fn parse_name(key: &str, value: &str, names: &[(&str, u8)]) -> RBoxErr<u8> {
+    names
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, x)| *x)
+        .ok_or_else(|| invalid_value(key, value).into())
+}
+
+fn show_bool(x: bool) -> String {
+    if x { "on" } else { "off" }.to_string()
+}
+
+fn show_name(x: u8, names: &[(&str, u8)]) -> String {
+    names
+        .iter()
+        .find(|(_, v)| *v == x)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| format!("unknown ({x})"))
+}
+
+fn parse_progchange_channel(key: &str, value: &str) -> RBoxErr<Option<u8>> {
+    if value.eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+    Ok(Some(parse_num::<u8>(key, value)?))
+}
+
+fn show_progchange_channel(x: &ProjectMidiChannels) -> String {
+    match x {
+        ProjectMidiChannels::Disabled => "off".to_string(),
+        x => x
+            .value()
+            .map(|channel| channel.to_string())
+            .unwrap_or_else(|_| format!("unknown ({x:?})")),
+    }
+}
+
+fn parse_time_signature(key: &str, value: &str) -> RBoxErr<(u8, u8)> {
+    let (numerator, denominator) = value
+        .split_once('/')
+        .ok_or_else(|| invalid_value(key, value))?;
+    Ok((
+        parse_num::<u8>(key, numerator.trim())?,
+        parse_num::<u8>(key, denominator.trim())?,
+    ))
+}
+
+/// Setting key for each of the simple on / off settings.
+macro_rules! bool_setting {
+    ($key:literal, $($field:ident).+) => {
+        SettingKey {
+            key: $key,
+            values: BOOL_VALUES,
+            get: |s| show_bool(s.$($field).+),
+            set: |s, v| {
+                s.$($field).+ = parse_bool($key, v)?;
+                Ok(())
+            },
+        }
+    };
+}
+
+/// Setting key for a numeric setting with a validated setter.
+macro_rules! num_setting {
+    ($key:literal, $values:expr, $t:ty, $get:expr, $($setter:ident).+) => {
+        SettingKey {
+            key: $key,
+            values: $values,
+            get: |s| ($get)(s).to_string(),
+            set: |s, v| s.$($setter).+(parse_num::<$t>($key, v)?),
+        }
+    };
+}
+
+/// Setting key for the MIDI channel of a MIDI trig.
+macro_rules! trig_channel_setting {
+    ($key:literal, $trig:literal) => {
+        SettingKey {
+            key: $key,
+            values: MIDI_CHANNEL_VALUES,
+            get: |s| {
+                s.control
+                    .midi
+                    .channels
+                    .trig_channel($trig)
+                    .map(|x| x.to_string())
+                    .unwrap_or_default()
+            },
+            set: |s, v| {
+                s.control
+                    .midi
+                    .channels
+                    .set_trig_channel($trig, parse_num::<u8>($key, v)?)
+            },
+        }
+    };
+}
+
+/// Every project setting which can be changed by key, in the order they're listed.
+const SETTING_KEYS: &[SettingKey] = &[
+    num_setting!(
+        "tempo.bpm",
+        "30 to 300",
+        u32,
+        |s: &ProjectSettings| s.tempo.tempo,
+        tempo.set_tempo
+    ),
+    bool_setting!("tempo.pattern_tempo", tempo.pattern_tempo_enabled),
+    num_setting!(
+        "mixer.gain_ab",
+        SIGNED_LEVEL_VALUES,
+        i8,
+        |s: &ProjectSettings| s.mixer.gain_ab_signed(),
+        mixer.set_gain_ab
+    ),
+    num_setting!(
+        "mixer.gain_cd",
+        SIGNED_LEVEL_VALUES,
+        i8,
+        |s: &ProjectSettings| s.mixer.gain_cd_signed(),
+        mixer.set_gain_cd
+    ),
+    num_setting!(
+        "mixer.dir_ab",
+        UNSIGNED_LEVEL_VALUES,
+        u8,
+        |s: &ProjectSettings| s.mixer.dir_ab,
+        mixer.set_dir_ab
+    ),
+    num_setting!(
+        "mixer.dir_cd",
+        UNSIGNED_LEVEL_VALUES,
+        u8,
+        |s: &ProjectSettings| s.mixer.dir_cd,
+        mixer.set_dir_cd
+    ),
+    num_setting!(
+        "mixer.phones_mix",
+        UNSIGNED_LEVEL_VALUES,
+        u8,
+        |s: &ProjectSettings| s.mixer.phones_mix,
+        mixer.set_phones_mix
+    ),
+    num_setting!(
+        "mixer.main_to_cue",
+        UNSIGNED_LEVEL_VALUES,
+        u8,
+        |s: &ProjectSettings| s.mixer.main_to_cue,
+        mixer.set_main_to_cue
+    ),
+    num_setting!(
+        "mixer.main_level",
+        SIGNED_LEVEL_VALUES,
+        i8,
+        |s: &ProjectSettings| s.mixer.main_level_signed(),
+        mixer.set_main_level
+    ),
+    num_setting!(
+        "mixer.cue_level",
+        SIGNED_LEVEL_VALUES,
+        i8,
+        |s: &ProjectSettings| s.mixer.cue_level_signed(),
+        mixer.set_cue_level
+    ),
+    bool_setting!("audio.master_track", control.audio.master_track),
+    bool_setting!("audio.cue_studio_mode", control.audio.cue_studio_mode),
+    num_setting!(
+        "input.gate_ab",
+        UNSIGNED_LEVEL_VALUES,
+        u8,
+        |s: &ProjectSettings| s.control.input.gate_ab,
+        control.input.set_gate_ab
+    ),
+    num_setting!(
+        "input.gate_cd",
+        UNSIGNED_LEVEL_VALUES,
+        u8,
+        |s: &ProjectSettings| s.control.input.gate_cd,
+        control.input.set_gate_cd
+    ),
+    bool_setting!(
+        "input.delay_compensation",
+        control.input.input_delay_compensation
+    ),
+    bool_setting!(
+        "sequencer.silence_tracks",
+        control.sequencer.pattern_change_auto_silence_tracks
+    ),
+    bool_setting!(
+        "sequencer.lfo_auto_change",
+        control.sequencer.pattern_change_auto_trig_lfos
+    ),
+    bool_setting!("memory.load_24bit_flex", control.memory.load_24bit_flex),
+    bool_setting!("memory.dynamic_recorders", control.memory.dynamic_recorders),
+    bool_setting!("memory.record_24bit", control.memory.record_24bit),
+    num_setting!(
+        "memory.reserved_recorder_count",
+        "0 to 8",
+        u8,
+        |s: &ProjectSettings| s.control.memory.reserved_recorder_count,
+        control.memory.set_reserved_recorder_count
+    ),
+    num_setting!(
+        "memory.reserved_recorder_length",
+        "1 to 64",
+        u32,
+        |s: &ProjectSettings| s.control.memory.reserved_recorder_length,
+        control.memory.set_reserved_recorder_length
+    ),
+    bool_setting!("metronome.enabled", control.metronome.metronome_enabled),
+    SettingKey {
+        key: "metronome.time_signature",
+        values: "N/D, N 1 to 16, D 1, 2, 4, 8 or 16",
+        get: |s| match s.control.metronome.time_signature() {
+            Some((n, d)) => format!("{n}/{d}"),
+            None => "unknown".to_string(),
+        },
+        set: |s, v| {
+            let (n, d) = parse_time_signature("metronome.time_signature", v)?;
+            s.control.metronome.set_time_signature(n, d)
+        },
+    },
+    num_setting!(
+        "metronome.preroll",
+        "0 to 16",
+        u8,
+        |s: &ProjectSettings| s.control.metronome.metronome_preroll,
+        control.metronome.set_preroll
+    ),
+    num_setting!(
+        "metronome.cue_volume",
+        UNSIGNED_LEVEL_VALUES,
+        u8,
+        |s: &ProjectSettings| s.control.metronome.metronome_cue_volume,
+        control.metronome.set_cue_volume
+    ),
+    num_setting!(
+        "metronome.main_volume",
+        UNSIGNED_LEVEL_VALUES,
+        u8,
+        |s: &ProjectSettings| s.control.metronome.metronome_main_volume,
+        control.metronome.set_main_volume
+    ),
+    num_setting!(
+        "metronome.pitch",
+        "0 to 24",
+        u8,
+        |s: &ProjectSettings| s.control.metronome.metronome_pitch,
+        control.metronome.set_pitch
+    ),
+    bool_setting!("metronome.tonal", control.metronome.metronome_tonal),
+    bool_setting!(
+        "midi.audio_cc_in",
+        control.midi.control.midi_audio_track_cc_in
+    ),
+    SettingKey {
+        key: "midi.audio_cc_out",
+        values: MIDI_OUT_VALUES,
+        get: |s| {
+            show_name(
+                s.control.midi.control.midi_audio_track_cc_out,
+                &MIDI_OUT_NAMES,
+            )
+        },
+        set: |s, v| {
+            let x = parse_name("midi.audio_cc_out", v, &MIDI_OUT_NAMES)?;
+            s.control.midi.control.set_audio_track_cc_out(x)
+        },
+    },
+    SettingKey {
+        key: "midi.audio_note_in",
+        values: MIDI_NOTE_IN_VALUES,
+        get: |s| {
+            show_name(
+                s.control.midi.control.midi_audio_track_note_in,
+                &MIDI_NOTE_IN_NAMES,
+            )
+        },
+        set: |s, v| {
+            let x = parse_name("midi.audio_note_in", v, &MIDI_NOTE_IN_NAMES)?;
+            s.control.midi.control.set_audio_track_note_in(x)
+        },
+    },
+    SettingKey {
+        key: "midi.audio_note_out",
+        values: MIDI_OUT_VALUES,
+        get: |s| {
+            show_name(
+                s.control.midi.control.midi_audio_track_note_out,
+                &MIDI_OUT_NAMES,
+            )
+        },
+        set: |s, v| {
+            let x = parse_name("midi.audio_note_out", v, &MIDI_OUT_NAMES)?;
+            s.control.midi.control.set_audio_track_note_out(x)
+        },
+    },
+    bool_setting!("midi.clock_send", control.midi.sync.midi_clock_send),
+    bool_setting!("midi.clock_receive", control.midi.sync.midi_clock_receive),
+    bool_setting!("midi.transport_send", control.midi.sync.midi_transport_send),
+    bool_setting!(
+        "midi.transport_receive",
+        control.midi.sync.midi_transport_receive
+    ),
+    SettingKey {
+        key: "midi.progchange_send_channel",
+        values: PROGCHANGE_CHANNEL_VALUES,
+        get: |s| show_progchange_channel(&s.control.midi.sync.midi_progchange_send_channel),
+        set: |s, v| {
+            let x = parse_progchange_channel("midi.progchange_send_channel", v)?;
+            s.control.midi.sync.set_progchange_send_channel(x)
+        },
+    },
+    SettingKey {
+        key: "midi.progchange_receive_channel",
+        values: PROGCHANGE_CHANNEL_VALUES,
+        get: |s| show_progchange_channel(&s.control.midi.sync.midi_progchange_receive_channel),
+        set: |s, v| {
+            let x = parse_progchange_channel("midi.progchange_receive_channel", v)?;
+            s.control.midi.sync.set_progchange_receive_channel(x)
+        },
+    },
+    trig_channel_setting!("midi.trig_ch1", 1),
+    trig_channel_setting!("midi.trig_ch2", 2),
+    trig_channel_setting!("midi.trig_ch3", 3),
+    trig_channel_setting!("midi.trig_ch4", 4),
+    trig_channel_setting!("midi.trig_ch5", 5),
+    trig_channel_setting!("midi.trig_ch6", 6),
+    trig_channel_setting!("midi.trig_ch7", 7),
+    trig_channel_setting!("midi.trig_ch8", 8),
+    num_setting!(
+        "midi.auto_channel",
+        MIDI_CHANNEL_VALUES,
+        u8,
+        |s: &ProjectSettings| s.control.midi.channels.auto_channel(),
+        control.midi.channels.set_auto_channel
+    ),
+    bool_setting!("midi.soft_thru", midi_soft_thru),
+];
+
+fn find_setting_key(key: &str) -> RBoxErr<&'static SettingKey> {
+    SETTING_KEYS
+        .iter()
+        .find(|x| x.key == key)
+        .ok_or_else(|| ProjectSettingErrors::UnknownKey(key.to_string()).into())
+}
+
+/// Current value of a project setting, as shown on the device.
+pub fn get_project_setting(settings: &ProjectSettings, key: &str) -> RBoxErr<String> {
+    Ok((find_setting_key(key)?.get)(settings))
+}
+
+/// Change a project setting using the value shown on the device, e.g. `-12` for `mixer.gain_ab`.
+/// The settings are left unchanged when the value is invalid.
+pub fn set_project_setting(settings: &mut ProjectSettings, key: &str, value: &str) -> RBoxErr<()> {
+    (find_setting_key(key)?.set)(settings, value.trim())
+}
+
+/// Split a `key=value` setting assignment.
+pub fn parse_setting_assignment(x: &str) -> RBoxErr<(String, String)> {
+    match x.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(ProjectSettingErrors::InvalidAssignment(x.to_string()).into()),
+    }
+}
+
+/// A project setting's current value and the values it accepts.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProjectSettingValue {
+    pub key: String,
+    pub value: String,
+    pub values: String,
+}
+
+/// List every project setting which can be changed by key with its current value.
+pub fn list_project_settings(settings: &ProjectSettings) -> Vec<ProjectSettingValue> {
+    SETTING_KEYS
+        .iter()
+        .map(|x| ProjectSettingValue {
+            key: x.key.to_string(),
+            value: (x.get)(settings),
+            values: x.values.to_string(),
+        })
+        .collect()
+}
+
+/// Print the settings of a project's working project file as a list, or as JSON.
+pub fn print_project_settings(project_dirpath: &Path, json: bool) -> RBoxErr<()> {
+    let project = read_type_from_bin_file::<Project>(&project_dirpath.join("project.work"))?;
+    let settings = list_project_settings(&project.settings);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&settings)?);
+        return Ok(());
+    }
+
+    for setting in &settings {
+        println!("{}={} ({})", setting.key, setting.value, setting.values);
+    }
+
+    Ok(())
+}
+
+/// Change settings in a project's working project file (`project.work`).
+/// Nothing is written unless every setting is valid.
+pub fn set_project_settings(
+    project_dirpath: &Path,
+    assignments: &[(String, String)],
+) -> RBoxErr<()> {
+    finish_transaction(
+        stage_set_project_settings(project_dirpath, assignments)?,
+        false,
+        None,
+    )
+}
+
+/// Stage the changes for `set_project_settings` without changing anything on the file system.
+pub fn stage_set_project_settings(
+    project_dirpath: &Path,
+    assignments: &[(String, String)],
+) -> RBoxErr<Transaction> {
+    let project_fpath = project_dirpath.join("project.work");
+    if !project_fpath.is_file() {
+        return Err(OctatoolErrors::PathDoesNotExist.into());
+    }
+    let project = read_type_from_bin_file::<Project>(&project_fpath)?;
+    if !validate_project_version(&project) {
+        return Err(OctatoolErrors::InvalidOsVersion.into());
+    };
+
+    let mut new_project = project.clone();
+    for (key, value) in assignments {
+        set_project_setting(&mut new_project.settings, key, value)?;
+    }
+
+    let mut tx = Transaction::new(
+        &get_backups_root_for_project(project_dirpath)?,
+        &format!("change project settings in {project_dirpath:?}"),
+    );
+    if new_project != project {
+        tx.stage_type::<Project>(&new_project, &project_fpath)?;
+    }
+
+    Ok(tx)
+}
+
+/// Check every setting with a known range is within that range.
+pub fn validate_project_settings(settings: &ProjectSettings) -> RBoxErr<()> {
+    // round trip each value through its validated setter
+    let mut x = settings.clone();
+    for setting in SETTING_KEYS {
+        (setting.set)(&mut x, &(setting.get)(settings))?;
+    }
+    Ok(())
+}
+
+/// Merge a (partial) settings overlay into the serialized settings, erroring on unknown keys.
+fn merge_settings_overlay(base: &mut Value, overlay: &Value, path: &str) -> RBoxErr<()> {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (k, v) in overlay {
+                let key_path = if path.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{path}.{k}")
+                };
+                let base_value = base
+                    .get_mut(k)
+                    .ok_or(ProjectSettingErrors::UnknownOverlayKey(key_path.clone()))?;
+                merge_settings_overlay(base_value, v, &key_path)?;
+            }
+            Ok(())
+        }
+        (Value::Object(_), _) | (_, Value::Object(_)) => {
+            Err(ProjectSettingErrors::InvalidOverlayValue(path.to_string()).into())
+        }
+        (base, overlay) => {
+            *base = overlay.clone();
+            Ok(())
+        }
+    }
+}
+
+/// Apply a partial settings overlay, using the same structure as a YAML/JSON serialized
+/// `ProjectSettings`, e.g. `{control: {memory: {load_24bit_flex: true}}}`.
+/// Overlay values are the stored values, not the device values `set_project_setting` takes (e.g.
+/// the mixer levels are stored with an offset of 64, MIDI channels are 0-indexed).
+/// The new settings are checked with `validate_project_settings`.
+pub fn apply_project_settings_overlay(
+    settings: &ProjectSettings,
+    overlay: &Value,
+) -> RBoxErr<ProjectSettings> {
+    let mut value = serde_json::to_value(settings)?;
+    merge_settings_overlay(&mut value, overlay, "")?;
+    let new_settings = serde_json::from_value::<ProjectSettings>(value)?;
+    validate_project_settings(&new_settings)?;
+    Ok(new_settings)
+}
+
+/// Read a settings overlay (see `apply_project_settings_overlay`) from a YAML file.
+pub fn read_project_settings_overlay(overlay_fpath: &Path) -> RBoxErr<Value> {
+    let overlay = yaml_file_to_type::<Value>(overlay_fpath)?;
+    if !overlay.is_object() {
+        return Err(ProjectSettingErrors::InvalidOverlayValue("".to_string()).into());
+    }
+    Ok(overlay)
+}
+
+/// Flatten serialized settings to `(dotted.key, value)` pairs.
+fn flatten_settings_value(value: &Value, path: &str, flat: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                let key_path = if path.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{path}.{k}")
+                };
+                flatten_settings_value(v, &key_path, flat);
+            }
+        }
+        x => flat.push((path.to_string(), x.clone())),
+    }
+}
+
+/// A changed setting, with the serialized (stored) values.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProjectSettingChange {
+    pub key: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// Changes between two sets of project settings, keyed like a settings overlay.
+pub fn diff_project_settings(
+    old: &ProjectSettings,
+    new: &ProjectSettings,
+) -> RBoxErr<Vec<ProjectSettingChange>> {
+    let (mut old_flat, mut new_flat) = (vec![], vec![]);
+    flatten_settings_value(&serde_json::to_value(old)?, "", &mut old_flat);
+    flatten_settings_value(&serde_json::to_value(new)?, "", &mut new_flat);
+
+    Ok(old_flat
+        .into_iter()
+        .zip(new_flat)
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((key, old), (_, new))| ProjectSettingChange { key, old, new })
+        .collect())
+}
+
+/// What happened to a project when applying a settings overlay.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "status", content = "value", rename_all = "snake_case")]
+pub enum ProjectSettingsOverlayStatus {
+    Changed(Vec<ProjectSettingChange>),
+    Unchanged,
+    /// Project was not changed, e.g. it was saved by an unsupported OS version
+    Skipped(String),
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProjectSettingsOverlayReport {
+    pub project_dirpath: PathBuf,
+    #[serde(flatten)]
+    pub status: ProjectSettingsOverlayStatus,
+}
+
+/// Print the per-project results of applying a settings overlay as a list, or as JSON.
+pub fn print_project_settings_overlay_reports(
+    reports: &[ProjectSettingsOverlayReport],
+    json: bool,
+) -> RBoxErr<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(reports)?);
+        return Ok(());
+    }
+
+    for report in reports {
+        match &report.status {
+            ProjectSettingsOverlayStatus::Changed(changes) => {
+                println!("{:?}: {} changes", report.project_dirpath, changes.len());
+                for change in changes {
+                    println!("    {}: {} -> {}", change.key, change.old, change.new);
+                }
+            }
+            ProjectSettingsOverlayStatus::Unchanged => {
+                println!("{:?}: no changes", report.project_dirpath);
+            }
+            ProjectSettingsOverlayStatus::Skipped(reason) => {
+                println!("{:?}: skipped - {reason}", report.project_dirpath);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply a settings overlay (see `apply_project_settings_overlay`) to the working project file
+/// of every project in a set or compact flash card directory.
+pub fn apply_project_settings_overlay_to_projects(
+    root_dirpath: &Path,
+    overlay: &Value,
+) -> RBoxErr<Vec<ProjectSettingsOverlayReport>> {
+    let (tx, reports) = stage_apply_project_settings_overlay_to_projects(root_dirpath, overlay)?;
+    finish_transaction(tx, false, None)?;
+    Ok(reports)
+}
+
+/// Stage the changes for `apply_project_settings_overlay_to_projects` without changing anything
+/// on the file system, returning the per-project results.
+///
+/// Projects which can't be read, were saved by an unsupported OS version or already have
+/// out of range settings are skipped.
+pub fn stage_apply_project_settings_overlay_to_projects(
+    root_dirpath: &Path,
+    overlay: &Value,
+) -> RBoxErr<(Transaction, Vec<ProjectSettingsOverlayReport>)> {
+    // problems with the overlay itself stop everything, not just one project
+    apply_project_settings_overlay(&ProjectSettings::default(), overlay)?;
+
+    let mut tx = Transaction::new(
+        &fs::canonicalize(root_dirpath)?,
+        &format!("apply project settings overlay to projects in {root_dirpath:?}"),
+    );
+    let mut reports = vec![];
+
+    for project_dirpath in find_project_dirpaths(root_dirpath)? {
+        let project_fpath = project_dirpath.join("project.work");
+        let status = match read_type_from_bin_file::<Project>(&project_fpath) {
+            Err(e) => ProjectSettingsOverlayStatus::Skipped(format!("could not read project: {e}")),
+            Ok(project) if !validate_project_version(&project) => {
+                ProjectSettingsOverlayStatus::Skipped(OctatoolErrors::InvalidOsVersion.to_string())
+            }
+            Ok(project) => match apply_project_settings_overlay(&project.settings, overlay) {
+                Err(e) => ProjectSettingsOverlayStatus::Skipped(e.to_string()),
+                Ok(new_settings) => {
+                    let changes = diff_project_settings(&project.settings, &new_settings)?;
+                    if changes.is_empty() {
+                        ProjectSettingsOverlayStatus::Unchanged
+                    } else {
+                        let mut new_project = project.clone();
+                        new_project.settings = new_settings;
+                        tx.stage_type::<Project>(&new_project, &project_fpath)?;
+                        ProjectSettingsOverlayStatus::Changed(changes)
+                    }
+                }
+            },
+        };
+        reports.push(ProjectSettingsOverlayReport {
+            project_dirpath,
+            status,
+        });
+    }
+
+    Ok((tx, reports))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn assignments(x: &[(&str, &str)]) -> Vec<(String, String)> {
+        x.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    mod set {
+        use super::*;
+
+        #[test]
+        fn signed_gain() {
+            let mut settings = ProjectSettings::default();
+            set_project_setting(&mut settings, "mixer.gain_ab", "-12").unwrap();
+            assert_eq!(settings.mixer.gain_ab, 52);
+            assert_eq!(
+                get_project_setting(&settings, "mixer.gain_ab").unwrap(),
+                "-12"
+            );
+        }
+
+        #[test]
+        fn signed_gain_out_of_range_err() {
+            let mut settings = ProjectSettings::default();
+            assert!(set_project_setting(&mut settings, "mixer.gain_ab", "64").is_err());
+            assert_eq!(settings, ProjectSettings::default());
+        }
+
+        #[test]
+        fn time_signature() {
+            let mut settings = ProjectSettings::default();
+            set_project_setting(&mut settings, "metronome.time_signature", "7/8").unwrap();
+            assert_eq!(settings.control.metronome.metronome_time_signature, 6);
+            assert_eq!(
+                settings
+                    .control
+                    .metronome
+                    .metronome_time_signature_denominator,
+                3
+            );
+        }
+
+        #[test]
+        fn time_signature_invalid_err() {
+            let mut settings = ProjectSettings::default();
+            for x in ["7", "7/3", "0/4", "a/b"] {
+                assert!(
+                    set_project_setting(&mut settings, "metronome.time_signature", x).is_err(),
+                    "{x}"
+                );
+            }
+        }
+
+        #[test]
+        fn bool_values() {
+            let mut settings = ProjectSettings::default();
+            set_project_setting(&mut settings, "metronome.enabled", "on").unwrap();
+            assert!(settings.control.metronome.metronome_enabled);
+            set_project_setting(&mut settings, "metronome.enabled", "false").unwrap();
+            assert!(!settings.control.metronome.metronome_enabled);
+            assert!(set_project_setting(&mut settings, "metronome.enabled", "maybe").is_err());
+        }
+
+        #[test]
+        fn named_values() {
+            let mut settings = ProjectSettings::default();
+            set_project_setting(&mut settings, "midi.audio_cc_out", "EXT").unwrap();
+            assert_eq!(settings.control.midi.control.midi_audio_track_cc_out, 2);
+            set_project_setting(&mut settings, "midi.audio_note_in", "off").unwrap();
+            assert_eq!(settings.control.midi.control.midi_audio_track_note_in, 0);
+        }
+
+        #[test]
+        fn progchange_channel() {
+            let mut settings = ProjectSettings::default();
+            set_project_setting(&mut settings, "midi.progchange_send_channel", "16").unwrap();
+            assert!(settings.control.midi.sync.midi_progchange_send);
+            assert_eq!(
+                get_project_setting(&settings, "midi.progchange_send_channel").unwrap(),
+                "16"
+            );
+        }
+
+        #[test]
+        fn unknown_key_err() {
+            let mut settings = ProjectSettings::default();
+            assert!(set_project_setting(&mut settings, "mixer.volume", "1").is_err());
+        }
+    }
+
+    mod list {
+        use super::*;
+
+        #[test]
+        fn defaults() {
+            let settings = list_project_settings(&ProjectSettings::default());
+            let get = |key: &str| {
+                settings
+                    .iter()
+                    .find(|x| x.key == key)
+                    .map(|x| x.value.clone())
+                    .unwrap()
+            };
+            assert_eq!(get("tempo.bpm"), "120");
+            assert_eq!(get("mixer.main_level"), "0");
+            assert_eq!(get("metronome.time_signature"), "4/4");
+            assert_eq!(get("midi.trig_ch8"), "8");
+            assert_eq!(get("midi.auto_channel"), "11");
+            assert_eq!(get("midi.progchange_receive_channel"), "off");
+        }
+
+        #[test]
+        fn keys_unique() {
+            let mut keys = SETTING_KEYS.iter().map(|x| x.key).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            assert_eq!(keys.len(), SETTING_KEYS.len());
+        }
+    }
+
+    #[test]
+    fn parse_assignment() {
+        assert_eq!(
+            parse_setting_assignment("mixer.gain_ab=-12").unwrap(),
+            ("mixer.gain_ab".to_string(), "-12".to_string())
+        );
+        assert!(parse_setting_assignment("mixer.gain_ab").is_err());
+        assert!(parse_setting_assignment("=1").is_err());
+    }
+
+    mod overlay {
+        use super::*;
+        use ot_tools_io::write_type_to_bin_file;
+        use serde_json::json;
+
+        #[test]
+        fn partial_overlay() {
+            let overlay = json!({"control": {"memory": {"load_24bit_flex": true}}});
+            let old = ProjectSettings::default();
+            let new = apply_project_settings_overlay(&old, &overlay).unwrap();
+            assert!(new.control.memory.load_24bit_flex);
+            assert_eq!(
+                diff_project_settings(&old, &new).unwrap(),
+                vec![ProjectSettingChange {
+                    key: "control.memory.load_24bit_flex".to_string(),
+                    old: json!(false),
+                    new: json!(true),
+                }]
+            );
+        }
+
+        #[test]
+        fn unknown_key_err() {
+            let overlay = json!({"control": {"memory": {"load_32bit_flex": true}}});
+            let r = apply_project_settings_overlay(&ProjectSettings::default(), &overlay);
+            assert_eq!(
+                r.unwrap_err().to_string(),
+                ProjectSettingErrors::UnknownOverlayKey(
+                    "control.memory.load_32bit_flex".to_string()
+                )
+                .to_string()
+            );
+        }
+
+        #[test]
+        fn value_for_group_err() {
+            let overlay = json!({"control": {"memory": 1}});
+            assert!(apply_project_settings_overlay(&ProjectSettings::default(), &overlay).is_err());
+        }
+
+        #[test]
+        fn out_of_range_err() {
+            let overlay = json!({"control": {"memory": {"reserved_recorder_count": 9}}});
+            assert!(apply_project_settings_overlay(&ProjectSettings::default(), &overlay).is_err());
+        }
+
+        fn mock_set(name: &str) -> PathBuf {
+            let set_dirpath = std::env::temp_dir().join(format!("ot_settings_overlay_{name}"));
+            if set_dirpath.exists() {
+                let _ = fs::remove_dir_all(&set_dirpath);
+            }
+            fs::create_dir_all(&set_dirpath).unwrap();
+            for project_name in ["A", "B", "C"] {
+                let project_dirpath = set_dirpath.join(project_name);
+                fs::create_dir_all(&project_dirpath).unwrap();
+                fs::copy(
+                    "../data/tests/blank-project/project.work",
+                    project_dirpath.join("project.work"),
+                )
+                .unwrap();
+            }
+            set_dirpath
+        }
+
+        #[test]
+        fn batch_reports() {
+            let set_dirpath = mock_set("batch_reports");
+
+            let old_version_fpath = set_dirpath.join("C").join("project.work");
+            let mut old_version = read_type_from_bin_file::<Project>(&old_version_fpath).unwrap();
+            old_version.metadata.os_version = "R0177     1.25E".to_string();
+            write_type_to_bin_file(&old_version, &old_version_fpath).unwrap();
+
+            let overlay = json!({"control": {"metronome": {"metronome_pitch": 20}}});
+            let b_fpath = set_dirpath.join("B").join("project.work");
+            let mut b = read_type_from_bin_file::<Project>(&b_fpath).unwrap();
+            b.settings.control.metronome.metronome_pitch = 20;
+            write_type_to_bin_file(&b, &b_fpath).unwrap();
+
+            let reports =
+                apply_project_settings_overlay_to_projects(&set_dirpath, &overlay).unwrap();
+            let statuses = reports.iter().map(|x| &x.status).collect::<Vec<_>>();
+            assert!(matches!(
+                statuses[0],
+                ProjectSettingsOverlayStatus::Changed(changes) if changes.len() == 1
+            ));
+            assert_eq!(statuses[1], &ProjectSettingsOverlayStatus::Unchanged);
+            assert!(matches!(
+                statuses[2],
+                ProjectSettingsOverlayStatus::Skipped(_)
+            ));
+
+            let a = read_type_from_bin_file::<Project>(&set_dirpath.join("A").join("project.work"))
+                .unwrap();
+            assert_eq!(a.settings.control.metronome.metronome_pitch, 20);
+            let c = read_type_from_bin_file::<Project>(&old_version_fpath).unwrap();
+            assert_eq!(c, old_version);
+
+            let _ = fs::remove_dir_all(&set_dirpath);
+        }
+    }
+
+    mod stage {
+        use super::*;
+
+        fn project_dir() -> PathBuf {
+            PathBuf::from("../data/tests/blank-project")
+        }
+
+        #[test]
+        fn staged_project_file() {
+            let tx = stage_set_project_settings(
+                &project_dir(),
+                &assignments(&[("tempo.bpm", "90"), ("metronome.time_signature", "3/4")]),
+            )
+            .unwrap();
+            assert_eq!(tx.plan().unwrap().files.len(), 1);
+        }
+
+        #[test]
+        fn invalid_setting_stages_nothing() {
+            let r = stage_set_project_settings(
+                &project_dir(),
+                &assignments(&[("tempo.bpm", "90"), ("tempo.bpm", "400")]),
+            );
+            assert!(r.is_err());
+        }
+    }
+}
@@ -1,19 +1,43 @@
 use crate::{
     actions::banks::utils::{
-        create_backup_of_work_file, find_sample_slot_settings_match,
-        get_one_indexed_slots_from_zero_indexed, get_zero_indexed_slots_from_one_indexed, BankMeta,
-        ProjectMeta, SlotReferenceReassignment,
+        find_sample_slot_settings_match, get_one_indexed_slots_from_zero_indexed,
+        get_zero_indexed_slots_from_one_indexed, BankMeta, ProjectMeta, SlotReferenceReassignment,
     },
-    actions::{part_update_sample_slot_refs, pattern_update_sample_slot_refs},
+    actions::projects::usage::referenced_slot_ids,
+    actions::{
+        part_remap_sample_slot_refs, part_update_sample_slot_refs, pattern_remap_sample_slot_refs,
+        pattern_update_sample_slot_refs,
+    },
+    transaction::{finish_transaction, get_backups_root_for_project, Transaction},
     RBoxErr,
 };
 use itertools::Itertools;
 use ot_tools_io::{
     banks::Bank,
-    projects::{slots::ProjectSampleSlot, Project},
-    read_type_from_bin_file, write_type_to_bin_file,
+    projects::{options::ProjectSampleSlotType, slots::ProjectSampleSlot, Project},
+    read_type_from_bin_file,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
 };
-use std::path::Path;
+
+#[derive(Debug)]
+pub enum SlotErrors {
+    /// The saved project state has different static / flex sample slots to the working state
+    SavedSlotsDiffer(PathBuf),
+}
+impl std::fmt::Display for SlotErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::SavedSlotsDiffer(path) => write!(
+                f,
+                "saved project state has different sample slots to the working state, save the project first: {path:?}"
+            ),
+        }
+    }
+}
+impl std::error::Error for SlotErrors {}
 
 /// De-duplicate sample slots based on the slot settings, reassigning references
 /// to the duplicate slots in the provided bank data.
@@ -96,21 +120,24 @@ fn get_new_deduplicated_sample_slots_and_updated_banks(
 
 // TODO: BankMeta and ProjectMeta need to be changed to load the work and strd file paths
 
-fn load_work_banks_for_project(project_dirpath: &Path) -> RBoxErr<Vec<Bank>> {
+pub(crate) fn load_work_banks_for_project(project_dirpath: &Path) -> RBoxErr<Vec<Bank>> {
     let mut banks: Vec<Bank> = vec![];
     for bank_id in 1..=16 {
         let bank_paths = BankMeta::frompath(project_dirpath, bank_id)?;
-        create_backup_of_work_file(&bank_paths.filepath)?;
         banks.push(read_type_from_bin_file::<Bank>(&bank_paths.filepath)?)
     }
     Ok(banks)
 }
 
 /// Assumes `banks` ordering is the order in which to write the bank files
-fn write_work_banks_for_project(project_dirpath: &Path, banks: &[Bank]) -> RBoxErr<()> {
+fn stage_work_banks_for_project(
+    tx: &mut Transaction,
+    project_dirpath: &Path,
+    banks: &[Bank],
+) -> RBoxErr<()> {
     for (bank_id, new_bank) in (1..=16).zip(banks) {
         let bank_paths = BankMeta::frompath(project_dirpath, bank_id)?;
-        write_type_to_bin_file::<Bank>(new_bank, &bank_paths.filepath)?;
+        tx.stage_type::<Bank>(new_bank, &bank_paths.filepath)?;
     }
     Ok(())
 }
@@ -128,8 +155,13 @@ fn write_work_banks_for_project(project_dirpath: &Path, banks: &[Bank]) -> RBoxE
 /// WARNING: Does not check whether sample files are unique based on content --
 /// requires end-users have been fastidious when naming their sample files.
 pub fn cmd_slots_deduplicate(project_dirpath: &Path) -> RBoxErr<()> {
+    finish_transaction(stage_cmd_slots_deduplicate(project_dirpath)?, false, None)
+}
+
+/// Stage the changes for sample slot deduplication (see `cmd_slots_deduplicate`) without
+/// changing anything on the file system.
+pub fn stage_cmd_slots_deduplicate(project_dirpath: &Path) -> RBoxErr<Transaction> {
     let project_paths = ProjectMeta::frompath(project_dirpath)?;
-    create_backup_of_work_file(&project_paths.filepath)?;
     let project = read_type_from_bin_file::<Project>(&project_paths.filepath)?;
     let banks = load_work_banks_for_project(project_dirpath)?;
     let zero_index_slots = get_zero_indexed_slots_from_one_indexed(&project.slots)?;
@@ -140,8 +172,630 @@ pub fn cmd_slots_deduplicate(project_dirpath: &Path) -> RBoxErr<()> {
     let mut new_project = project.clone();
     new_project.slots = one_index_slots;
 
-    write_type_to_bin_file::<Project>(&new_project, &project_paths.filepath)?;
-    write_work_banks_for_project(project_dirpath, &new_banks)?;
+    let mut tx = Transaction::new(
+        &get_backups_root_for_project(project_dirpath)?,
+        &format!("deduplicate sample slots in {project_dirpath:?}"),
+    );
+    tx.stage_type::<Project>(&new_project, &project_paths.filepath)?;
+    stage_work_banks_for_project(&mut tx, project_dirpath, &new_banks)?;
+    tx.record_project_slot_changes(&project_paths.filepath, &project.slots, &new_project.slots);
 
-    Ok(())
+    Ok(tx)
+}
+
+/// Order used when renumbering sample slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlotRenumberOrder {
+    /// Keep the existing order of the slots, only remove the gaps between them
+    #[default]
+    SlotId,
+    /// Order slots by the file path of the registered sample file
+    Path,
+    /// Order slots by the number of references to the slot in the bank data (most used first),
+    /// slots with an equal number of references keep their existing order
+    Usage,
+}
+
+/// Count references to each zero-indexed sample slot across all pattern p-locks and all saved and
+/// unsaved part audio track machines in the provided bank data.
+fn count_sample_slot_usage(banks: &[Bank]) -> HashMap<(ProjectSampleSlotType, u8), usize> {
+    let mut counts = HashMap::new();
+    let mut count = |sample_type: ProjectSampleSlotType, slot_id: u8| {
+        // 255 means a p-lock / machine slot is disabled
+        if slot_id != 255 {
+            *counts.entry((sample_type, slot_id)).or_insert(0) += 1;
+        }
+    };
+
+    for bank in banks {
+        for pattern in bank.patterns.iter() {
+            for plock in pattern
+                .audio_track_trigs
+                .iter()
+                .flat_map(|x| x.plocks.iter())
+            {
+                count(ProjectSampleSlotType::Static, plock.static_slot_id);
+                count(ProjectSampleSlotType::Flex, plock.flex_slot_id);
+            }
+        }
+        for part in bank.parts.unsaved.iter().chain(bank.parts.saved.iter()) {
+            for machine in part.audio_track_machine_slots.iter() {
+                count(ProjectSampleSlotType::Static, machine.static_slot_id);
+                count(ProjectSampleSlotType::Flex, machine.flex_slot_id);
+            }
+        }
+    }
+    counts
+}
+
+/// Highest static / flex sample slot id (flex slots above this are recorder buffers).
+const MAX_SAMPLE_SLOT_ID: u8 = 128;
+
+/// Zero-indexed slot id reassignments (old slot id -> new slot id) for each sample slot type.
+type SlotRemaps = Vec<(ProjectSampleSlotType, HashMap<u8, u8>)>;
+
+/// Renumber static and flex sample slots so each type is numbered contiguously from slot 1,
+/// returning the renumbered slots and the slot id reassignments needed to update bank data.
+///
+/// Empty slots are renumbered too, moving after the loaded slots in the same order. Anything in
+/// the bank data which refers to an empty slot (e.g. the track 1-8 -> slot 1-8 assignments of
+/// default parts) still refers to an empty slot afterwards, instead of picking up whichever
+/// sample was moved into that slot id.
+///
+/// Assumes 1-indexed sample slots (as stored in Octatrack project data files). Recording buffer
+/// slots are never renumbered. `banks` is only used for `SlotRenumberOrder::Usage`.
+fn get_renumbered_sample_slots(
+    slots: &[ProjectSampleSlot],
+    banks: &[Bank],
+    order: SlotRenumberOrder,
+) -> (Vec<ProjectSampleSlot>, SlotRemaps) {
+    let usage = count_sample_slot_usage(banks);
+    let mut new_slots = slots.to_vec();
+    let mut remaps: SlotRemaps = vec![];
+
+    for sample_type in [ProjectSampleSlotType::Static, ProjectSampleSlotType::Flex] {
+        let ordered = new_slots
+            .iter_mut()
+            .filter(|x| x.sample_type == sample_type)
+            .sorted_by(|x, y| match order {
+                SlotRenumberOrder::SlotId => Ord::cmp(&x.slot_id, &y.slot_id),
+                SlotRenumberOrder::Path => Ord::cmp(&(&x.path, x.slot_id), &(&y.path, y.slot_id)),
+                SlotRenumberOrder::Usage => {
+                    let n = |s: &ProjectSampleSlot| {
+                        usage
+                            .get(&(sample_type.clone(), s.slot_id - 1))
+                            .copied()
+                            .unwrap_or(0)
+                    };
+                    Ord::cmp(&n(y), &n(x)).then(Ord::cmp(&x.slot_id, &y.slot_id))
+                }
+            })
+            .collect_vec();
+
+        // zero-indexed, as used in bank data
+        let mut remap: HashMap<u8, u8> = HashMap::new();
+        let mut loaded: HashSet<u8> = HashSet::new();
+        for (new_slot_id, slot) in (1..).zip(ordered) {
+            loaded.insert(slot.slot_id);
+            if slot.slot_id != new_slot_id {
+                remap.insert(slot.slot_id - 1, new_slot_id - 1);
+                slot.slot_id = new_slot_id;
+            }
+        }
+
+        let empty = (1..=MAX_SAMPLE_SLOT_ID).filter(|x| !loaded.contains(x));
+        for (new_slot_id, old_slot_id) in (loaded.len() as u8 + 1..).zip(empty) {
+            if old_slot_id != new_slot_id {
+                remap.insert(old_slot_id - 1, new_slot_id - 1);
+            }
+        }
+
+        if !remap.is_empty() {
+            remaps.push((sample_type, remap));
+        }
+    }
+
+    (new_slots, remaps)
+}
+
+/// Reassign references to renumbered sample slots in all pattern p-locks and all saved and
+/// unsaved parts of the provided bank data.
+fn remap_bank_sample_slot_refs(banks: &[Bank], remaps: &SlotRemaps) -> RBoxErr<Vec<Bank>> {
+    let mut new_banks = banks.to_owned();
+    for (sample_type, remap) in remaps {
+        for bank in &mut new_banks {
+            for pattern in bank.patterns.iter_mut() {
+                pattern_remap_sample_slot_refs(pattern, sample_type, remap)?;
+            }
+            for part in bank
+                .parts
+                .unsaved
+                .iter_mut()
+                .chain(bank.parts.saved.iter_mut())
+            {
+                part_remap_sample_slot_refs(part, sample_type, remap)?;
+            }
+        }
+    }
+    Ok(new_banks)
+}
+
+/// Static / flex sample slot ids, used to check the saved project state matches the working state.
+fn static_flex_slot_ids(slots: &[ProjectSampleSlot]) -> HashSet<(ProjectSampleSlotType, u8)> {
+    slots
+        .iter()
+        .filter(|x| x.sample_type != ProjectSampleSlotType::RecorderBuffer)
+        .map(|x| (x.sample_type.clone(), x.slot_id))
+        .collect()
+}
+
+/// Apply slot id reassignments to 1-indexed sample slots.
+fn remap_sample_slot_ids(
+    slots: &[ProjectSampleSlot],
+    remaps: &SlotRemaps,
+) -> Vec<ProjectSampleSlot> {
+    let mut new_slots = slots.to_vec();
+    for slot in new_slots.iter_mut() {
+        let new_slot_id = remaps
+            .iter()
+            .filter(|(sample_type, _)| *sample_type == slot.sample_type)
+            .find_map(|(_, remap)| remap.get(&(slot.slot_id - 1)));
+        if let Some(new_slot_id) = new_slot_id {
+            slot.slot_id = new_slot_id + 1;
+        }
+    }
+    new_slots
+}
+
+/// Renumber the static and flex sample slots for a project located at `dirpath`, so each slot
+/// type is numbered contiguously from slot 1 with no gaps.
+///
+/// The command will also update slot references in all bankXX.work files within the project
+/// directory (p-locks and saved / unsaved part machine slot assignments are changed to point at
+/// the renumbered slot). References to empty slots are moved to slots which are still empty, so
+/// tracks with a default part's slot assignments don't start playing a renumbered sample.
+///
+/// The saved project state (project.strd and any bankXX.strd files) is renumbered in the same
+/// way, so reloading the project still points at the same samples. The command refuses to run
+/// when the static / flex slots in project.strd differ from project.work (save the project on
+/// the Octatrack first).
+pub fn cmd_slots_renumber(project_dirpath: &Path, order: SlotRenumberOrder) -> RBoxErr<()> {
+    finish_transaction(
+        stage_cmd_slots_renumber(project_dirpath, order)?,
+        false,
+        None,
+    )
+}
+
+/// Stage the changes for renumbering sample slots (see `cmd_slots_renumber`) without changing
+/// anything on the file system.
+pub fn stage_cmd_slots_renumber(
+    project_dirpath: &Path,
+    order: SlotRenumberOrder,
+) -> RBoxErr<Transaction> {
+    let project_paths = ProjectMeta::frompath(project_dirpath)?;
+    let project = read_type_from_bin_file::<Project>(&project_paths.filepath)?;
+    let banks = load_work_banks_for_project(project_dirpath)?;
+
+    let strd_project_fpath = project_dirpath.join("project.strd");
+    let strd_project = if strd_project_fpath.exists() {
+        let strd_project = read_type_from_bin_file::<Project>(&strd_project_fpath)?;
+        if static_flex_slot_ids(&strd_project.slots) != static_flex_slot_ids(&project.slots) {
+            return Err(SlotErrors::SavedSlotsDiffer(strd_project_fpath).into());
+        }
+        Some(strd_project)
+    } else {
+        None
+    };
+
+    let (new_slots, remaps) = get_renumbered_sample_slots(&project.slots, &banks, order);
+    let new_banks = remap_bank_sample_slot_refs(&banks, &remaps)?;
+
+    let mut new_project = project.clone();
+    new_project.slots = new_slots;
+
+    let mut tx = Transaction::new(
+        &get_backups_root_for_project(project_dirpath)?,
+        &format!("renumber sample slots in {project_dirpath:?}"),
+    );
+    if new_project.slots != project.slots {
+        tx.stage_type::<Project>(&new_project, &project_paths.filepath)?;
+        stage_work_banks_for_project(&mut tx, project_dirpath, &new_banks)?;
+        tx.record_project_slot_changes(&project_paths.filepath, &project.slots, &new_project.slots);
+
+        if let Some(strd_project) = strd_project {
+            let mut new_strd_project = strd_project.clone();
+            new_strd_project.slots = remap_sample_slot_ids(&strd_project.slots, &remaps);
+            tx.stage_type::<Project>(&new_strd_project, &strd_project_fpath)?;
+        }
+        for bank_id in 1..=16 {
+            let fpath = project_dirpath.join(format!("bank{bank_id:0>2}.strd"));
+            if fpath.exists() {
+                let bank = read_type_from_bin_file::<Bank>(&fpath)?;
+                let new_bank = remap_bank_sample_slot_refs(&[bank], &remaps)?;
+                tx.stage_type::<Bank>(&new_bank[0], &fpath)?;
+            }
+        }
+    }
+
+    Ok(tx)
+}
+
+/// Load any `bankXX.strd` (saved bank state) files which exist in the project directory.
+fn load_strd_banks_for_project(project_dirpath: &Path) -> RBoxErr<Vec<Bank>> {
+    let mut banks: Vec<Bank> = vec![];
+    for bank_id in 1..=16 {
+        let fpath = project_dirpath.join(format!("bank{bank_id:0>2}.strd"));
+        if fpath.exists() {
+            banks.push(read_type_from_bin_file::<Bank>(&fpath)?)
+        }
+    }
+    Ok(banks)
+}
+
+/// Get the project's sample slots without the static / flex slots which nothing in the provided
+/// bank data refers to. Slots referenced by `keep_banks` are kept as well.
+///
+/// Assumes 1-indexed sample slots (as stored in Octatrack project data files).
+pub(crate) fn get_sample_slots_without_unreferenced(
+    slots: &[ProjectSampleSlot],
+    banks: &[Bank],
+    keep_banks: &[Bank],
+) -> Vec<ProjectSampleSlot> {
+    let refs = referenced_slot_ids(banks)
+        .into_iter()
+        .chain(referenced_slot_ids(keep_banks))
+        .collect::<HashSet<_>>();
+
+    slots
+        .iter()
+        .filter(|x| {
+            x.sample_type == ProjectSampleSlotType::RecorderBuffer
+                || refs.contains(&(x.sample_type.clone(), x.slot_id))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Remove static and flex sample slots which are not referenced by any part audio track machine
+/// or pattern p-lock in the bankXX.work files of a project located at `dirpath`.
+///
+/// When `keep_strd_refs` is set, slots which are only referenced by the saved bank state
+/// (bankXX.strd files) are also kept, so reloading a bank doesn't point at an empty slot.
+///
+/// Sample files are not removed (see `purge_project_pool`).
+pub fn cmd_slots_purge(project_dirpath: &Path, keep_strd_refs: bool) -> RBoxErr<()> {
+    finish_transaction(
+        stage_cmd_slots_purge(project_dirpath, keep_strd_refs)?,
+        false,
+        None,
+    )
+}
+
+/// Stage the removal of unreferenced sample slots (see `cmd_slots_purge`) without changing
+/// anything on the file system.
+pub fn stage_cmd_slots_purge(project_dirpath: &Path, keep_strd_refs: bool) -> RBoxErr<Transaction> {
+    let project_paths = ProjectMeta::frompath(project_dirpath)?;
+    let project = read_type_from_bin_file::<Project>(&project_paths.filepath)?;
+    let banks = load_work_banks_for_project(project_dirpath)?;
+    let keep_banks = if keep_strd_refs {
+        load_strd_banks_for_project(project_dirpath)?
+    } else {
+        vec![]
+    };
+
+    let mut new_project = project.clone();
+    new_project.slots = get_sample_slots_without_unreferenced(&project.slots, &banks, &keep_banks);
+
+    let freed = |sample_type: ProjectSampleSlotType| {
+        project
+            .slots
+            .iter()
+            .filter(|x| x.sample_type == sample_type && !new_project.slots.contains(x))
+            .count()
+    };
+    println!(
+        "Freeing {}/{} unreferenced static/flex sample slots.",
+        freed(ProjectSampleSlotType::Static),
+        freed(ProjectSampleSlotType::Flex),
+    );
+
+    let mut tx = Transaction::new(
+        &get_backups_root_for_project(project_dirpath)?,
+        &format!("purge unreferenced sample slots in {project_dirpath:?}"),
+    );
+    if new_project.slots != project.slots {
+        tx.stage_type::<Project>(&new_project, &project_paths.filepath)?;
+        tx.record_project_slot_changes(&project_paths.filepath, &project.slots, &new_project.slots);
+    }
+
+    Ok(tx)
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use ot_tools_io::write_type_to_bin_file;
+    use std::path::PathBuf;
+
+    fn slot(sample_type: ProjectSampleSlotType, slot_id: u8, path: &str) -> ProjectSampleSlot {
+        ProjectSampleSlot::new(
+            sample_type,
+            slot_id,
+            PathBuf::from(path),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    mod renumber {
+        use super::*;
+
+        fn slot_ids(slots: &[ProjectSampleSlot]) -> Vec<(ProjectSampleSlotType, u8, PathBuf)> {
+            slots
+                .iter()
+                .map(|x| (x.sample_type.clone(), x.slot_id, x.path.clone()))
+                .sorted_by_key(|x| (x.0.clone() as u8, x.1))
+                .collect()
+        }
+
+        fn get_renumbered_sample_slots_and_updated_banks(
+            slots: &[ProjectSampleSlot],
+            banks: &[Bank],
+            order: SlotRenumberOrder,
+        ) -> RBoxErr<(Vec<ProjectSampleSlot>, Vec<Bank>)> {
+            let (new_slots, remaps) = get_renumbered_sample_slots(slots, banks, order);
+            Ok((new_slots, remap_bank_sample_slot_refs(banks, &remaps)?))
+        }
+
+        /// Copy of the blank test project with flex slots 3 and 7, saved (.strd) and working
+        /// (.work) state both refer to flex slot 7 from bank 1 part 1 track 1.
+        fn mock_project(name: &str, strd_slots: &[ProjectSampleSlot]) -> PathBuf {
+            let dirpath = std::env::temp_dir().join("ot-tools-slots").join(name);
+            let _ = std::fs::remove_dir_all(&dirpath);
+            std::fs::create_dir_all(dirpath.parent().unwrap()).unwrap();
+            copy_dir::copy_dir("../data/tests/blank-project", &dirpath).unwrap();
+
+            let mut project =
+                read_type_from_bin_file::<Project>(&dirpath.join("project.work")).unwrap();
+            project.slots.extend([
+                slot(ProjectSampleSlotType::Flex, 3, "a.wav"),
+                slot(ProjectSampleSlotType::Flex, 7, "b.wav"),
+            ]);
+            write_type_to_bin_file(&project, &dirpath.join("project.work")).unwrap();
+            project.slots.extend_from_slice(strd_slots);
+            write_type_to_bin_file(&project, &dirpath.join("project.strd")).unwrap();
+
+            let mut bank = read_type_from_bin_file::<Bank>(&dirpath.join("bank01.work")).unwrap();
+            bank.parts.unsaved[0].audio_track_machine_slots[0].flex_slot_id = 6;
+            write_type_to_bin_file(&bank, &dirpath.join("bank01.work")).unwrap();
+            write_type_to_bin_file(&bank, &dirpath.join("bank01.strd")).unwrap();
+            dirpath
+        }
+
+        #[test]
+        fn saved_state_renumbered() {
+            let dirpath = mock_project("saved_state_renumbered", &[]);
+            stage_cmd_slots_renumber(&dirpath, SlotRenumberOrder::SlotId)
+                .unwrap()
+                .commit()
+                .unwrap();
+
+            for fname in ["project.work", "project.strd"] {
+                let project = read_type_from_bin_file::<Project>(&dirpath.join(fname)).unwrap();
+                let flex = project
+                    .slots
+                    .iter()
+                    .filter(|x| x.sample_type == ProjectSampleSlotType::Flex)
+                    .map(|x| (x.slot_id, x.path.clone()))
+                    .sorted()
+                    .collect_vec();
+                assert_eq!(
+                    flex,
+                    [(1, PathBuf::from("a.wav")), (2, PathBuf::from("b.wav"))],
+                    "{fname}"
+                );
+            }
+            for fname in ["bank01.work", "bank01.strd"] {
+                let bank = read_type_from_bin_file::<Bank>(&dirpath.join(fname)).unwrap();
+                assert_eq!(
+                    bank.parts.unsaved[0].audio_track_machine_slots[0].flex_slot_id, 1,
+                    "{fname}"
+                );
+            }
+            let _ = std::fs::remove_dir_all(&dirpath);
+        }
+
+        #[test]
+        fn saved_state_differs_err() {
+            let dirpath = mock_project(
+                "saved_state_differs_err",
+                &[slot(ProjectSampleSlotType::Flex, 9, "c.wav")],
+            );
+            let r = stage_cmd_slots_renumber(&dirpath, SlotRenumberOrder::SlotId);
+            assert!(r.is_err());
+            let _ = std::fs::remove_dir_all(&dirpath);
+        }
+
+        #[test]
+        fn slot_id_order_removes_gaps() {
+            let slots = vec![
+                slot(ProjectSampleSlotType::Static, 3, "a.wav"),
+                slot(ProjectSampleSlotType::Static, 10, "b.wav"),
+                slot(ProjectSampleSlotType::Flex, 128, "c.wav"),
+            ];
+            let mut bank = Bank::default();
+            bank.patterns[0].audio_track_trigs[0].plocks.0[0].static_slot_id = 9;
+            bank.parts.saved[1].audio_track_machine_slots[2].static_slot_id = 2;
+            bank.parts.unsaved[0].audio_track_machine_slots[0].flex_slot_id = 127;
+
+            let (new_slots, new_banks) = get_renumbered_sample_slots_and_updated_banks(
+                &slots,
+                &[bank],
+                SlotRenumberOrder::SlotId,
+            )
+            .unwrap();
+
+            assert_eq!(
+                slot_ids(&new_slots),
+                vec![
+                    (ProjectSampleSlotType::Static, 1, PathBuf::from("a.wav")),
+                    (ProjectSampleSlotType::Static, 2, PathBuf::from("b.wav")),
+                    (ProjectSampleSlotType::Flex, 1, PathBuf::from("c.wav")),
+                ]
+            );
+            let bank = &new_banks[0];
+            assert_eq!(
+                bank.patterns[0].audio_track_trigs[0].plocks.0[0].static_slot_id,
+                1
+            );
+            assert_eq!(
+                bank.parts.saved[1].audio_track_machine_slots[2].static_slot_id,
+                0
+            );
+            assert_eq!(
+                bank.parts.unsaved[0].audio_track_machine_slots[0].flex_slot_id,
+                0
+            );
+        }
+
+        #[test]
+        fn default_part_assignments_stay_empty() {
+            let slots = vec![slot(ProjectSampleSlotType::Static, 5, "a.wav")];
+            let (new_slots, new_banks) = get_renumbered_sample_slots_and_updated_banks(
+                &slots,
+                &[Bank::default()],
+                SlotRenumberOrder::SlotId,
+            )
+            .unwrap();
+            assert_eq!(
+                slot_ids(&new_slots),
+                vec![(ProjectSampleSlotType::Static, 1, PathBuf::from("a.wav"))]
+            );
+
+            // default parts assign slots 1-8 to tracks 1-8, only track 5 had a sample loaded
+            for part in new_banks[0]
+                .parts
+                .unsaved
+                .iter()
+                .chain(new_banks[0].parts.saved.iter())
+            {
+                let static_ids = part
+                    .audio_track_machine_slots
+                    .iter()
+                    .map(|x| x.static_slot_id)
+                    .collect_vec();
+                assert_eq!(static_ids, vec![1, 2, 3, 4, 0, 5, 6, 7]);
+            }
+        }
+
+        #[test]
+        fn path_order_swaps_refs() {
+            let slots = vec![
+                slot(ProjectSampleSlotType::Static, 1, "b.wav"),
+                slot(ProjectSampleSlotType::Static, 2, "a.wav"),
+            ];
+            let mut bank = Bank::default();
+            bank.patterns[0].audio_track_trigs[0].plocks.0[0].static_slot_id = 0;
+            bank.patterns[0].audio_track_trigs[0].plocks.0[1].static_slot_id = 1;
+
+            let (new_slots, new_banks) = get_renumbered_sample_slots_and_updated_banks(
+                &slots,
+                &[bank],
+                SlotRenumberOrder::Path,
+            )
+            .unwrap();
+
+            assert_eq!(
+                slot_ids(&new_slots),
+                vec![
+                    (ProjectSampleSlotType::Static, 1, PathBuf::from("a.wav")),
+                    (ProjectSampleSlotType::Static, 2, PathBuf::from("b.wav")),
+                ]
+            );
+            let plocks = &new_banks[0].patterns[0].audio_track_trigs[0].plocks.0;
+            assert_eq!(plocks[0].static_slot_id, 1);
+            assert_eq!(plocks[1].static_slot_id, 0);
+        }
+
+        #[test]
+        fn usage_order_most_used_first() {
+            let slots = vec![
+                slot(ProjectSampleSlotType::Flex, 5, "rare.wav"),
+                slot(ProjectSampleSlotType::Flex, 6, "common.wav"),
+            ];
+            let mut bank = Bank::default();
+            for plock in bank.patterns[0].audio_track_trigs[0].plocks.0[0..3].iter_mut() {
+                plock.flex_slot_id = 5;
+            }
+
+            let (new_slots, _) = get_renumbered_sample_slots_and_updated_banks(
+                &slots,
+                &[bank],
+                SlotRenumberOrder::Usage,
+            )
+            .unwrap();
+
+            assert_eq!(
+                slot_ids(&new_slots),
+                vec![
+                    (ProjectSampleSlotType::Flex, 1, PathBuf::from("common.wav")),
+                    (ProjectSampleSlotType::Flex, 2, PathBuf::from("rare.wav")),
+                ]
+            );
+        }
+
+        #[test]
+        fn recorder_buffers_unchanged() {
+            let slots = Project::default().slots;
+            let (new_slots, _) = get_renumbered_sample_slots_and_updated_banks(
+                &slots,
+                &[Bank::default()],
+                SlotRenumberOrder::SlotId,
+            )
+            .unwrap();
+            assert_eq!(new_slots, slots);
+        }
+    }
+
+    mod purge {
+        use super::*;
+
+        #[test]
+        fn removes_unreferenced_slots() {
+            let slots = vec![
+                slot(ProjectSampleSlotType::Static, 1, "plock.wav"),
+                slot(ProjectSampleSlotType::Static, 20, "unused.wav"),
+                slot(ProjectSampleSlotType::Flex, 1, "machine.wav"),
+                slot(ProjectSampleSlotType::Flex, 30, "strd.wav"),
+            ];
+            let bank = Bank::default();
+            let mut strd_bank = Bank::default();
+            strd_bank.patterns[0].audio_track_trigs[0].plocks.0[0].flex_slot_id = 29;
+
+            let kept =
+                get_sample_slots_without_unreferenced(&slots, std::slice::from_ref(&bank), &[]);
+            // default parts assign slot 1 to track 1
+            assert_eq!(kept, vec![slots[0].clone(), slots[2].clone()]);
+
+            let kept = get_sample_slots_without_unreferenced(&slots, &[bank], &[strd_bank]);
+            assert_eq!(
+                kept,
+                vec![slots[0].clone(), slots[2].clone(), slots[3].clone()]
+            );
+        }
+
+        #[test]
+        fn recorder_buffers_kept() {
+            let slots = Project::default().slots;
+            let kept = get_sample_slots_without_unreferenced(&slots, &[], &[]);
+            assert_eq!(kept, slots);
+        }
+    }
 }
@@ -0,0 +1,490 @@
+//! Project level reports on where sample slots are used.
+
+use crate::actions::projects::slots::load_work_banks_for_project;
+use crate::{validate_project_version_readable, OctatoolErrors, RBoxErr};
+use itertools::Itertools;
+use ot_tools_io::{
+    banks::Bank,
+    projects::{options::ProjectSampleSlotType, Project},
+    read_type_from_bin_file,
+};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Audio track machine type values (`Part.audio_track_machine_types`) which use a sample slot
+const STATIC_MACHINE_TYPE: u8 = 0;
+const FLEX_MACHINE_TYPE: u8 = 1;
+
+/// Where a sample slot is referenced in bank data. All IDs are 1-indexed.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SlotReferenceLocation {
+    /// Sample slot assigned to an audio track machine in a part
+    Machine {
+        bank_id: usize,
+        part_id: usize,
+        saved: bool,
+        track_id: usize,
+        /// Whether the track's machine type actually plays this slot type
+        /// (e.g. a static slot assignment on a flex machine track is not played).
+        machine_selected: bool,
+        /// Whether the assignment is the same as in a default part, which assigns slots 1-8 to
+        /// tracks 1-8. Doesn't mean the assignment is unused, only that it hasn't been changed.
+        default_assignment: bool,
+    },
+    /// Sample slot p-locked on a pattern step
+    PLock {
+        bank_id: usize,
+        pattern_id: usize,
+        track_id: usize,
+        step: usize,
+    },
+}
+
+impl std::fmt::Display for SlotReferenceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Machine {
+                bank_id,
+                part_id,
+                saved,
+                track_id,
+                machine_selected,
+                default_assignment,
+            } => write!(
+                f,
+                "bank {bank_id:>2} part {part_id} ({}) track {track_id} machine{}{}",
+                if *saved { "saved" } else { "unsaved" },
+                if *machine_selected {
+                    ""
+                } else {
+                    " (not selected)"
+                },
+                if *default_assignment {
+                    " (matches default part)"
+                } else {
+                    ""
+                },
+            ),
+            Self::PLock {
+                bank_id,
+                pattern_id,
+                track_id,
+                step,
+            } => write!(
+                f,
+                "bank {bank_id:>2} pattern {pattern_id:>2} track {track_id} step {step:>2} p-lock"
+            ),
+        }
+    }
+}
+
+/// A project sample slot. Slot IDs are 1-indexed.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SlotId {
+    pub sample_type: ProjectSampleSlotType,
+    pub slot_id: u8,
+}
+
+/// A reference to a sample slot from bank data. Slot IDs are 1-indexed.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SlotReference {
+    pub sample_type: ProjectSampleSlotType,
+    pub slot_id: u8,
+    pub location: SlotReferenceLocation,
+}
+
+/// A loaded project sample slot and everything in the project's bank data which refers to it.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SlotUsage {
+    pub sample_type: ProjectSampleSlotType,
+    pub slot_id: u8,
+    pub path: PathBuf,
+    pub file_exists: bool,
+    pub references: Vec<SlotReferenceLocation>,
+}
+
+/// Usage of all static and flex sample slots in a project.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SlotUsageReport {
+    pub slots: Vec<SlotUsage>,
+    /// References to sample slots which have no sample loaded. Machine assignments which match
+    /// a default part are left out, as every project has lots of them.
+    pub orphaned_references: Vec<SlotReference>,
+    /// Slots with a sample loaded which nothing in the bank data refers to (see
+    /// `referenced_slot_ids`). These are the slots `slots purge` frees.
+    pub unreferenced_slots: Vec<SlotId>,
+}
+
+/// Find every sample slot reference in a list of banks (bank IDs are the 1-indexed list position).
+/// Disabled p-locks are skipped.
+pub(crate) fn find_all_slot_references(banks: &[Bank]) -> Vec<SlotReference> {
+    let mut refs = vec![];
+
+    for (bank_id, bank) in (1..).zip(banks) {
+        for (saved, parts) in [(false, &bank.parts.unsaved), (true, &bank.parts.saved)] {
+            for (part_id, part) in (1..).zip(parts.iter()) {
+                for (track_idx, machine) in part.audio_track_machine_slots.iter().enumerate() {
+                    let machine_type = part.audio_track_machine_types[track_idx];
+                    for (sample_type, slot_id, selected_type) in [
+                        (
+                            ProjectSampleSlotType::Static,
+                            machine.static_slot_id,
+                            STATIC_MACHINE_TYPE,
+                        ),
+                        (
+                            ProjectSampleSlotType::Flex,
+                            machine.flex_slot_id,
+                            FLEX_MACHINE_TYPE,
+                        ),
+                    ] {
+                        if slot_id == 255 {
+                            continue;
+                        }
+                        refs.push(SlotReference {
+                            sample_type,
+                            slot_id: slot_id + 1,
+                            location: SlotReferenceLocation::Machine {
+                                bank_id,
+                                part_id,
+                                saved,
+                                track_id: track_idx + 1,
+                                machine_selected: machine_type == selected_type,
+                                default_assignment: slot_id as usize == track_idx,
+                            },
+                        });
+                    }
+                }
+            }
+        }
+
+        for (pattern_id, pattern) in (1..).zip(bank.patterns.iter()) {
+            for (track_id, trigs) in (1..).zip(pattern.audio_track_trigs.iter()) {
+                for (step, plock) in (1..).zip(trigs.plocks.iter()) {
+                    for (sample_type, slot_id) in [
+                        (ProjectSampleSlotType::Static, plock.static_slot_id),
+                        (ProjectSampleSlotType::Flex, plock.flex_slot_id),
+                    ] {
+                        // plock slot reference is disabled
+                        if slot_id == 255 {
+                            continue;
+                        }
+                        refs.push(SlotReference {
+                            sample_type,
+                            slot_id: slot_id + 1,
+                            location: SlotReferenceLocation::PLock {
+                                bank_id,
+                                pattern_id,
+                                track_id,
+                                step,
+                            },
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    refs
+}
+
+/// Sample slots (type, 1-indexed ID) referenced by a list of banks. Every part machine assignment
+/// and p-lock counts, whether or not the track's machine type currently plays the slot, as
+/// changing the machine type on the device would start using it.
+pub(crate) fn referenced_slot_ids(banks: &[Bank]) -> HashSet<(ProjectSampleSlotType, u8)> {
+    find_all_slot_references(banks)
+        .into_iter()
+        .map(|x| (x.sample_type, x.slot_id))
+        .collect()
+}
+
+/// Build a sample slot usage report for a project's sample slots and bank data.
+/// Sample file paths are resolved relative to `project_dirpath`.
+pub fn build_slot_usage_report(
+    project_dirpath: &Path,
+    project: &Project,
+    banks: &[Bank],
+) -> SlotUsageReport {
+    let refs = find_all_slot_references(banks);
+    let referenced = referenced_slot_ids(banks);
+    let is_slot = |sample_type: &ProjectSampleSlotType, slot_id: u8| {
+        // flex slots 129-136 are the recorder buffers, which always exist
+        (*sample_type == ProjectSampleSlotType::Flex && slot_id > 128)
+            || project
+                .slots
+                .iter()
+                .any(|s| s.sample_type == *sample_type && s.slot_id == slot_id)
+    };
+
+    let slots = project
+        .slots
+        .iter()
+        .filter(|s| s.sample_type != ProjectSampleSlotType::RecorderBuffer)
+        .sorted_by_key(|s| (s.sample_type.clone() as u8, s.slot_id))
+        .map(|s| SlotUsage {
+            sample_type: s.sample_type.clone(),
+            slot_id: s.slot_id,
+            path: s.path.clone(),
+            file_exists: project_dirpath.join(&s.path).is_file(),
+            references: refs
+                .iter()
+                .filter(|r| r.sample_type == s.sample_type && r.slot_id == s.slot_id)
+                .map(|r| r.location.clone())
+                .collect(),
+        })
+        .collect_vec();
+
+    let orphaned_references = refs
+        .iter()
+        .filter(|r| {
+            let default_assignment = matches!(
+                r.location,
+                SlotReferenceLocation::Machine {
+                    default_assignment: true,
+                    ..
+                }
+            );
+            !default_assignment && !is_slot(&r.sample_type, r.slot_id)
+        })
+        .cloned()
+        .collect_vec();
+
+    let unreferenced_slots = slots
+        .iter()
+        .filter(|s| !referenced.contains(&(s.sample_type.clone(), s.slot_id)))
+        .map(|s| SlotId {
+            sample_type: s.sample_type.clone(),
+            slot_id: s.slot_id,
+        })
+        .collect_vec();
+
+    SlotUsageReport {
+        slots,
+        orphaned_references,
+        unreferenced_slots,
+    }
+}
+
+/// Load a project's `project.work` and `bankXX.work` files and build a sample slot usage report.
+pub fn get_project_slot_usage_report(project_dirpath: &Path) -> RBoxErr<SlotUsageReport> {
+    let project = read_type_from_bin_file::<Project>(&project_dirpath.join("project.work"))?;
+
+    if !validate_project_version_readable(&project) {
+        return Err(OctatoolErrors::UnsupportedOsVersion.into());
+    };
+
+    let banks = load_work_banks_for_project(project_dirpath)?;
+    Ok(build_slot_usage_report(project_dirpath, &project, &banks))
+}
+
+/// Print a project's sample slot usage report as a table, or as JSON.
+pub fn print_project_slot_usage_report(project_dirpath: &Path, json: bool) -> RBoxErr<()> {
+    let report = get_project_slot_usage_report(project_dirpath)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<8} {:>4} {:>6} {:>5}  PATH",
+        "TYPE", "SLOT", "EXISTS", "REFS"
+    );
+    for slot in &report.slots {
+        println!(
+            "{:<8} {:>4} {:>6} {:>5}  {}",
+            format!("{:?}", slot.sample_type),
+            slot.slot_id,
+            if slot.file_exists { "yes" } else { "NO" },
+            slot.references.len(),
+            slot.path.display(),
+        );
+        for location in &slot.references {
+            println!("{:>27}{location}", "");
+        }
+    }
+
+    println!();
+    println!(
+        "Orphaned references to empty slots: {}",
+        report.orphaned_references.len()
+    );
+    for r in &report.orphaned_references {
+        println!("    {:?} slot {}: {}", r.sample_type, r.slot_id, r.location);
+    }
+
+    println!("Unreferenced slots: {}", report.unreferenced_slots.len());
+    for r in &report.unreferenced_slots {
+        println!("    {:?} slot {}", r.sample_type, r.slot_id);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use crate::actions::projects::slots::get_sample_slots_without_unreferenced;
+    use ot_tools_io::projects::slots::ProjectSampleSlot;
+
+    fn project_with_slots(slots: &[(ProjectSampleSlotType, u8, &str)]) -> Project {
+        let mut project = Project::default();
+        for (sample_type, slot_id, path) in slots {
+            project.slots.push(
+                ProjectSampleSlot::new(
+                    sample_type.clone(),
+                    *slot_id,
+                    PathBuf::from(path),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap(),
+            );
+        }
+        project
+    }
+
+    #[test]
+    fn default_project_has_no_orphans() {
+        let banks: Vec<Bank> = (0..16).map(|_| Bank::default()).collect();
+        let report = build_slot_usage_report(Path::new("."), &Project::default(), &banks);
+        assert_eq!(report.slots, vec![]);
+        assert_eq!(report.orphaned_references, vec![]);
+        assert_eq!(report.unreferenced_slots, vec![]);
+    }
+
+    #[test]
+    fn references_orphans_and_unreferenced() {
+        let project = project_with_slots(&[
+            (ProjectSampleSlotType::Static, 1, "used.wav"),
+            (ProjectSampleSlotType::Flex, 20, "unused.wav"),
+        ]);
+        let mut bank = Bank::default();
+        bank.patterns[2].audio_track_trigs[1].plocks.0[5].static_slot_id = 0;
+        bank.patterns[0].audio_track_trigs[0].plocks.0[0].flex_slot_id = 9;
+
+        let report = build_slot_usage_report(Path::new("."), &project, &[bank]);
+
+        let static_slot = &report.slots[0];
+        assert_eq!(static_slot.slot_id, 1);
+        assert!(!static_slot.file_exists);
+        assert!(static_slot
+            .references
+            .contains(&SlotReferenceLocation::PLock {
+                bank_id: 1,
+                pattern_id: 3,
+                track_id: 2,
+                step: 6,
+            }));
+        assert_eq!(
+            report.orphaned_references,
+            vec![SlotReference {
+                sample_type: ProjectSampleSlotType::Flex,
+                slot_id: 10,
+                location: SlotReferenceLocation::PLock {
+                    bank_id: 1,
+                    pattern_id: 1,
+                    track_id: 1,
+                    step: 1,
+                },
+            }]
+        );
+        assert_eq!(
+            report.unreferenced_slots,
+            vec![SlotId {
+                sample_type: ProjectSampleSlotType::Flex,
+                slot_id: 20,
+            }]
+        );
+    }
+
+    #[test]
+    fn machine_assignments_are_references() {
+        let project = project_with_slots(&[
+            (ProjectSampleSlotType::Static, 1, "default.wav"),
+            (ProjectSampleSlotType::Static, 2, "plock.wav"),
+            (ProjectSampleSlotType::Static, 12, "machine.wav"),
+            (ProjectSampleSlotType::Static, 20, "unreferenced.wav"),
+            (ProjectSampleSlotType::Flex, 1, "not_selected.wav"),
+        ]);
+        let mut bank = Bank::default();
+        bank.parts.unsaved[0].audio_track_machine_slots[3].static_slot_id = 11;
+        bank.patterns[0].audio_track_trigs[0].plocks.0[0].static_slot_id = 1;
+
+        let report = build_slot_usage_report(Path::new("."), &project, &[bank]);
+
+        // slot 1 loaded on track 1 is how most projects are set up, so it is referenced
+        assert!(report.slots[0]
+            .references
+            .contains(&SlotReferenceLocation::Machine {
+                bank_id: 1,
+                part_id: 1,
+                saved: false,
+                track_id: 1,
+                machine_selected: true,
+                default_assignment: true,
+            }));
+        assert!(report.slots[2]
+            .references
+            .contains(&SlotReferenceLocation::Machine {
+                bank_id: 1,
+                part_id: 1,
+                saved: false,
+                track_id: 4,
+                machine_selected: true,
+                default_assignment: false,
+            }));
+        assert_eq!(
+            report.unreferenced_slots,
+            vec![SlotId {
+                sample_type: ProjectSampleSlotType::Static,
+                slot_id: 20,
+            }]
+        );
+        assert_eq!(report.orphaned_references, vec![]);
+    }
+
+    #[test]
+    fn unreferenced_slots_match_purge() {
+        let project = project_with_slots(&[
+            (ProjectSampleSlotType::Static, 1, "default.wav"),
+            (ProjectSampleSlotType::Static, 20, "unreferenced.wav"),
+            (ProjectSampleSlotType::Flex, 3, "default.wav"),
+            (ProjectSampleSlotType::Flex, 30, "unreferenced.wav"),
+        ]);
+        let banks = vec![Bank::default()];
+
+        let report = build_slot_usage_report(Path::new("."), &project, &banks);
+        let kept = get_sample_slots_without_unreferenced(&project.slots, &banks, &[]);
+        let purged = project
+            .slots
+            .iter()
+            .filter(|s| !kept.contains(s))
+            .map(|s| SlotId {
+                sample_type: s.sample_type.clone(),
+                slot_id: s.slot_id,
+            })
+            .collect_vec();
+
+        assert_eq!(report.unreferenced_slots, purged);
+        assert_eq!(
+            report.unreferenced_slots,
+            vec![
+                SlotId {
+                    sample_type: ProjectSampleSlotType::Static,
+                    slot_id: 20,
+                },
+                SlotId {
+                    sample_type: ProjectSampleSlotType::Flex,
+                    slot_id: 30,
+                },
+            ]
+        );
+    }
+}
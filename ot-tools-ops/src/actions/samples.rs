@@ -3,7 +3,7 @@
 #[cfg(test)]
 #[allow(unused_imports)]
 mod test;
-mod yaml;
+pub(crate) mod yaml;
 
 use log::trace;
 use ot_tools_io::samples::{
@@ -16,8 +16,20 @@ use ot_tools_io::samples::{
 };
 
 use crate::{
-    audio::wav::{WavFile, ALLOWED_BIT_DEPTHS, ALLOWED_SAMPLE_RATE},
-    utils::{get_bin_nbars_ileaved_wavfiles, get_otsample_nbars_from_wavfile},
+    audio::{
+        fades::FadeCurve,
+        file::{AudioFile, FileFormat},
+        loudness::{LoudnessMode, LoudnessStats},
+        onsets::OnsetOpts,
+        tempo::TempoEstimate,
+        wav::{WavFile, ALLOWED_BIT_DEPTHS, ALLOWED_SAMPLE_RATE},
+        zero_crossings::{snap_position, SnapMode},
+        AudioErrors,
+    },
+    utils::{
+        get_bin_nbars_ileaved_audio_files, get_otsample_nbars_from_audio_file,
+        get_otsample_nbars_from_len,
+    },
     RBoxErr,
 };
 use itertools::Itertools;
@@ -25,9 +37,11 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::array::from_fn;
 use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
 
 use ot_tools_io::{
-    read_type_from_bin_file, type_to_yaml_file, write_type_to_bin_file, yaml_file_to_type,
+    projects::settings::tempo::check_tempo, read_type_from_bin_file, type_to_yaml_file,
+    write_type_to_bin_file, yaml_file_to_type,
 };
 use yaml::{
     create::YamlChainCreate,
@@ -48,6 +62,13 @@ enum CliSampleErrors {
     TooManySlices,
     AudioTooShort,
     NotADirectory,
+    InvalidNoteDivision(String),
+    SliceLongerThanPadding(PathBuf),
+    TempoNotDetected(PathBuf),
+    InvalidFadeLength,
+    OutputDirInInputDir(PathBuf),
+    OutputFileExists(PathBuf),
+    OutputFileCollision(PathBuf),
 }
 impl std::fmt::Display for CliSampleErrors {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -64,6 +85,30 @@ impl std::fmt::Display for CliSampleErrors {
             Self::NotADirectory => {
                 write!(f, "provided path does not point to an existing directory")
             }
+            Self::InvalidNoteDivision(x) => write!(
+                f,
+                "invalid note division, expected e.g. '1/16', '1/8T' (triplet) or '1/4D' (dotted): {x}"
+            ),
+            Self::SliceLongerThanPadding(x) => write!(
+                f,
+                "audio file is longer than the padded slice length, use a longer padding: path={x:#?}"
+            ),
+            Self::TempoNotDetected(x) => write!(
+                f,
+                "could not detect the tempo of an audio file, set the source tempo: path={x:#?}"
+            ),
+            Self::InvalidFadeLength => write!(f, "invalid fade length, must not be negative"),
+            Self::OutputDirInInputDir(x) => write!(
+                f,
+                "output directory must not be the input directory or within it: path={x:#?}"
+            ),
+            Self::OutputFileExists(x) => {
+                write!(f, "output file already exists, not overwriting: path={x:#?}")
+            }
+            Self::OutputFileCollision(x) => write!(
+                f,
+                "more than one input file would be written to the same output file: path={x:#?}"
+            ),
         }
     }
 }
@@ -80,6 +125,19 @@ impl std::error::Error for CliSampleErrors {
             CliSampleErrors::NotADirectory => {
                 "provided path does not point to an existing directory"
             }
+            CliSampleErrors::InvalidNoteDivision(_) => "invalid note division",
+            CliSampleErrors::SliceLongerThanPadding(_) => {
+                "audio file is longer than the padded slice length"
+            }
+            CliSampleErrors::TempoNotDetected(_) => "could not detect the tempo of an audio file",
+            CliSampleErrors::InvalidFadeLength => "invalid fade length, must not be negative",
+            CliSampleErrors::OutputDirInInputDir(_) => {
+                "output directory must not be the input directory or within it"
+            }
+            CliSampleErrors::OutputFileExists(_) => "output file already exists",
+            CliSampleErrors::OutputFileCollision(_) => {
+                "more than one input file would be written to the same output file"
+            }
         }
     }
 
@@ -89,33 +147,67 @@ impl std::error::Error for CliSampleErrors {
             CliSampleErrors::TooManySlices => None,
             CliSampleErrors::AudioTooShort => None,
             CliSampleErrors::NotADirectory => None,
+            CliSampleErrors::InvalidNoteDivision(_) => None,
+            CliSampleErrors::SliceLongerThanPadding(_) => None,
+            CliSampleErrors::TempoNotDetected(_) => None,
+            CliSampleErrors::InvalidFadeLength => None,
+            CliSampleErrors::OutputDirInInputDir(_) => None,
+            CliSampleErrors::OutputFileExists(_) => None,
+            CliSampleErrors::OutputFileCollision(_) => None,
+        }
+    }
+}
+
+/// Tempo used for sample attributes files when the tempo of the audio can't be detected.
+pub const DEFAULT_TEMPO_BPM: f32 = 120.0;
+
+/// Minimum confidence of a detected tempo before it is used in sample attributes files.
+pub const DEFAULT_MIN_TEMPO_CONFIDENCE: f32 = 0.4;
+
+/// Tempo to use in a sample attributes file for some audio, the detected tempo if it was
+/// detected confidently, otherwise the default tempo.
+fn detected_tempo_or_default(audio: &AudioFile) -> f32 {
+    match audio.estimate_tempo() {
+        Some(x) if x.confidence >= DEFAULT_MIN_TEMPO_CONFIDENCE => {
+            trace!("Using detected tempo: {x:?} path={:#?}", audio.file_path);
+            x.bpm
+        }
+        x => {
+            trace!("Using default tempo: {x:?} path={:#?}", audio.file_path);
+            DEFAULT_TEMPO_BPM
         }
     }
 }
 
 // todo: tests
-// TODO: maybe options for setting stuff like tempo to non-default? change to a
+// TODO: maybe options for setting stuff like stretch mode to non-default? change to a
 //       "new" command?
-/// Create a default OctaTrack sample attributes file for some wav file
-pub fn create_default_ot_file_for_wav_file(path: &Path) -> RBoxErr<()> {
+/// Create a default OctaTrack sample attributes file for some WAV/AIFF file. The tempo is
+/// detected from the audio when possible.
+///
+/// The attributes file is written in the binary `.ot` format the Octatrack loads (the old
+/// `create_default_ot_file_for_wav_file` wrote YAML to the `.ot` file path).
+pub fn create_default_ot_file_for_audio_file(path: &Path) -> RBoxErr<()> {
     let mut ot_path = path.to_path_buf();
     ot_path.set_extension("ot");
 
-    let wavfile = WavFile::from_path(path)?;
+    let wavfile = AudioFile::from_path(path)?;
+    let tempo = detected_tempo_or_default(&wavfile);
+    let bars = get_otsample_nbars_from_audio_file(&wavfile, &tempo)?;
 
     let ot_data = SampleAttributes::new(
-        &120.0,
+        &tempo,
         &SampleAttributeTimestrechMode::default(),
         &SampleAttributeTrigQuantizationMode::default(),
         &0.0,
         &SampleTrimConfig {
             start: 0,
             end: wavfile.len,
-            length: wavfile.len,
+            length: bars,
         },
         &SampleLoopConfig {
             start: 0,
-            length: wavfile.len,
+            length: bars,
             mode: SampleAttributeLoopMode::default(),
         },
         &Slices {
@@ -128,20 +220,36 @@ pub fn create_default_ot_file_for_wav_file(path: &Path) -> RBoxErr<()> {
         },
     );
 
-    type_to_yaml_file::<SampleAttributes>(&ot_data?, &ot_path)?;
+    write_type_to_bin_file::<SampleAttributes>(&ot_data?, &ot_path)?;
 
     Ok(())
 }
 
 // todo: test
-/// Create Nx default OctaTrack sample attributes file for Nx wav files
-pub fn create_default_ot_files_for_wav_files(paths: &[PathBuf]) -> RBoxErr<()> {
+/// Create Nx default OctaTrack sample attributes file for Nx WAV/AIFF files
+pub fn create_default_ot_files_for_audio_files(paths: &[PathBuf]) -> RBoxErr<()> {
     for path in paths {
-        create_default_ot_file_for_wav_file(path)?;
+        create_default_ot_file_for_audio_file(path)?;
     }
     Ok(())
 }
 
+/// Create a default OctaTrack sample attributes file for some WAV file.
+#[deprecated(
+    note = "use `create_default_ot_file_for_audio_file`, which writes a binary `.ot` file instead of YAML"
+)]
+pub fn create_default_ot_file_for_wav_file(path: &Path) -> RBoxErr<()> {
+    create_default_ot_file_for_audio_file(path)
+}
+
+/// Create Nx default OctaTrack sample attributes file for Nx WAV files
+#[deprecated(
+    note = "use `create_default_ot_files_for_audio_files`, which writes binary `.ot` files instead of YAML"
+)]
+pub fn create_default_ot_files_for_wav_files(paths: &[PathBuf]) -> RBoxErr<()> {
+    create_default_ot_files_for_audio_files(paths)
+}
+
 /// Create Octatrack samplechain file-pairs from a loaded yaml config.
 pub fn create_samplechains_from_yaml(yaml_conf_fpath: &Path) -> RBoxErr<()> {
     let chain_conf = yaml_file_to_type::<YamlChainCreate>(yaml_conf_fpath)?;
@@ -194,37 +302,293 @@ pub struct SampleChainOpts {
 }
 
 /// Options that control audio processing of each slice in a sample chain
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct SliceProcOpts {
-    /// normalize individual slices
+    /// normalize individual slices (to their peak level)
     pub normalize: Option<bool>,
-    /// %-age length of linear fade in to apply to slices
+    /// normalize slices to a loudness (integrated LUFS or RMS level) instead of their peak
+    /// level, used instead of `normalize` when set
+    pub loudness: Option<SliceLoudnessOpts>,
+    /// %-age length of fade in to apply to slices
     pub fade_in_percent: Option<f32>,
-    /// %-age length of linear fade out to apply to slices
+    /// %-age length of fade out to apply to slices
     pub fade_out_percent: Option<f32>,
-    /// Resampled time stretch factor
+    /// length of fade in to apply to slices in milliseconds, used instead of
+    /// `fade_in_percent` when set
+    pub fade_in_ms: Option<f32>,
+    /// length of fade out to apply to slices in milliseconds, used instead of
+    /// `fade_out_percent` when set
+    pub fade_out_ms: Option<f32>,
+    /// shape of the fade in / fade out (default linear)
+    pub fade_curve: Option<FadeCurve>,
+    /// short fades at the start and end of every slice, so slices don't click when played
+    pub declick: Option<SliceDeclickOpts>,
+    /// Resampled time stretch factor (lossy and changes pitch, see `stretch`)
     pub time_stretch: Option<i8>,
+    /// time stretch slices without changing their pitch
+    pub stretch: Option<SliceStretchOpts>,
+    /// pitch shift slices by semitones without changing their length
+    pub pitch_semitones: Option<f32>,
+    /// pitch shift slices by cents (1/100th of a semitone), added to `pitch_semitones`
+    pub pitch_cents: Option<f32>,
+    /// move slice start/end points to zero crossings / quiet points within each slice
+    pub snap: Option<SliceSnapOpts>,
+    /// trim leading/trailing silence from slices (before any other processing)
+    pub trim_silence: Option<SilenceTrimOpts>,
+    /// pad slices with silence to this many sequencer steps (1/16 notes at the chain tempo)
+    pub pad_steps: Option<u32>,
+    /// pad slices with silence to a power of two number of sequencer steps (1, 2, 4, 8 ...),
+    /// ignored when `pad_steps` is set
+    pub pad_pow2_steps: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[non_exhaustive]
-pub enum FileFormat {
-    Wav,
-    // TODO
-    // Aiff
+/// Default length of declick fades, in milliseconds.
+pub const DEFAULT_DECLICK_MS: f32 = 2.0;
+
+/// Options for the short fades applied at the start and end of slices to remove clicks
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct SliceDeclickOpts {
+    /// length of the fades in milliseconds (default 2.0)
+    pub len_ms: Option<f32>,
+    /// shape of the fades (default S-curve)
+    pub curve: Option<FadeCurve>,
+}
+
+/// Default loudness slices are normalized to, in LUFS or dBFS (RMS).
+pub const DEFAULT_LOUDNESS_TARGET: f32 = -16.0;
+
+/// Default maximum true peak level when normalizing loudness, in dBTP.
+pub const DEFAULT_TRUE_PEAK_CEILING: f32 = -1.0;
+
+/// Options for normalizing slices to a loudness, so slices sound equally loud
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct SliceLoudnessOpts {
+    /// which level to normalize (default integrated loudness, LUFS)
+    #[serde(default)]
+    pub mode: LoudnessMode,
+    /// target level in LUFS or dBFS (RMS) (default -16.0)
+    pub target: Option<f32>,
+    /// the gain is reduced so the true peak level doesn't go above this, in dBTP (default -1.0)
+    pub true_peak_ceiling: Option<f32>,
+}
+
+/// Options for time stretching slices without changing their pitch, either to a tempo or by
+/// a fixed factor
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct SliceStretchOpts {
+    /// stretch slices to this tempo (e.g. the project's tempo), also used as the chain's
+    /// tempo unless set in the Octatrack settings
+    pub bpm: Option<f32>,
+    /// tempo of the audio files being stretched to `bpm` (detected from each audio file when
+    /// not set)
+    pub source_bpm: Option<f32>,
+    /// stretch slices by a fixed factor when `bpm` isn't set (2.0 = twice as long)
+    pub factor: Option<f32>,
+}
+
+/// Time stretch factor for a slice's audio, `None` when no stretching is set.
+fn slice_stretch_factor(audio: &AudioFile, opts: &SliceStretchOpts) -> RBoxErr<Option<f32>> {
+    let Some(bpm) = opts.bpm else {
+        return Ok(opts.factor);
+    };
+    let source_bpm = match opts.source_bpm {
+        Some(x) => x,
+        None => match audio.estimate_tempo() {
+            Some(x) if x.confidence >= DEFAULT_MIN_TEMPO_CONFIDENCE => x.bpm,
+            _ => return Err(CliSampleErrors::TempoNotDetected(audio.file_path.clone()).into()),
+        },
+    };
+    check_tempo(bpm)?;
+    check_tempo(source_bpm)?;
+    trace!(
+        "Stretching slice to tempo: from={source_bpm} to={bpm} path={:#?}",
+        audio.file_path
+    );
+    Ok(Some(source_bpm / bpm))
+}
+
+/// Default level below which audio counts as silence when trimming slices, in dBFS.
+pub const DEFAULT_SILENCE_THRESHOLD_DB: f32 = -60.0;
+
+/// Default length of audio kept before the first sound when trimming slices, in milliseconds.
+pub const DEFAULT_SILENCE_PREROLL_MS: f32 = 5.0;
+
+/// Options for trimming leading/trailing silence from slices
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct SilenceTrimOpts {
+    /// level below which audio counts as silence in dBFS (default -60.0)
+    pub threshold_db: Option<f32>,
+    /// how much audio to keep before the first sound in milliseconds, so attacks aren't cut
+    /// off (default 5ms)
+    pub preroll_ms: Option<f32>,
+}
+
+/// Length of a fade, in milliseconds or as a percentage of the slice's length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FadeLen {
+    Ms(f32),
+    Percent(f32),
+}
+
+impl FadeLen {
+    /// Length of the fade in samples (frames) for a slice's audio.
+    fn to_len(self, audio: &AudioFile) -> usize {
+        match self {
+            Self::Ms(x) => audio.ms_to_len(x),
+            Self::Percent(x) => {
+                ((audio.samples.len() / audio.channels as usize) as f32 * x) as usize
+            }
+        }
+    }
+}
+
+/// Length of a slice fade, milliseconds are used instead of percentages when both are set.
+/// `None` when no fade is set.
+fn slice_fade_len(ms: Option<f32>, percent: Option<f32>) -> RBoxErr<Option<FadeLen>> {
+    match (ms, percent) {
+        (Some(x), _) if x < 0.0 => Err(CliSampleErrors::InvalidFadeLength.into()),
+        (Some(x), _) => Ok(Some(FadeLen::Ms(x))),
+        (None, Some(x)) if !(0.0..=1.0).contains(&x) => Err(AudioErrors::FadePercentageOOB.into()),
+        (None, Some(x)) => Ok(Some(FadeLen::Percent(x))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Length (in samples) to pad a slice's audio to, a whole number of sequencer steps (1/16
+/// notes) at the chain's tempo so slices line up with the sequencer. `None` when no padding
+/// is set.
+fn padded_slice_len(audio: &AudioFile, bpm: f32, opts: &SliceProcOpts) -> RBoxErr<Option<u32>> {
+    let step_len = audio.sample_rate as f64 * 60.0 / bpm as f64 / 4.0;
+    let steps = match (opts.pad_steps, opts.pad_pow2_steps) {
+        (Some(steps), _) => steps,
+        (None, Some(true)) => ((audio.len as f64 / step_len).ceil() as u32)
+            .max(1)
+            .next_power_of_two(),
+        _ => return Ok(None),
+    };
+
+    let len = (steps as f64 * step_len).ceil() as u32;
+    if len < audio.len {
+        return Err(CliSampleErrors::SliceLongerThanPadding(audio.file_path.clone()).into());
+    }
+    trace!("Padded slice length: steps={steps} len={len}");
+    Ok(Some(len))
+}
+
+/// Default distance slice points can be moved when snapping, in milliseconds.
+pub const DEFAULT_SNAP_WINDOW_MS: f32 = 10.0;
+
+/// Options for moving slice start/end points to nearby zero crossings or quiet points, so
+/// slices don't click when played
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct SliceSnapOpts {
+    /// how to choose the point slice points are moved to
+    #[serde(default)]
+    pub mode: SnapMode,
+    /// how far slice points can be moved in milliseconds (default 10ms)
+    pub window_ms: Option<f32>,
+}
+
+/// Which directions slice points can be moved when snapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SnapRange {
+    /// either direction, for slices of continuous audio
+    Nearest,
+    /// only backwards, so slices still start before an onset
+    Before,
+    /// only towards the middle of each slice, for slices of separate audio files (chains)
+    WithinSlice,
+}
+
+/// Move the start/end points of slices to nearby zero crossings or quiet points. Slice points at
+/// the start/end of the audio are only moved for `SnapRange::WithinSlice`.
+fn snap_slices(slices: &mut Slices, audio: &AudioFile, opts: &SliceSnapOpts, range: SnapRange) {
+    let active = &mut slices.slices[..slices.count as usize];
+
+    // never move slice points more than half of the shortest slice, so slices can't swap
+    // places or become empty (and slices sharing a point keep sharing it)
+    let window_ms = opts.window_ms.unwrap_or(DEFAULT_SNAP_WINDOW_MS).max(0.0);
+    let window = active
+        .iter()
+        .filter(|x| x.trim_end > x.trim_start)
+        .map(|x| (x.trim_end - x.trim_start - 1) / 2)
+        .min()
+        .unwrap_or(0)
+        .min((window_ms / 1000.0 * audio.sample_rate as f32) as u32);
+
+    let snap = |pos: u32, before: u32, after: u32| {
+        snap_position(
+            &audio.samples,
+            audio.channels,
+            pos,
+            before,
+            after,
+            opts.mode,
+        )
+    };
+
+    for slice in active.iter_mut() {
+        let (start, end) = match range {
+            SnapRange::WithinSlice => (
+                snap(slice.trim_start, 0, window),
+                snap(slice.trim_end, window, 0),
+            ),
+            SnapRange::Nearest | SnapRange::Before => {
+                let after = if range == SnapRange::Nearest {
+                    window
+                } else {
+                    0
+                };
+                let snap_inner = |pos: u32| match pos {
+                    0 => 0,
+                    x if x >= audio.len => x,
+                    x => snap(x, window, after),
+                };
+                (snap_inner(slice.trim_start), snap_inner(slice.trim_end))
+            }
+        };
+        trace!("Snapped slice: {slice:?} start={start} end={end}");
+        if start <= end {
+            slice.trim_start = start;
+            slice.trim_end = end;
+        }
+    }
 }
 
 /// Options that control the output file formats
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct FileFormatOpts {
-    /// normalize individual slices
+    /// bit depth of the output audio files (16 or 24)
     pub bit_depth: Option<u16>,
-    /// %-age length of linear fade in to apply to slices
-    pub format: Option<f32>,
+    /// format of the output audio files (WAV or AIFF)
+    pub format: Option<FileFormat>,
+}
+
+impl FileFormatOpts {
+    /// Output bit depth and format, falling back to the provided defaults for unset options.
+    fn resolve(
+        opts: Option<Self>,
+        bit_depth: u16,
+        format: FileFormat,
+    ) -> RBoxErr<(u16, FileFormat)> {
+        let opts = opts.unwrap_or_default();
+        let bit_depth = opts.bit_depth.unwrap_or(bit_depth);
+        if !ALLOWED_BIT_DEPTHS.contains(&bit_depth) {
+            return Err(CliSampleErrors::InvalidOptBitDepth.into());
+        }
+        Ok((bit_depth, opts.format.unwrap_or(format)))
+    }
 }
 
 /// Get a new `Slices` struct, given an arbitrary length `Vec` of `WavFile`s.
+#[deprecated(note = "use `create_slices_from_audio_files`")]
 pub fn create_slices_from_wavfiles(wavfiles: &[WavFile], offset: u32) -> RBoxErr<Slices> {
+    let audio_files = wavfiles.iter().cloned().map(AudioFile::from).collect_vec();
+    create_slices_from_audio_files(&audio_files, offset)
+}
+
+/// Get a new `Slices` struct, given an arbitrary length `Vec` of `AudioFile`s.
+pub fn create_slices_from_audio_files(wavfiles: &[AudioFile], offset: u32) -> RBoxErr<Slices> {
     let mut new_slices: Vec<Slice> = Vec::new();
     let mut off = offset;
 
@@ -259,8 +623,11 @@ pub fn batch_create_samplechains(
     audio_options: Option<SliceProcOpts>,
     format_options: Option<FileFormatOpts>,
 ) -> RBoxErr<()> {
-    // defaults for octatrack options
-    let mut bpm: f32 = 120.0;
+    // defaults for octatrack options, slices stretched to a tempo are played at that tempo
+    let mut bpm: f32 = audio_options
+        .as_ref()
+        .and_then(|x| x.stretch?.bpm)
+        .unwrap_or(120.0);
     let mut gain: f32 = 0.0;
     let mut tsmode = SampleAttributeTimestrechMode::default();
     let mut tqmode = SampleAttributeTrigQuantizationMode::default();
@@ -284,20 +651,11 @@ pub fn batch_create_samplechains(
             lpmode = opt;
         }
     };
+    // before any audio is processed, slice padding lengths depend on the tempo
+    check_tempo(bpm)?;
 
-    // defaults for output file format options
-    let mut bit_depth = 16;
-    // TODO: AIFF not currently supported
-    // let mut format = FileFormat::Wav;
-
-    if let Some(opts) = format_options {
-        if let Some(opt) = opts.bit_depth {
-            if !ALLOWED_BIT_DEPTHS.contains(&opt) {
-                return Err(CliSampleErrors::InvalidOptBitDepth.into());
-            }
-            bit_depth = opt;
-        }
-    };
+    // output files are 16-bit WAV files by default
+    let (bit_depth, format) = FileFormatOpts::resolve(format_options, 16, FileFormat::Wav)?;
 
     println!(
         "Using sample settings: bpm={} gain={} tsmode={:?} tqmode={:?} loopmode={:?}",
@@ -305,17 +663,17 @@ pub fn batch_create_samplechains(
     );
 
     for (idx, fps) in wav_fps.chunks(64).enumerate() {
-        let mut wavfiles: Vec<WavFile> = vec![];
+        let mut wavfiles: Vec<AudioFile> = vec![];
         for fp in fps {
-            let w = WavFile::from_path(fp)?;
+            let w = AudioFile::from_path(fp)?;
             wavfiles.push(w);
         }
 
         // at least one file is stereo, so all files must be converted to stereo
-        if !wavfiles.iter().map(|x| x.spec.channels).all_equal() {
+        if !wavfiles.iter().map(|x| x.channels).all_equal() {
             // convert any mono files to interleaved stereo samples
             for w in wavfiles.iter_mut() {
-                if w.spec.channels == 1 {
+                if w.channels == 1 {
                     w.mono_to_stereo_interleaved()?;
                 }
             }
@@ -323,31 +681,80 @@ pub fn batch_create_samplechains(
 
         // modify each slice's audio samples according to any options
         if let Some(opts) = &audio_options {
+            if let Some(opt) = opts.trim_silence {
+                for w in wavfiles.iter_mut() {
+                    w.trim_silence(
+                        opt.threshold_db.unwrap_or(DEFAULT_SILENCE_THRESHOLD_DB),
+                        opt.preroll_ms.unwrap_or(DEFAULT_SILENCE_PREROLL_MS),
+                    )?;
+                }
+            }
             if let Some(opt) = opts.time_stretch {
                 for w in wavfiles.iter_mut() {
                     w.resample_time_stretch(opt)?;
                 }
             }
-            if let Some(opt) = opts.fade_in_percent {
+            if let Some(opt) = opts.stretch {
+                for w in wavfiles.iter_mut() {
+                    if let Some(factor) = slice_stretch_factor(w, &opt)? {
+                        w.time_stretch(factor)?;
+                    }
+                }
+            }
+            if opts.pitch_semitones.is_some() || opts.pitch_cents.is_some() {
+                let semitones =
+                    opts.pitch_semitones.unwrap_or(0.0) + opts.pitch_cents.unwrap_or(0.0) / 100.0;
+                for w in wavfiles.iter_mut() {
+                    w.pitch_shift(semitones)?;
+                }
+            }
+            let curve = opts.fade_curve.unwrap_or_default();
+            if let Some(len) = slice_fade_len(opts.fade_in_ms, opts.fade_in_percent)? {
+                for w in wavfiles.iter_mut() {
+                    w.fade_in(len.to_len(w), curve);
+                }
+            }
+            if let Some(len) = slice_fade_len(opts.fade_out_ms, opts.fade_out_percent)? {
                 for w in wavfiles.iter_mut() {
-                    w.linear_fade_in(opt)?;
+                    w.fade_out(len.to_len(w), curve);
                 }
             }
-            if let Some(opt) = opts.fade_out_percent {
+            if let Some(opt) = opts.declick {
+                let len_ms = opt.len_ms.unwrap_or(DEFAULT_DECLICK_MS);
+                if len_ms < 0.0 {
+                    return Err(CliSampleErrors::InvalidFadeLength.into());
+                }
+                let curve = opt.curve.unwrap_or(FadeCurve::SCurve);
                 for w in wavfiles.iter_mut() {
-                    w.linear_fade_out(opt)?;
+                    let len = w.ms_to_len(len_ms);
+                    w.fade_in(len, curve);
+                    w.fade_out(len, curve);
                 }
             }
-            if opts.normalize.is_some() {
+            if let Some(opt) = opts.loudness {
+                for w in wavfiles.iter_mut() {
+                    w.loudness_normalize(
+                        opt.mode,
+                        opt.target.unwrap_or(DEFAULT_LOUDNESS_TARGET),
+                        opt.true_peak_ceiling.unwrap_or(DEFAULT_TRUE_PEAK_CEILING),
+                    )?;
+                }
+            } else if opts.normalize.is_some() {
                 for w in wavfiles.iter_mut() {
                     w.normalize()?;
                 }
             }
+            // after fades, so the fade out is applied to the audio and not the padding
+            for w in wavfiles.iter_mut() {
+                if let Some(len) = padded_slice_len(w, bpm, opts)? {
+                    w.pad_to_len(len);
+                }
+            }
         };
 
-        let chain_channels = wavfiles[0].spec.channels;
+        let chain_channels = wavfiles[0].channels;
 
-        let chain_len_interleaved = wavfiles.iter().map(|x| x.len).sum::<u32>();
+        let chain_len = wavfiles.iter().map(|x| x.len).sum::<u32>();
         let chain_samples = wavfiles
             .iter()
             .flat_map(|x| x.samples.clone())
@@ -355,25 +762,24 @@ pub fn batch_create_samplechains(
 
         // mixed channels issue should be dealt with, so can use first audio
         // file as an indicator of whether we're mono or stereo
-        let wavspec = hound::WavSpec {
+        let chain_wav = AudioFile {
+            format,
             channels: chain_channels,
             sample_rate: ALLOWED_SAMPLE_RATE,
-            bits_per_sample: bit_depth,
-            sample_format: hound::SampleFormat::Int,
-        };
-
-        let chain_wav = WavFile {
-            spec: wavspec,
-            len: chain_len_interleaved,
+            bit_depth,
+            len: chain_len,
             samples: chain_samples,
             file_path: Default::default(),
         };
 
         trace!("Making chain: {}", idx + 1);
-        let slices = create_slices_from_wavfiles(&wavfiles, 0)?;
+        let mut slices = create_slices_from_audio_files(&wavfiles, 0)?;
+        if let Some(snap) = audio_options.as_ref().and_then(|x| x.snap) {
+            snap_slices(&mut slices, &chain_wav, &snap, SnapRange::WithinSlice);
+        }
 
         trace!("Calculating bar length: chainIdx={}", idx + 1);
-        let bars = get_bin_nbars_ileaved_wavfiles(&wavfiles, &bpm, chain_channels)?;
+        let bars = get_bin_nbars_ileaved_audio_files(&wavfiles, &bpm)?;
 
         trace!("Setting up sample attributes data: chainIdx={}", idx + 1);
         let trim_config = SampleTrimConfig {
@@ -418,7 +824,7 @@ pub fn batch_create_samplechains(
                 .join(format!["{}-{}", outchain_name, idx + 1]);
 
         let mut wav_sliced_outpath = base_outchain_path;
-        wav_sliced_outpath.set_extension("wav");
+        wav_sliced_outpath.set_extension(format.extension());
         chain_wav.to_path(&wav_sliced_outpath)?;
         println!("Creating chain audio file: {wav_sliced_outpath:#?}");
 
@@ -435,16 +841,21 @@ pub fn batch_create_samplechains(
 
 // todo: needs tests
 /// Extract a slices from a sliced sample chain into individual samples.
+/// Slices are written in the same format / bit depth as the sample chain unless
+/// `format_options` are provided.
 pub fn deconstruct_samplechain_from_paths(
     audio_fpath: &Path,
     attributes_fpath: &Path,
     out_dirpath: &Path,
+    format_options: Option<FileFormatOpts>,
 ) -> RBoxErr<Vec<PathBuf>> {
     if !out_dirpath.is_dir() {
         return Err(CliSampleErrors::NotADirectory.into());
     }
 
-    let wavfile = WavFile::from_path(audio_fpath)?;
+    let wavfile = AudioFile::from_path(audio_fpath)?;
+    let (bit_depth, format) =
+        FileFormatOpts::resolve(format_options, wavfile.bit_depth, wavfile.format)?;
 
     let attrs = read_type_from_bin_file::<SampleAttributes>(attributes_fpath)?;
 
@@ -463,21 +874,25 @@ pub fn deconstruct_samplechain_from_paths(
         // so need to multiply slices starts/ends by n_channels to get the actual
         // start position
         // TODO: Why do i not do this for creating sample chains?
-        let strt_ileave = slice.trim_start * (wavfile.spec.channels as u32);
-        let end_ileave = slice.trim_end * (wavfile.spec.channels as u32);
+        let strt_ileave = slice.trim_start * (wavfile.channels as u32);
+        let end_ileave = slice.trim_end * (wavfile.channels as u32);
         let w = wavfile.samples[(strt_ileave as usize)..(end_ileave as usize)].to_vec();
         let wav_len = slice.trim_end - slice.trim_start;
 
-        let wavslice = WavFile {
-            spec: wavfile.spec,
+        let sample_fname = format!("{base_sample_fname}-{i:#?}");
+        let mut out_fpath = out_dirpath.to_path_buf().join(sample_fname);
+        out_fpath.set_extension(format.extension());
+
+        let wavslice = AudioFile {
+            format,
+            channels: wavfile.channels,
+            sample_rate: wavfile.sample_rate,
+            bit_depth,
             len: wav_len,
             samples: w,
-            file_path: std::env::temp_dir().join("dummy.wav"),
+            file_path: out_fpath.clone(),
         };
 
-        let sample_fname = format!("{base_sample_fname}-{i:#?}");
-        let mut out_fpath = out_dirpath.to_path_buf().join(sample_fname);
-        out_fpath.set_extension("wav");
         wavslice.to_path(&out_fpath)?;
         out_fpaths.push(out_fpath);
     }
@@ -495,19 +910,302 @@ pub fn deconstruct_samplechains_from_yaml(yaml_conf_fpath: &Path) -> RBoxErr<()>
             &chain_config.sample,
             &chain_config.otfile,
             &chain_conf.global_settings.out_dir_path,
+            chain_conf.global_settings.audio_format.clone(),
         )?;
     }
 
     Ok(())
 }
 
-/// Given a wavfile, create Nx random slices stored in a sample attributes file.
-pub fn create_randomly_sliced_sample(wav_fp: &Path, n_slices: usize) -> RBoxErr<()> {
+/// Default output bit depth when converting audio: 8/16-bit audio is converted to 16-bit,
+/// anything with a higher bit depth is converted to 24-bit.
+fn default_converted_bit_depth(bit_depth: u16) -> u16 {
+    if bit_depth <= 16 {
+        16
+    } else {
+        24
+    }
+}
+
+/// Convert a WAV/AIFF file with any sample rate / bit depth into an Octatrack compatible
+/// (44.1kHz, 16/24-bit) WAV/AIFF file. Output bit depth defaults to 16-bit for 8/16-bit audio
+/// and 24-bit for everything else, output format defaults to the format of the input file.
+pub fn convert_audio_file(
+    in_fpath: &Path,
+    out_fpath: &Path,
+    format_options: Option<FileFormatOpts>,
+) -> RBoxErr<AudioFile> {
+    let mut audio = AudioFile::from_path_any_spec(in_fpath)?;
+    let (bit_depth, format) = FileFormatOpts::resolve(
+        format_options,
+        default_converted_bit_depth(audio.bit_depth),
+        audio.format,
+    )?;
+
+    audio.convert_to_compatible_spec(bit_depth)?;
+    audio.format = format;
+    audio.to_path(out_fpath)?;
+    Ok(audio)
+}
+
+/// Absolute path with symlinks resolved, for a path which might not exist yet (the closest
+/// existing ancestor is canonicalized).
+fn canonicalize_new_path(path: &Path) -> RBoxErr<PathBuf> {
+    let abs = std::path::absolute(path)?;
+    let existing = abs
+        .ancestors()
+        .find(|x| x.exists())
+        .ok_or(CliSampleErrors::NotADirectory)?;
+    Ok(existing.canonicalize()?.join(abs.strip_prefix(existing)?))
+}
+
+/// Convert all WAV/AIFF files in a directory tree into Octatrack compatible WAV/AIFF files,
+/// written to the same relative paths under `out_dirpath`. Files which can't be converted are
+/// reported and skipped. Returns the file paths of the converted files.
+///
+/// The output directory can't be the input directory (or within it), and existing files are
+/// never overwritten -- nothing is converted when an output file already exists, or when two
+/// input files would be written to the same output file (e.g. `a.wav` and `a.aif` with a forced
+/// output format).
+pub fn convert_audio_files(
+    in_dirpath: &Path,
+    out_dirpath: &Path,
+    format_options: Option<FileFormatOpts>,
+) -> RBoxErr<Vec<PathBuf>> {
+    if !in_dirpath.is_dir() {
+        return Err(CliSampleErrors::NotADirectory.into());
+    }
+    if canonicalize_new_path(out_dirpath)?.starts_with(in_dirpath.canonicalize()?) {
+        return Err(CliSampleErrors::OutputDirInInputDir(out_dirpath.to_path_buf()).into());
+    }
+
+    let in_fpaths = WalkDir::new(in_dirpath)
+        .sort_by_file_name()
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && FileFormat::from_path(e.path()).is_some())
+        .map(|e| e.into_path())
+        .collect::<Vec<_>>();
+
+    // check every output file path before writing anything
+    let mut planned: Vec<(PathBuf, PathBuf)> = vec![];
+    for in_fpath in in_fpaths {
+        let relpath = in_fpath.strip_prefix(in_dirpath)?;
+        let mut out_fpath = out_dirpath.join(relpath);
+
+        let format = format_options
+            .as_ref()
+            .and_then(|x| x.format)
+            .or(FileFormat::from_path(&in_fpath))
+            .unwrap_or_default();
+        out_fpath.set_extension(format.extension());
+
+        if out_fpath.exists() {
+            return Err(CliSampleErrors::OutputFileExists(out_fpath).into());
+        }
+        if planned.iter().any(|(_, x)| *x == out_fpath) {
+            return Err(CliSampleErrors::OutputFileCollision(out_fpath).into());
+        }
+        planned.push((in_fpath, out_fpath));
+    }
+
+    println!(
+        "Converting audio files: n={} in={in_dirpath:#?} out={out_dirpath:#?}",
+        planned.len()
+    );
+
+    let mut out_fpaths: Vec<PathBuf> = vec![];
+    for (in_fpath, out_fpath) in planned {
+        if let Some(parent) = out_fpath.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        match convert_audio_file(&in_fpath, &out_fpath, format_options.clone()) {
+            Ok(x) => {
+                println!(
+                    "Converted: {in_fpath:#?} -> {out_fpath:#?} ({}Hz {}-bit)",
+                    x.sample_rate, x.bit_depth
+                );
+                out_fpaths.push(out_fpath);
+            }
+            Err(e) => println!("ERROR: Skipping {in_fpath:#?}: {e}"),
+        }
+    }
+
+    println!("Converted audio files: n={}", out_fpaths.len());
+    Ok(out_fpaths)
+}
+
+/// Detect the tempo of a WAV/AIFF file. When `write` is set and the tempo was detected with at
+/// least `min_confidence`, the tempo and trim/loop lengths (in bars) of the existing sample
+/// attributes file next to the audio file are updated.
+pub fn detect_bpm_audio_file(
+    audio_fpath: &Path,
+    write: bool,
+    min_confidence: f32,
+) -> RBoxErr<Option<TempoEstimate>> {
+    let audio = AudioFile::from_path(audio_fpath)?;
+    let Some(estimate) = audio.estimate_tempo() else {
+        return Ok(None);
+    };
+
+    if write && estimate.confidence >= min_confidence {
+        let ot_fpath = audio_fpath.with_extension("ot");
+        let mut attrs = read_type_from_bin_file::<SampleAttributes>(&ot_fpath)?;
+
+        // estimates are always within the machine's 30-300 BPM range
+        attrs.tempo = (estimate.bpm * 24.0).round() as u32;
+        attrs.trim_len = get_otsample_nbars_from_len(
+            attrs.trim_end.saturating_sub(attrs.trim_start),
+            &estimate.bpm,
+        )?;
+        attrs.loop_len = get_otsample_nbars_from_len(
+            attrs.trim_end.saturating_sub(attrs.loop_start),
+            &estimate.bpm,
+        )?;
+        // recalculated when written
+        attrs.checksum = 0;
+
+        write_type_to_bin_file::<SampleAttributes>(&attrs, &ot_fpath)?;
+        println!("Updated sample attributes file tempo: {ot_fpath:#?}");
+    }
+
+    Ok(Some(estimate))
+}
+
+/// File paths of WAV/AIFF files, searching any directories (but not hidden files/directories)
+/// for WAV/AIFF files.
+fn find_audio_file_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut audio_fpaths: Vec<PathBuf> = vec![];
+    for path in paths {
+        if path.is_dir() {
+            audio_fpaths.extend(
+                WalkDir::new(path)
+                    .sort_by_file_name()
+                    .min_depth(1)
+                    .into_iter()
+                    .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'))
+                    .filter_map(|e| e.ok())
+                    .filter(|e| {
+                        e.file_type().is_file() && FileFormat::from_path(e.path()).is_some()
+                    })
+                    .map(|e| e.into_path()),
+            );
+        } else {
+            audio_fpaths.push(path.clone());
+        }
+    }
+    audio_fpaths
+}
+
+/// Detect the tempo of WAV/AIFF files, directories are searched for WAV/AIFF files.
+/// See [`detect_bpm_audio_file`]. Files which can't be read (or have no existing sample attributes
+/// file to update when `write` is set) are reported and skipped. Returns the audio file paths
+/// with a detected tempo.
+pub fn detect_bpm_audio_files(
+    paths: &[PathBuf],
+    write: bool,
+    min_confidence: f32,
+) -> RBoxErr<Vec<(PathBuf, TempoEstimate)>> {
+    let mut detected: Vec<(PathBuf, TempoEstimate)> = vec![];
+    for audio_fpath in find_audio_file_paths(paths) {
+        match detect_bpm_audio_file(&audio_fpath, write, min_confidence) {
+            Ok(Some(x)) => {
+                let status = if x.confidence >= min_confidence {
+                    ""
+                } else {
+                    " (low confidence)"
+                };
+                println!(
+                    "{audio_fpath:#?}: bpm={:.2} confidence={:.2}{status}",
+                    x.bpm, x.confidence
+                );
+                detected.push((audio_fpath, x));
+            }
+            Ok(None) => println!("{audio_fpath:#?}: no tempo detected"),
+            Err(e) => println!("ERROR: Skipping {audio_fpath:#?}: {e}"),
+        }
+    }
+
+    Ok(detected)
+}
+
+/// Measure the peak, true peak, RMS and integrated loudness (LUFS) levels of WAV/AIFF files with
+/// any sample rate / bit depth, directories are searched for WAV/AIFF files. Files which can't be
+/// read are reported and skipped. Returns the levels of each audio file.
+pub fn analyze_audio_files(paths: &[PathBuf]) -> RBoxErr<Vec<(PathBuf, LoudnessStats)>> {
+    let mut analyzed: Vec<(PathBuf, LoudnessStats)> = vec![];
+    for audio_fpath in find_audio_file_paths(paths) {
+        match AudioFile::from_path_any_spec(&audio_fpath) {
+            Ok(audio) => {
+                let x = audio.loudness_stats();
+                println!(
+                    "{audio_fpath:#?}: peak={:.1}dBFS true_peak={:.1}dBTP rms={:.1}dBFS lufs={:.1}LUFS",
+                    x.peak, x.true_peak, x.rms, x.lufs
+                );
+                analyzed.push((audio_fpath, x));
+            }
+            Err(e) => println!("ERROR: Skipping {audio_fpath:#?}: {e}"),
+        }
+    }
+
+    Ok(analyzed)
+}
+
+/// Write a sample attributes file with default settings, a tempo and a slice grid, next to an
+/// audio file.
+fn write_sliced_sample_ot_file(
+    audio_fpath: &Path,
+    audio: &AudioFile,
+    tempo: f32,
+    slices: &Slices,
+) -> RBoxErr<()> {
+    let bars = get_otsample_nbars_from_audio_file(audio, &tempo)?;
+
+    let trim_config = SampleTrimConfig {
+        start: 0,
+        end: audio.len,
+        length: bars,
+    };
+
+    let loop_config = SampleLoopConfig {
+        start: 0,
+        length: bars,
+        mode: SampleAttributeLoopMode::default(),
+    };
+
+    let chain_data = SampleAttributes::new(
+        &tempo,
+        &SampleAttributeTimestrechMode::default(),
+        &SampleAttributeTrigQuantizationMode::default(),
+        &0.0,
+        &trim_config,
+        &loop_config,
+        slices,
+    )?;
+
+    let mut ot_outpath = audio_fpath.to_path_buf();
+    ot_outpath.set_extension("ot");
+
+    write_type_to_bin_file::<SampleAttributes>(&chain_data, &ot_outpath)?;
+    println!("Created chain attributes file: {ot_outpath:#?}");
+    Ok(())
+}
+
+/// Given a WAV/AIFF file, create Nx random slices stored in a sample attributes file.
+/// Slice points are moved to nearby zero crossings / quiet points when `snap` is set.
+pub fn create_randomly_sliced_sample(
+    wav_fp: &Path,
+    n_slices: usize,
+    snap: Option<SliceSnapOpts>,
+) -> RBoxErr<()> {
     if n_slices > 64 {
         return Err(CliSampleErrors::TooManySlices.into());
     };
 
-    let wavfile = WavFile::from_path(wav_fp)?;
+    let wavfile = AudioFile::from_path(wav_fp)?;
 
     if wavfile.len < 128 {
         return Err(CliSampleErrors::AudioTooShort.into());
@@ -528,7 +1226,7 @@ pub fn create_randomly_sliced_sample(wav_fp: &Path, n_slices: usize) -> RBoxErr<
         let trim_start: u32 = rng.gen_range(0..=(wavfile.len - 64));
         // clipped random length so we don't always end up with long slices
         // at the start and shorter ones at the end
-        let rndlen = trim_start + (wavfile.len / n_slices as u32).max(64);
+        let rndlen = (trim_start + (wavfile.len / n_slices as u32).max(64)).min(wavfile.len);
         let trim_end: u32 = rng.gen_range(trim_start..=rndlen);
         let loop_start: u32 = 0xFFFFFFFF;
 
@@ -541,50 +1239,34 @@ pub fn create_randomly_sliced_sample(wav_fp: &Path, n_slices: usize) -> RBoxErr<
         slices_arr[i] = slice;
     }
 
-    let slices = Slices {
+    let mut slices = Slices {
         slices: slices_arr,
         count: n_slices as u32,
     };
+    if let Some(snap) = snap {
+        snap_slices(&mut slices, &wavfile, &snap, SnapRange::Nearest);
+    }
 
-    let bars = get_otsample_nbars_from_wavfile(&wavfile, &120.0)?;
-
-    let trim_config = SampleTrimConfig {
-        start: 0,
-        end: wavfile.len,
-        length: bars,
-    };
-
-    let loop_config = SampleLoopConfig {
-        start: 0,
-        length: bars,
-        mode: SampleAttributeLoopMode::default(),
-    };
-
-    let chain_data = SampleAttributes::new(
-        &120.0,
-        &SampleAttributeTimestrechMode::default(),
-        &SampleAttributeTrigQuantizationMode::default(),
-        &0.0,
-        &trim_config,
-        &loop_config,
+    write_sliced_sample_ot_file(
+        wav_fp,
+        &wavfile,
+        detected_tempo_or_default(&wavfile),
         &slices,
-    )?;
-
-    let mut ot_outpath = wav_fp.to_path_buf();
-    ot_outpath.set_extension("ot");
-
-    write_type_to_bin_file::<SampleAttributes>(&chain_data, &ot_outpath)?;
-    println!("Created chain attributes file: {ot_outpath:#?}");
-    Ok(())
+    )
 }
 
-/// Given a wavfile, create Nx equal length slices stored in a sample attributes file.
-pub fn create_equally_sliced_sample(wav_fp: &Path, n_slices: usize) -> RBoxErr<()> {
+/// Given a WAV/AIFF file, create Nx equal length slices stored in a sample attributes file.
+/// Slice points are moved to nearby zero crossings / quiet points when `snap` is set.
+pub fn create_equally_sliced_sample(
+    wav_fp: &Path,
+    n_slices: usize,
+    snap: Option<SliceSnapOpts>,
+) -> RBoxErr<()> {
     if n_slices > 64 {
         return Err(CliSampleErrors::TooManySlices.into());
     };
 
-    let wavfile = WavFile::from_path(wav_fp)?;
+    let wavfile = AudioFile::from_path(wav_fp)?;
 
     if wavfile.len < 128 {
         return Err(CliSampleErrors::AudioTooShort.into());
@@ -613,41 +1295,201 @@ pub fn create_equally_sliced_sample(wav_fp: &Path, n_slices: usize) -> RBoxErr<(
         slices_arr[i] = slice;
     }
 
-    let slices = Slices {
+    let mut slices = Slices {
         slices: slices_arr,
         count: n_slices as u32,
     };
+    if let Some(snap) = snap {
+        snap_slices(&mut slices, &wavfile, &snap, SnapRange::Nearest);
+    }
 
-    let bars = get_otsample_nbars_from_wavfile(&wavfile, &120.0)?;
+    write_sliced_sample_ot_file(
+        wav_fp,
+        &wavfile,
+        detected_tempo_or_default(&wavfile),
+        &slices,
+    )
+}
 
-    let trim_config = SampleTrimConfig {
-        start: 0,
-        end: wavfile.len,
-        length: bars,
+/// Get a new `Slices` struct from slice start positions (e.g. onsets), each slice runs from one
+/// position to the next (the last slice runs to the end of the audio).
+pub fn create_slices_from_positions(positions: &[u32], len: u32) -> RBoxErr<Slices> {
+    if positions.len() > 64 {
+        return Err(CliSampleErrors::TooManySlices.into());
+    }
+
+    let default_slice = Slice {
+        trim_end: 0,
+        trim_start: 0,
+        loop_start: 0,
     };
+    let mut slices_arr: [Slice; 64] = [default_slice; 64];
 
-    let loop_config = SampleLoopConfig {
-        start: 0,
-        length: bars,
-        mode: SampleAttributeLoopMode::default(),
+    for (i, trim_start) in positions.iter().enumerate() {
+        slices_arr[i] = Slice {
+            trim_start: *trim_start,
+            trim_end: positions.get(i + 1).copied().unwrap_or(len),
+            loop_start: 0xFFFFFFFF,
+        };
+    }
+
+    Ok(Slices {
+        slices: slices_arr,
+        count: positions.len() as u32,
+    })
+}
+
+/// Given a WAV/AIFF file, detect the onsets (transients, e.g. drum hits) and create a slice
+/// for each one, stored in a sample attributes file. With `snap`, slices are moved back to a
+/// zero crossing / quiet point just before each onset.
+pub fn create_transient_sliced_sample(
+    wav_fp: &Path,
+    opts: &OnsetOpts,
+    snap: Option<SliceSnapOpts>,
+) -> RBoxErr<usize> {
+    if opts.max_onsets > 64 {
+        return Err(CliSampleErrors::TooManySlices.into());
     };
 
-    let chain_data = SampleAttributes::new(
-        &120.0,
-        &SampleAttributeTimestrechMode::default(),
-        &SampleAttributeTrigQuantizationMode::default(),
-        &0.0,
-        &trim_config,
-        &loop_config,
+    let wavfile = AudioFile::from_path(wav_fp)?;
+
+    if wavfile.len < 128 {
+        return Err(CliSampleErrors::AudioTooShort.into());
+    };
+
+    let onsets = wavfile.detect_onsets(opts);
+    println!("Detected transients: n={} path={wav_fp:#?}", onsets.len());
+
+    let mut slices = create_slices_from_positions(&onsets, wavfile.len)?;
+    if let Some(snap) = snap {
+        // slices are moved to just before the onset, so the start of the hit isn't cut off
+        snap_slices(&mut slices, &wavfile, &snap, SnapRange::Before);
+    }
+    write_sliced_sample_ot_file(
+        wav_fp,
+        &wavfile,
+        detected_tempo_or_default(&wavfile),
         &slices,
     )?;
+    Ok(onsets.len())
+}
 
-    let mut ot_outpath = wav_fp.to_path_buf();
-    ot_outpath.set_extension("ot");
+/// A musical note length, relative to a whole note (one bar of 4/4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteDivision {
+    /// e.g. `1/16` is a sixteenth note
+    Straight(u32, u32),
+    /// e.g. `1/8T`, three fit in the length of two straight notes
+    Triplet(u32, u32),
+    /// e.g. `1/4D`, one and a half times the length of a straight note
+    Dotted(u32, u32),
+}
 
-    write_type_to_bin_file::<SampleAttributes>(&chain_data, &ot_outpath)?;
-    println!("Created chain attributes file: {ot_outpath:#?}");
-    Ok(())
+impl NoteDivision {
+    /// Length of the note in beats (quarter notes).
+    pub fn beats(&self) -> f64 {
+        match self {
+            Self::Straight(n, d) => 4.0 * *n as f64 / *d as f64,
+            Self::Triplet(n, d) => 4.0 * *n as f64 / *d as f64 * 2.0 / 3.0,
+            Self::Dotted(n, d) => 4.0 * *n as f64 / *d as f64 * 1.5,
+        }
+    }
+}
+
+impl std::fmt::Display for NoteDivision {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Straight(n, d) => write!(f, "{n}/{d}"),
+            Self::Triplet(n, d) => write!(f, "{n}/{d}T"),
+            Self::Dotted(n, d) => write!(f, "{n}/{d}D"),
+        }
+    }
+}
+
+/// Parse a note division like `1/16`, `1/8T` (triplet) or `1/4D` (dotted).
+pub fn parse_note_division(x: &str) -> RBoxErr<NoteDivision> {
+    let err = || CliSampleErrors::InvalidNoteDivision(x.to_string());
+
+    let trimmed = x.trim();
+    let (fraction, modifier) = match trimmed.chars().last() {
+        Some(c @ ('t' | 'T' | 'd' | 'D')) => (&trimmed[..trimmed.len() - 1], Some(c)),
+        _ => (trimmed, None),
+    };
+
+    let (n, d) = fraction.split_once('/').ok_or_else(err)?;
+    let n = n.trim().parse::<u32>().map_err(|_| err())?;
+    let d = d.trim().parse::<u32>().map_err(|_| err())?;
+    if n == 0 || d == 0 {
+        return Err(err().into());
+    }
+
+    Ok(match modifier {
+        Some('t' | 'T') => NoteDivision::Triplet(n, d),
+        Some(_) => NoteDivision::Dotted(n, d),
+        None => NoteDivision::Straight(n, d),
+    })
+}
+
+/// Start positions of each note in a beat grid at some tempo, from the start of the audio.
+/// A note which only partly fits before the end of the audio still gets a position.
+pub fn beat_grid_positions(
+    len: u32,
+    sample_rate: u32,
+    tempo_bpm: f32,
+    division: &NoteDivision,
+) -> Vec<u32> {
+    let step = division.beats() * 60.0 / tempo_bpm as f64 * sample_rate as f64;
+    // tolerance so rounding in the audio length doesn't create a tiny extra note
+    let n_notes = (len as f64 / step - 0.01).ceil().max(1.0) as u32;
+    (0..n_notes)
+        .map(|i| (i as f64 * step).round() as u32)
+        .collect()
+}
+
+/// Given a WAV/AIFF file, create a slice for each note of a beat grid, e.g. every 1/16 note at
+/// 120 BPM, stored in a sample attributes file. The tempo is taken from (in order of preference)
+/// `tempo_bpm`, the tempo in an existing sample attributes file for the audio, or is detected from
+/// the audio. Slice points are moved to nearby zero crossings / quiet points when `snap` is set.
+/// Returns the number of slices.
+pub fn create_beat_grid_sliced_sample(
+    wav_fp: &Path,
+    tempo_bpm: Option<f32>,
+    division: &NoteDivision,
+    snap: Option<SliceSnapOpts>,
+) -> RBoxErr<usize> {
+    let wavfile = AudioFile::from_path(wav_fp)?;
+
+    if wavfile.len < 128 {
+        return Err(CliSampleErrors::AudioTooShort.into());
+    };
+
+    let ot_fpath = wav_fp.with_extension("ot");
+    let tempo = match tempo_bpm {
+        Some(x) => x,
+        None if ot_fpath.exists() => {
+            read_type_from_bin_file::<SampleAttributes>(&ot_fpath)?.tempo as f32 / 24.0
+        }
+        None => detected_tempo_or_default(&wavfile),
+    };
+
+    check_tempo(tempo)?;
+
+    let positions = beat_grid_positions(wavfile.len, wavfile.sample_rate, tempo, division);
+    if positions.len() > 64 {
+        return Err(CliSampleErrors::TooManySlices.into());
+    }
+
+    println!(
+        "Beat grid slices: n={} bpm={tempo} division={division} path={wav_fp:#?}",
+        positions.len()
+    );
+
+    let mut slices = create_slices_from_positions(&positions, wavfile.len)?;
+    if let Some(snap) = snap {
+        snap_slices(&mut slices, &wavfile, &snap, SnapRange::Nearest);
+    }
+    write_sliced_sample_ot_file(wav_fp, &wavfile, tempo, &slices)?;
+    Ok(positions.len())
 }
 
 pub fn create_index_samples_dir_simple(
@@ -1,6 +1,7 @@
 mod chain_deconstruct {
 
-    use crate::actions::samples::deconstruct_samplechain_from_paths;
+    use crate::actions::samples::{deconstruct_samplechain_from_paths, FileFormatOpts};
+    use crate::audio::file::{AudioFile, FileFormat};
     use std::{fs, path::Path, path::PathBuf};
 
     #[test]
@@ -9,7 +10,8 @@ mod chain_deconstruct {
         let attributes_fpath = PathBuf::from("../data/tests/chains/deconstruct/test.ot");
         let outdir = std::env::temp_dir().join("");
 
-        let res = deconstruct_samplechain_from_paths(&audio_fpath, &attributes_fpath, &outdir);
+        let res =
+            deconstruct_samplechain_from_paths(&audio_fpath, &attributes_fpath, &outdir, None);
 
         let outfiles = res.unwrap();
 
@@ -22,10 +24,44 @@ mod chain_deconstruct {
 
         assert!(files_exist)
     }
+
+    #[test]
+    fn test_aiff_format() {
+        let audio_fpath = PathBuf::from("../data/tests/chains/deconstruct/test.wav");
+        let attributes_fpath = PathBuf::from("../data/tests/chains/deconstruct/test.ot");
+        let outdir = std::env::temp_dir().join("ot-tools-deconstruct-aiff");
+        let _ = fs::create_dir_all(&outdir);
+
+        let opts = FileFormatOpts {
+            bit_depth: Some(24),
+            format: Some(FileFormat::Aiff),
+        };
+        let outfiles = deconstruct_samplechain_from_paths(
+            &audio_fpath,
+            &attributes_fpath,
+            &outdir,
+            Some(opts),
+        )
+        .unwrap();
+
+        let slices = outfiles
+            .iter()
+            .map(|fp| AudioFile::from_path(fp).unwrap())
+            .collect::<Vec<_>>();
+
+        let _ = fs::remove_dir_all(&outdir);
+
+        assert!(!slices.is_empty());
+        for slice in slices {
+            assert_eq!(slice.format, FileFormat::Aiff);
+            assert_eq!(slice.bit_depth, 24);
+        }
+    }
 }
 
 mod chain_create {
-    use crate::actions::samples::{batch_create_samplechains, SampleChainOpts};
+    use crate::actions::samples::{batch_create_samplechains, FileFormatOpts, SampleChainOpts};
+    use crate::audio::file::{AudioFile, FileFormat};
     use std::array::from_fn;
     use std::env::temp_dir;
     use std::path::PathBuf;
@@ -367,16 +403,71 @@ mod chain_create {
         ok_boilerplate_test_sample_chain::<256>(None, None);
     }
 
+    #[test]
+    fn ok_aiff_format_2_samples() {
+        let test_dir = get_base_outpath("aiff-format-2-samples");
+        let _ = std::fs::create_dir_all(&test_dir);
+
+        let dummy_wav_fp = PathBuf::from("../data/tests/samples/chains/create/wav.wav");
+        let opts = FileFormatOpts {
+            bit_depth: None,
+            format: Some(FileFormat::Aiff),
+        };
+
+        batch_create_samplechains(
+            &[dummy_wav_fp.clone(), dummy_wav_fp],
+            &test_dir,
+            &"chain".to_string(),
+            None,
+            None,
+            Some(opts),
+        )
+        .unwrap();
+
+        let created_ot =
+            read_type_from_bin_file::<SampleAttributes>(&test_dir.join("chain-1.ot")).unwrap();
+        let created_aiff = AudioFile::from_path(&test_dir.join("chain-1.aiff")).unwrap();
+
+        let valid_dir = PathBuf::from("../data/tests/samples/chains/create/default-2-samples");
+        let valid_ot =
+            read_type_from_bin_file::<SampleAttributes>(&valid_dir.join("valid-1.ot")).unwrap();
+        let valid_wav = WavFile::from_path(&valid_dir.join("valid-1.wav")).unwrap();
+
+        assert_eq!(created_aiff.format, FileFormat::Aiff);
+        assert_eq!(created_aiff.bit_depth, 16);
+        assert_eq!(valid_ot, created_ot);
+        assert_eq!(created_aiff.samples, valid_wav.samples);
+    }
+
+    #[test]
+    fn err_invalid_bit_depth() {
+        let test_dir = get_base_outpath("invalid-bit-depth");
+        let opts = FileFormatOpts {
+            bit_depth: Some(32),
+            format: None,
+        };
+
+        assert!(batch_create_samplechains(
+            &[PathBuf::from("../data/tests/samples/chains/create/wav.wav")],
+            &test_dir,
+            &"chain".to_string(),
+            None,
+            None,
+            Some(opts),
+        )
+        .is_err());
+    }
+
     mod slices_from_wavs {
 
-        use crate::actions::samples::create_slices_from_wavfiles;
-        use crate::audio::wav::WavFile;
+        use crate::actions::samples::create_slices_from_audio_files;
+        use crate::audio::file::AudioFile;
         use std::path::PathBuf;
 
         #[test]
         fn no_offset_ok() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
             let wavs = [
                 wav.clone(),
                 wav.clone(),
@@ -386,13 +477,13 @@ mod chain_create {
             ]
             .to_vec();
 
-            assert!(create_slices_from_wavfiles(&wavs, 0).is_ok())
+            assert!(create_slices_from_audio_files(&wavs, 0).is_ok())
         }
 
         #[test]
         fn offset_100_ok() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
             let wavs = [
                 wav.clone(),
                 wav.clone(),
@@ -402,13 +493,13 @@ mod chain_create {
             ]
             .to_vec();
 
-            assert!(create_slices_from_wavfiles(&wavs, 100).is_ok())
+            assert!(create_slices_from_audio_files(&wavs, 100).is_ok())
         }
 
         #[test]
         fn offset_30000_ok() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
             let wavs = [
                 wav.clone(),
                 wav.clone(),
@@ -418,9 +509,1764 @@ mod chain_create {
             ]
             .to_vec();
 
-            assert!(create_slices_from_wavfiles(&wavs, 30000).is_ok())
+            assert!(create_slices_from_audio_files(&wavs, 30000).is_ok())
+        }
+    }
+}
+
+mod grids {
+    use crate::actions::samples::{
+        beat_grid_positions, create_beat_grid_sliced_sample, create_default_ot_file_for_audio_file,
+        create_equally_sliced_sample, create_randomly_sliced_sample, create_slices_from_positions,
+        create_transient_sliced_sample, parse_note_division, NoteDivision, SliceSnapOpts,
+    };
+    use crate::audio::file::{AudioFile, FileFormat};
+    use crate::audio::onsets::OnsetOpts;
+    use ot_tools_io::read_type_from_bin_file;
+    use ot_tools_io::samples::SampleAttributes;
+    use std::path::{Path, PathBuf};
+
+    // copy the test wav file into a new aiff file, so the grid functions have an aiff file to read
+    fn create_aiff(fname: &str) -> PathBuf {
+        let mut audio = AudioFile::from_path(Path::new("../data/tests/misc/test.wav")).unwrap();
+        audio.format = FileFormat::Aiff;
+
+        let dirpath = std::env::temp_dir().join("ot-tools-grids");
+        let _ = std::fs::create_dir_all(&dirpath);
+        let fpath = dirpath.join(fname);
+        audio.to_path(&fpath).unwrap();
+        fpath
+    }
+
+    fn read_ot_file(audio_fpath: &Path) -> SampleAttributes {
+        let ot_fpath = audio_fpath.with_extension("ot");
+        let attrs = read_type_from_bin_file::<SampleAttributes>(&ot_fpath).unwrap();
+        let _ = std::fs::remove_file(audio_fpath);
+        let _ = std::fs::remove_file(ot_fpath);
+        attrs
+    }
+
+    #[test]
+    fn linear_aiff() {
+        let fpath = create_aiff("linear.aiff");
+        create_equally_sliced_sample(&fpath, 4, None).unwrap();
+        assert_eq!(read_ot_file(&fpath).slices_len, 4);
+    }
+
+    #[test]
+    fn random_aiff() {
+        let fpath = create_aiff("random.aiff");
+        create_randomly_sliced_sample(&fpath, 8, None).unwrap();
+        assert_eq!(read_ot_file(&fpath).slices_len, 8);
+    }
+
+    #[test]
+    fn default_ot_file_aiff() {
+        let fpath = create_aiff("default.aif");
+        let len = AudioFile::from_path(&fpath).unwrap().len;
+        create_default_ot_file_for_audio_file(&fpath).unwrap();
+        let attrs = read_ot_file(&fpath);
+        assert_eq!(attrs.slices_len, 0);
+        assert_eq!(attrs.trim_end, len);
+    }
+
+    // decaying clicks (drum hit-ish) at each position in an otherwise silent stereo file
+    fn create_break(fname: &str, positions: &[u32], len: u32, format: FileFormat) -> PathBuf {
+        let mut samples = vec![0.0_f32; len as usize * 2];
+        for pos in positions {
+            for i in 0..4410_u32.min(len - pos) {
+                let x = (i as f32 * 0.3).sin() * 0.8 * (-(i as f32) / 600.0).exp();
+                let idx = (pos + i) as usize * 2;
+                samples[idx] = x;
+                samples[idx + 1] = x;
+            }
+        }
+
+        let audio = AudioFile {
+            format,
+            channels: 2,
+            sample_rate: 44100,
+            bit_depth: 16,
+            len,
+            samples,
+            file_path: PathBuf::new(),
+        };
+
+        let dirpath = std::env::temp_dir().join("ot-tools-grids");
+        let _ = std::fs::create_dir_all(&dirpath);
+        let fpath = dirpath.join(fname);
+        audio.to_path(&fpath).unwrap();
+        fpath
+    }
+
+    fn check_transient_slices(fpath: &Path, positions: &[u32], len: u32) {
+        let n = create_transient_sliced_sample(fpath, &OnsetOpts::default(), None).unwrap();
+        let attrs = read_ot_file(fpath);
+
+        assert_eq!(n, positions.len());
+        assert_eq!(attrs.slices_len, positions.len() as u32);
+        for (i, pos) in positions.iter().enumerate() {
+            let slice = attrs.slices[i];
+            assert!(slice.trim_start.abs_diff(*pos) <= 512, "{slice:?} {pos}");
+            assert_eq!(slice.loop_start, 0xFFFFFFFF);
+            if i + 1 < positions.len() {
+                assert_eq!(slice.trim_end, attrs.slices[i + 1].trim_start);
+            } else {
+                assert_eq!(slice.trim_end, len);
+            }
+        }
+    }
+
+    #[test]
+    fn transient_wav() {
+        let positions = [0, 11025, 16537, 22050, 33075];
+        let fpath = create_break("transient.wav", &positions, 44100, FileFormat::Wav);
+        check_transient_slices(&fpath, &positions, 44100);
+    }
+
+    #[test]
+    fn transient_aiff() {
+        let positions = [2000, 12000, 30000];
+        let fpath = create_break("transient.aiff", &positions, 44100, FileFormat::Aiff);
+        check_transient_slices(&fpath, &positions, 44100);
+    }
+
+    #[test]
+    fn transient_silence_no_slices() {
+        let fpath = create_break("transient-silence.wav", &[], 44100, FileFormat::Wav);
+        check_transient_slices(&fpath, &[], 44100);
+    }
+
+    #[test]
+    fn transient_too_many_onsets() {
+        let opts = OnsetOpts {
+            max_onsets: 65,
+            ..OnsetOpts::default()
+        };
+        assert!(create_transient_sliced_sample(
+            Path::new("../data/tests/misc/test.wav"),
+            &opts,
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn slices_from_positions() {
+        let slices = create_slices_from_positions(&[0, 100, 250], 1000).unwrap();
+        assert_eq!(slices.count, 3);
+        assert_eq!(
+            slices.slices[..3]
+                .iter()
+                .map(|x| (x.trim_start, x.trim_end))
+                .collect::<Vec<_>>(),
+            vec![(0, 100), (100, 250), (250, 1000)]
+        );
+    }
+
+    #[test]
+    fn slices_from_positions_too_many() {
+        let onsets = (0..65).collect::<Vec<u32>>();
+        assert!(create_slices_from_positions(&onsets, 1000).is_err());
+    }
+
+    #[test]
+    fn note_division_parse() {
+        assert_eq!(
+            parse_note_division("1/16").unwrap(),
+            NoteDivision::Straight(1, 16)
+        );
+        assert_eq!(
+            parse_note_division("1/8T").unwrap(),
+            NoteDivision::Triplet(1, 8)
+        );
+        assert_eq!(
+            parse_note_division(" 1/4d ").unwrap(),
+            NoteDivision::Dotted(1, 4)
+        );
+        assert_eq!(
+            parse_note_division("2/1").unwrap(),
+            NoteDivision::Straight(2, 1)
+        );
+        for x in ["", "16", "1/", "/4", "1/0", "0/4", "1/4X", "a/b", "1/-4"] {
+            assert!(parse_note_division(x).is_err(), "{x}");
+        }
+    }
+
+    #[test]
+    fn note_division_display() {
+        for x in ["1/16", "1/8T", "1/4D", "2/1"] {
+            assert_eq!(parse_note_division(x).unwrap().to_string(), x);
+        }
+    }
+
+    #[test]
+    fn note_division_beats() {
+        assert_eq!(NoteDivision::Straight(1, 16).beats(), 0.25);
+        assert_eq!(NoteDivision::Straight(2, 1).beats(), 8.0);
+        assert!((NoteDivision::Triplet(1, 8).beats() - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(NoteDivision::Dotted(1, 4).beats(), 1.5);
+    }
+
+    #[test]
+    fn beat_grid_positions_two_bars() {
+        // 2 bars at 120 BPM
+        let positions = beat_grid_positions(176400, 44100, 120.0, &NoteDivision::Straight(1, 16));
+        assert_eq!(positions.len(), 32);
+        assert_eq!(positions[..3], [0, 5513, 11025]);
+        assert_eq!(positions[16], 88200);
+    }
+
+    #[test]
+    fn beat_grid_positions_rounded_len() {
+        let division = NoteDivision::Straight(1, 4);
+        assert_eq!(
+            beat_grid_positions(176399, 44100, 120.0, &division).len(),
+            8
+        );
+        assert_eq!(
+            beat_grid_positions(176401, 44100, 120.0, &division).len(),
+            8
+        );
+        // partial note at the end
+        assert_eq!(
+            beat_grid_positions(176400 + 5000, 44100, 120.0, &division).len(),
+            9
+        );
+        // less than one note
+        assert_eq!(beat_grid_positions(1000, 44100, 120.0, &division), vec![0]);
+    }
+
+    #[test]
+    fn beat_grid_positions_triplets() {
+        // 1 bar at 90 BPM, 12 eighth note triplets
+        let positions = beat_grid_positions(117600, 44100, 90.0, &NoteDivision::Triplet(1, 8));
+        assert_eq!(positions.len(), 12);
+        assert_eq!(positions[3], 29400);
+    }
+
+    #[test]
+    fn beat_grid_explicit_tempo() {
+        let fpath = create_break("beat-grid.wav", &[0], 176400, FileFormat::Wav);
+        let n = create_beat_grid_sliced_sample(
+            &fpath,
+            Some(120.0),
+            &NoteDivision::Straight(1, 8),
+            None,
+        )
+        .unwrap();
+        let attrs = read_ot_file(&fpath);
+
+        assert_eq!(n, 16);
+        assert_eq!(attrs.slices_len, 16);
+        assert_eq!(attrs.tempo, 120 * 24);
+        assert_eq!(attrs.slices[1].trim_start, 11025);
+        assert_eq!(attrs.slices[1].trim_end, 22050);
+        assert_eq!(attrs.slices[15].trim_end, 176400);
+    }
+
+    #[test]
+    fn beat_grid_tempo_from_ot_file() {
+        let fpath = create_break("beat-grid-ot.aiff", &[0], 176400, FileFormat::Aiff);
+        create_beat_grid_sliced_sample(&fpath, Some(60.0), &NoteDivision::Straight(1, 4), None)
+            .unwrap();
+        let n = create_beat_grid_sliced_sample(&fpath, None, &NoteDivision::Straight(1, 8), None)
+            .unwrap();
+        let attrs = read_ot_file(&fpath);
+
+        assert_eq!(n, 8);
+        assert_eq!(attrs.tempo, 60 * 24);
+    }
+
+    #[test]
+    fn beat_grid_too_many_slices() {
+        let fpath = create_break("beat-grid-too-many.wav", &[0], 176400, FileFormat::Wav);
+        let res = create_beat_grid_sliced_sample(
+            &fpath,
+            Some(120.0),
+            &NoteDivision::Straight(1, 64),
+            None,
+        );
+        let _ = std::fs::remove_file(&fpath);
+        assert!(res.is_err());
+        assert!(!fpath.with_extension("ot").exists());
+    }
+
+    #[test]
+    fn beat_grid_invalid_tempo() {
+        let fpath = create_break("beat-grid-tempo.wav", &[0], 176400, FileFormat::Wav);
+        let res = create_beat_grid_sliced_sample(
+            &fpath,
+            Some(500.0),
+            &NoteDivision::Straight(1, 4),
+            None,
+        );
+        let _ = std::fs::remove_file(&fpath);
+        assert!(res.is_err());
+        assert!(!fpath.with_extension("ot").exists());
+    }
+
+    #[test]
+    fn beat_grid_zero_crossings() {
+        // 110Hz sine, doesn't cross zero on the grid positions
+        let len = 176400;
+        let samples = (0..len)
+            .flat_map(|i| {
+                let x = (2.0 * std::f32::consts::PI * 110.0 * (i as f32 + 17.0) / 44100.0).sin();
+                [x * 0.5, x * 0.5]
+            })
+            .collect::<Vec<_>>();
+        let fpath = std::env::temp_dir()
+            .join("ot-tools-grids")
+            .join("beat-grid-zero.wav");
+        let _ = std::fs::create_dir_all(fpath.parent().unwrap());
+        AudioFile {
+            format: FileFormat::Wav,
+            channels: 2,
+            sample_rate: 44100,
+            bit_depth: 16,
+            len,
+            samples: samples.clone(),
+            file_path: PathBuf::new(),
+        }
+        .to_path(&fpath)
+        .unwrap();
+
+        let division = NoteDivision::Straight(1, 4);
+        create_beat_grid_sliced_sample(
+            &fpath,
+            Some(120.0),
+            &division,
+            Some(SliceSnapOpts::default()),
+        )
+        .unwrap();
+        let attrs = read_ot_file(&fpath);
+        let grid = beat_grid_positions(len, 44100, 120.0, &division);
+
+        assert_eq!(attrs.slices_len, 8);
+        assert_eq!(attrs.slices[0].trim_start, 0);
+        for (slice, pos) in attrs.slices[1..8].iter().zip(&grid[1..]) {
+            assert_ne!(slice.trim_start, *pos);
+            // half a cycle of 110Hz
+            assert!(slice.trim_start.abs_diff(*pos) <= 201, "{slice:?} {pos}");
+            // one sample of a 110Hz sine at 0.5 amplitude changes by ~0.008
+            assert!(
+                samples[slice.trim_start as usize * 2].abs() < 0.005,
+                "{slice:?}"
+            );
+        }
+        for i in 0..7 {
+            assert_eq!(attrs.slices[i].trim_end, attrs.slices[i + 1].trim_start);
+        }
+    }
+}
+
+mod snap {
+    use crate::actions::samples::{
+        batch_create_samplechains, create_equally_sliced_sample, create_randomly_sliced_sample,
+        create_samplechains_from_yaml, create_transient_sliced_sample, SliceProcOpts,
+        SliceSnapOpts,
+    };
+    use crate::audio::file::{AudioFile, FileFormat};
+    use crate::audio::onsets::OnsetOpts;
+    use crate::audio::zero_crossings::SnapMode;
+    use crate::test_utils::{sine, test_dir};
+    use ot_tools_io::read_type_from_bin_file;
+    use ot_tools_io::samples::{slices::Slice, SampleAttributes};
+    use std::path::{Path, PathBuf};
+
+    const TEST_DIR_GROUP: &str = "ot-tools-snap";
+
+    // 16-bit so the samples read back are the samples written
+    fn write_audio(fpath: &Path, samples: Vec<f32>) -> AudioFile {
+        let audio = AudioFile {
+            format: FileFormat::Wav,
+            channels: 1,
+            sample_rate: 44100,
+            bit_depth: 16,
+            len: samples.len() as u32,
+            samples,
+            file_path: PathBuf::new(),
+        };
+        audio.to_path(fpath).unwrap();
+        AudioFile::from_path(fpath).unwrap()
+    }
+
+    /// 110Hz sine, with a phase offset so it doesn't cross zero at the start of the audio.
+    fn offset_sine(len: usize) -> Vec<f32> {
+        sine(110.0, 44100, len + 50).split_off(50)
+    }
+
+    fn read_slices(audio_fpath: &Path) -> Vec<Slice> {
+        let attrs =
+            read_type_from_bin_file::<SampleAttributes>(&audio_fpath.with_extension("ot")).unwrap();
+        attrs.slices[..attrs.slices_len as usize].to_vec()
+    }
+
+    // one sample of a 110Hz sine at 0.5 amplitude changes by at most ~0.008
+    fn assert_near_zero(audio: &AudioFile, pos: u32) {
+        let x = audio.samples[pos as usize];
+        assert!(x.abs() < 0.005, "pos={pos} x={x}");
+    }
+
+    #[test]
+    fn linear() {
+        let fpath = test_dir(TEST_DIR_GROUP, "linear").join("sine.wav");
+        let audio = write_audio(&fpath, offset_sine(44100));
+        create_equally_sliced_sample(&fpath, 8, Some(SliceSnapOpts::default())).unwrap();
+        let slices = read_slices(&fpath);
+
+        assert_eq!(slices.len(), 8);
+        assert_eq!(slices[0].trim_start, 0);
+        assert!(slices[7].trim_end.abs_diff(44096) <= 441);
+        for (i, slice) in slices.iter().enumerate().skip(1) {
+            let unsnapped = i as u32 * 44100 / 8;
+            assert_ne!(slice.trim_start, unsnapped);
+            assert!(slice.trim_start.abs_diff(unsnapped) <= 441, "{slice:?}");
+            assert_near_zero(&audio, slice.trim_start);
+            assert_eq!(slices[i - 1].trim_end, slice.trim_start);
+        }
+    }
+
+    #[test]
+    fn random() {
+        let fpath = test_dir(TEST_DIR_GROUP, "random").join("sine.wav");
+        write_audio(&fpath, offset_sine(44100));
+        create_randomly_sliced_sample(&fpath, 16, Some(SliceSnapOpts::default())).unwrap();
+
+        // the window is limited by the shortest slice, so only check slices are still valid
+        for slice in read_slices(&fpath) {
+            assert!(slice.trim_start <= slice.trim_end, "{slice:?}");
+            assert!(slice.trim_end <= 44100, "{slice:?}");
+        }
+    }
+
+    #[test]
+    fn zero_window_unchanged() {
+        let fpath = test_dir(TEST_DIR_GROUP, "zero-window").join("sine.wav");
+        write_audio(&fpath, offset_sine(44100));
+        let snap = SliceSnapOpts {
+            mode: SnapMode::ZeroCrossing,
+            window_ms: Some(0.0),
+        };
+        create_equally_sliced_sample(&fpath, 4, Some(snap)).unwrap();
+        assert_eq!(
+            read_slices(&fpath)
+                .iter()
+                .map(|x| x.trim_start)
+                .collect::<Vec<_>>(),
+            vec![0, 11025, 22050, 33075]
+        );
+    }
+
+    /// Noise bursts, starting with a short quiet noise "pre-hit" before each burst, on top of
+    /// a constant low noise floor.
+    fn bursts_with_quiet_gaps(positions: &[usize], len: usize) -> Vec<f32> {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut x = (0..len)
+            .map(|_| rng.gen_range(-0.02..0.02))
+            .collect::<Vec<f32>>();
+        for pos in positions {
+            for (i, y) in x[*pos..(*pos + 4000).min(len)].iter_mut().enumerate() {
+                *y = rng.gen_range(-0.8..0.8) * (-(i as f32) / 800.0).exp();
+            }
+        }
+        x
+    }
+
+    #[test]
+    fn transient_snaps_before_onsets() {
+        let positions = [0, 11025, 22050, 33075];
+        let fpath = test_dir(TEST_DIR_GROUP, "transient").join("hits.wav");
+        write_audio(&fpath, bursts_with_quiet_gaps(&positions, 44100));
+
+        create_transient_sliced_sample(&fpath, &OnsetOpts::default(), None).unwrap();
+        let unsnapped = read_slices(&fpath);
+
+        let snap = SliceSnapOpts {
+            mode: SnapMode::LowEnergy,
+            window_ms: None,
+        };
+        create_transient_sliced_sample(&fpath, &OnsetOpts::default(), Some(snap)).unwrap();
+        let snapped = read_slices(&fpath);
+
+        assert_eq!(snapped.len(), positions.len());
+        assert_eq!(snapped[0].trim_start, 0);
+        for (a, b) in snapped.iter().zip(&unsnapped) {
+            assert!(a.trim_start <= b.trim_start, "{a:?} {b:?}");
+            assert!(b.trim_start - a.trim_start <= 441, "{a:?} {b:?}");
+        }
+        // quiet point before the hit, not in the middle of the previous hit's decay
+        for (slice, pos) in snapped.iter().zip(&positions).skip(1) {
+            assert!(slice.trim_start <= *pos as u32, "{slice:?} {pos}");
+        }
+    }
+
+    fn chain_slices(out_dir: &Path) -> (AudioFile, Vec<Slice>) {
+        let wav_fpath = out_dir.join("chain-1.wav");
+        (
+            AudioFile::from_path(&wav_fpath).unwrap(),
+            read_slices(&wav_fpath),
+        )
+    }
+
+    #[test]
+    fn chain_within_slices() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "chain");
+        let in_fpath = dirpath.join("sine.wav");
+        write_audio(&in_fpath, offset_sine(10000));
+
+        let opts = SliceProcOpts {
+            snap: Some(SliceSnapOpts::default()),
+            ..Default::default()
+        };
+        batch_create_samplechains(
+            &[in_fpath.clone(), in_fpath.clone(), in_fpath],
+            &dirpath,
+            &"chain".to_string(),
+            None,
+            Some(opts),
+            None,
+        )
+        .unwrap();
+        let (chain, slices) = chain_slices(&dirpath);
+
+        assert_eq!(slices.len(), 3);
+        for (i, slice) in slices.iter().enumerate() {
+            let (start, end) = (i as u32 * 10000, (i as u32 + 1) * 10000);
+            // moved inwards, staying within each file's audio
+            assert!(
+                slice.trim_start > start && slice.trim_start <= start + 441,
+                "{slice:?}"
+            );
+            assert!(
+                slice.trim_end < end && slice.trim_end >= end - 441,
+                "{slice:?}"
+            );
+            assert_near_zero(&chain, slice.trim_start);
+            assert_near_zero(&chain, slice.trim_end);
+        }
+    }
+
+    #[test]
+    fn chain_yaml() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "chain-yaml");
+        let in_fpath = dirpath.join("sine.wav");
+        write_audio(&in_fpath, offset_sine(10000));
+
+        let yaml_fpath = dirpath.join("chain.yaml");
+        std::fs::write(
+            &yaml_fpath,
+            format!(
+                "global_settings:\n  out_dir_path: {dirpath:?}\nchains:\n  - chain_name: chain\n    audio_processing:\n      snap:\n        mode: LowEnergy\n        window_ms: 5.0\n    audio_file_paths:\n      - {in_fpath:?}\n      - {in_fpath:?}\n"
+            ),
+        )
+        .unwrap();
+        create_samplechains_from_yaml(&yaml_fpath).unwrap();
+        let (_, slices) = chain_slices(&dirpath);
+
+        assert_eq!(slices.len(), 2);
+        assert!(slices[0].trim_start <= 220 && slices[0].trim_end < 10000);
+        assert!(slices[1].trim_start > 10000 && slices[1].trim_end >= 20000 - 220);
+    }
+}
+
+mod slice_proc {
+    use crate::actions::samples::{
+        batch_create_samplechains, SampleChainOpts, SilenceTrimOpts, SliceDeclickOpts,
+        SliceLoudnessOpts, SliceProcOpts, SliceStretchOpts,
+    };
+    use crate::audio::fades::FadeCurve;
+    use crate::audio::file::{AudioFile, FileFormat};
+    use crate::audio::loudness::{integrated_loudness, rms, true_peak, LoudnessMode};
+    use crate::test_utils::{sine, test_dir};
+    use ot_tools_io::projects::settings::ProjectSettingsErrors;
+    use ot_tools_io::read_type_from_bin_file;
+    use ot_tools_io::samples::SampleAttributes;
+    use std::path::{Path, PathBuf};
+
+    const TEST_DIR_GROUP: &str = "ot-tools-slice-proc";
+
+    /// Mono one-shot with `before`/`after` samples of silence around 1000 samples of sound.
+    fn write_one_shot(dirpath: &Path, name: &str, before: usize, after: usize) -> PathBuf {
+        let mut samples = vec![0.0_f32; before];
+        samples.extend((0..1000).map(|i| if i % 2 == 0 { 0.5 } else { -0.5 }));
+        samples.extend(vec![0.0_f32; after]);
+        write_mono(dirpath, name, samples)
+    }
+
+    fn write_mono(dirpath: &Path, name: &str, samples: Vec<f32>) -> PathBuf {
+        let fpath = dirpath.join(name);
+        AudioFile {
+            format: FileFormat::Wav,
+            channels: 1,
+            sample_rate: 44100,
+            bit_depth: 16,
+            len: samples.len() as u32,
+            samples,
+            file_path: PathBuf::new(),
+        }
+        .to_path(&fpath)
+        .unwrap();
+        fpath
+    }
+
+    /// Create a chain, returning the (start, end) of each slice and the chain's length.
+    fn create_chain(
+        dirpath: &Path,
+        fpaths: &[PathBuf],
+        bpm: Option<f32>,
+        opts: SliceProcOpts,
+    ) -> (Vec<(u32, u32)>, u32) {
+        let ot_opts = bpm.map(|bpm| SampleChainOpts {
+            bpm: Some(bpm),
+            gain: None,
+            timestretch_mode: None,
+            trig_quantization_mode: None,
+            loop_mode: None,
+        });
+        batch_create_samplechains(
+            fpaths,
+            dirpath,
+            &"chain".to_string(),
+            ot_opts,
+            Some(opts),
+            None,
+        )
+        .unwrap();
+
+        let chain = AudioFile::from_path(&dirpath.join("chain-1.wav")).unwrap();
+        let attrs =
+            read_type_from_bin_file::<SampleAttributes>(&dirpath.join("chain-1.ot")).unwrap();
+        let slices = attrs.slices[..attrs.slices_len as usize]
+            .iter()
+            .map(|x| (x.trim_start, x.trim_end))
+            .collect();
+        (slices, chain.len)
+    }
+
+    #[test]
+    fn trim_silence_default() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "trim-default");
+        let fpath = write_one_shot(&dirpath, "a.wav", 10000, 20000);
+        let trim = Some(SilenceTrimOpts::default());
+
+        let (slices, len) = create_chain(
+            &dirpath,
+            &[fpath.clone(), fpath],
+            None,
+            SliceProcOpts {
+                trim_silence: trim,
+                ..Default::default()
+            },
+        );
+        // 5ms (220 samples) of pre-roll
+        assert_eq!(slices, vec![(0, 1220), (1220, 2440)]);
+        assert_eq!(len, 2440);
+    }
+
+    #[test]
+    fn trim_silence_no_preroll() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "trim-no-preroll");
+        let fpath = write_one_shot(&dirpath, "a.wav", 10000, 20000);
+        let trim = Some(SilenceTrimOpts {
+            threshold_db: Some(-40.0),
+            preroll_ms: Some(0.0),
+        });
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[fpath],
+            None,
+            SliceProcOpts {
+                trim_silence: trim,
+                ..Default::default()
+            },
+        );
+        assert_eq!(slices, vec![(0, 1000)]);
+    }
+
+    #[test]
+    fn pad_steps() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "pad-steps");
+        let a = write_one_shot(&dirpath, "a.wav", 0, 0);
+        let b = write_one_shot(&dirpath, "b.wav", 0, 5000);
+
+        // one step at 120 BPM is 5512.5 samples
+        let (slices, len) = create_chain(
+            &dirpath,
+            &[a, b],
+            None,
+            SliceProcOpts {
+                pad_steps: Some(2),
+                ..Default::default()
+            },
+        );
+        assert_eq!(slices, vec![(0, 11025), (11025, 22050)]);
+        assert_eq!(len, 22050);
+    }
+
+    #[test]
+    fn pad_pow2_steps() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "pad-pow2");
+        // 1, 1.2 and 2.4 steps long at 120 BPM
+        let a = write_one_shot(&dirpath, "a.wav", 0, 4512);
+        let b = write_one_shot(&dirpath, "b.wav", 0, 5600);
+        let c = write_one_shot(&dirpath, "c.wav", 0, 12300);
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[a, b, c],
+            None,
+            SliceProcOpts {
+                pad_pow2_steps: Some(true),
+                ..Default::default()
+            },
+        );
+        let lens = slices.iter().map(|(s, e)| e - s).collect::<Vec<_>>();
+        assert_eq!(lens, vec![5513, 11025, 22050]);
+    }
+
+    #[test]
+    fn pad_pow2_steps_chain_tempo() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "pad-pow2-tempo");
+        let a = write_one_shot(&dirpath, "a.wav", 0, 4000);
+
+        // one step at 90 BPM is 7350 samples
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[a],
+            Some(90.0),
+            SliceProcOpts {
+                pad_pow2_steps: Some(true),
+                ..Default::default()
+            },
+        );
+        assert_eq!(slices, vec![(0, 7350)]);
+    }
+
+    #[test]
+    fn trim_then_pad() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "trim-pad");
+        let fpath = write_one_shot(&dirpath, "a.wav", 30000, 30000);
+        let trim = Some(SilenceTrimOpts::default());
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[fpath],
+            None,
+            SliceProcOpts {
+                trim_silence: trim,
+                pad_pow2_steps: Some(true),
+                ..Default::default()
+            },
+        );
+        assert_eq!(slices, vec![(0, 5513)]);
+
+        let chain = AudioFile::from_path(&dirpath.join("chain-1.wav")).unwrap();
+        assert_eq!(chain.samples[219], 0.0);
+        assert!(chain.samples[220].abs() > 0.4);
+        assert!(chain.samples[1219].abs() > 0.4);
+        assert!(chain.samples[1220..].iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn mixed_mono_stereo_slice_lens() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "mixed-channels");
+        let mono = write_one_shot(&dirpath, "mono.wav", 0, 0);
+        let stereo = dirpath.join("stereo.wav");
+        AudioFile {
+            format: FileFormat::Wav,
+            channels: 2,
+            sample_rate: 44100,
+            bit_depth: 16,
+            len: 500,
+            samples: vec![0.25; 1000],
+            file_path: PathBuf::new(),
+        }
+        .to_path(&stereo)
+        .unwrap();
+
+        // the upmixed mono file used to count its interleaved samples as its length, so the
+        // slices were (0, 2000) and (2000, 2500), past the end of the 1500 sample chain
+        let (slices, len) = create_chain(&dirpath, &[mono, stereo], None, SliceProcOpts::default());
+        assert_eq!(slices, vec![(0, 1000), (1000, 1500)]);
+        assert_eq!(len, 1500);
+    }
+
+    #[test]
+    fn stereo_time_stretch_slice_lens() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "stereo-stretch");
+        let stereo = dirpath.join("stereo.wav");
+        AudioFile {
+            format: FileFormat::Wav,
+            channels: 2,
+            sample_rate: 44100,
+            bit_depth: 16,
+            len: 500,
+            samples: vec![0.25; 1000],
+            file_path: PathBuf::new(),
+        }
+        .to_path(&stereo)
+        .unwrap();
+        let opts = SliceProcOpts {
+            time_stretch: Some(-1),
+            ..Default::default()
+        };
+
+        // the stretched stereo files used to count their interleaved samples as their length,
+        // so the slices were (0, 2000) and (2000, 4000), past the end of the 2000 sample chain
+        let (slices, len) = create_chain(&dirpath, &[stereo.clone(), stereo], None, opts);
+        assert_eq!(slices, vec![(0, 1000), (1000, 2000)]);
+        assert_eq!(len, 2000);
+    }
+
+    #[test]
+    fn pad_steps_too_short_err() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "pad-too-short");
+        let fpath = write_one_shot(&dirpath, "a.wav", 0, 10000);
+        let r = batch_create_samplechains(
+            &[fpath],
+            &dirpath,
+            &"chain".to_string(),
+            None,
+            Some(SliceProcOpts {
+                pad_steps: Some(1),
+                ..Default::default()
+            }),
+            None,
+        );
+        assert!(r.is_err());
+        assert!(!dirpath.join("chain-1.wav").exists());
+    }
+
+    #[test]
+    fn pad_steps_zero_tempo_err() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "pad-zero-tempo");
+        let fpath = write_one_shot(&dirpath, "a.wav", 0, 0);
+        let err = batch_create_samplechains(
+            &[fpath],
+            &dirpath,
+            &"chain".to_string(),
+            Some(SampleChainOpts {
+                bpm: Some(0.0),
+                gain: None,
+                timestretch_mode: None,
+                trig_quantization_mode: None,
+                loop_mode: None,
+            }),
+            Some(SliceProcOpts {
+                pad_steps: Some(16),
+                ..Default::default()
+            }),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ProjectSettingsErrors>(),
+            Some(&ProjectSettingsErrors::InvalidTempo(0.0))
+        );
+        assert!(!dirpath.join("chain-1.wav").exists());
+    }
+
+    #[test]
+    fn stereo_chain_bars() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "stereo-bars");
+        let mono = write_one_shot(&dirpath, "mono.wav", 0, 0);
+        let stereo = dirpath.join("stereo.wav");
+        AudioFile {
+            format: FileFormat::Wav,
+            channels: 2,
+            sample_rate: 44100,
+            bit_depth: 16,
+            len: 1000,
+            samples: vec![0.5; 2000],
+            file_path: PathBuf::new(),
+        }
+        .to_path(&stereo)
+        .unwrap();
+
+        // one bar at 120 BPM is 88200 frames, whatever the number of channels
+        let (_, len) = create_chain(
+            &dirpath,
+            &[mono, stereo],
+            Some(120.0),
+            SliceProcOpts {
+                pad_steps: Some(8),
+                ..Default::default()
+            },
+        );
+        assert_eq!(len, 88200);
+        let attrs =
+            read_type_from_bin_file::<SampleAttributes>(&dirpath.join("chain-1.ot")).unwrap();
+        assert_eq!(attrs.trim_len, 100);
+        assert_eq!(attrs.loop_len, 100);
+    }
+
+    /// Frequency of a (mono) sine from the number of times it crosses zero.
+    fn frequency(x: &[f32]) -> f32 {
+        let crossings = x
+            .windows(2)
+            .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+            .count();
+        crossings as f32 / 2.0 / (x.len() as f32 / 44100.0)
+    }
+
+    /// Decaying noise bursts on every beat.
+    fn drum_loop(bpm: f32, n_beats: usize) -> Vec<f32> {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(0);
+        let beat_len = 60.0 * 44100.0 / bpm;
+        let len = (beat_len * n_beats as f32).round() as usize;
+        let mut x = vec![0.0_f32; len];
+        for beat in 0..n_beats {
+            let pos = (beat as f32 * beat_len).round() as usize;
+            for (i, y) in x[pos..(pos + 3000).min(len)].iter_mut().enumerate() {
+                *y = rng.gen_range(-0.9..0.9) * (-(i as f32) / 500.0).exp();
+            }
+        }
+        x
+    }
+
+    fn chain_tempo(dirpath: &Path) -> u32 {
+        read_type_from_bin_file::<SampleAttributes>(&dirpath.join("chain-1.ot"))
+            .unwrap()
+            .tempo
+    }
+
+    #[test]
+    fn stretch_factor() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "stretch-factor");
+        let fpath = write_mono(&dirpath, "a.wav", sine(440.0, 44100, 10000));
+        let stretch = Some(SliceStretchOpts {
+            factor: Some(1.5),
+            ..Default::default()
+        });
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[fpath.clone(), fpath],
+            None,
+            SliceProcOpts {
+                stretch,
+                ..Default::default()
+            },
+        );
+        assert_eq!(slices, vec![(0, 15000), (15000, 30000)]);
+        // default tempo
+        assert_eq!(chain_tempo(&dirpath), 120 * 24);
+
+        let chain = AudioFile::from_path(&dirpath.join("chain-1.wav")).unwrap();
+        let f = frequency(&chain.samples[1000..14000]);
+        assert!((f - 440.0).abs() < 5.0, "{f}");
+    }
+
+    #[test]
+    fn stretch_source_bpm() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "stretch-source-bpm");
+        let fpath = write_mono(&dirpath, "a.wav", sine(440.0, 44100, 12000));
+        let stretch = Some(SliceStretchOpts {
+            bpm: Some(100.0),
+            source_bpm: Some(125.0),
+            // ignored when a tempo is set
+            factor: Some(3.0),
+        });
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[fpath],
+            None,
+            SliceProcOpts {
+                stretch,
+                ..Default::default()
+            },
+        );
+        assert_eq!(slices, vec![(0, 15000)]);
+        assert_eq!(chain_tempo(&dirpath), 100 * 24);
+    }
+
+    #[test]
+    fn stretch_detected_bpm() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "stretch-detected-bpm");
+        // 8 beats at 120 BPM is 4s
+        let fpath = write_mono(&dirpath, "a.wav", drum_loop(120.0, 8));
+        let stretch = Some(SliceStretchOpts {
+            bpm: Some(96.0),
+            ..Default::default()
+        });
+
+        // tempo in the octatrack settings is used over the stretched tempo
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[fpath],
+            Some(130.0),
+            SliceProcOpts {
+                stretch,
+                ..Default::default()
+            },
+        );
+        assert_eq!(slices, vec![(0, 220500)]);
+        assert_eq!(chain_tempo(&dirpath), 130 * 24);
+    }
+
+    #[test]
+    fn stretch_tempo_not_detected_err() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "stretch-not-detected");
+        let fpath = write_mono(&dirpath, "a.wav", sine(440.0, 44100, 10000));
+        let stretch = Some(SliceStretchOpts {
+            bpm: Some(96.0),
+            ..Default::default()
+        });
+        let r = batch_create_samplechains(
+            &[fpath],
+            &dirpath,
+            &"chain".to_string(),
+            None,
+            Some(SliceProcOpts {
+                stretch,
+                ..Default::default()
+            }),
+            None,
+        );
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn stretch_tempo_out_of_range_err() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "stretch-tempo-range");
+        let fpath = write_mono(&dirpath, "a.wav", sine(440.0, 44100, 10000));
+        let stretch = Some(SliceStretchOpts {
+            bpm: Some(301.0),
+            source_bpm: Some(120.0),
+            ..Default::default()
+        });
+        let err = batch_create_samplechains(
+            &[fpath],
+            &dirpath,
+            &"chain".to_string(),
+            None,
+            Some(SliceProcOpts {
+                stretch,
+                ..Default::default()
+            }),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ProjectSettingsErrors>(),
+            Some(&ProjectSettingsErrors::InvalidTempo(301.0))
+        );
+    }
+
+    #[test]
+    fn pitch_shift() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "pitch-shift");
+        let fpath = write_mono(&dirpath, "a.wav", sine(440.0, 44100, 22050));
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[fpath],
+            None,
+            SliceProcOpts {
+                pitch_semitones: Some(7.0),
+                pitch_cents: Some(50.0),
+                ..Default::default()
+            },
+        );
+        assert_eq!(slices, vec![(0, 22050)]);
+
+        // 7.5 semitones up
+        let chain = AudioFile::from_path(&dirpath.join("chain-1.wav")).unwrap();
+        let f = frequency(&chain.samples[2000..20000]);
+        assert!((f - 678.57).abs() < 5.0, "{f}");
+    }
+
+    /// Slices of the chain's audio.
+    fn chain_slice_audio(dirpath: &Path, slices: &[(u32, u32)]) -> Vec<Vec<f32>> {
+        let chain = AudioFile::from_path(&dirpath.join("chain-1.wav")).unwrap();
+        slices
+            .iter()
+            .map(|(start, end)| chain.samples[*start as usize..*end as usize].to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn loudness_lufs() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "loudness-lufs");
+        // a quiet high and a loud low sine
+        let a = write_mono(
+            &dirpath,
+            "a.wav",
+            sine(4000.0, 44100, 22050).iter().map(|x| x * 0.1).collect(),
+        );
+        let b = write_mono(&dirpath, "b.wav", sine(100.0, 44100, 22050));
+        let loudness = Some(SliceLoudnessOpts {
+            target: Some(-20.0),
+            ..Default::default()
+        });
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[a, b],
+            None,
+            SliceProcOpts {
+                loudness,
+                // ignored
+                normalize: Some(true),
+                ..Default::default()
+            },
+        );
+        for x in chain_slice_audio(&dirpath, &slices) {
+            let lufs = integrated_loudness(&x, 1, 44100);
+            assert!((lufs + 20.0).abs() < 0.1, "{lufs}");
+        }
+    }
+
+    #[test]
+    fn loudness_rms_ceiling() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "loudness-rms");
+        let a = write_mono(
+            &dirpath,
+            "a.wav",
+            sine(440.0, 44100, 22050).iter().map(|x| x * 0.1).collect(),
+        );
+        let loudness = Some(SliceLoudnessOpts {
+            mode: LoudnessMode::Rms,
+            target: Some(-3.0),
+            true_peak_ceiling: Some(-6.0),
+        });
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[a],
+            None,
+            SliceProcOpts {
+                loudness,
+                ..Default::default()
+            },
+        );
+        // a sine's RMS level is 3dB below its peak, so the ceiling is reached first
+        let x = &chain_slice_audio(&dirpath, &slices)[0];
+        let (tp, level) = (true_peak(x, 1), rms(x));
+        assert!((tp + 6.0).abs() < 0.1, "{tp}");
+        assert!((level + 9.0).abs() < 0.1, "{level}");
+    }
+
+    fn assert_level(x: &[f32], pos: usize, expected: f32) {
+        assert!((x[pos] - expected).abs() < 0.001, "pos={pos} x={}", x[pos]);
+    }
+
+    #[test]
+    fn fade_ms() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "fade-ms");
+        let a = write_mono(&dirpath, "a.wav", vec![0.5; 4410]);
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[a],
+            None,
+            SliceProcOpts {
+                fade_in_ms: Some(10.0),
+                fade_out_ms: Some(20.0),
+                ..Default::default()
+            },
+        );
+        // 441 samples fade in, 882 samples fade out
+        let x = &chain_slice_audio(&dirpath, &slices)[0];
+        assert_level(x, 0, 0.0);
+        assert_level(x, 220, 0.25);
+        assert_level(x, 441, 0.5);
+        assert_level(x, 4410 - 883, 0.5);
+        assert_level(x, 4410 - 441, 0.25);
+        assert_level(x, 4409, 0.0);
+    }
+
+    #[test]
+    fn fade_ms_overrides_percent() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "fade-ms-percent");
+        let a = write_mono(&dirpath, "a.wav", vec![0.5; 4410]);
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[a],
+            None,
+            SliceProcOpts {
+                fade_out_percent: Some(0.5),
+                fade_out_ms: Some(10.0),
+                ..Default::default()
+            },
+        );
+        let x = &chain_slice_audio(&dirpath, &slices)[0];
+        assert_level(x, 2205, 0.5);
+        assert_level(x, 4409, 0.0);
+    }
+
+    #[test]
+    fn fade_percent_curve() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "fade-percent-curve");
+        let a = write_mono(&dirpath, "a.wav", vec![0.5; 4410]);
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[a],
+            None,
+            SliceProcOpts {
+                fade_in_percent: Some(0.5),
+                fade_curve: Some(FadeCurve::EqualPower),
+                ..Default::default()
+            },
+        );
+        // half way through the fade in
+        let x = &chain_slice_audio(&dirpath, &slices)[0];
+        assert_level(x, 0, 0.0);
+        assert_level(x, 1102, 0.5 * 0.5_f32.sqrt());
+        assert_level(x, 2205, 0.5);
+    }
+
+    #[test]
+    fn fade_negative_ms_err() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "fade-negative");
+        let a = write_mono(&dirpath, "a.wav", vec![0.5; 4410]);
+        let opts = SliceProcOpts {
+            fade_in_ms: Some(-1.0),
+            ..Default::default()
+        };
+        let r =
+            batch_create_samplechains(&[a], &dirpath, &"chain".to_string(), None, Some(opts), None);
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn declick() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "declick");
+        let a = write_mono(&dirpath, "a.wav", vec![0.5; 4410]);
+        let b = write_mono(&dirpath, "b.wav", vec![-0.5; 2000]);
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[a, b],
+            None,
+            SliceProcOpts {
+                declick: Some(SliceDeclickOpts::default()),
+                ..Default::default()
+            },
+        );
+        // 2ms (88 samples) S-curve fades at both ends of each slice
+        for (x, level) in chain_slice_audio(&dirpath, &slices).iter().zip([0.5, -0.5]) {
+            let n = x.len();
+            assert_level(x, 0, 0.0);
+            assert_level(x, 44, level / 2.0);
+            assert_level(x, 88, level);
+            assert_level(x, n - 89, level);
+            assert_level(x, n - 1, 0.0);
         }
     }
+
+    #[test]
+    fn declick_len() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "declick-len");
+        let a = write_mono(&dirpath, "a.wav", vec![0.5; 4410]);
+
+        let (slices, _) = create_chain(
+            &dirpath,
+            &[a],
+            None,
+            SliceProcOpts {
+                declick: Some(SliceDeclickOpts {
+                    len_ms: Some(10.0),
+                    curve: Some(FadeCurve::Linear),
+                }),
+                ..Default::default()
+            },
+        );
+        let x = &chain_slice_audio(&dirpath, &slices)[0];
+        assert_level(x, 88, 0.1);
+        assert_level(x, 441, 0.5);
+    }
+}
+mod convert {
+    use crate::actions::samples::{convert_audio_file, convert_audio_files, FileFormatOpts};
+    use crate::audio::file::{AudioFile, FileFormat};
+    use crate::test_utils::{sine, test_dir};
+    use std::path::{Path, PathBuf};
+
+    fn write_wav(path: &Path, rate: u32, bits: u16, float: bool, channels: u16) {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate: rate,
+            bits_per_sample: bits,
+            sample_format: if float {
+                hound::SampleFormat::Float
+            } else {
+                hound::SampleFormat::Int
+            },
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for x in sine(440.0, rate, rate as usize / 10) {
+            for _ in 0..channels {
+                match (float, bits) {
+                    (true, _) => writer.write_sample(x).unwrap(),
+                    (false, 8) => writer.write_sample((x * 127.0) as i8).unwrap(),
+                    (false, _) => writer
+                        .write_sample((x * (1 << (bits - 1)) as f32) as i32)
+                        .unwrap(),
+                }
+            }
+        }
+        writer.finalize().unwrap();
+    }
+
+    fn write_aiff_48k(path: &Path) {
+        let info = aifc::AifcWriteInfo {
+            file_format: aifc::FileFormat::Aiff,
+            channels: 1,
+            sample_rate: 48000.0,
+            sample_format: aifc::SampleFormat::I16,
+        };
+        let mut buf = std::io::BufWriter::new(std::fs::File::create(path).unwrap());
+        let mut writer = aifc::AifcWriter::new(&mut buf, &info).unwrap();
+        let data = sine(440.0, 48000, 4800)
+            .iter()
+            .map(|x| (x * 32767.0) as i16)
+            .collect::<Vec<_>>();
+        writer.write_samples_i16(&data).unwrap();
+        writer.finalize().unwrap();
+    }
+
+    fn setup(name: &str) -> (PathBuf, PathBuf) {
+        let base = test_dir("ot-tools-convert", name);
+        let in_dir = base.join("in");
+        let out_dir = base.join("out");
+        std::fs::create_dir_all(in_dir.join("sub")).unwrap();
+
+        write_wav(&in_dir.join("float-48k.wav"), 48000, 32, true, 2);
+        write_wav(&in_dir.join("sub").join("int-96k.wav"), 96000, 24, false, 1);
+        write_wav(&in_dir.join("sub").join("8-bit.wav"), 44100, 8, false, 1);
+        write_aiff_48k(&in_dir.join("sub").join("aiff-48k.aif"));
+        write_wav(&in_dir.join("surround.wav"), 44100, 16, false, 6);
+        std::fs::write(in_dir.join("notes.txt"), "not audio").unwrap();
+
+        (in_dir, out_dir)
+    }
+
+    #[test]
+    fn folder_keeps_structure() {
+        let (in_dir, out_dir) = setup("structure");
+        let converted = convert_audio_files(&in_dir, &out_dir, None).unwrap();
+
+        // 6 channel file is skipped, text file ignored
+        assert_eq!(converted.len(), 4);
+        for (fname, bit_depth, format) in [
+            ("float-48k.wav", 24, FileFormat::Wav),
+            ("sub/int-96k.wav", 24, FileFormat::Wav),
+            ("sub/8-bit.wav", 16, FileFormat::Wav),
+            ("sub/aiff-48k.aiff", 16, FileFormat::Aiff),
+        ] {
+            let audio = AudioFile::from_path(&out_dir.join(fname)).unwrap();
+            assert!(audio.is_compatible_spec(), "{fname}");
+            assert_eq!(audio.bit_depth, bit_depth, "{fname}");
+            assert_eq!(audio.format, format, "{fname}");
+            assert_eq!(audio.len, 4410, "{fname}");
+        }
+        assert!(!out_dir.join("surround.wav").exists());
+        assert!(!out_dir.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn folder_format_opts() {
+        let (in_dir, out_dir) = setup("opts");
+        let opts = FileFormatOpts {
+            bit_depth: Some(16),
+            format: Some(FileFormat::Aiff),
+        };
+        let converted = convert_audio_files(&in_dir, &out_dir, Some(opts)).unwrap();
+
+        assert_eq!(converted.len(), 4);
+        for fpath in converted {
+            assert_eq!(fpath.extension().unwrap(), "aiff");
+            let audio = AudioFile::from_path(&fpath).unwrap();
+            assert_eq!(audio.bit_depth, 16);
+            assert_eq!(audio.format, FileFormat::Aiff);
+        }
+    }
+
+    #[test]
+    fn compatible_file_unchanged() {
+        let in_fpath = PathBuf::from("../data/tests/misc/test.wav");
+        let out_fpath = std::env::temp_dir().join("ot-tools-convert-compatible.wav");
+        convert_audio_file(&in_fpath, &out_fpath, None).unwrap();
+
+        let original = AudioFile::from_path(&in_fpath).unwrap();
+        let converted = AudioFile::from_path(&out_fpath).unwrap();
+        let _ = std::fs::remove_file(&out_fpath);
+        assert_eq!(original.samples, converted.samples);
+    }
+
+    #[test]
+    fn not_a_directory_err() {
+        let fpath = PathBuf::from("../data/tests/misc/test.wav");
+        assert!(convert_audio_files(&fpath, &std::env::temp_dir(), None).is_err());
+    }
+
+    #[test]
+    fn out_dir_in_in_dir_err() {
+        let (in_dir, _) = setup("nested");
+        assert!(convert_audio_files(&in_dir, &in_dir, None).is_err());
+        assert!(convert_audio_files(&in_dir, &in_dir.join("sub"), None).is_err());
+        assert!(convert_audio_files(&in_dir, &in_dir.join("new").join("out"), None).is_err());
+        assert!(!in_dir.join("new").exists());
+        assert!(!in_dir.join("sub").join("8-bit.aiff").exists());
+    }
+
+    #[test]
+    fn existing_out_file_err() {
+        let (in_dir, out_dir) = setup("existing");
+        std::fs::create_dir_all(out_dir.join("sub")).unwrap();
+        std::fs::write(out_dir.join("sub").join("8-bit.wav"), "existing").unwrap();
+
+        assert!(convert_audio_files(&in_dir, &out_dir, None).is_err());
+        assert_eq!(
+            std::fs::read(out_dir.join("sub").join("8-bit.wav")).unwrap(),
+            b"existing"
+        );
+        assert!(!out_dir.join("float-48k.wav").exists());
+    }
+
+    #[test]
+    fn same_stem_collision_err() {
+        let (in_dir, out_dir) = setup("collision");
+        write_wav(
+            &in_dir.join("sub").join("aiff-48k.wav"),
+            48000,
+            16,
+            false,
+            1,
+        );
+        let opts = FileFormatOpts {
+            bit_depth: None,
+            format: Some(FileFormat::Wav),
+        };
+
+        assert!(convert_audio_files(&in_dir, &out_dir, Some(opts)).is_err());
+        assert!(!out_dir.exists());
+    }
+}
+
+mod analyze {
+    use crate::actions::samples::analyze_audio_files;
+    use crate::audio::file::{AudioFile, FileFormat};
+    use crate::test_utils::{sine, test_dir};
+    use std::path::{Path, PathBuf};
+
+    const TEST_DIR_GROUP: &str = "ot-tools-analyze";
+
+    // half amplitude 1kHz sine
+    fn write_sine(fpath: &Path) {
+        let samples = sine(1000.0, 44100, 44100);
+        AudioFile {
+            format: FileFormat::from_path(fpath).unwrap(),
+            channels: 1,
+            sample_rate: 44100,
+            bit_depth: 24,
+            len: samples.len() as u32,
+            samples,
+            file_path: PathBuf::new(),
+        }
+        .to_path(fpath)
+        .unwrap();
+    }
+
+    #[test]
+    fn levels() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "levels");
+        let fpath = dirpath.join("sine.wav");
+        write_sine(&fpath);
+
+        let analyzed = analyze_audio_files(std::slice::from_ref(&fpath)).unwrap();
+        assert_eq!(analyzed.len(), 1);
+        let (path, stats) = &analyzed[0];
+        assert_eq!(path, &fpath);
+        assert!((stats.peak + 6.02).abs() < 0.01, "{stats:?}");
+        assert!((stats.true_peak + 6.02).abs() < 0.05, "{stats:?}");
+        assert!((stats.rms + 9.03).abs() < 0.01, "{stats:?}");
+        // a 1kHz sine is ~0.7dB louder after K-weighting
+        assert!((stats.lufs + 9.03).abs() < 1.0, "{stats:?}");
+    }
+
+    #[test]
+    fn directory() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "directory");
+        write_sine(&dirpath.join("a.wav"));
+        std::fs::create_dir_all(dirpath.join("sub")).unwrap();
+        write_sine(&dirpath.join("sub").join("b.aiff"));
+        write_sine(&dirpath.join(".hidden.wav"));
+        std::fs::write(dirpath.join("notes.txt"), "not audio").unwrap();
+        // unreadable files are skipped
+        std::fs::write(dirpath.join("broken.wav"), "not audio").unwrap();
+
+        let analyzed = analyze_audio_files(std::slice::from_ref(&dirpath)).unwrap();
+        let paths = analyzed.iter().map(|(x, _)| x.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![dirpath.join("a.wav"), dirpath.join("sub").join("b.aiff")]
+        );
+        assert_eq!(analyzed[0].1, analyzed[1].1);
+    }
+
+    #[test]
+    fn silence() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "silence");
+        let fpath = dirpath.join("silence.wav");
+        AudioFile {
+            format: FileFormat::Wav,
+            channels: 2,
+            sample_rate: 44100,
+            bit_depth: 16,
+            len: 1000,
+            samples: vec![0.0; 2000],
+            file_path: PathBuf::new(),
+        }
+        .to_path(&fpath)
+        .unwrap();
+
+        let stats = analyze_audio_files(&[fpath]).unwrap()[0].1;
+        assert_eq!(stats.lufs, f32::NEG_INFINITY);
+        assert_eq!(stats.rms, f32::NEG_INFINITY);
+    }
+}
+
+mod detect_bpm {
+    use crate::actions::samples::{
+        create_default_ot_file_for_audio_file, create_equally_sliced_sample, detect_bpm_audio_file,
+        detect_bpm_audio_files, DEFAULT_MIN_TEMPO_CONFIDENCE,
+    };
+    use crate::audio::file::{AudioFile, FileFormat};
+    use crate::test_utils::test_dir;
+    use ot_tools_io::samples::SampleAttributes;
+    use ot_tools_io::{
+        read_type_from_bin_file, write_type_to_bin_file, yaml_file_to_type, CheckHeader,
+    };
+    use std::path::{Path, PathBuf};
+
+    const TEST_DIR_GROUP: &str = "ot-tools-detect-bpm";
+
+    // stereo drum loop-ish audio, a decaying click on every beat
+    fn write_loop(fpath: &Path, bpm: f32, n_beats: usize) {
+        let beat_len = 60.0 * 44100.0 / bpm;
+        let len = (beat_len * n_beats as f32).round() as usize;
+        let mut samples = vec![0.0_f32; len * 2];
+        for beat in 0..n_beats {
+            let pos = (beat as f32 * beat_len).round() as usize;
+            for i in 0..3000.min(len - pos) {
+                let x = (i as f32 * 0.3).sin() * 0.8 * (-(i as f32) / 500.0).exp();
+                samples[(pos + i) * 2] = x;
+                samples[(pos + i) * 2 + 1] = x;
+            }
+        }
+
+        AudioFile {
+            format: FileFormat::from_path(fpath).unwrap(),
+            channels: 2,
+            sample_rate: 44100,
+            bit_depth: 16,
+            len: len as u32,
+            samples,
+            file_path: PathBuf::new(),
+        }
+        .to_path(fpath)
+        .unwrap();
+    }
+
+    fn read_ot_file(audio_fpath: &Path) -> SampleAttributes {
+        read_type_from_bin_file::<SampleAttributes>(&audio_fpath.with_extension("ot")).unwrap()
+    }
+
+    #[test]
+    fn default_ot_file_detected_tempo() {
+        let fpath = test_dir(TEST_DIR_GROUP, "default").join("loop.wav");
+        write_loop(&fpath, 140.0, 8);
+        create_default_ot_file_for_audio_file(&fpath).unwrap();
+
+        let attrs = read_ot_file(&fpath);
+        assert_eq!(attrs.tempo, 140 * 24);
+        // 2 bars
+        assert_eq!(attrs.trim_len, 200);
+        assert_eq!(attrs.loop_len, 200);
+    }
+
+    #[test]
+    fn default_ot_file_is_binary() {
+        let fpath = test_dir(TEST_DIR_GROUP, "default-binary").join("test.wav");
+        std::fs::copy("../data/tests/misc/test.wav", &fpath).unwrap();
+        create_default_ot_file_for_audio_file(&fpath).unwrap();
+
+        assert!(read_ot_file(&fpath).check_header());
+        assert!(yaml_file_to_type::<SampleAttributes>(&fpath.with_extension("ot")).is_err());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_wav_file_aliases() {
+        use crate::actions::samples::{
+            create_default_ot_file_for_wav_file, create_slices_from_audio_files,
+            create_slices_from_wavfiles,
+        };
+        use crate::audio::wav::WavFile;
+        use crate::utils::{
+            get_bin_nbars_ileaved_audio_files, get_bin_nbars_ileaved_wavfiles,
+            get_otsample_nbars_from_audio_file, get_otsample_nbars_from_wavfile,
+        };
+
+        let fpath = test_dir(TEST_DIR_GROUP, "deprecated-aliases").join("test.wav");
+        std::fs::copy("../data/tests/misc/test.wav", &fpath).unwrap();
+        create_default_ot_file_for_wav_file(&fpath).unwrap();
+        assert!(read_ot_file(&fpath).check_header());
+
+        let wav = WavFile::from_path(&fpath).unwrap();
+        let audio = AudioFile::from(wav.clone());
+        assert_eq!(
+            get_otsample_nbars_from_wavfile(&wav, &120.0).unwrap(),
+            get_otsample_nbars_from_audio_file(&audio, &120.0).unwrap()
+        );
+        assert_eq!(
+            get_bin_nbars_ileaved_wavfiles(std::slice::from_ref(&wav), &120.0, 1).unwrap(),
+            get_bin_nbars_ileaved_audio_files(std::slice::from_ref(&audio), &120.0).unwrap()
+        );
+        let (old, new) = (
+            create_slices_from_wavfiles(&[wav], 0).unwrap(),
+            create_slices_from_audio_files(&[audio], 0).unwrap(),
+        );
+        assert_eq!(old.count, new.count);
+        assert_eq!(old.slices[0].trim_end, new.slices[0].trim_end);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_wav_file_methods() {
+        use crate::audio::wav::WavFile;
+
+        let wav = WavFile::from_path(Path::new("../data/tests/misc/test.wav")).unwrap();
+        let audio = AudioFile::from(wav.clone());
+
+        let (mut old, mut new) = (wav.clone(), audio.clone());
+        old.normalize().unwrap();
+        new.normalize().unwrap();
+        assert_eq!(old.samples, new.samples);
+
+        let (mut old, mut new) = (wav.clone(), audio.clone());
+        old.resample_time_stretch(-1).unwrap();
+        new.resample_time_stretch(-1).unwrap();
+        assert_eq!((old.samples, old.len), (new.samples, new.len));
+
+        let (mut old, mut new) = (wav.clone(), audio.clone());
+        old.linear_fade_in(0.5).unwrap();
+        old.linear_fade_out(0.5).unwrap();
+        new.linear_fade_in(0.5).unwrap();
+        new.linear_fade_out(0.5).unwrap();
+        assert_eq!(old.samples, new.samples);
+        assert!(old.linear_fade_in(1.5).is_err());
+
+        let (mut old, mut new) = (wav, audio);
+        old.mono_to_stereo_interleaved().unwrap();
+        new.mono_to_stereo_interleaved().unwrap();
+        assert_eq!(old.spec.channels, 2);
+        assert_eq!(old.samples, new.samples);
+    }
+
+    #[test]
+    fn default_ot_file_short_audio_default_tempo() {
+        let fpath = test_dir(TEST_DIR_GROUP, "default-short").join("test.wav");
+        std::fs::copy("../data/tests/misc/test.wav", &fpath).unwrap();
+        create_default_ot_file_for_audio_file(&fpath).unwrap();
+        assert_eq!(read_ot_file(&fpath).tempo, 120 * 24);
+    }
+
+    #[test]
+    fn grid_detected_tempo() {
+        let fpath = test_dir(TEST_DIR_GROUP, "grid").join("loop.aiff");
+        write_loop(&fpath, 90.0, 8);
+        create_equally_sliced_sample(&fpath, 8, None).unwrap();
+
+        let attrs = read_ot_file(&fpath);
+        assert_eq!(attrs.tempo, 90 * 24);
+        assert_eq!(attrs.slices_len, 8);
+    }
+
+    #[test]
+    fn write_existing_ot_file() {
+        let fpath = test_dir(TEST_DIR_GROUP, "write").join("loop.wav");
+        write_loop(&fpath, 100.0, 8);
+        create_equally_sliced_sample(&fpath, 4, None).unwrap();
+
+        // pretend the file was created at some other tempo
+        let mut attrs = read_ot_file(&fpath);
+        attrs.tempo = 120 * 24;
+        attrs.trim_len = 0;
+        attrs.loop_len = 0;
+        attrs.checksum = 0;
+        write_type_to_bin_file(&attrs, &fpath.with_extension("ot")).unwrap();
+
+        let estimate = detect_bpm_audio_file(&fpath, true, DEFAULT_MIN_TEMPO_CONFIDENCE)
+            .unwrap()
+            .unwrap();
+        assert_eq!(estimate.bpm.round(), 100.0);
+
+        let updated = read_ot_file(&fpath);
+        assert_eq!(updated.tempo, 100 * 24);
+        assert_eq!(updated.trim_len, 200);
+        assert_eq!(updated.loop_len, 200);
+        assert_eq!(updated.slices, attrs.slices);
+        assert_eq!(updated.slices_len, 4);
+    }
+
+    #[test]
+    fn write_low_confidence_unchanged() {
+        let fpath = test_dir(TEST_DIR_GROUP, "low-confidence").join("loop.wav");
+        write_loop(&fpath, 100.0, 8);
+        create_equally_sliced_sample(&fpath, 4, None).unwrap();
+        let attrs = read_ot_file(&fpath);
+
+        assert!(detect_bpm_audio_file(&fpath, true, 1.1).unwrap().is_some());
+        assert_eq!(read_ot_file(&fpath), attrs);
+    }
+
+    #[test]
+    fn write_no_ot_file_err() {
+        let fpath = test_dir(TEST_DIR_GROUP, "no-ot-file").join("loop.wav");
+        write_loop(&fpath, 100.0, 8);
+        assert!(detect_bpm_audio_file(&fpath, true, DEFAULT_MIN_TEMPO_CONFIDENCE).is_err());
+        assert!(detect_bpm_audio_file(&fpath, false, DEFAULT_MIN_TEMPO_CONFIDENCE).is_ok());
+    }
+
+    #[test]
+    fn batch_directory() {
+        let dirpath = test_dir(TEST_DIR_GROUP, "batch");
+        std::fs::create_dir_all(dirpath.join("sub")).unwrap();
+        write_loop(&dirpath.join("a.wav"), 95.0, 8);
+        write_loop(&dirpath.join("sub").join("b.aiff"), 170.0, 16);
+        std::fs::write(dirpath.join("notes.txt"), "not audio").unwrap();
+
+        let detected = detect_bpm_audio_files(
+            std::slice::from_ref(&dirpath),
+            false,
+            DEFAULT_MIN_TEMPO_CONFIDENCE,
+        )
+        .unwrap();
+        assert_eq!(
+            detected
+                .iter()
+                .map(|(p, x)| (
+                    p.strip_prefix(&dirpath).unwrap().to_path_buf(),
+                    x.bpm.round()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (PathBuf::from("a.wav"), 95.0),
+                (PathBuf::from("sub").join("b.aiff"), 170.0)
+            ]
+        );
+    }
 }
 
 mod indexing {
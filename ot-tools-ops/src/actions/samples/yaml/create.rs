@@ -21,7 +21,7 @@ pub struct YamlChainCreateSampleChains {
     pub octatrack_settings: Option<SampleChainOpts>,
     /// Per-slice audio modifications to apply
     pub audio_processing: Option<SliceProcOpts>,
-    /// Output file format: 16-bit WAV / 24-bit WAV
+    /// Output file format: 16/24-bit WAV / AIFF
     pub audio_format: Option<FileFormatOpts>,
     pub audio_file_paths: Vec<PathBuf>,
 }
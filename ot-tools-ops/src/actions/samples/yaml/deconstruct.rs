@@ -1,5 +1,6 @@
 //! Read a YAML file config to batch deconstruct sliced sample chains into constituent slice samples.
 
+use crate::actions::samples::FileFormatOpts;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -7,6 +8,8 @@ use std::path::PathBuf;
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct YamlChainDeconstructConfigGlobalSettings {
     pub out_dir_path: PathBuf,
+    /// Output file format, defaults to the format of each sliced sample
+    pub audio_format: Option<FileFormatOpts>,
 }
 
 // Deliberately does not include the trim / loop length settings
@@ -1,13 +1,11 @@
 //! A 'Sample Directory' contains samples that a user might want to load onto an Octatrack compact flash card.
 
 use std::path::PathBuf;
-use std::{fs::File, io::Read};
 
 use crate::audio::utils::scan_dir_path_for_audio_files;
+use crate::utils::get_md5_hash_from_path;
 use crate::{OctatoolErrors, RBoxErr};
-use base64ct::{Base64, Encoding};
 use log::{debug, info};
-use md5::Digest;
 use ot_tools_derive::{Decodeable, Encodeable};
 use serde::{Deserialize, Serialize};
 
@@ -25,35 +23,6 @@ fn get_stem_from_pathbuf(pathbuf: &PathBuf) -> RBoxErr<String> {
     Ok(fname)
 }
 
-fn get_md5_hash_from_pathbuf(pathbuf: &PathBuf) -> Result<String, Box<dyn std::error::Error>> {
-    debug!("Getting md5 hash: file={pathbuf:#?}");
-
-    let mut f: File = File::open(pathbuf)?;
-    let mut buf: Vec<u8> = vec![];
-    let _: usize = f.read_to_end(&mut buf)?;
-    let md5_hash: Digest = md5::compute(buf);
-
-    let mut buf = [0u8; 32];
-
-    // TODO: hard exit on error
-    /*
-    the trait bound `InvalidLengthError: StdError` is not satisfied
-
-    the following other types implement trait `FromResidual<R>`:
-    `Result<T, F>` implements `FromResidual<Result<Infallible, E>>`
-    `Result<T, F>` implements `FromResidual<Yeet<E>>`
-
-    required for `Box<dyn StdError>` to implement `From<InvalidLengthError>`
-    required for `Result<std::string::String, Box<dyn StdError>>` to implement `FromResidual<Result<Infallible, InvalidLengthError>>`rustc(Click for full compiler diagnostic)
-    */
-
-    let md5_hash_string = Base64::encode(&md5_hash[..], &mut buf).unwrap().to_string();
-
-    debug!("Got md5 hash: file={pathbuf:#?} md5={md5_hash_string:#?}");
-
-    Ok(md5_hash_string)
-}
-
 /// One audio file detected in a scanned directory of samples.
 
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
@@ -65,7 +34,7 @@ pub struct SamplesDirAudioFile {
 
 impl SamplesDirAudioFile {
     pub fn new(fp: PathBuf) -> RBoxErr<Self> {
-        let md5_hash = get_md5_hash_from_pathbuf(&fp)?;
+        let md5_hash = get_md5_hash_from_path(&fp)?;
 
         // todo: unwrap failure
         let file_name = get_stem_from_pathbuf(&fp).unwrap();
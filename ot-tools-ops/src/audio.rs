@@ -1,5 +1,69 @@
 //! Module for various Audio I/O, DSP etc. related functionality
 
 pub mod aiff;
+pub mod dither;
+pub mod fades;
+pub mod file;
+pub mod loudness;
+pub mod onsets;
+pub mod resample;
+pub mod stretch;
+pub mod tempo;
 pub mod utils;
 pub mod wav;
+pub mod zero_crossings;
+
+#[derive(Debug)]
+pub(crate) enum AudioErrors {
+    InvalidSampleFormat,
+    InvalidBitDepth,
+    InvalidSampleRate,
+    InvalidChannelCount,
+    FadePercentageOOB,
+    SilenceThresholdOOB,
+    StretchFactorOOB,
+    PitchShiftOOB,
+    LoudnessTargetOOB,
+    UnsupportedFileFormat(std::path::PathBuf),
+    AiffError(String),
+}
+impl std::fmt::Display for AudioErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::InvalidSampleFormat => write!(f, "Only signed PCM audio files are supported"),
+            Self::InvalidBitDepth => {
+                write!(f, "Only 16/24-bit signed PCM audio files are supported")
+            }
+            Self::InvalidSampleRate => write!(f, "Only 44.1kHz audio files are supported"),
+            Self::InvalidChannelCount => {
+                write!(f, "Only mono and stereo audio files are supported")
+            }
+            Self::FadePercentageOOB => write!(
+                f,
+                "Fade percentage parameter out of bounds, must be between 0.0 and 1.0",
+            ),
+            Self::SilenceThresholdOOB => write!(
+                f,
+                "Silence threshold out of bounds, must be 0.0 dBFS or lower",
+            ),
+            Self::StretchFactorOOB => write!(
+                f,
+                "Time stretch factor out of bounds, must be between 0.25 and 4.0",
+            ),
+            Self::PitchShiftOOB => write!(
+                f,
+                "Pitch shift out of bounds, must be between -24.0 and 24.0 semitones",
+            ),
+            Self::LoudnessTargetOOB => write!(
+                f,
+                "Loudness target and true peak ceiling must be 0.0 dB or lower",
+            ),
+            Self::UnsupportedFileFormat(x) => write!(
+                f,
+                "unsupported audio file format, only WAV and AIFF files are supported: path={x:#?}"
+            ),
+            Self::AiffError(x) => write!(f, "failed to read/write AIFF file: {x}"),
+        }
+    }
+}
+impl std::error::Error for AudioErrors {}
@@ -1,22 +1,85 @@
+//! Reading and Writing .aiff files.
+
+use crate::audio::wav::{
+    f32_to_i16, f32_to_i24, i16_to_f32, i24_to_f32, ALLOWED_CHANNELS, ALLOWED_SAMPLE_RATE,
+};
+use crate::audio::AudioErrors;
+use crate::RBoxErr;
 use aifc::{AifcReadInfo, AifcReader};
-use log::{debug, trace};
-use std::{error::Error, fs::File, io::BufReader, path::PathBuf};
+use log::trace;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+fn aifc_err(e: aifc::AifcError) -> AudioErrors {
+    AudioErrors::AiffError(format!("{e:?}"))
+}
 
 // simple conversion algorithm to convert Sample to f32
 // source: https://github.com/karip/aifc/blob/main/examples/aifc-tinyaudio/src/main.rs
-#[allow(dead_code)]
+// 16/24 bit samples use the same conversion as WAV files so chains are identical in both formats
 fn sample_to_f32(s: aifc::Sample) -> f32 {
     match s {
         aifc::Sample::U8(s) => s as f32 / (1u32 << 7) as f32 - 1.0,
         aifc::Sample::I8(s) => s as f32 / (1u32 << 7) as f32,
-        aifc::Sample::I16(s) => s as f32 / (1u32 << 15) as f32,
-        aifc::Sample::I24(s) => s as f32 / (1u32 << 23) as f32,
+        aifc::Sample::I16(s) => i16_to_f32(s),
+        aifc::Sample::I24(s) => i24_to_f32(s),
         aifc::Sample::I32(s) => s as f32 / (1u32 << 31) as f32,
         aifc::Sample::F32(s) => s,
         aifc::Sample::F64(s) => s as f32,
     }
 }
 
+/// Bit depth of an AIFF sample format, only 16/24 bit signed PCM is supported.
+pub fn aiff_bit_depth(sample_format: &aifc::SampleFormat) -> RBoxErr<u16> {
+    match sample_format {
+        aifc::SampleFormat::I16 => Ok(16),
+        aifc::SampleFormat::I24 => Ok(24),
+        _ => Err(AudioErrors::InvalidBitDepth.into()),
+    }
+}
+
+/// Write interleaved samples to a new 16/24 bit signed PCM AIFF file.
+pub fn write_aiff(path: &Path, channels: u16, bit_depth: u16, samples: &[f32]) -> RBoxErr<()> {
+    trace!("Writing to new AIFF file: path={path:#?}");
+    let sample_format = match bit_depth {
+        16 => aifc::SampleFormat::I16,
+        24 => aifc::SampleFormat::I24,
+        _ => return Err(AudioErrors::InvalidBitDepth.into()),
+    };
+
+    let write_info = aifc::AifcWriteInfo {
+        file_format: aifc::FileFormat::Aiff,
+        channels: channels as i16,
+        sample_rate: ALLOWED_SAMPLE_RATE as f64,
+        sample_format,
+    };
+
+    let mut buf_writer = BufWriter::new(File::create(path)?);
+    let mut writer = aifc::AifcWriter::new(&mut buf_writer, &write_info).map_err(aifc_err)?;
+
+    match sample_format {
+        aifc::SampleFormat::I16 => {
+            trace!("Writing 16-bit AIFF: path={path:#?}");
+            let data = samples.iter().map(|x| f32_to_i16(*x)).collect::<Vec<_>>();
+            writer.write_samples_i16(&data)
+        }
+        _ => {
+            trace!("Writing 24-bit AIFF: path={path:#?}");
+            let data = samples.iter().map(|x| f32_to_i24(*x)).collect::<Vec<_>>();
+            writer.write_samples_i24(&data)
+        }
+    }
+    .map_err(aifc_err)?;
+
+    writer.finalize().map_err(aifc_err)?;
+    trace!("Wrote to new AIFF file: path={path:#?}");
+
+    Ok(())
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct AiffFile {
     /// specification struct for the Aiff file.
@@ -33,84 +96,87 @@ pub struct AiffFile {
 }
 
 impl AiffFile {
-    /// Write samples to a WAV file.
-    #[allow(dead_code)]
-    pub fn to_file(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        trace!("Writing to new AIFF file: path={path:#?}");
-        let f = std::fs::File::create(path)?;
-        let mut stream = std::io::BufWriter::new(f);
-
-        trace!("Creating new AIFF Write Spec: path={path:#?}");
-        let write_format = aifc::AifcWriteInfo {
-            file_format: self.spec.file_format,
-            channels: self.spec.channels,
-            sample_rate: self.spec.sample_rate,
-            sample_format: aifc::SampleFormat::F32,
-        };
-
-        trace!("Creating new AIFF writer: path={path:#?}");
-        let mut writer =
-            aifc::AifcWriter::new(&mut stream, &write_format).expect("Can't create writer");
-
-        trace!("Writing samples: path={path:#?}");
-        writer
-            .write_samples_f32(&self.samples)
-            .expect("Can't write samples");
-
-        writer.finalize().expect("Can't finalize");
-        debug!("Wrote to new AIFF file: path={path:#?}");
-
-        Ok(())
+    /// Crete a new file at the path from the current struct. Can only be used to write 16/24 bit
+    /// signed PCM files.
+    pub fn to_path(&self, path: &Path) -> RBoxErr<()> {
+        write_aiff(
+            path,
+            self.spec.channels as u16,
+            aiff_bit_depth(&self.spec.sample_format)?,
+            &self.samples,
+        )
     }
 
     /// Read samples, specficiation etc. from an AIFF file.
-    #[allow(dead_code)]
-    pub fn from_file(path: PathBuf) -> Result<AiffFile, Box<dyn Error>> {
+    pub fn from_path(path: &Path) -> RBoxErr<Self> {
         trace!("Reading AIFF data from file: path={path:#?}");
-        let mut reader = AiffFile::open(&path)?;
+        let mut reader = AiffFile::open(path)?;
 
         trace!("Reading AIFF info spec: path={path:#?}");
         let info = AiffFile::read_spec(&mut reader)?;
 
+        if info.sample_rate != ALLOWED_SAMPLE_RATE as f64 {
+            return Err(AudioErrors::InvalidSampleRate.into());
+        }
+
+        if !ALLOWED_CHANNELS.contains(&(info.channels as u16)) {
+            return Err(AudioErrors::InvalidChannelCount.into());
+        }
+
+        aiff_bit_depth(&info.sample_format)?;
+
         trace!("Reading AIFF samples: path={path:#?}");
         let samples = AiffFile::read_samples(&mut reader)?;
 
-        trace!("Getting AIFF sample length: path={path:#?}");
-        let length = info.sample_len.unwrap() as u32;
+        trace!("Read AIFF file: path={path:#?}");
+        Ok(AiffFile {
+            len: samples.len() as u32 / info.channels as u32,
+            spec: info,
+            samples,
+            file_path: path.to_path_buf(),
+        })
+    }
+
+    /// Read samples, specficiation etc. from any AIFF / AIFF-C file, without checking it is
+    /// compatible with the Octatrack.
+    pub fn from_path_any_spec(path: &Path) -> RBoxErr<Self> {
+        trace!("Reading AIFF data from file: path={path:#?}");
+        let mut reader = AiffFile::open(path)?;
+        let info = AiffFile::read_spec(&mut reader)?;
+
+        trace!("Reading AIFF samples: path={path:#?} spec={info:?}");
+        let samples = AiffFile::read_samples(&mut reader)?;
 
-        debug!("Read AIFF file: path={path:#?}");
         Ok(AiffFile {
+            len: samples.len() as u32 / info.channels as u32,
             spec: info,
-            len: length,
             samples,
-            file_path: path,
+            file_path: path.to_path_buf(),
         })
     }
 
     /// Open an AIFF file into a read buffer
-    pub fn open(path: &PathBuf) -> Result<AifcReader<BufReader<File>>, Box<dyn Error>> {
+    pub fn open(path: &Path) -> RBoxErr<AifcReader<BufReader<File>>> {
         let bufreader = BufReader::new(File::open(path)?);
-        let reader = aifc::AifcReader::new(bufreader).expect("Can't create reader");
+        let reader = aifc::AifcReader::new(bufreader).map_err(aifc_err)?;
         Ok(reader)
     }
 
-    /// Read the hound WavSpec for an opened wav file buffer
-    pub fn read_spec(
-        reader: &mut AifcReader<BufReader<File>>,
-    ) -> Result<AifcReadInfo, Box<dyn Error>> {
+    /// Read the AIFF info spec for an opened AIFF file buffer
+    pub fn read_spec(reader: &mut AifcReader<BufReader<File>>) -> RBoxErr<AifcReadInfo> {
         trace!("Reading AIFF spec from reader.");
-        let info = reader.read_info().expect("Can't read header");
+        let info = reader.read_info().map_err(aifc_err)?;
         Ok(info)
     }
 
-    /// Write samples to an open and writeable file buffer.
-    fn read_samples(reader: &mut AifcReader<BufReader<File>>) -> Result<Vec<f32>, Box<dyn Error>> {
+    /// Read all samples from an opened AIFF file buffer.
+    fn read_samples(reader: &mut AifcReader<BufReader<File>>) -> RBoxErr<Vec<f32>> {
         trace!("Reading AIFF samples from reader.");
-        let samples: Vec<f32> = reader
+        let samples = reader
             .samples()
-            .expect("Can't iterate samples")
-            .map(|x| sample_to_f32(x.unwrap()))
-            .collect();
+            .map_err(aifc_err)?
+            .map(|x| x.map(sample_to_f32).map_err(aifc_err))
+            .collect::<Result<Vec<f32>, AudioErrors>>()?;
 
         Ok(samples)
     }
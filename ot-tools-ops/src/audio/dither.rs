@@ -0,0 +1,61 @@
+//! Dithering for reducing the bit depth of audio samples.
+//!
+//! Quantizing to a lower bit depth without dither creates distortion which is correlated with
+//! the signal (very noticeable on quiet fades / tails). Adding triangular (TPDF) noise of
+//! +/- 1 LSB before quantizing turns that distortion into a constant, low level noise floor.
+
+use rand::Rng;
+
+/// Size of one least significant bit at a bit depth, for samples between -1.0 and 1.0.
+pub fn lsb(bit_depth: u16) -> f32 {
+    1.0 / 2_u32.pow(bit_depth as u32 - 1) as f32
+}
+
+/// Add triangular probability density function (TPDF) dither noise to samples, before they're
+/// quantized to `bit_depth` bits. Samples are clamped to -1.0..=1.0.
+pub fn tpdf_dither<R: Rng>(samples: &mut [f32], bit_depth: u16, rng: &mut R) {
+    let lsb = lsb(bit_depth);
+    for x in samples.iter_mut() {
+        let noise = rng.gen::<f32>() - rng.gen::<f32>();
+        *x = (*x + noise * lsb).clamp(-1.0, 1.0);
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn lsb_16_bit() {
+        assert_eq!(lsb(16), 1.0 / 32768.0);
+    }
+
+    #[test]
+    fn noise_within_one_lsb() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut x = vec![0.25_f32; 10000];
+        tpdf_dither(&mut x, 16, &mut rng);
+        assert!(x.iter().all(|v| (v - 0.25).abs() <= lsb(16)));
+        assert!(x.iter().any(|v| *v != 0.25));
+    }
+
+    #[test]
+    fn noise_zero_mean() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut x = vec![0.0_f32; 100000];
+        tpdf_dither(&mut x, 16, &mut rng);
+        let mean = x.iter().sum::<f32>() / x.len() as f32;
+        assert!(mean.abs() < lsb(16) * 0.01);
+    }
+
+    #[test]
+    fn clamped() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut x = vec![1.0_f32; 1000];
+        x.extend(vec![-1.0_f32; 1000]);
+        tpdf_dither(&mut x, 16, &mut rng);
+        assert!(x.iter().all(|v| (-1.0..=1.0).contains(v)));
+    }
+}
@@ -0,0 +1,151 @@
+//! Fade in / fade out curves.
+
+use serde::{Deserialize, Serialize};
+
+/// Shape of a fade's gain curve.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FadeCurve {
+    /// Gain changes at a constant rate.
+    #[default]
+    Linear,
+    /// Quarter sine, keeps a constant total power when crossfading, sounds smoother than linear.
+    EqualPower,
+    /// Slow start, fast finish (a fade in stays quiet for longer).
+    Exponential,
+    /// Fast start, slow finish (a fade in gets loud quicker).
+    Logarithmic,
+    /// Slow start and finish (raised cosine), the smoothest fade.
+    SCurve,
+}
+
+/// Steepness of the exponential/logarithmic curves.
+const EXP_STEEPNESS: f32 = 5.0;
+
+impl FadeCurve {
+    /// Gain of a fade in at `t` (0.0 at the start of the fade, 1.0 at the end).
+    pub fn gain(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        let exponential = |t: f32| (EXP_STEEPNESS * t).exp_m1() / EXP_STEEPNESS.exp_m1();
+        match self {
+            Self::Linear => t,
+            Self::EqualPower => (t * std::f32::consts::FRAC_PI_2).sin(),
+            Self::Exponential => exponential(t),
+            Self::Logarithmic => 1.0 - exponential(1.0 - t),
+            Self::SCurve => 0.5 - 0.5 * (t * std::f32::consts::PI).cos(),
+        }
+    }
+}
+
+/// Fade in the first `len` samples (frames) of interleaved audio samples, from silence.
+pub fn fade_in(samples: &mut [f32], channels: u16, len: usize, curve: FadeCurve) {
+    for (i, frame) in samples.chunks_mut(channels as usize).take(len).enumerate() {
+        let k = curve.gain(i as f32 / len as f32);
+        frame.iter_mut().for_each(|x| *x *= k);
+    }
+}
+
+/// Fade out the last `len` samples (frames) of interleaved audio samples, to silence. The
+/// fade out is the reverse of a fade in with the same curve.
+pub fn fade_out(samples: &mut [f32], channels: u16, len: usize, curve: FadeCurve) {
+    for (i, frame) in samples
+        .chunks_mut(channels as usize)
+        .rev()
+        .take(len)
+        .enumerate()
+    {
+        let k = curve.gain(i as f32 / len as f32);
+        frame.iter_mut().for_each(|x| *x *= k);
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+
+    const CURVES: [FadeCurve; 5] = [
+        FadeCurve::Linear,
+        FadeCurve::EqualPower,
+        FadeCurve::Exponential,
+        FadeCurve::Logarithmic,
+        FadeCurve::SCurve,
+    ];
+
+    #[test]
+    fn gain_end_points() {
+        for curve in CURVES {
+            assert!(curve.gain(0.0).abs() < 1e-6, "{curve:?}");
+            assert!((curve.gain(1.0) - 1.0).abs() < 1e-6, "{curve:?}");
+            assert!(curve.gain(-1.0).abs() < 1e-6, "{curve:?}");
+            assert!((curve.gain(2.0) - 1.0).abs() < 1e-6, "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn gain_increasing() {
+        for curve in CURVES {
+            let gains = (0..=100)
+                .map(|i| curve.gain(i as f32 / 100.0))
+                .collect::<Vec<_>>();
+            assert!(gains.windows(2).all(|x| x[1] > x[0]), "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn gain_midpoints() {
+        assert_eq!(FadeCurve::Linear.gain(0.5), 0.5);
+        assert!((FadeCurve::EqualPower.gain(0.5) - 0.5_f32.sqrt()).abs() < 1e-6);
+        assert!((FadeCurve::SCurve.gain(0.5) - 0.5).abs() < 1e-6);
+        assert!(FadeCurve::Exponential.gain(0.5) < 0.1);
+        assert!(FadeCurve::Logarithmic.gain(0.5) > 0.9);
+    }
+
+    #[test]
+    fn equal_power_crossfade() {
+        for i in 0..=10 {
+            let t = i as f32 / 10.0;
+            let (a, b) = (
+                FadeCurve::EqualPower.gain(t),
+                FadeCurve::EqualPower.gain(1.0 - t),
+            );
+            assert!((a * a + b * b - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn fade_in_stereo() {
+        let mut x = vec![1.0; 20];
+        fade_in(&mut x, 2, 4, FadeCurve::Linear);
+        assert_eq!(
+            x[..10],
+            [0.0, 0.0, 0.25, 0.25, 0.5, 0.5, 0.75, 0.75, 1.0, 1.0]
+        );
+        assert!(x[8..].iter().all(|x| *x == 1.0));
+    }
+
+    #[test]
+    fn fade_out_stereo() {
+        let mut x = vec![1.0; 20];
+        fade_out(&mut x, 2, 4, FadeCurve::Linear);
+        assert_eq!(
+            x[10..],
+            [1.0, 1.0, 0.75, 0.75, 0.5, 0.5, 0.25, 0.25, 0.0, 0.0]
+        );
+        assert!(x[..12].iter().all(|x| *x == 1.0));
+    }
+
+    #[test]
+    fn fade_longer_than_audio() {
+        let mut x = vec![1.0; 4];
+        fade_in(&mut x, 1, 8, FadeCurve::Linear);
+        assert_eq!(x, [0.0, 0.125, 0.25, 0.375]);
+    }
+
+    #[test]
+    fn fade_zero_len() {
+        let mut x = vec![1.0; 4];
+        fade_in(&mut x, 1, 0, FadeCurve::SCurve);
+        fade_out(&mut x, 1, 0, FadeCurve::SCurve);
+        assert_eq!(x, [1.0; 4]);
+    }
+}
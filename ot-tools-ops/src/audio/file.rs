@@ -0,0 +1,778 @@
+//! Format independent representation of Octatrack compatible audio files (WAV / AIFF).
+
+use crate::audio::{
+    aiff::{aiff_bit_depth, write_aiff, AiffFile},
+    dither::tpdf_dither,
+    fades::{fade_in, fade_out, FadeCurve},
+    loudness::{analyze, normalization_gain, LoudnessMode, LoudnessStats},
+    onsets::{detect_onsets, OnsetOpts},
+    resample::resample,
+    stretch::{
+        pitch_shift, time_stretch, MAX_PITCH_SHIFT_SEMITONES, MAX_STRETCH_FACTOR,
+        MIN_STRETCH_FACTOR,
+    },
+    tempo::{estimate_tempo, TempoEstimate},
+    wav::{WavFile, ALLOWED_BIT_DEPTHS, ALLOWED_CHANNELS, ALLOWED_SAMPLE_RATE},
+    AudioErrors,
+};
+use crate::RBoxErr;
+use log::trace;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Audio file formats which can be read and written.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum FileFormat {
+    #[default]
+    Wav,
+    Aiff,
+}
+
+impl FileFormat {
+    /// Determine the audio file format from a file path's extension
+    /// (`.wav`, `.aif` or `.aiff`, case insensitive).
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "wav" => Some(Self::Wav),
+            "aif" | "aiff" => Some(Self::Aiff),
+            _ => None,
+        }
+    }
+
+    /// File extension used when writing files in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Aiff => "aiff",
+        }
+    }
+}
+
+/// An audio file read from either a WAV or an AIFF file. Only Octatrack compatible audio
+/// (44.1kHz, 16/24 bit signed PCM, mono/stereo) can be written.
+#[derive(PartialEq, Debug, Clone)]
+pub struct AudioFile {
+    /// Format the audio will be written as
+    pub format: FileFormat,
+
+    /// Number of channels, samples are interleaved
+    pub channels: u16,
+
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+
+    /// Bit depth the audio will be written with
+    pub bit_depth: u16,
+
+    /// Number of audio samples in the file.
+    pub len: u32,
+
+    /// Audio samples
+    pub samples: Vec<f32>, // cannot use Copy trait
+
+    /// File path of the audio file
+    pub file_path: PathBuf,
+}
+
+impl From<WavFile> for AudioFile {
+    fn from(wav: WavFile) -> Self {
+        Self {
+            format: FileFormat::Wav,
+            channels: wav.spec.channels,
+            sample_rate: wav.spec.sample_rate,
+            bit_depth: wav.spec.bits_per_sample,
+            len: wav.len,
+            samples: wav.samples,
+            file_path: wav.file_path,
+        }
+    }
+}
+
+impl TryFrom<AiffFile> for AudioFile {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(aiff: AiffFile) -> RBoxErr<Self> {
+        Ok(Self {
+            format: FileFormat::Aiff,
+            channels: aiff.spec.channels as u16,
+            sample_rate: aiff.spec.sample_rate as u32,
+            bit_depth: aiff_bit_depth(&aiff.spec.sample_format)?,
+            len: aiff.len,
+            samples: aiff.samples,
+            file_path: aiff.file_path,
+        })
+    }
+}
+
+impl AudioFile {
+    /// Crete a new struct by reading a WAV or AIFF file located at `path`,
+    /// the format is determined by the file extension.
+    pub fn from_path(path: &Path) -> RBoxErr<Self> {
+        trace!("Reading audio file from path: {path:#?}");
+        match FileFormat::from_path(path) {
+            Some(FileFormat::Wav) => Ok(WavFile::from_path(path)?.into()),
+            Some(FileFormat::Aiff) => AiffFile::from_path(path)?.try_into(),
+            None => Err(AudioErrors::UnsupportedFileFormat(path.to_path_buf()).into()),
+        }
+    }
+
+    /// Crete a new struct by reading a WAV or AIFF file located at `path` with any sample rate,
+    /// bit depth or channel count (e.g. 48kHz 32-bit float), without checking it is compatible
+    /// with the Octatrack. Use [`AudioFile::convert_to_compatible_spec`] before writing.
+    pub fn from_path_any_spec(path: &Path) -> RBoxErr<Self> {
+        trace!("Reading audio file from path: {path:#?}");
+        match FileFormat::from_path(path) {
+            Some(FileFormat::Wav) => Ok(WavFile::from_path_any_spec(path)?.into()),
+            Some(FileFormat::Aiff) => {
+                let aiff = AiffFile::from_path_any_spec(path)?;
+                Ok(Self {
+                    format: FileFormat::Aiff,
+                    channels: aiff.spec.channels as u16,
+                    sample_rate: aiff.spec.sample_rate as u32,
+                    bit_depth: aiff.spec.sample_format.decoded_size() as u16 * 8,
+                    len: aiff.len,
+                    samples: aiff.samples,
+                    file_path: aiff.file_path,
+                })
+            }
+            None => Err(AudioErrors::UnsupportedFileFormat(path.to_path_buf()).into()),
+        }
+    }
+
+    /// Whether the audio can be written as is (44.1kHz, 16/24 bit, mono/stereo).
+    pub fn is_compatible_spec(&self) -> bool {
+        self.sample_rate == ALLOWED_SAMPLE_RATE
+            && ALLOWED_CHANNELS.contains(&self.channels)
+            && ALLOWED_BIT_DEPTHS.contains(&self.bit_depth)
+    }
+
+    /// Convert the audio to 44.1kHz with the given bit depth (16 or 24).
+    /// Audio is resampled with a band-limited (windowed sinc) resampler and TPDF dither is
+    /// applied when the conversion reduces the bit depth or the audio has been resampled.
+    pub fn convert_to_compatible_spec(&mut self, bit_depth: u16) -> RBoxErr<()> {
+        if !ALLOWED_CHANNELS.contains(&self.channels) {
+            return Err(AudioErrors::InvalidChannelCount.into());
+        }
+        if !ALLOWED_BIT_DEPTHS.contains(&bit_depth) {
+            return Err(AudioErrors::InvalidBitDepth.into());
+        }
+
+        let resampled = self.sample_rate != ALLOWED_SAMPLE_RATE;
+        if resampled {
+            self.samples = resample(
+                &self.samples,
+                self.channels,
+                self.sample_rate,
+                ALLOWED_SAMPLE_RATE,
+            );
+            self.len = self.samples.len() as u32 / self.channels as u32;
+            self.sample_rate = ALLOWED_SAMPLE_RATE;
+        }
+
+        if resampled || self.bit_depth > bit_depth {
+            trace!("Dithering audio: from={} to={bit_depth}", self.bit_depth);
+            tpdf_dither(&mut self.samples, bit_depth, &mut rand::thread_rng());
+        }
+        self.bit_depth = bit_depth;
+
+        Ok(())
+    }
+
+    /// Detect onsets (transients) in the audio, returning their sample positions.
+    pub fn detect_onsets(&self, opts: &OnsetOpts) -> Vec<u32> {
+        detect_onsets(&self.samples, self.channels, self.sample_rate, opts)
+    }
+
+    /// Estimate the tempo of the audio, `None` if the audio is too short or has no onsets.
+    pub fn estimate_tempo(&self) -> Option<TempoEstimate> {
+        estimate_tempo(&self.samples, self.channels, self.sample_rate)
+    }
+
+    /// Crete a new file at the path from the current struct, using the struct's format and
+    /// bit depth. Can only be used to write Octatrack compatible files (44.1kHz, 16/24 bit
+    /// signed PCM, mono/stereo).
+    pub fn to_path(&self, path: &Path) -> RBoxErr<()> {
+        if !ALLOWED_BIT_DEPTHS.contains(&self.bit_depth) {
+            return Err(AudioErrors::InvalidBitDepth.into());
+        }
+        if self.sample_rate != ALLOWED_SAMPLE_RATE {
+            return Err(AudioErrors::InvalidSampleRate.into());
+        }
+        if !ALLOWED_CHANNELS.contains(&self.channels) {
+            return Err(AudioErrors::InvalidChannelCount.into());
+        }
+
+        match self.format {
+            FileFormat::Wav => WavFile {
+                spec: hound::WavSpec {
+                    channels: self.channels,
+                    sample_rate: self.sample_rate,
+                    bits_per_sample: self.bit_depth,
+                    sample_format: hound::SampleFormat::Int,
+                },
+                len: self.len,
+                samples: self.samples.clone(),
+                file_path: path.to_path_buf(),
+            }
+            .to_path(path),
+            FileFormat::Aiff => write_aiff(path, self.channels, self.bit_depth, &self.samples),
+        }
+    }
+
+    /// Normalise audio samples between 0 and 1
+    pub fn normalize(&mut self) -> RBoxErr<()> {
+        trace!("Normalizing audio.");
+        let max_abs = self
+            .samples
+            .iter()
+            .map(|x| f32::abs(*x))
+            .reduce(f32::max)
+            .unwrap_or(1.0_f32);
+
+        let normd = self
+            .samples
+            .iter()
+            .map(|x| *x * (1.0 / max_abs))
+            .collect::<Vec<f32>>();
+
+        self.samples = normd;
+        Ok(())
+    }
+
+    /// Measure the peak, true peak, RMS and integrated loudness levels of the audio, see
+    /// [`crate::audio::loudness`].
+    pub fn loudness_stats(&self) -> LoudnessStats {
+        analyze(&self.samples, self.channels, self.sample_rate)
+    }
+
+    /// Normalise the audio to a target RMS level (dBFS) or integrated loudness (LUFS), without
+    /// the true peak level going above `ceiling` (dBTP). Silent audio is left as is.
+    pub fn loudness_normalize(
+        &mut self,
+        mode: LoudnessMode,
+        target: f32,
+        ceiling: f32,
+    ) -> RBoxErr<()> {
+        if target > 0.0 || ceiling > 0.0 {
+            return Err(AudioErrors::LoudnessTargetOOB.into());
+        }
+        let gain = normalization_gain(
+            &self.samples,
+            self.channels,
+            self.sample_rate,
+            mode,
+            target,
+            ceiling,
+        );
+        if let Some(gain) = gain {
+            trace!("Normalizing audio loudness: gain={gain}dB");
+            let k = 10.0_f32.powf(gain / 20.0);
+            self.samples.iter_mut().for_each(|x| *x *= k);
+        }
+        Ok(())
+    }
+
+    // really hacky and simple time stretching
+    /// Time stretch audio vector by resampling, inserts duplicate samples to
+    /// mimic slowing the audio down and removes samples to mimic speeding it up.
+    /// WARNING: Speeding up via resampling is a lossy procedure.
+    pub fn resample_time_stretch(&mut self, stretch: i8) -> RBoxErr<()> {
+        trace!("Resampling audio file with time stretch factor: {stretch}");
+        let mut resampled: Vec<f32> = vec![];
+
+        let n_channels = self.channels as usize;
+
+        // extending to mimic slowing own the audio
+        if stretch < 0 {
+            // audio samples are interleaved by channels
+            for chn_sample in self.samples.chunks(n_channels) {
+                for _ in 0..=stretch.abs() {
+                    for x in chn_sample {
+                        resampled.push(*x);
+                    }
+                }
+            }
+        }
+
+        // remove entries to mimic speed up
+        // WARNING: this is lossy
+        if stretch > 0 {
+            for (idx, chn_sample) in self.samples.chunks(n_channels).enumerate() {
+                if idx.rem_euclid((stretch + 1) as usize) == 0 {
+                    for x in chn_sample {
+                        resampled.push(*x);
+                    }
+                }
+            }
+        }
+
+        self.len = resampled.len() as u32 / self.channels as u32;
+        self.samples = resampled;
+        Ok(())
+    }
+
+    /// Time stretch the audio to `factor` times its length (2.0 is twice as long, i.e. half
+    /// speed) without changing its pitch, see [`crate::audio::stretch`].
+    pub fn time_stretch(&mut self, factor: f32) -> RBoxErr<()> {
+        if !(MIN_STRETCH_FACTOR..=MAX_STRETCH_FACTOR).contains(&factor) {
+            return Err(AudioErrors::StretchFactorOOB.into());
+        }
+        self.samples = time_stretch(&self.samples, self.channels, factor);
+        self.len = self.samples.len() as u32 / self.channels as u32;
+        Ok(())
+    }
+
+    /// Pitch shift the audio by some (fractional) number of semitones without changing its
+    /// length, see [`crate::audio::stretch`].
+    pub fn pitch_shift(&mut self, semitones: f32) -> RBoxErr<()> {
+        if semitones.abs() > MAX_PITCH_SHIFT_SEMITONES {
+            return Err(AudioErrors::PitchShiftOOB.into());
+        }
+        self.samples = pitch_shift(&self.samples, self.channels, self.sample_rate, semitones);
+        Ok(())
+    }
+
+    /// Fade in the first `len` samples (frames) of the audio with some curve, see
+    /// [`crate::audio::fades`]. Fades longer than the audio finish after the end of the audio.
+    pub fn fade_in(&mut self, len: usize, curve: FadeCurve) {
+        trace!("Creating fade in for audio file: len={len} curve={curve:?}");
+        fade_in(&mut self.samples, self.channels, len, curve);
+    }
+
+    /// Fade out the last `len` samples (frames) of the audio with some curve, see
+    /// [`crate::audio::fades`]. Fades longer than the audio start before the start of the audio.
+    pub fn fade_out(&mut self, len: usize, curve: FadeCurve) {
+        trace!("Creating fade out for audio file: len={len} curve={curve:?}");
+        fade_out(&mut self.samples, self.channels, len, curve);
+    }
+
+    /// Number of samples (frames) in some number of milliseconds of audio.
+    pub fn ms_to_len(&self, ms: f32) -> usize {
+        (ms.max(0.0) * self.sample_rate as f32 / 1000.0).round() as usize
+    }
+
+    /// Linear fade in over the first `percent` (0.0 to 1.0) of the audio
+    ///
+    /// ```text
+    ///     x_{n} = x_n.k_n
+    ///     forall n < K, n >= 0
+    ///     where k_n = k_{n-1} + 1/K; k_0 = 0
+    /// ```
+    pub fn linear_fade_in(&mut self, percent: f32) -> RBoxErr<()> {
+        // floats have no concept of a 'step' in ranges
+        #[allow(clippy::manual_range_contains)]
+        if percent > 1.0 || percent < 0.0 {
+            return Err(AudioErrors::FadePercentageOOB.into());
+        }
+        let n_frames = self.samples.len() / self.channels as usize;
+        self.fade_in((n_frames as f32 * percent) as usize, FadeCurve::Linear);
+        Ok(())
+    }
+
+    /// Linear fade out over the last `percent` (0.0 to 1.0) of the audio
+    ///
+    /// ```text
+    ///     x_{n} = x_n.k_n
+    ///     forall n > N - K, n >= 0
+    ///     where k_n = k_{n-1} - 1/K; k_N = 0
+    /// ```
+    pub fn linear_fade_out(&mut self, percent: f32) -> RBoxErr<()> {
+        // floats have no concept of a 'step' in ranges
+        #[allow(clippy::manual_range_contains)]
+        if percent > 1.0 || percent < 0.0 {
+            return Err(AudioErrors::FadePercentageOOB.into());
+        }
+        let n_frames = self.samples.len() / self.channels as usize;
+        self.fade_out((n_frames as f32 * percent) as usize, FadeCurve::Linear);
+        Ok(())
+    }
+
+    /// Remove leading and trailing audio quieter than `threshold_db` (dBFS, in any channel),
+    /// keeping `preroll_ms` milliseconds of audio before the first sound so the start of its
+    /// attack isn't cut off. Audio that is silent throughout is left as is.
+    pub fn trim_silence(&mut self, threshold_db: f32, preroll_ms: f32) -> RBoxErr<()> {
+        if threshold_db > 0.0 {
+            return Err(AudioErrors::SilenceThresholdOOB.into());
+        }
+        let threshold = 10.0_f32.powf(threshold_db / 20.0);
+        let n_chans = self.channels as usize;
+
+        let is_sound = |frame: &[f32]| frame.iter().any(|x| x.abs() > threshold);
+        let first = self.samples.chunks(n_chans).position(is_sound);
+        let last = self.samples.chunks(n_chans).rposition(is_sound);
+
+        let (Some(first), Some(last)) = (first, last) else {
+            trace!("Audio is silent, not trimming: path={:#?}", self.file_path);
+            return Ok(());
+        };
+
+        let preroll = (preroll_ms.max(0.0) / 1000.0 * self.sample_rate as f32) as usize;
+        let start = first.saturating_sub(preroll);
+        let end = last + 1;
+        trace!(
+            "Trimming silence: start={start} end={end} len={} path={:#?}",
+            self.len,
+            self.file_path
+        );
+
+        self.samples = self.samples[start * n_chans..end * n_chans].to_vec();
+        self.len = (end - start) as u32;
+        Ok(())
+    }
+
+    /// Add silence to the end of the audio so it is `len` samples long. Audio that is
+    /// already at least `len` samples long is left as is.
+    pub fn pad_to_len(&mut self, len: u32) {
+        if len <= self.len {
+            return;
+        }
+        trace!("Padding audio with silence: from={} to={len}", self.len);
+        self.samples
+            .resize(len as usize * self.channels as usize, 0.0);
+        self.len = len;
+    }
+
+    /// Naive upmix of mono signal to an interleaved stereo signal
+    /// (duplicate channels, attenuate by 0.5)
+    // TODO: test
+    pub fn mono_to_stereo_interleaved(&mut self) -> RBoxErr<()> {
+        let mut buf: Vec<f32> = vec![];
+        for sample in &self.samples {
+            buf.push(sample * 0.5);
+            buf.push(sample * 0.5);
+        }
+        self.samples = buf;
+        self.channels = 2;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+
+    fn audio(channels: u16, samples: Vec<f32>) -> AudioFile {
+        AudioFile {
+            format: FileFormat::Wav,
+            channels,
+            sample_rate: 44100,
+            bit_depth: 16,
+            len: samples.len() as u32 / channels as u32,
+            samples,
+            file_path: PathBuf::new(),
+        }
+    }
+
+    fn test_wav() -> AudioFile {
+        AudioFile::from_path(Path::new("../data/tests/misc/test.wav")).unwrap()
+    }
+
+    mod format {
+        use super::*;
+
+        #[test]
+        fn from_path() {
+            for (x, y) in [
+                ("a.wav", Some(FileFormat::Wav)),
+                ("a.WAV", Some(FileFormat::Wav)),
+                ("a.aif", Some(FileFormat::Aiff)),
+                ("a.aiff", Some(FileFormat::Aiff)),
+                ("a.AIFF", Some(FileFormat::Aiff)),
+                ("a.mp3", None),
+                ("a", None),
+            ] {
+                assert_eq!(FileFormat::from_path(Path::new(x)), y, "{x}");
+            }
+        }
+
+        #[test]
+        fn unsupported_extension_err() {
+            assert!(AudioFile::from_path(Path::new("../data/tests/misc/pair.ot")).is_err());
+        }
+    }
+
+    mod read_write {
+        use super::*;
+
+        fn roundtrip(format: FileFormat, bit_depth: u16, fname: &str) -> (AudioFile, AudioFile) {
+            let mut wav = test_wav();
+            wav.format = format;
+            wav.bit_depth = bit_depth;
+
+            let fpath = temp_dir().join(fname);
+            wav.to_path(&fpath).unwrap();
+            let written = AudioFile::from_path(&fpath).unwrap();
+            let _ = std::fs::remove_file(&fpath);
+            (wav, written)
+        }
+
+        #[test]
+        fn wav_file_spec() {
+            let wav = test_wav();
+            assert_eq!(wav.format, FileFormat::Wav);
+            assert!(ALLOWED_BIT_DEPTHS.contains(&wav.bit_depth));
+            assert_eq!(wav.samples.len() as u32, wav.len * wav.channels as u32);
+        }
+
+        #[test]
+        fn aiff_16_bit() {
+            let (wav, aiff) = roundtrip(FileFormat::Aiff, 16, "ot-tools-aiff-16.aiff");
+            assert_eq!(aiff.format, FileFormat::Aiff);
+            assert_eq!(aiff.bit_depth, 16);
+            assert_eq!(aiff.channels, wav.channels);
+            assert_eq!(aiff.len, wav.len);
+        }
+
+        #[test]
+        fn aiff_24_bit() {
+            let (wav, aiff) = roundtrip(FileFormat::Aiff, 24, "ot-tools-aiff-24.aif");
+            assert_eq!(aiff.bit_depth, 24);
+            assert_eq!(aiff.len, wav.len);
+        }
+
+        #[test]
+        fn aiff_samples_match_wav() {
+            let (_, wav) = roundtrip(FileFormat::Wav, 16, "ot-tools-aiff-match.wav");
+            let (_, aiff) = roundtrip(FileFormat::Aiff, 16, "ot-tools-aiff-match.aiff");
+            assert_eq!(wav.samples, aiff.samples);
+        }
+
+        #[test]
+        fn invalid_bit_depth_err() {
+            let mut wav = test_wav();
+            wav.bit_depth = 32;
+            assert!(wav
+                .to_path(&temp_dir().join("ot-tools-invalid-bit-depth.aiff"))
+                .is_err());
+        }
+    }
+
+    mod trim_pad {
+        use super::*;
+
+        /// Silence, 100 samples of sound, then silence.
+        fn padded_sound(before: usize, after: usize) -> Vec<f32> {
+            let mut x = vec![0.0001_f32; before];
+            x.extend(vec![0.5_f32; 100]);
+            x.extend(vec![0.0001_f32; after]);
+            x
+        }
+
+        #[test]
+        fn trim_silence() {
+            let mut x = audio(1, padded_sound(1000, 2000));
+            x.trim_silence(-60.0, 0.0).unwrap();
+            assert_eq!(x.len, 100);
+            assert_eq!(x.samples, vec![0.5; 100]);
+        }
+
+        #[test]
+        fn trim_silence_keeps_preroll() {
+            let mut x = audio(1, padded_sound(1000, 2000));
+            // 441 samples
+            x.trim_silence(-60.0, 10.0).unwrap();
+            assert_eq!(x.len, 541);
+            assert_eq!(x.samples[441], 0.5);
+        }
+
+        #[test]
+        fn trim_silence_preroll_longer_than_silence() {
+            let mut x = audio(1, padded_sound(100, 2000));
+            x.trim_silence(-60.0, 10.0).unwrap();
+            assert_eq!(x.len, 200);
+        }
+
+        #[test]
+        fn trim_silence_threshold() {
+            let mut x = audio(1, padded_sound(1000, 2000));
+            // 0.0001 is -80 dBFS
+            x.trim_silence(-90.0, 0.0).unwrap();
+            assert_eq!(x.len, 3100);
+        }
+
+        #[test]
+        fn trim_silence_stereo() {
+            // sound in the right channel only
+            let x = padded_sound(1000, 2000)
+                .iter()
+                .flat_map(|x| [0.0, *x])
+                .collect::<Vec<_>>();
+            let mut x = audio(2, x);
+            x.trim_silence(-60.0, 0.0).unwrap();
+            assert_eq!(x.len, 100);
+            assert_eq!(x.samples.len(), 200);
+        }
+
+        #[test]
+        fn trim_silence_all_silent() {
+            let mut x = audio(1, vec![0.0; 1000]);
+            x.trim_silence(-60.0, 0.0).unwrap();
+            assert_eq!(x.len, 1000);
+        }
+
+        #[test]
+        fn trim_silence_threshold_oob_err() {
+            let mut x = audio(1, padded_sound(1000, 2000));
+            assert!(x.trim_silence(1.0, 0.0).is_err());
+        }
+
+        #[test]
+        fn pad_to_len() {
+            let mut x = audio(2, vec![0.5; 200]);
+            x.pad_to_len(250);
+            assert_eq!(x.len, 250);
+            assert_eq!(x.samples.len(), 500);
+            assert_eq!(x.samples[199], 0.5);
+            assert_eq!(x.samples[200], 0.0);
+        }
+
+        #[test]
+        fn pad_to_len_shorter_unchanged() {
+            let mut x = audio(1, vec![0.5; 200]);
+            x.pad_to_len(100);
+            assert_eq!(x.samples, vec![0.5; 200]);
+        }
+
+        #[test]
+        fn mono_to_stereo_len() {
+            let mut x = audio(1, vec![0.5; 200]);
+            x.mono_to_stereo_interleaved().unwrap();
+            assert_eq!(x.len, 200);
+            assert_eq!(x.samples.len(), 400);
+        }
+
+        #[test]
+        fn time_stretch_len() {
+            let mut x = audio(2, vec![0.5; 200]);
+            x.resample_time_stretch(-1).unwrap();
+            assert_eq!(x.len, 200);
+            assert_eq!(x.samples.len(), 400);
+        }
+    }
+
+    mod stretch {
+        use super::*;
+
+        #[test]
+        fn time_stretch() {
+            let mut x = test_wav();
+            let len = x.len;
+            x.time_stretch(1.5).unwrap();
+            assert_eq!(x.len, (len as f32 * 1.5).round() as u32);
+            assert_eq!(x.samples.len() as u32, x.len * x.channels as u32);
+        }
+
+        #[test]
+        fn time_stretch_oob_err() {
+            let mut x = test_wav();
+            assert!(x.time_stretch(0.2).is_err());
+            assert!(x.time_stretch(4.5).is_err());
+        }
+
+        #[test]
+        fn pitch_shift() {
+            let mut x = test_wav();
+            let len = x.len;
+            x.pitch_shift(-3.5).unwrap();
+            assert_eq!(x.len, len);
+            assert_eq!(x.samples.len() as u32, x.len * x.channels as u32);
+        }
+
+        #[test]
+        fn pitch_shift_oob_err() {
+            let mut x = test_wav();
+            assert!(x.pitch_shift(24.5).is_err());
+            assert!(x.pitch_shift(-25.0).is_err());
+        }
+    }
+
+    mod loudness {
+        use super::*;
+
+        #[test]
+        fn normalize_lufs() {
+            let mut x = test_wav();
+            x.loudness_normalize(LoudnessMode::Lufs, -20.0, 0.0)
+                .unwrap();
+            let stats = x.loudness_stats();
+            assert!((stats.lufs + 20.0).abs() < 0.01, "{stats:?}");
+        }
+
+        #[test]
+        fn normalize_rms() {
+            let mut x = test_wav();
+            x.loudness_normalize(LoudnessMode::Rms, -24.0, 0.0).unwrap();
+            let stats = x.loudness_stats();
+            assert!((stats.rms + 24.0).abs() < 0.01, "{stats:?}");
+        }
+
+        #[test]
+        fn normalize_ceiling() {
+            let mut x = test_wav();
+            x.loudness_normalize(LoudnessMode::Lufs, 0.0, -3.0).unwrap();
+            let stats = x.loudness_stats();
+            assert!((stats.true_peak + 3.0).abs() < 0.01, "{stats:?}");
+            assert!(stats.lufs < -3.0, "{stats:?}");
+        }
+
+        #[test]
+        fn normalize_oob_err() {
+            let mut x = test_wav();
+            assert!(x.loudness_normalize(LoudnessMode::Lufs, 1.0, -1.0).is_err());
+            assert!(x.loudness_normalize(LoudnessMode::Rms, -14.0, 0.5).is_err());
+        }
+    }
+
+    mod fades {
+        use super::*;
+
+        #[test]
+        fn linear_fade_in() {
+            let mut x = audio(2, vec![0.5; 200]);
+            x.linear_fade_in(0.1).unwrap();
+            assert_eq!(x.samples[..4], [0.0, 0.0, 0.05, 0.05]);
+            assert!(x.samples[20..].iter().all(|x| *x == 0.5));
+        }
+
+        #[test]
+        fn linear_fade_out() {
+            let mut x = audio(2, vec![0.5; 200]);
+            x.linear_fade_out(0.1).unwrap();
+            assert_eq!(x.samples[196..], [0.05, 0.05, 0.0, 0.0]);
+            assert!(x.samples[..180].iter().all(|x| *x == 0.5));
+        }
+
+        #[test]
+        fn linear_fade_tiny_percent() {
+            let mut x = audio(1, vec![0.5; 10]);
+            x.linear_fade_in(0.01).unwrap();
+            x.linear_fade_out(0.01).unwrap();
+            assert_eq!(x.samples, vec![0.5; 10]);
+        }
+
+        #[test]
+        fn linear_fade_oob_err() {
+            let mut x = audio(1, vec![0.5; 10]);
+            assert!(x.linear_fade_in(1.1).is_err());
+            assert!(x.linear_fade_out(-0.1).is_err());
+        }
+
+        #[test]
+        fn fade_curve_ms() {
+            let mut x = test_wav();
+            let len = x.ms_to_len(10.0);
+            assert_eq!(len, x.sample_rate as usize / 100);
+            let original = x.samples.clone();
+            x.fade_out(len, FadeCurve::SCurve);
+            let n = x.samples.len() - len * x.channels as usize;
+            assert_eq!(x.samples[..n], original[..n]);
+            assert!(x.samples[x.samples.len() - x.channels as usize..]
+                .iter()
+                .all(|x| *x == 0.0));
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! Level measurements (peak, true peak, RMS and integrated loudness) and loudness
+//! normalization.
+//!
+//! Integrated loudness follows ITU-R BS.1770 / EBU R128: channels are K-weighted (a filter
+//! roughly matching how loud different frequencies sound), the mean square level is measured
+//! over 400ms blocks overlapping by 75%, and blocks quieter than -70 LUFS or 10 LU below the
+//! level of the louder blocks are ignored. True peak is the peak level of the audio
+//! oversampled 4x, which catches peaks between samples that would clip once converted to
+//! analogue.
+
+use log::trace;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Which level to normalize audio to.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoudnessMode {
+    /// Integrated loudness (LUFS), closest to how loud audio sounds.
+    #[default]
+    Lufs,
+    /// RMS level (dBFS).
+    Rms,
+}
+
+/// Level measurements of some audio, in dB. Silent audio measures `f32::NEG_INFINITY`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessStats {
+    /// Sample peak level (dBFS)
+    pub peak: f32,
+    /// Peak level between samples (dBTP)
+    pub true_peak: f32,
+    /// RMS level of all channels (dBFS)
+    pub rms: f32,
+    /// Integrated loudness (LUFS)
+    pub lufs: f32,
+}
+
+/// Length of loudness measurement blocks, in seconds.
+const BLOCK_SECS: f64 = 0.4;
+
+/// Spacing of loudness measurement blocks, in seconds (75% overlap).
+const BLOCK_STEP_SECS: f64 = 0.1;
+
+/// Blocks quieter than this (LUFS) are ignored.
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// Blocks this much quieter (LU) than the level of the blocks above the absolute gate are
+/// ignored.
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Oversampling factor for true peak measurement.
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+/// Number of samples either side used to interpolate oversampled samples.
+const TRUE_PEAK_TAPS: i64 = 8;
+
+fn to_db(x: f64) -> f32 {
+    (20.0 * x.log10()) as f32
+}
+
+/// Biquad filter, transposed direct form II.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&self, x: &mut [f64]) {
+        let (mut z1, mut z2) = (0.0, 0.0);
+        for v in x.iter_mut() {
+            let y = self.b[0] * *v + z1;
+            z1 = self.b[1] * *v - self.a[0] * y + z2;
+            z2 = self.b[2] * *v - self.a[1] * y;
+            *v = y;
+        }
+    }
+}
+
+/// The two K-weighting filter stages (a high shelf for the acoustic effect of the head, then
+/// a high pass), with the BS.1770 48kHz coefficients recalculated for the sample rate.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let fs = sample_rate as f64;
+
+    let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / fs).tan();
+    let vh = 10.0_f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+
+    [shelf, high_pass]
+}
+
+/// Sample peak level of interleaved audio samples (dBFS).
+pub fn peak(samples: &[f32]) -> f32 {
+    to_db(samples.iter().map(|x| x.abs()).fold(0.0, f32::max) as f64)
+}
+
+/// True peak level of interleaved audio samples (dBTP).
+pub fn true_peak(samples: &[f32], channels: u16) -> f32 {
+    let n_chans = channels as usize;
+    let n_frames = (samples.len() / n_chans) as i64;
+
+    // Hann windowed sinc interpolation filter for each position between samples
+    let filters = (1..TRUE_PEAK_OVERSAMPLING)
+        .map(|phase| {
+            let frac = phase as f64 / TRUE_PEAK_OVERSAMPLING as f64;
+            (1 - TRUE_PEAK_TAPS..=TRUE_PEAK_TAPS)
+                .map(|k| {
+                    let x = frac - k as f64;
+                    let window = 0.5 + 0.5 * (PI * x / TRUE_PEAK_TAPS as f64).cos();
+                    ((PI * x).sin() / (PI * x) * window) as f32
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut max = samples.iter().map(|x| x.abs()).fold(0.0, f32::max);
+    for c in 0..n_chans {
+        let at = |i: i64| {
+            if i >= 0 && i < n_frames {
+                samples[i as usize * n_chans + c]
+            } else {
+                0.0
+            }
+        };
+        for i in -1..n_frames {
+            for filter in &filters {
+                let y = filter
+                    .iter()
+                    .zip(1 - TRUE_PEAK_TAPS..=TRUE_PEAK_TAPS)
+                    .map(|(w, k)| w * at(i + k))
+                    .sum::<f32>();
+                max = max.max(y.abs());
+            }
+        }
+    }
+    to_db(max as f64)
+}
+
+/// RMS level of interleaved audio samples (dBFS), all channels together.
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let mean_square =
+        samples.iter().map(|x| (*x as f64).powi(2)).sum::<f64>() / samples.len() as f64;
+    to_db(mean_square.sqrt())
+}
+
+/// Integrated loudness of interleaved audio samples (LUFS). Audio shorter than one
+/// measurement block (400ms, e.g. a drum hit) is measured as a single block.
+pub fn integrated_loudness(samples: &[f32], channels: u16, sample_rate: u32) -> f32 {
+    let n_chans = channels as usize;
+    let n_frames = samples.len() / n_chans;
+    if n_frames == 0 {
+        return f32::NEG_INFINITY;
+    }
+
+    // K-weighted, squared samples of each channel
+    let filters = k_weighting(sample_rate);
+    let squared = (0..n_chans)
+        .map(|c| {
+            let mut x = samples
+                .iter()
+                .skip(c)
+                .step_by(n_chans)
+                .map(|x| *x as f64)
+                .collect::<Vec<_>>();
+            filters.iter().for_each(|f| f.process(&mut x));
+            x.iter().map(|x| x * x).collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let block_len = ((BLOCK_SECS * sample_rate as f64).round() as usize).min(n_frames);
+    let step = (BLOCK_STEP_SECS * sample_rate as f64).round() as usize;
+
+    // sum of the channels' mean squares of each block (all channels are weighted 1.0 for
+    // mono/stereo audio)
+    let blocks = (0..=(n_frames - block_len) / step)
+        .map(|i| {
+            squared
+                .iter()
+                .map(|x| x[i * step..i * step + block_len].iter().sum::<f64>() / block_len as f64)
+                .sum::<f64>()
+        })
+        .collect::<Vec<_>>();
+
+    let loudness = |z: f64| -0.691 + 10.0 * z.log10();
+    let gated_mean = |gate: f64| {
+        let gated = blocks
+            .iter()
+            .filter(|z| loudness(**z) > gate)
+            .collect::<Vec<_>>();
+        (!gated.is_empty()).then(|| gated.iter().copied().sum::<f64>() / gated.len() as f64)
+    };
+
+    let Some(ungated) = gated_mean(ABSOLUTE_GATE) else {
+        return f32::NEG_INFINITY;
+    };
+    let relative_gate = loudness(ungated) + RELATIVE_GATE;
+    let lufs = gated_mean(relative_gate.max(ABSOLUTE_GATE))
+        .map_or(f32::NEG_INFINITY, |z| loudness(z) as f32);
+    trace!("Integrated loudness: lufs={lufs} blocks={}", blocks.len());
+    lufs
+}
+
+/// Measure the levels of interleaved audio samples.
+pub fn analyze(samples: &[f32], channels: u16, sample_rate: u32) -> LoudnessStats {
+    LoudnessStats {
+        peak: peak(samples),
+        true_peak: true_peak(samples, channels),
+        rms: rms(samples),
+        lufs: integrated_loudness(samples, channels, sample_rate),
+    }
+}
+
+/// Gain (in dB) which brings interleaved audio samples to `target` (LUFS or dBFS, depending on
+/// the mode), reduced if needed so the true peak level stays at or below `ceiling` (dBTP).
+/// `None` for silent audio.
+pub fn normalization_gain(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    mode: LoudnessMode,
+    target: f32,
+    ceiling: f32,
+) -> Option<f32> {
+    let level = match mode {
+        LoudnessMode::Lufs => integrated_loudness(samples, channels, sample_rate),
+        LoudnessMode::Rms => rms(samples),
+    };
+    if !level.is_finite() {
+        return None;
+    }
+    let headroom = ceiling - true_peak(samples, channels);
+    let gain = (target - level).min(headroom);
+    trace!("Loudness normalization: mode={mode:?} level={level} target={target} gain={gain}");
+    Some(gain)
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn sine(freq: f64, amp: f64, rate: u32, n_frames: usize) -> Vec<f32> {
+        (0..n_frames)
+            .map(|i| ((2.0 * PI * freq * i as f64 / rate as f64).sin() * amp) as f32)
+            .collect()
+    }
+
+    fn stereo(x: &[f32]) -> Vec<f32> {
+        x.iter().flat_map(|x| [*x, *x]).collect()
+    }
+
+    fn assert_close(x: f32, expected: f32, tolerance: f32) {
+        assert!((x - expected).abs() <= tolerance, "{x} != {expected}");
+    }
+
+    #[test]
+    fn peak_and_rms() {
+        let x = sine(1000.0, 0.5, SAMPLE_RATE, 48000);
+        assert_close(peak(&x), -6.02, 0.01);
+        // sine RMS is 3.01dB below the peak
+        assert_close(rms(&x), -9.03, 0.01);
+    }
+
+    #[test]
+    fn silence() {
+        let x = vec![0.0; 48000];
+        let stats = analyze(&x, 1, SAMPLE_RATE);
+        assert_eq!(stats.peak, f32::NEG_INFINITY);
+        assert_eq!(stats.rms, f32::NEG_INFINITY);
+        assert_eq!(stats.lufs, f32::NEG_INFINITY);
+        assert!(normalization_gain(&x, 1, SAMPLE_RATE, LoudnessMode::Lufs, -14.0, -1.0).is_none());
+    }
+
+    #[test]
+    fn lufs_reference_tone() {
+        // EBU Tech 3341: a stereo 1kHz sine at -18 dBFS (peak) in each channel is -18 LUFS
+        let amp = 10.0_f64.powf(-18.0 / 20.0);
+        let x = stereo(&sine(1000.0, amp, SAMPLE_RATE, 20 * 48000));
+        assert_close(integrated_loudness(&x, 2, SAMPLE_RATE), -18.0, 0.1);
+    }
+
+    #[test]
+    fn lufs_reference_tone_44100() {
+        let amp = 10.0_f64.powf(-23.0 / 20.0);
+        let x = stereo(&sine(1000.0, amp, 44100, 10 * 44100));
+        assert_close(integrated_loudness(&x, 2, 44100), -23.0, 0.1);
+    }
+
+    #[test]
+    fn lufs_k_weighting() {
+        // low frequencies are quieter, high frequencies a little louder
+        let low = sine(40.0, 0.5, SAMPLE_RATE, 48000);
+        let mid = sine(1000.0, 0.5, SAMPLE_RATE, 48000);
+        let high = sine(8000.0, 0.5, SAMPLE_RATE, 48000);
+        let lufs = |x: &[f32]| integrated_loudness(x, 1, SAMPLE_RATE);
+        assert!(lufs(&low) < lufs(&mid) - 1.0);
+        assert!(lufs(&high) > lufs(&mid) + 3.0);
+    }
+
+    #[test]
+    fn lufs_gating() {
+        // like EBU Tech 3341 case 3 (10s at -36 dBFS, 60s at -23 dBFS, 10s at -36 dBFS) but
+        // shorter, measures -23 LUFS as the quiet parts are gated out
+        let quiet = 10.0_f64.powf(-36.0 / 20.0);
+        let loud = 10.0_f64.powf(-23.0 / 20.0);
+        let mut x = sine(1000.0, quiet, SAMPLE_RATE, 2 * 48000);
+        x.extend(sine(1000.0, loud, SAMPLE_RATE, 12 * 48000));
+        x.extend(sine(1000.0, quiet, SAMPLE_RATE, 2 * 48000));
+        let x = stereo(&x);
+        assert_close(integrated_loudness(&x, 2, SAMPLE_RATE), -23.0, 0.1);
+    }
+
+    #[test]
+    fn lufs_shorter_than_block() {
+        let amp = 10.0_f64.powf(-20.0 / 20.0);
+        let x = stereo(&sine(1000.0, amp, SAMPLE_RATE, 4800));
+        assert_close(integrated_loudness(&x, 2, SAMPLE_RATE), -20.0, 0.2);
+    }
+
+    #[test]
+    fn true_peak_between_samples() {
+        // a quarter sample rate sine sampled at +-45 degrees peaks between samples
+        let x = (0..4800)
+            .map(|i| (PI / 2.0 * i as f64 + PI / 4.0).sin() as f32 * 0.5)
+            .collect::<Vec<_>>();
+        assert_close(peak(&x), -9.03, 0.01);
+        assert_close(true_peak(&x, 1), -6.02, 0.2);
+    }
+
+    #[test]
+    fn gain_to_target() {
+        let x = sine(1000.0, 0.1, SAMPLE_RATE, 48000);
+        let gain = normalization_gain(&x, 1, SAMPLE_RATE, LoudnessMode::Rms, -12.0, 0.0).unwrap();
+        assert_close(gain, -12.0 - rms(&x), 0.01);
+    }
+
+    #[test]
+    fn gain_limited_by_ceiling() {
+        // -20 dBFS peak, so can only be raised 19 dB for a -1 dBTP ceiling
+        let x = sine(1000.0, 0.1, SAMPLE_RATE, 48000);
+        let gain = normalization_gain(&x, 1, SAMPLE_RATE, LoudnessMode::Lufs, 0.0, -1.0).unwrap();
+        assert_close(gain, 19.0, 0.1);
+    }
+}
@@ -0,0 +1,253 @@
+//! Onset (transient) detection, e.g. for finding the hits in a drum break.
+//!
+//! The onset strength of each short frame of audio is the rise in energy (in dB) from the
+//! previous frame. Onsets are peaks in the onset strength which stand out from the surrounding
+//! frames by more than a threshold, and are at least a minimum gap apart.
+
+use log::trace;
+use serde::{Deserialize, Serialize};
+
+/// Number of samples between the start of consecutive analysis frames (~5.8ms at 44.1kHz).
+pub const ONSET_HOP_SIZE: usize = 256;
+
+/// Number of samples in each analysis frame.
+const FRAME_SIZE: usize = 512;
+
+/// Energy floor relative to the loudest frame, so that noise in near-silent sections
+/// doesn't show up as large jumps in dB.
+const ENERGY_FLOOR_DB: f32 = 60.0;
+
+/// Energy (dB) below which audio is considered silent.
+const SILENCE_DB: f32 = -90.0;
+
+/// Rise in energy (dB) between frames which counts as a full strength onset.
+const FULL_STRENGTH_DB: f32 = 20.0;
+
+/// Number of frames either side of a frame used to calculate the adaptive threshold (~100ms).
+const ADAPTIVE_WINDOW: usize = 16;
+
+/// Number of frames either side of a frame it must be larger than to be a peak.
+const PEAK_WINDOW: usize = 2;
+
+/// Settings for onset detection.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct OnsetOpts {
+    /// Sensitivity, between 0.0 and 1.0. The onset strength (rise in energy, where 1.0 is a 20dB
+    /// rise) must exceed the average strength of the surrounding ~100ms by this much.
+    /// Lower values find more (quieter) onsets.
+    pub threshold: f32,
+    /// Minimum time between onsets in milliseconds, weaker onsets within this time of a stronger
+    /// onset are ignored.
+    pub min_gap_ms: f32,
+    /// Maximum number of onsets, the strongest onsets are kept.
+    pub max_onsets: usize,
+}
+
+impl Default for OnsetOpts {
+    fn default() -> Self {
+        Self {
+            threshold: 0.2,
+            min_gap_ms: 50.0,
+            max_onsets: 64,
+        }
+    }
+}
+
+/// Mean energy (mean squared amplitude) of each frame of mono audio. Frame `i` starts at sample
+/// `i * ONSET_HOP_SIZE`.
+pub(crate) fn frame_energy(mono: &[f32]) -> Vec<f32> {
+    (0..mono.len().div_ceil(ONSET_HOP_SIZE))
+        .map(|i| {
+            let start = i * ONSET_HOP_SIZE;
+            let frame = &mono[start..(start + FRAME_SIZE).min(mono.len())];
+            frame.iter().map(|x| x * x).sum::<f32>() / FRAME_SIZE as f32
+        })
+        .collect()
+}
+
+/// Onset strength of each frame of mono audio, between 0.0 and 1.0 (a rise in energy of
+/// `FULL_STRENGTH_DB` or more). Frame `i` starts at sample `i * ONSET_HOP_SIZE`.
+pub fn onset_strength(mono: &[f32]) -> Vec<f32> {
+    let energy_db = frame_energy(mono)
+        .iter()
+        .map(|energy| 10.0 * (energy + 1e-12).log10())
+        .collect::<Vec<_>>();
+
+    let floor =
+        (energy_db.iter().copied().fold(f32::MIN, f32::max) - ENERGY_FLOOR_DB).max(SILENCE_DB);
+
+    // audio starting at the very first sample is an onset, so start from the floor
+    let mut previous = floor;
+    energy_db
+        .iter()
+        .map(|e| {
+            let e = e.max(floor);
+            let rise = (e - previous).max(0.0);
+            previous = e;
+            (rise / FULL_STRENGTH_DB).min(1.0)
+        })
+        .collect()
+}
+
+/// Detect onsets in interleaved audio samples. Returns the sample (frame) positions of the
+/// onsets in ascending order.
+pub fn detect_onsets(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    opts: &OnsetOpts,
+) -> Vec<u32> {
+    let mono = samples
+        .chunks(channels as usize)
+        .map(|x| x.iter().sum::<f32>() / channels as f32)
+        .collect::<Vec<_>>();
+
+    let strength = onset_strength(&mono);
+    let n = strength.len();
+
+    let mut peaks = (0..n)
+        .filter(|&i| {
+            let lo = i.saturating_sub(ADAPTIVE_WINDOW);
+            let hi = (i + ADAPTIVE_WINDOW + 1).min(n);
+            let local_mean = strength[lo..hi].iter().sum::<f32>() / (hi - lo) as f32;
+
+            let is_peak = strength[i.saturating_sub(PEAK_WINDOW)..(i + PEAK_WINDOW + 1).min(n)]
+                .iter()
+                .all(|x| *x <= strength[i]);
+
+            is_peak && strength[i] > 0.0 && strength[i] >= local_mean + opts.threshold
+        })
+        .collect::<Vec<_>>();
+
+    // strongest first, so weaker onsets are the ones dropped by the min gap / max onsets
+    peaks.sort_by(|a, b| strength[*b].total_cmp(&strength[*a]).then(a.cmp(b)));
+
+    let min_gap_frames = (opts.min_gap_ms / 1000.0 * sample_rate as f32) as usize / ONSET_HOP_SIZE;
+    let mut onsets: Vec<usize> = vec![];
+    for peak in peaks {
+        if onsets.len() >= opts.max_onsets {
+            break;
+        }
+        if onsets.iter().all(|x| x.abs_diff(peak) > min_gap_frames) {
+            onsets.push(peak);
+        }
+    }
+
+    onsets.sort();
+    trace!("Detected onsets: n={} frames={onsets:?}", onsets.len());
+    onsets.iter().map(|x| (x * ONSET_HOP_SIZE) as u32).collect()
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    /// Silence with decaying noise bursts (drum hit-ish) starting at each position.
+    fn bursts(positions: &[usize], amplitudes: &[f32], len: usize) -> Vec<f32> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut x = vec![0.0_f32; len];
+        for (pos, amp) in positions.iter().zip(amplitudes) {
+            for (i, y) in x[*pos..(*pos + 4410).min(len)].iter_mut().enumerate() {
+                let decay = (-(i as f32) / 600.0).exp();
+                *y += rng.gen_range(-1.0..1.0) * amp * decay;
+            }
+        }
+        x
+    }
+
+    fn assert_close(onsets: &[u32], positions: &[usize]) {
+        assert_eq!(onsets.len(), positions.len(), "{onsets:?} != {positions:?}");
+        for (onset, pos) in onsets.iter().zip(positions) {
+            assert!(
+                (*onset as i64 - *pos as i64).abs() <= FRAME_SIZE as i64,
+                "{onsets:?} != {positions:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn silence() {
+        let x = vec![0.0; 44100];
+        assert!(detect_onsets(&x, 1, SAMPLE_RATE, &OnsetOpts::default()).is_empty());
+    }
+
+    #[test]
+    fn evenly_spaced_hits() {
+        let positions = [0, 11025, 22050, 33075];
+        let x = bursts(&positions, &[0.8; 4], 44100);
+        let onsets = detect_onsets(&x, 1, SAMPLE_RATE, &OnsetOpts::default());
+        assert_close(&onsets, &positions);
+    }
+
+    #[test]
+    fn uneven_hits_with_quiet_ghost_note() {
+        let positions = [1000, 8000, 13000, 30000];
+        let x = bursts(&positions, &[0.9, 0.2, 0.7, 0.9], 44100);
+        let onsets = detect_onsets(&x, 1, SAMPLE_RATE, &OnsetOpts::default());
+        assert_close(&onsets, &positions);
+    }
+
+    #[test]
+    fn stereo() {
+        let positions = [2000, 20000];
+        let mono = bursts(&positions, &[0.8; 2], 44100);
+        let x = mono.iter().flat_map(|x| [*x, *x * 0.5]).collect::<Vec<_>>();
+        let onsets = detect_onsets(&x, 2, SAMPLE_RATE, &OnsetOpts::default());
+        assert_close(&onsets, &positions);
+    }
+
+    #[test]
+    fn higher_threshold_fewer_onsets() {
+        // quiet hit during the decay of a loud hit
+        let positions = [1000, 4000, 13000, 30000];
+        let x = bursts(&positions, &[0.9, 0.08, 0.7, 0.9], 44100);
+        let low = detect_onsets(&x, 1, SAMPLE_RATE, &OnsetOpts::default());
+        let high = detect_onsets(
+            &x,
+            1,
+            SAMPLE_RATE,
+            &OnsetOpts {
+                threshold: 0.6,
+                ..OnsetOpts::default()
+            },
+        );
+        assert!(high.len() < low.len(), "{high:?} {low:?}");
+    }
+
+    #[test]
+    fn min_gap_drops_weaker_onset() {
+        // flam: two hits 30ms apart
+        let positions = [5000, 6323, 25000];
+        let x = bursts(&positions, &[0.9, 0.5, 0.9], 44100);
+        let opts = OnsetOpts {
+            threshold: 0.05,
+            ..OnsetOpts::default()
+        };
+        let onsets = detect_onsets(&x, 1, SAMPLE_RATE, &opts);
+        assert_close(&onsets, &[5000, 25000]);
+
+        let onsets = detect_onsets(
+            &x,
+            1,
+            SAMPLE_RATE,
+            &OnsetOpts {
+                min_gap_ms: 5.0,
+                ..opts
+            },
+        );
+        assert_close(&onsets, &positions);
+    }
+
+    #[test]
+    fn max_onsets() {
+        let positions = (0..100).map(|i| i * 4410).collect::<Vec<_>>();
+        let x = bursts(&positions, &[0.8; 100], 441000);
+        let onsets = detect_onsets(&x, 1, SAMPLE_RATE, &OnsetOpts::default());
+        assert_eq!(onsets.len(), 64);
+        assert!(onsets.windows(2).all(|x| x[0] < x[1]));
+    }
+}
@@ -0,0 +1,206 @@
+//! Band-limited sample rate conversion with a windowed-sinc interpolation filter.
+//!
+//! Each output sample is a weighted sum of the surrounding input samples, weighted by a
+//! Kaiser windowed sinc function centred on the output sample's position in the input.
+//! When downsampling, the sinc cutoff is lowered to the output Nyquist frequency so content
+//! which can't be represented at the lower sample rate is filtered out instead of aliasing.
+
+use log::trace;
+use std::f64::consts::PI;
+
+/// Number of sinc zero crossings on either side of the filter's centre.
+/// More zero crossings gives a steeper transition band, at the cost of speed.
+const ZERO_CROSSINGS: usize = 32;
+
+/// Number of filter table entries per sinc zero crossing, the filter is linearly
+/// interpolated between entries.
+const TABLE_OVERSAMPLING: usize = 512;
+
+/// Kaiser window shape parameter, ~80dB stopband attenuation.
+const KAISER_BETA: f64 = 8.0;
+
+/// Cutoff frequency as a ratio of the (lower) Nyquist frequency. Slightly below Nyquist
+/// so the transition band sits (mostly) below it.
+const CUTOFF: f64 = 0.95;
+
+/// Zeroth order modified Bessel function of the first kind (for the Kaiser window).
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    let mut k = 1.0;
+    while term > sum * 1e-12 {
+        term *= (half_x / k) * (half_x / k);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+/// Right hand half of the windowed sinc filter, sampled `TABLE_OVERSAMPLING` times per
+/// zero crossing. The filter is symmetric so only one half is needed.
+fn windowed_sinc_table() -> Vec<f32> {
+    let len = ZERO_CROSSINGS * TABLE_OVERSAMPLING;
+    let i0_beta = bessel_i0(KAISER_BETA);
+
+    (0..=len)
+        .map(|i| {
+            let x = i as f64 / TABLE_OVERSAMPLING as f64;
+            let sinc = if i == 0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            let r = i as f64 / len as f64;
+            let window = bessel_i0(KAISER_BETA * (1.0 - r * r).sqrt()) / i0_beta;
+            (sinc * window) as f32
+        })
+        .collect()
+}
+
+/// Look up the filter value at `x` zero crossings from the centre.
+fn filter_value(table: &[f32], x: f64) -> f32 {
+    let pos = x.abs() * TABLE_OVERSAMPLING as f64;
+    let idx = pos as usize;
+    if idx + 1 >= table.len() {
+        return 0.0;
+    }
+    let frac = (pos - idx as f64) as f32;
+    table[idx] + (table[idx + 1] - table[idx]) * frac
+}
+
+/// Number of output frames when converting `n_frames` from one sample rate to another.
+pub fn resampled_len(n_frames: usize, from_rate: u32, to_rate: u32) -> usize {
+    (n_frames as u64 * to_rate as u64).div_ceil(from_rate as u64) as usize
+}
+
+/// Convert interleaved audio samples from one sample rate to another.
+pub fn resample(samples: &[f32], channels: u16, from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+    trace!("Resampling audio: from={from_rate} to={to_rate} channels={channels}");
+
+    let n_chans = channels as usize;
+    let in_frames = samples.len() / n_chans;
+    let out_frames = resampled_len(in_frames, from_rate, to_rate);
+
+    let table = windowed_sinc_table();
+
+    // filter cutoff relative to the input nyquist frequency, lowered when downsampling
+    let cutoff = CUTOFF * (to_rate as f64 / from_rate as f64).min(1.0);
+    // number of input samples either side of the output sample within the filter
+    let half_width = (ZERO_CROSSINGS as f64 / cutoff).ceil() as i64;
+
+    let mut out = vec![0.0_f32; out_frames * n_chans];
+
+    for (n, out_frame) in out.chunks_mut(n_chans).enumerate() {
+        // exact input position of the output frame, as an integer and fractional part
+        let pos = n as u64 * from_rate as u64;
+        let centre = (pos / to_rate as u64) as i64;
+        let frac = (pos % to_rate as u64) as f64 / to_rate as f64;
+
+        let first = (centre - half_width + 1).max(0);
+        let last = (centre + half_width).min(in_frames as i64 - 1);
+
+        for k in first..=last {
+            let distance = (k - centre) as f64 - frac;
+            let weight = filter_value(&table, distance * cutoff);
+            if weight == 0.0 {
+                continue;
+            }
+            let in_frame = &samples[k as usize * n_chans..(k as usize + 1) * n_chans];
+            for (y, x) in out_frame.iter_mut().zip(in_frame) {
+                *y += x * weight;
+            }
+        }
+
+        // normalise gain (filter is scaled by the cutoff when lowpass filtering)
+        for y in out_frame.iter_mut() {
+            *y *= cutoff as f32;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use crate::test_utils::sine;
+
+    fn rms(x: &[f32]) -> f32 {
+        (x.iter().map(|v| v * v).sum::<f32>() / x.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn same_rate_unchanged() {
+        let x = sine(440.0, 44100, 1000);
+        assert_eq!(resample(&x, 1, 44100, 44100), x);
+    }
+
+    #[test]
+    fn output_length() {
+        assert_eq!(resampled_len(48000, 48000, 44100), 44100);
+        assert_eq!(resampled_len(96000, 96000, 44100), 44100);
+        assert_eq!(resampled_len(22050, 22050, 44100), 44100);
+        assert_eq!(resampled_len(1, 48000, 44100), 1);
+
+        let x = sine(440.0, 48000, 4800);
+        assert_eq!(resample(&x, 1, 48000, 44100).len(), 4410);
+    }
+
+    #[test]
+    fn stereo_length() {
+        let x = vec![0.0; 9600];
+        assert_eq!(resample(&x, 2, 48000, 44100).len(), 4410 * 2);
+    }
+
+    // compare against a sine generated at the output rate, ignoring the edges where the
+    // filter runs off the ends of the input
+    fn check_sine(freq: f64, from_rate: u32) {
+        let x = sine(freq, from_rate, from_rate as usize / 10);
+        let y = resample(&x, 1, from_rate, 44100);
+        let expected = sine(freq, 44100, y.len());
+
+        let max_err = y[200..y.len() - 200]
+            .iter()
+            .zip(&expected[200..y.len() - 200])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(max_err < 2e-3, "from={from_rate} max_err={max_err}");
+    }
+
+    #[test]
+    fn sine_48k() {
+        check_sine(1000.0, 48000);
+    }
+
+    #[test]
+    fn sine_96k() {
+        check_sine(5000.0, 96000);
+    }
+
+    #[test]
+    fn sine_22k_upsample() {
+        check_sine(1000.0, 22050);
+    }
+
+    #[test]
+    fn downsample_removes_content_above_nyquist() {
+        // 30kHz can't be represented at 44.1kHz, should be filtered out rather than alias
+        let x = sine(30000.0, 96000, 9600);
+        let y = resample(&x, 1, 96000, 44100);
+        assert!(rms(&y[200..y.len() - 200]) < 1e-3);
+    }
+
+    #[test]
+    fn stereo_channels_independent() {
+        let left = sine(1000.0, 48000, 4800);
+        let interleaved = left.iter().flat_map(|x| [*x, 0.0]).collect::<Vec<_>>();
+        let y = resample(&interleaved, 2, 48000, 44100);
+        assert!(y.iter().skip(1).step_by(2).all(|x| *x == 0.0));
+        assert!(rms(&y.iter().step_by(2).copied().collect::<Vec<_>>()) > 0.3);
+    }
+}
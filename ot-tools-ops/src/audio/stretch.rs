@@ -0,0 +1,296 @@
+//! Time stretching with WSOLA (waveform similarity overlap-add), and pitch shifting by time
+//! stretching then resampling back to the original length.
+//!
+//! WSOLA cuts the audio into overlapping windowed frames, and overlap-adds them at a different
+//! spacing than they were read at. Each frame is read from up to a few milliseconds either side
+//! of its ideal position, wherever it best lines up with the audio that naturally followed the
+//! previous frame, so the waveforms join up without the phasing of a plain overlap-add. Unlike
+//! duplicating or dropping samples, the pitch doesn't change.
+
+use crate::audio::resample::resample;
+use log::trace;
+
+/// Shortest time stretch, as a multiple of the original length.
+pub const MIN_STRETCH_FACTOR: f32 = 0.25;
+
+/// Longest time stretch, as a multiple of the original length.
+pub const MAX_STRETCH_FACTOR: f32 = 4.0;
+
+/// Largest pitch shift up or down, in semitones.
+pub const MAX_PITCH_SHIFT_SEMITONES: f32 = 24.0;
+
+/// Length of the overlap-added frames in samples (~23ms at 44.1kHz), long enough to hold a
+/// couple of cycles of low notes.
+const FRAME_LEN: usize = 1024;
+
+/// Spacing of frames in the output. Hann windows at 50% overlap sum to one.
+const SYNTHESIS_HOP: usize = FRAME_LEN / 2;
+
+/// How far (in samples) frames can be moved from their ideal position to line up waveforms.
+const TOLERANCE: i64 = 256;
+
+/// Frames are first lined up roughly, trying every Nth position and comparing every Nth
+/// sample, then refined around the best rough position, for speed.
+const COARSE_STEP: usize = 4;
+
+/// Periodic Hann window.
+fn hann(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / len as f32).cos())
+        .collect()
+}
+
+/// Time stretch interleaved audio samples to `factor` times their length (2.0 is twice as
+/// long, i.e. half speed) without changing the pitch. Channels are mixed together to line up
+/// frames, so all channels are stretched the same way.
+pub fn time_stretch(samples: &[f32], channels: u16, factor: f32) -> Vec<f32> {
+    let n_chans = channels as usize;
+    let in_frames = samples.len() / n_chans;
+    let out_frames = (in_frames as f64 * factor as f64).round() as usize;
+    if (factor - 1.0).abs() < f32::EPSILON || in_frames == 0 {
+        return samples.to_vec();
+    }
+    trace!("Time stretching audio: factor={factor} frames={in_frames} channels={channels}");
+
+    let analysis_hop = SYNTHESIS_HOP as f64 / factor as f64;
+    let n_hops = out_frames.div_ceil(SYNTHESIS_HOP);
+
+    // mixed down, with silence either side so frames can be compared past the ends. the last
+    // ideal frame position can be past the end of the input (by up to an analysis hop), and
+    // frames are compared up to a synthesis hop and `TOLERANCE` after that
+    let padding = FRAME_LEN + TOLERANCE as usize;
+    let max_ideal = (n_hops as f64 * analysis_hop).round() as usize;
+    let tail = (max_ideal + SYNTHESIS_HOP + FRAME_LEN).saturating_sub(in_frames) + padding;
+    let mut mono = vec![0.0_f32; padding];
+    mono.extend(samples.chunks(n_chans).map(|x| x.iter().sum::<f32>()));
+    mono.extend(vec![0.0_f32; tail]);
+
+    // how well the frame starting at `a` lines up with the frame starting at `b`, comparing
+    // every `step`th sample
+    let similarity = |a: i64, b: i64, step: usize| {
+        let a = &mono[(a + padding as i64) as usize..][..FRAME_LEN];
+        let b = &mono[(b + padding as i64) as usize..][..FRAME_LEN];
+        a.iter()
+            .step_by(step)
+            .zip(b.iter().step_by(step))
+            .map(|(x, y)| x * y)
+            .sum::<f32>()
+    };
+    // offset (from `ideal`) of the frame which best lines up with `natural`, nearest to the
+    // ideal position when scores are equal (e.g. silence)
+    let best_offset = |natural: i64, ideal: i64, offsets: &mut dyn Iterator<Item = i64>, step| {
+        offsets
+            .map(|offset| (offset, similarity(natural, ideal + offset, step)))
+            .fold((0_i64, f32::MIN), |best, (offset, score)| {
+                if score > best.1 || (score == best.1 && offset.abs() < best.0.abs()) {
+                    (offset, score)
+                } else {
+                    best
+                }
+            })
+            .0
+    };
+
+    let window = hann(FRAME_LEN);
+    let half = (FRAME_LEN / 2) as i64;
+
+    let mut out = vec![0.0_f32; out_frames * n_chans];
+    // input position of the (centre of the) previous frame
+    let mut previous = 0_i64;
+
+    // frames are centred on multiples of the hop, the first half of the first frame is
+    // before the start of the audio so the windows sum to one everywhere
+    for k in 0..=n_hops {
+        let ideal = (k as f64 * analysis_hop).round() as i64;
+        let pos = if k == 0 {
+            0
+        } else {
+            // audio that followed the previous frame, which the next frame should line up with
+            let (natural, start) = (previous + SYNTHESIS_HOP as i64 - half, ideal - half);
+            let step = COARSE_STEP as i64;
+            let rough = best_offset(
+                natural,
+                start,
+                &mut (-TOLERANCE..=TOLERANCE).step_by(COARSE_STEP),
+                COARSE_STEP,
+            );
+            let fine = best_offset(
+                natural,
+                start,
+                &mut ((rough - step + 1).max(-TOLERANCE)..=(rough + step - 1).min(TOLERANCE)),
+                1,
+            );
+            ideal + fine
+        };
+
+        let out_start = (k * SYNTHESIS_HOP) as i64 - half;
+        for (j, w) in window.iter().enumerate() {
+            let (o, i) = (out_start + j as i64, pos - half + j as i64);
+            if o < 0 || o as usize >= out_frames || i < 0 || i as usize >= in_frames {
+                continue;
+            }
+            let (o, i) = (o as usize * n_chans, i as usize * n_chans);
+            for c in 0..n_chans {
+                out[o + c] += samples[i + c] * w;
+            }
+        }
+        previous = pos;
+    }
+
+    out
+}
+
+/// Pitch shift interleaved audio samples by some (fractional) number of semitones without
+/// changing their length, by time stretching and then resampling back to the original length.
+pub fn pitch_shift(samples: &[f32], channels: u16, sample_rate: u32, semitones: f32) -> Vec<f32> {
+    if semitones == 0.0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+    trace!("Pitch shifting audio: semitones={semitones} channels={channels}");
+
+    let ratio = 2.0_f64.powf(semitones as f64 / 12.0);
+    let stretched = time_stretch(samples, channels, ratio as f32);
+
+    // playing the stretched audio back `ratio` times faster shifts the pitch by `ratio`, and
+    // returns it to the original length
+    let from_rate = (sample_rate as f64 * ratio).round() as u32;
+    let mut shifted = resample(&stretched, channels, from_rate, sample_rate);
+    shifted.resize(samples.len(), 0.0);
+    shifted
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use crate::test_utils::sine;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    fn rms(x: &[f32]) -> f32 {
+        (x.iter().map(|v| v * v).sum::<f32>() / x.len() as f32).sqrt()
+    }
+
+    /// Frequency of a (mono) sine from the number of times it crosses zero, ignoring the
+    /// first and last 10% of the audio.
+    fn frequency(x: &[f32]) -> f32 {
+        let x = &x[x.len() / 10..x.len() * 9 / 10];
+        let crossings = x
+            .windows(2)
+            .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+            .count();
+        crossings as f32 / 2.0 / (x.len() as f32 / SAMPLE_RATE as f32)
+    }
+
+    fn assert_close(x: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (x - expected).abs() <= expected * tolerance,
+            "{x} != {expected}"
+        );
+    }
+
+    #[test]
+    fn stretch_len() {
+        let x = sine(440.0, SAMPLE_RATE, 22050);
+        assert_eq!(time_stretch(&x, 1, 1.5).len(), 33075);
+        assert_eq!(time_stretch(&x, 1, 0.5).len(), 11025);
+        assert_eq!(time_stretch(&x, 1, 0.3).len(), 6615);
+    }
+
+    #[test]
+    fn stretch_range_end_points_any_len() {
+        // lengths which aren't a multiple of the hop, so the last frames are past the end
+        for n_frames in [1, 1023, 5001, 20483, 44101] {
+            let x = sine(440.0, SAMPLE_RATE, n_frames);
+            for factor in [MIN_STRETCH_FACTOR, MAX_STRETCH_FACTOR] {
+                let expected = (n_frames as f64 * factor as f64).round() as usize;
+                assert_eq!(
+                    time_stretch(&x, 1, factor).len(),
+                    expected,
+                    "{n_frames} {factor}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stretch_unity() {
+        let x = sine(440.0, SAMPLE_RATE, 1000);
+        assert_eq!(time_stretch(&x, 1, 1.0), x);
+    }
+
+    #[test]
+    fn stretch_keeps_pitch() {
+        let x = sine(440.0, SAMPLE_RATE, 22050);
+        for factor in [0.5, 0.8, 1.25, 2.0] {
+            let y = time_stretch(&x, 1, factor);
+            assert_close(frequency(&y), 440.0, 0.01);
+        }
+    }
+
+    #[test]
+    fn stretch_keeps_level() {
+        let x = sine(220.0, SAMPLE_RATE, 22050);
+        for factor in [0.5, 1.5, 3.0] {
+            let y = time_stretch(&x, 1, factor);
+            let n = y.len();
+            assert_close(rms(&y[n / 10..n * 9 / 10]), rms(&x), 0.05);
+        }
+    }
+
+    #[test]
+    fn stretch_stereo() {
+        // sine in the left channel, silent right channel
+        let x = sine(440.0, SAMPLE_RATE, 22050)
+            .iter()
+            .flat_map(|x| [*x, 0.0])
+            .collect::<Vec<_>>();
+        let y = time_stretch(&x, 2, 1.5);
+        assert_eq!(y.len(), 33075 * 2);
+
+        let left = y.iter().step_by(2).copied().collect::<Vec<_>>();
+        let right = y.iter().skip(1).step_by(2).copied().collect::<Vec<_>>();
+        assert_close(frequency(&left), 440.0, 0.01);
+        assert!(right.iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn stretch_silence() {
+        assert_eq!(time_stretch(&[0.0; 1000], 1, 2.0), vec![0.0; 2000]);
+    }
+
+    #[test]
+    fn pitch_shift_octave() {
+        let x = sine(440.0, SAMPLE_RATE, 22050);
+        let up = pitch_shift(&x, 1, SAMPLE_RATE, 12.0);
+        assert_eq!(up.len(), x.len());
+        assert_close(frequency(&up), 880.0, 0.01);
+
+        let down = pitch_shift(&x, 1, SAMPLE_RATE, -12.0);
+        assert_eq!(down.len(), x.len());
+        assert_close(frequency(&down), 220.0, 0.01);
+    }
+
+    #[test]
+    fn pitch_shift_fifth_and_cents() {
+        let x = sine(440.0, SAMPLE_RATE, 22050);
+        let y = pitch_shift(&x, 1, SAMPLE_RATE, 7.0);
+        assert_close(frequency(&y), 659.26, 0.01);
+
+        let y = pitch_shift(&x, 1, SAMPLE_RATE, -0.5);
+        assert_close(frequency(&y), 427.47, 0.005);
+    }
+
+    #[test]
+    fn pitch_shift_keeps_level() {
+        let x = sine(220.0, SAMPLE_RATE, 22050);
+        let y = pitch_shift(&x, 1, SAMPLE_RATE, 5.0);
+        assert_close(rms(&y[2205..19845]), rms(&x), 0.05);
+    }
+
+    #[test]
+    fn pitch_shift_zero() {
+        let x = sine(440.0, SAMPLE_RATE, 1000);
+        assert_eq!(pitch_shift(&x, 1, SAMPLE_RATE, 0.0), x);
+    }
+}
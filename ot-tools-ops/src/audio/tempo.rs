@@ -0,0 +1,248 @@
+//! Tempo (BPM) estimation for loops.
+//!
+//! The rise in level between short frames of audio (an onset envelope, like the one used in
+//! [`crate::audio::onsets`] but keeping the difference between accented and quieter hits) of a
+//! loop with a steady beat repeats every beat, so its autocorrelation peaks at the beat period.
+//! Candidate periods are weighted towards ~120 BPM to avoid half/double tempo guesses, and
+//! the estimate is snapped to a whole number of beats over the length of the audio when it's
+//! close, as loops are usually cut to an exact number of beats.
+
+use crate::audio::onsets::{frame_energy, ONSET_HOP_SIZE};
+use log::trace;
+
+/// Slowest tempo considered.
+pub const MIN_BPM: f32 = 60.0;
+
+/// Fastest tempo considered.
+pub const MAX_BPM: f32 = 200.0;
+
+/// Tempo that candidate tempos are weighted towards.
+const PRIOR_BPM: f32 = 120.0;
+
+/// Width of the tempo weighting, in octaves.
+const PRIOR_OCTAVES: f32 = 1.5;
+
+/// Maximum relative difference between the estimated tempo and the tempo that makes the audio
+/// a whole number of beats long, for the estimate to be snapped.
+const SNAP_TOLERANCE: f32 = 0.03;
+
+/// An estimated tempo.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoEstimate {
+    /// Tempo in beats per minute.
+    pub bpm: f32,
+    /// How strongly the audio repeats at the estimated tempo, between 0.0 (not at all) and
+    /// 1.0 (perfectly).
+    pub confidence: f32,
+}
+
+/// Normalised autocorrelation of a (mean removed) signal at a lag. Not corrected for the number
+/// of overlapping values, so longer lags (multiples of the beat period) score lower.
+fn autocorrelation(x: &[f32], lag: usize, energy: f32) -> f32 {
+    let sum = x.iter().zip(&x[lag..]).map(|(a, b)| a * b).sum::<f32>();
+    sum / energy
+}
+
+/// Smooth a signal with a 5 point triangular filter.
+fn smooth(x: &[f32]) -> Vec<f32> {
+    const KERNEL: [f32; 5] = [1.0 / 9.0, 2.0 / 9.0, 3.0 / 9.0, 2.0 / 9.0, 1.0 / 9.0];
+    (0..x.len())
+        .map(|i| {
+            KERNEL
+                .iter()
+                .enumerate()
+                .filter_map(|(k, w)| x.get((i + k).checked_sub(2)?).map(|v| v * w))
+                .sum()
+        })
+        .collect()
+}
+
+/// Estimate the tempo of interleaved audio samples. Returns `None` if the audio is too short
+/// to contain two beats at the fastest tempo, or has no onsets.
+pub fn estimate_tempo(samples: &[f32], channels: u16, sample_rate: u32) -> Option<TempoEstimate> {
+    let mono = samples
+        .chunks(channels as usize)
+        .map(|x| x.iter().sum::<f32>() / channels as f32)
+        .collect::<Vec<_>>();
+
+    // rise in RMS level, from silence so audio at the first sample counts as an onset
+    let mut previous = 0.0;
+    let rise = frame_energy(&mono)
+        .iter()
+        .map(|energy| {
+            let rms = energy.sqrt();
+            let rise = (rms - previous).max(0.0);
+            previous = rms;
+            rise
+        })
+        .collect::<Vec<_>>();
+
+    // smoothed, so beat periods between a whole number of frames don't split the peak
+    let strength = smooth(&rise);
+    let frame_rate = sample_rate as f32 / ONSET_HOP_SIZE as f32;
+    let min_lag = (60.0 * frame_rate / MAX_BPM).floor() as usize;
+    // at least two beats are needed to see a beat repeat
+    let max_lag = ((60.0 * frame_rate / MIN_BPM).ceil() as usize).min(strength.len() / 2);
+
+    if max_lag <= min_lag {
+        trace!(
+            "Audio too short for tempo estimation: frames={}",
+            strength.len()
+        );
+        return None;
+    }
+
+    let mean = strength.iter().sum::<f32>() / strength.len() as f32;
+    let x = strength.iter().map(|v| v - mean).collect::<Vec<_>>();
+    let energy = x.iter().map(|v| v * v).sum::<f32>();
+    if energy <= f32::EPSILON {
+        trace!("No onsets for tempo estimation.");
+        return None;
+    }
+
+    let acf = (0..=max_lag + 1)
+        .map(|lag| {
+            if lag < min_lag.saturating_sub(1) {
+                0.0
+            } else {
+                autocorrelation(&x, lag, energy)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let weight = |lag: usize| {
+        let bpm = 60.0 * frame_rate / lag as f32;
+        (-0.5 * ((bpm / PRIOR_BPM).log2() / PRIOR_OCTAVES).powi(2)).exp()
+    };
+
+    let best_lag = (min_lag..=max_lag)
+        .max_by(|a, b| (acf[*a] * weight(*a)).total_cmp(&(acf[*b] * weight(*b))))?;
+
+    // parabolic interpolation between lags for a fractional beat period
+    let (prev, peak, next) = (acf[best_lag - 1], acf[best_lag], acf[best_lag + 1]);
+    let denom = prev - 2.0 * peak + next;
+    let offset = if denom < 0.0 {
+        (0.5 * (prev - next) / denom).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+
+    let mut bpm = 60.0 * frame_rate / (best_lag as f32 + offset);
+
+    let duration_mins = mono.len() as f32 / sample_rate as f32 / 60.0;
+    let n_beats = (duration_mins * bpm).round();
+    let snapped = n_beats / duration_mins;
+    if n_beats >= 1.0 && ((snapped - bpm) / bpm).abs() <= SNAP_TOLERANCE {
+        trace!("Snapped tempo to whole beats: bpm={bpm} snapped={snapped} beats={n_beats}");
+        bpm = snapped;
+    }
+
+    let estimate = TempoEstimate {
+        bpm,
+        confidence: peak.clamp(0.0, 1.0),
+    };
+    trace!("Estimated tempo: {estimate:?}");
+    Some(estimate)
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    /// Decaying noise bursts every beat (with quieter hits every off-beat if `offbeats`).
+    fn drum_loop(bpm: f32, n_beats: usize, offbeats: bool) -> Vec<f32> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let beat_len = 60.0 * SAMPLE_RATE as f32 / bpm;
+        let len = (beat_len * n_beats as f32).round() as usize;
+        let mut x = vec![0.0_f32; len];
+
+        let n_hits = if offbeats { n_beats * 2 } else { n_beats };
+        for hit in 0..n_hits {
+            let (pos, amp) = if offbeats {
+                (
+                    hit as f32 * beat_len / 2.0,
+                    if hit % 2 == 0 { 0.9 } else { 0.3 },
+                )
+            } else {
+                (hit as f32 * beat_len, 0.9)
+            };
+            let pos = pos.round() as usize;
+            for (i, y) in x[pos..(pos + 3000).min(len)].iter_mut().enumerate() {
+                *y += rng.gen_range(-1.0..1.0) * amp * (-(i as f32) / 500.0).exp();
+            }
+        }
+        x
+    }
+
+    fn assert_bpm(x: &[f32], channels: u16, expected: f32) -> TempoEstimate {
+        let estimate = estimate_tempo(x, channels, SAMPLE_RATE).unwrap();
+        assert!(
+            (estimate.bpm - expected).abs() < 0.5,
+            "{estimate:?} != {expected}"
+        );
+        estimate
+    }
+
+    #[test]
+    fn silence() {
+        assert!(estimate_tempo(&[0.0; 441000], 1, SAMPLE_RATE).is_none());
+    }
+
+    #[test]
+    fn too_short() {
+        let x = drum_loop(120.0, 1, false);
+        assert!(estimate_tempo(&x, 1, SAMPLE_RATE).is_none());
+    }
+
+    #[test]
+    fn loop_120() {
+        let estimate = assert_bpm(&drum_loop(120.0, 8, false), 1, 120.0);
+        assert!(estimate.confidence > 0.5, "{estimate:?}");
+    }
+
+    #[test]
+    fn loop_90_with_offbeats() {
+        assert_bpm(&drum_loop(90.0, 8, true), 1, 90.0);
+    }
+
+    #[test]
+    fn loop_174() {
+        assert_bpm(&drum_loop(174.0, 16, false), 1, 174.0);
+    }
+
+    #[test]
+    fn loop_fractional_bpm_snapped() {
+        let estimate = assert_bpm(&drum_loop(97.5, 8, false), 1, 97.5);
+        assert!((estimate.bpm - 97.5).abs() < 0.05, "{estimate:?}");
+    }
+
+    #[test]
+    fn stereo() {
+        let x = drum_loop(128.0, 8, true)
+            .iter()
+            .flat_map(|x| [*x, *x])
+            .collect::<Vec<_>>();
+        assert_bpm(&x, 2, 128.0);
+    }
+
+    #[test]
+    fn noise_low_confidence() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let x = (0..441000)
+            .map(|_| rng.gen_range(-0.5..0.5))
+            .collect::<Vec<f32>>();
+        let confidence = estimate_tempo(&x, 1, SAMPLE_RATE)
+            .map(|x| x.confidence)
+            .unwrap_or(0.0);
+        assert!(confidence < 0.3, "{confidence}");
+    }
+
+    #[test]
+    fn one_bar_loop() {
+        assert_bpm(&drum_loop(140.0, 4, true), 1, 140.0);
+    }
+}
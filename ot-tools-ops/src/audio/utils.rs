@@ -77,7 +77,7 @@ fn file_path_is_compat_aiff(path: &PathBuf) -> RBoxErr<bool> {
     Ok(OCTATRACK_COMPATIBLE_AUDIO_SPECS.contains(&audio_spec))
 }
 
-/// Recursively search for WAV audio files for a given directory tree.
+/// Recursively search for WAV/AIFF audio files for a given directory tree.
 pub fn scan_dir_path_for_audio_files(dir_path: &PathBuf) -> RBoxErr<Vec<PathBuf>> {
     info!(
         "Recursively searching for Octatrack compatible audio files: dirpath={:1?}",
@@ -95,7 +95,11 @@ pub fn scan_dir_path_for_audio_files(dir_path: &PathBuf) -> RBoxErr<Vec<PathBuf>
                 // need to filter them out below (only want audio files)
                 direntry_is_dir(e)
                     || { direntry_has_file_extension(e, "wav") && direntry_is_compat_wav(e) }
-                    || { direntry_has_file_extension(e, "aiff") && direntry_is_compat_aiff(e) }
+                    || {
+                        (direntry_has_file_extension(e, "aiff")
+                            || direntry_has_file_extension(e, "aif"))
+                            && direntry_is_compat_aiff(e)
+                    }
             }
         })
         .map(|x| x.unwrap())
@@ -1,5 +1,7 @@
 //! Reading and Writing .wav files.
 
+use crate::audio::file::AudioFile;
+use crate::audio::AudioErrors;
 use crate::RBoxErr;
 use hound::{self, WavReader, WavSpec};
 use log::trace;
@@ -10,30 +12,6 @@ use std::{
     path::{Path, PathBuf},
 };
 
-#[derive(Debug)]
-enum AudioErrors {
-    InvalidSampleFormat,
-    InvalidBitDepth,
-    InvalidSampleRate,
-    InvalidChannelCount,
-    FadePercentageOOB,
-}
-impl std::fmt::Display for AudioErrors {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Self::InvalidSampleFormat => write!(f, "Only signed PCM WAV files are supported"),
-            Self::InvalidBitDepth => write!(f, "Only 16/24-bit signed PCM WAV files are supported"),
-            Self::InvalidSampleRate => write!(f, "Only 44.1kHz WAV files are supported"),
-            Self::InvalidChannelCount => write!(f, "Only mono and stereo WAV files are supported"),
-            Self::FadePercentageOOB => write!(
-                f,
-                "Fade percentage parameter out of bounds, must be between 0.0 and 1.0",
-            ),
-        }
-    }
-}
-impl std::error::Error for AudioErrors {}
-
 pub const ALLOWED_SAMPLE_RATE: u32 = 44100;
 pub const ALLOWED_CHANNELS: [u16; 2] = [1, 2];
 pub const ALLOWED_BIT_DEPTHS: [u16; 2] = [16, 24];
@@ -54,36 +32,24 @@ pub struct WavFile {
     pub file_path: PathBuf,
 }
 
-// TODO: when I've got AIFF reading/writing sorted
-// #[derive(PartialEq, Debug, Clone)]
-// pub struct AudioFile {
-//     pub bit_depth: usize,
-//     pub channels: usize,
-//     pub len: usize,
-//     pub samples: Vec<f32>, // cannot use Copy trait
-//     pub fname: String,
-//     pub fpath: PathBuf,
-// }
-
-// the branches are not the same, i don't know why clippy is saying they are.
-#[allow(clippy::if_same_then_else)]
-fn f32_to_i32(v: f32) -> i32 {
-    // need to use explicit values here
-    // the boundary value i32::MAX/i32::MIN clip for some reason
-    // when converting to f32
-    if v > 0.0 {
-        (v * (2_u32.pow(32 - 1) - 1) as f32) as i32
+// exact inverse of `pcm_to_f32` (rounded), so reading and writing audio is lossless.
+// values outside -1.0..=1.0 are clipped.
+fn f32_to_pcm(v: f32, bit_depth: u16) -> i32 {
+    let max = 2_i64.pow(bit_depth as u32 - 1);
+    let x = if v > 0.0 {
+        (v as f64 * (max - 1) as f64).round()
     } else {
-        (v * 2_u32.pow(32 - 1) as f32) as i32
-    }
+        (v as f64 * max as f64).round()
+    };
+    x.clamp(-max as f64, (max - 1) as f64) as i32
 }
 
-fn f32_to_i24(v: f32) -> i32 {
-    f32_to_i32(v) >> 8
+pub(crate) fn f32_to_i24(v: f32) -> i32 {
+    f32_to_pcm(v, 24)
 }
 
-fn f32_to_i16(v: f32) -> i16 {
-    (f32_to_i32(v) >> 16) as i16
+pub(crate) fn f32_to_i16(v: f32) -> i16 {
+    f32_to_pcm(v, 16) as i16
 }
 
 #[allow(dead_code)]
@@ -95,7 +61,7 @@ fn i32_to_f32(v: i32) -> f32 {
     }
 }
 
-fn i24_to_f32(v: i32) -> f32 {
+pub(crate) fn i24_to_f32(v: i32) -> f32 {
     if v > 0 {
         v as f32 / (2_u32.pow(24 - 1) - 1) as f32
     } else {
@@ -103,7 +69,7 @@ fn i24_to_f32(v: i32) -> f32 {
     }
 }
 
-fn i16_to_f32(v: i16) -> f32 {
+pub(crate) fn i16_to_f32(v: i16) -> f32 {
     if v > 0 {
         v as f32 / (2_u32.pow(16 - 1) - 1) as f32
     } else {
@@ -111,6 +77,16 @@ fn i16_to_f32(v: i16) -> f32 {
     }
 }
 
+// same conversion as the fixed bit depth functions, for any integer bit depth
+fn pcm_to_f32(v: i32, bit_depth: u16) -> f32 {
+    let max = 2_u64.pow(bit_depth as u32 - 1) as f32;
+    if v > 0 {
+        v as f32 / (max - 1.0)
+    } else {
+        v as f32 / max
+    }
+}
+
 fn read_wav_i16_samples(reader: &mut WavReader<BufReader<File>>) -> RBoxErr<Vec<f32>> {
     Ok(reader
         .samples::<i16>()
@@ -208,6 +184,30 @@ impl WavFile {
         })
     }
 
+    /// Crete a new struct by reading any integer PCM (8/16/24/32 bit) or 32-bit float WAV file
+    /// located at `path`, without checking it is compatible with the Octatrack.
+    pub fn from_path_any_spec(path: &Path) -> RBoxErr<Self> {
+        trace!("Reading WAV file from path: {path:#?}");
+        let mut reader = WavFile::open(path)?;
+        let spec = WavFile::read_spec(&mut reader)?;
+
+        trace!("Reading WAV Samples: path={path:#?} spec={spec:?}");
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Int => reader
+                .samples::<i32>()
+                .map(|x| x.map(|v| pcm_to_f32(v, spec.bits_per_sample)))
+                .collect::<Result<Vec<_>, _>>()?,
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        };
+
+        Ok(WavFile {
+            file_path: path.to_path_buf(),
+            len: samples.len() as u32 / spec.channels as u32,
+            samples,
+            spec,
+        })
+    }
+
     /// Crete a new file at the path from the current struct. Can only be used to write 16/24 bit
     /// signed PCM files. Any other specification type will panic (not compatible with Octatrack)
     pub fn to_path(&self, path: &Path) -> RBoxErr<()> {
@@ -228,174 +228,47 @@ impl WavFile {
         }
     }
 
+    /// Run an `AudioFile` method on this file's audio, then copy the result back.
+    fn with_audio_file(&mut self, f: impl FnOnce(&mut AudioFile) -> RBoxErr<()>) -> RBoxErr<()> {
+        let mut audio = AudioFile::from(self.clone());
+        f(&mut audio)?;
+        self.spec.channels = audio.channels;
+        self.len = audio.len;
+        self.samples = audio.samples;
+        Ok(())
+    }
+
     /// Normalise audio samples between 0 and 1
+    #[deprecated(note = "use `AudioFile::normalize`")]
     pub fn normalize(&mut self) -> RBoxErr<()> {
-        trace!("Normalizing audio.");
-        let max_abs = self
-            .samples
-            .iter()
-            .map(|x| f32::abs(*x))
-            .reduce(f32::max)
-            .unwrap_or(1.0_f32);
-
-        let normd = self
-            .samples
-            .iter()
-            .map(|x| *x * (1.0 / max_abs))
-            .collect::<Vec<f32>>();
-
-        self.samples = normd;
-        Ok(())
+        self.with_audio_file(AudioFile::normalize)
     }
 
-    // really hacky and simple time stretching
     /// Time stretch audio vector by resampling, inserts duplicate samples to
     /// mimic slowing the audio down and removes samples to mimic speeding it up.
     /// WARNING: Speeding up via resampling is a lossy procedure.
+    #[deprecated(note = "use `AudioFile::resample_time_stretch`")]
     pub fn resample_time_stretch(&mut self, stretch: i8) -> RBoxErr<()> {
-        trace!("Resampling WAV file with time stretch factor: {stretch}");
-        let mut resampled: Vec<f32> = vec![];
-
-        let n_channels = self.spec.channels as usize;
-
-        // extending to mimic slowing own the audio
-        if stretch < 0 {
-            // audio samples are interleaved by channels
-            for chn_sample in self.samples.chunks(n_channels) {
-                for _ in 0..=stretch.abs() {
-                    for x in chn_sample {
-                        resampled.push(*x);
-                    }
-                }
-            }
-        }
-
-        // remove entries to mimic speed up
-        // WARNING: this is lossy
-        if stretch > 0 {
-            for (idx, chn_sample) in self.samples.chunks(n_channels).enumerate() {
-                if idx.rem_euclid((stretch + 1) as usize) == 0 {
-                    for x in chn_sample {
-                        resampled.push(*x);
-                    }
-                }
-            }
-        }
-
-        self.len = resampled.len() as u32;
-        self.samples = resampled;
-        Ok(())
+        self.with_audio_file(|audio| audio.resample_time_stretch(stretch))
     }
 
-    /// Linear fade in
-    ///
-    /// ```text
-    ///     x_{n} = x_n.k_n
-    ///     forall n < K, n >= 0
-    ///     where k_n = k_{n-1} + 1/K; k_0 = 0
-    /// ```
+    /// Linear fade in over the first `percent` (0.0 to 1.0) of the audio
+    #[deprecated(note = "use `AudioFile::linear_fade_in`")]
     pub fn linear_fade_in(&mut self, percent: f32) -> RBoxErr<()> {
-        // floats have no concept of a 'step' in ranges
-        #[allow(clippy::manual_range_contains)]
-        if percent > 1.0 || percent < 0.0 {
-            return Err(AudioErrors::FadePercentageOOB.into());
-        }
-        trace!("Creating linear fade in for WAV file");
-
-        let mut buf: Vec<f32> = vec![];
-        let n_samples = self.samples.len() as f32;
-        let n_channels = self.spec.channels as f32;
-
-        // length of fade out in samples
-        // note: interleaved so need to divide by n channels
-        let n_fade_samples = ((n_samples / n_channels) * percent) as usize;
-
-        // start amplitude factor
-        let mut k = 0.0;
-
-        // difference in fade amount per sample step
-        let d = 1.0 / n_fade_samples as f32;
-
-        for (idx, chan_samples) in self.samples.chunks(n_channels as usize).enumerate() {
-            // if at the relevant % start of the sample, start applying fade in
-            if idx <= n_fade_samples {
-                for x in chan_samples {
-                    buf.push(*x * k);
-                }
-                k += d;
-            } else {
-                for x in chan_samples {
-                    buf.push(*x * k);
-                }
-            };
-        }
-
-        self.samples = buf;
-        Ok(())
+        self.with_audio_file(|audio| audio.linear_fade_in(percent))
     }
 
-    /// Linear fade out
-    ///
-    /// ```text
-    ///     x_{n} = x_n.k_n
-    ///     forall n > N - K, n >= 0
-    ///     where k_n = k_{n-1} - 1/K; k_0 = 1
-    /// ```
+    /// Linear fade out over the last `percent` (0.0 to 1.0) of the audio
+    #[deprecated(note = "use `AudioFile::linear_fade_out`")]
     pub fn linear_fade_out(&mut self, percent: f32) -> RBoxErr<()> {
-        // floats have no concept of a 'step' in ranges
-        #[allow(clippy::manual_range_contains)]
-        if percent > 1.0 || percent < 0.0 {
-            return Err(AudioErrors::FadePercentageOOB.into());
-        }
-        trace!("Creating linear fade out for WAV file");
-
-        let mut buf: Vec<f32> = vec![];
-        let n_samps = self.samples.len();
-        let n_chans = self.spec.channels as usize;
-
-        // deinterleaved sample length
-        let n_deint = n_samps / self.spec.channels as usize;
-
-        // length of fade out in deinterleaved samples
-        let n_fade_deint = (n_deint as f32 * percent) as usize;
-
-        // start amplitude factor
-        let mut k = 1.0;
-
-        // difference in fade amount per sample step
-        let d = 1.0 / n_fade_deint as f32;
-
-        for (idx, chan_samples) in self.samples.chunks(n_chans).enumerate() {
-            // if at the relevant % end of the sample, start applying fade out
-            if idx >= n_deint - n_fade_deint {
-                for x in chan_samples {
-                    buf.push(*x * k);
-                }
-                k -= d;
-            } else {
-                for x in chan_samples {
-                    buf.push(*x);
-                }
-            };
-        }
-
-        self.samples = buf;
-        Ok(())
+        self.with_audio_file(|audio| audio.linear_fade_out(percent))
     }
 
     /// Naive upmix of mono signal to an interleaved stereo signal
     /// (duplicate channels, attenuate by 0.5)
-    // TODO: test
+    #[deprecated(note = "use `AudioFile::mono_to_stereo_interleaved`")]
     pub fn mono_to_stereo_interleaved(&mut self) -> RBoxErr<()> {
-        let mut buf: Vec<f32> = vec![];
-        for sample in &self.samples {
-            buf.push(sample * 0.5);
-            buf.push(sample * 0.5);
-        }
-        self.len = buf.len() as u32;
-        self.samples = buf;
-        self.spec.channels = 2;
-        Ok(())
+        self.with_audio_file(AudioFile::mono_to_stereo_interleaved)
     }
 }
 
@@ -404,37 +277,11 @@ mod test {
     use super::*;
 
     // these are here purely because i keep forgetting the upper/lower bounds
-    const MAX_I32: i32 = 2147483647;
-    const MIN_I32: i32 = -2147483648;
     const MAX_I24: i32 = 8388607;
     const MIN_I24: i32 = -8388608;
     const MAX_I16: i16 = 32767;
     const MIN_I16: i16 = -32768;
 
-    mod f32_to_i32 {
-        use super::*;
-        #[test]
-        fn max_val() {
-            assert_eq!(MAX_I32, f32_to_i32(1.0_f32));
-        }
-        #[test]
-        fn min_val() {
-            assert_eq!(MIN_I32, f32_to_i32(-1.0_f32));
-        }
-        #[test]
-        fn zero_val() {
-            assert_eq!(0_i32, f32_to_i32(0.0_f32));
-        }
-        #[test]
-        fn half_pos() {
-            assert_eq!(1073741824, f32_to_i32(0.5_f32));
-        }
-        #[test]
-        fn half_neg() {
-            assert_eq!(-1073741824, f32_to_i32(-0.5_f32));
-        }
-    }
-
     mod f32_to_i24 {
         use super::*;
         #[test]
@@ -482,6 +329,29 @@ mod test {
         }
     }
 
+    mod roundtrip {
+        use super::*;
+        #[test]
+        fn all_i16() {
+            for x in i16::MIN..=i16::MAX {
+                assert_eq!(x, f32_to_i16(i16_to_f32(x)));
+            }
+        }
+        #[test]
+        fn all_i24() {
+            for x in MIN_I24..=MAX_I24 {
+                assert_eq!(x, f32_to_i24(i24_to_f32(x)));
+            }
+        }
+        #[test]
+        fn clipped() {
+            assert_eq!(MAX_I16, f32_to_i16(1.5));
+            assert_eq!(MIN_I16, f32_to_i16(-1.5));
+            assert_eq!(MAX_I24, f32_to_i24(1.5));
+            assert_eq!(MIN_I24, f32_to_i24(-1.5));
+        }
+    }
+
     mod i32_to_f32 {
         use super::*;
         #[test]
@@ -0,0 +1,239 @@
+//! Finding zero crossings and quiet points, so slice points can be placed where playback
+//! won't click.
+
+use serde::{Deserialize, Serialize};
+
+/// How a nearby point is chosen when moving (snapping) a slice point.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SnapMode {
+    /// The nearest point where the audio crosses zero.
+    #[default]
+    ZeroCrossing,
+    /// The quietest point (lowest energy over ~1.5ms), better for noisy audio which crosses
+    /// zero all the time.
+    LowEnergy,
+}
+
+/// Number of samples the energy is measured over for [`SnapMode::LowEnergy`].
+const LOW_ENERGY_FRAME: i64 = 64;
+
+/// Sum of all channels of a sample (frame) of interleaved audio.
+fn frame_sum(samples: &[f32], channels: u16, idx: usize) -> f32 {
+    let n = channels as usize;
+    samples[idx * n..(idx + 1) * n].iter().sum()
+}
+
+/// Position of the zero crossing nearest to `pos`, within `lo..=hi`.
+fn zero_crossing(samples: &[f32], channels: u16, pos: i64, lo: i64, hi: i64) -> Option<i64> {
+    let len = (samples.len() / channels as usize) as i64;
+
+    // a crossing at `i` is between samples `i - 1` and `i`, the quieter of the two is used
+    let crossing = |i: i64| -> Option<i64> {
+        if i < 1 || i >= len {
+            return None;
+        }
+        let prev = frame_sum(samples, channels, i as usize - 1);
+        let curr = frame_sum(samples, channels, i as usize);
+        if curr == 0.0 {
+            Some(i)
+        } else if prev.signum() != curr.signum() {
+            Some(if prev.abs() < curr.abs() { i - 1 } else { i }.clamp(lo, hi))
+        } else {
+            None
+        }
+    };
+
+    (0..=(pos - lo).max(hi - pos)).find_map(|offset| {
+        let before = (pos - offset >= lo)
+            .then(|| crossing(pos - offset))
+            .flatten();
+        before.or_else(|| {
+            (pos + offset <= hi)
+                .then(|| crossing(pos + offset))
+                .flatten()
+        })
+    })
+}
+
+/// Position of the lowest energy point nearest to `pos`, within `lo..=hi`.
+fn low_energy(samples: &[f32], channels: u16, pos: i64, lo: i64, hi: i64) -> Option<i64> {
+    let len = (samples.len() / channels as usize) as i64;
+    let hi = hi.min(len - 1);
+    if lo > hi {
+        return None;
+    }
+
+    // running sum of squares, for the energy of any range of samples
+    let start = (lo - LOW_ENERGY_FRAME / 2).max(0);
+    let end = (hi + LOW_ENERGY_FRAME / 2).min(len);
+    let mut cumulative = vec![0.0_f64; (end - start + 1) as usize];
+    for i in start..end {
+        let x = frame_sum(samples, channels, i as usize) as f64;
+        cumulative[(i - start + 1) as usize] = cumulative[(i - start) as usize] + x * x;
+    }
+    let energy = |p: i64| {
+        let a = (p - LOW_ENERGY_FRAME / 2).max(start);
+        let b = (p + LOW_ENERGY_FRAME / 2).min(end);
+        (cumulative[(b - start) as usize] - cumulative[(a - start) as usize]) / (b - a) as f64
+    };
+
+    (lo..=hi).min_by(|a, b| {
+        energy(*a)
+            .total_cmp(&energy(*b))
+            .then((a - pos).abs().cmp(&(b - pos).abs()))
+    })
+}
+
+/// Move a sample (frame) position of interleaved audio to a nearby point where starting or
+/// stopping playback won't click, searching up to `before` samples before and `after` samples
+/// after the position. Channels are mixed together. Returns the position unchanged when
+/// nothing suitable is found within the search window.
+pub fn snap_position(
+    samples: &[f32],
+    channels: u16,
+    pos: u32,
+    before: u32,
+    after: u32,
+    mode: SnapMode,
+) -> u32 {
+    let pos = pos as i64;
+    let lo = (pos - before as i64).max(0);
+    let hi = pos + after as i64;
+
+    match mode {
+        SnapMode::ZeroCrossing => zero_crossing(samples, channels, pos, lo, hi),
+        SnapMode::LowEnergy => low_energy(samples, channels, pos, lo, hi),
+    }
+    .map_or(pos as u32, |x| x as u32)
+}
+
+/// Find the zero crossing nearest to a sample (frame) position in interleaved audio, searching
+/// up to `window` samples either side. See [`snap_position`].
+pub fn nearest_zero_crossing(samples: &[f32], channels: u16, pos: u32, window: u32) -> u32 {
+    snap_position(
+        samples,
+        channels,
+        pos,
+        window,
+        window,
+        SnapMode::ZeroCrossing,
+    )
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+
+    fn sine(period: usize, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (2.0 * std::f32::consts::PI * i as f32 / period as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn nearest_crossing_in_sine() {
+        // crosses zero every 50 samples
+        let x = sine(100, 1000);
+        assert_eq!(nearest_zero_crossing(&x, 1, 120, 100), 100);
+        assert_eq!(nearest_zero_crossing(&x, 1, 140, 100), 150);
+    }
+
+    #[test]
+    fn exact_zero() {
+        let mut x = vec![0.5_f32; 100];
+        x[40] = 0.0;
+        assert_eq!(nearest_zero_crossing(&x, 1, 45, 10), 40);
+    }
+
+    #[test]
+    fn quieter_side_of_crossing() {
+        let x = vec![0.5, 0.5, 0.1, -0.4, -0.5, -0.5];
+        assert_eq!(nearest_zero_crossing(&x, 1, 4, 3), 2);
+    }
+
+    #[test]
+    fn none_within_window() {
+        let x = sine(1000, 1000);
+        assert_eq!(nearest_zero_crossing(&x, 1, 250, 100), 250);
+    }
+
+    #[test]
+    fn window_limits() {
+        let x = sine(100, 1000);
+        assert_eq!(nearest_zero_crossing(&x, 1, 125, 10), 125);
+        assert_eq!(nearest_zero_crossing(&x, 1, 0, 10), 0);
+        assert_eq!(nearest_zero_crossing(&x, 1, 999, 10), 999);
+    }
+
+    #[test]
+    fn stereo_channels_mixed() {
+        let left = sine(100, 1000);
+        let x = left.iter().flat_map(|x| [*x, *x * 0.5]).collect::<Vec<_>>();
+        assert_eq!(nearest_zero_crossing(&x, 2, 120, 100), 100);
+    }
+
+    #[test]
+    fn zero_crossing_one_direction() {
+        let x = sine(100, 1000);
+        let snap =
+            |pos, before, after| snap_position(&x, 1, pos, before, after, SnapMode::default());
+        // nearest is before, but only searching after
+        assert_eq!(snap(120, 0, 100), 150);
+        // nearest is after, but only searching before
+        assert_eq!(snap(140, 100, 0), 100);
+    }
+
+    #[test]
+    fn zero_crossing_stays_in_window() {
+        // crossing between 2 and 3, 2 is quieter but isn't in the window
+        let x = vec![0.5, 0.5, 0.1, -0.4, -0.5, -0.5];
+        assert_eq!(snap_position(&x, 1, 3, 0, 2, SnapMode::ZeroCrossing), 3);
+    }
+
+    /// Noise with a quiet gap.
+    fn noise_with_gap(gap: std::ops::Range<usize>) -> Vec<f32> {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..4000)
+            .map(|i| {
+                let amp = if gap.contains(&i) { 0.01 } else { 0.5 };
+                rng.gen_range(-1.0..1.0) * amp
+            })
+            .collect()
+    }
+
+    #[test]
+    fn low_energy_finds_gap() {
+        let x = noise_with_gap(1800..1900);
+        let pos = snap_position(&x, 1, 1700, 300, 300, SnapMode::LowEnergy);
+        assert!((1800..1900).contains(&pos), "{pos}");
+    }
+
+    #[test]
+    fn low_energy_one_direction() {
+        let x = noise_with_gap(1800..1900);
+        let pos = snap_position(&x, 1, 2000, 300, 0, SnapMode::LowEnergy);
+        assert!((1800..1900).contains(&pos), "{pos}");
+        let pos = snap_position(&x, 1, 2000, 0, 300, SnapMode::LowEnergy);
+        assert!((2000..=2300).contains(&pos), "{pos}");
+    }
+
+    #[test]
+    fn low_energy_silence_unchanged() {
+        let x = vec![0.0; 1000];
+        assert_eq!(
+            snap_position(&x, 1, 500, 100, 100, SnapMode::LowEnergy),
+            500
+        );
+    }
+
+    #[test]
+    fn low_energy_past_end() {
+        let x = vec![0.1; 1000];
+        assert_eq!(
+            snap_position(&x, 1, 1200, 100, 100, SnapMode::LowEnergy),
+            1200
+        );
+    }
+}
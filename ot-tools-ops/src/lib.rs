@@ -7,12 +7,15 @@
 
 pub mod actions;
 pub mod audio;
+pub mod transaction;
 pub mod utils;
 
-use itertools::Itertools;
+#[cfg(test)]
+mod test_utils;
+
 use ot_tools_io::projects::Project;
+use ot_tools_io::versions::{get_project_version_support, VersionSupport, READ_WRITE_OS_VERSIONS};
 use ot_tools_io::{Decode, Encode};
-use regex::Regex;
 
 pub type RBoxErr<T> = Result<T, Box<dyn std::error::Error>>;
 pub type RVoidError<T> = Result<T, ()>;
@@ -29,6 +32,7 @@ pub enum OctatoolErrors {
     CliMissingPatternIndex,
     InvalidFilenameOrExtension,
     InvalidOsVersion,
+    UnsupportedOsVersion,
     // not in use yet
     CliInvalidTrackIndex,
     Unknown,
@@ -60,7 +64,8 @@ impl std::fmt::Display for OctatoolErrors {
                 "Invalid pattern number(s) - only numbers between 1-16 (inclusive) can be provided"
             ),
             Self::InvalidFilenameOrExtension => write!(f, "path does not have a file extension"),
-            Self::InvalidOsVersion => write!(f, "ot-tools only supports the following OS versions: {:?}", ALLOWED_OS_VERSIONS),
+            Self::InvalidOsVersion => write!(f, "ot-tools can only change projects saved by the following OS versions: {:?} (projects from other OS versions are read-only)", ALLOWED_OS_VERSIONS),
+            Self::UnsupportedOsVersion => write!(f, "project OS version could not be read, so the project can't be used safely"),
             // not in use yet
             Self::CliInvalidTrackIndex => write!(
                 f,
@@ -72,21 +77,23 @@ impl std::fmt::Display for OctatoolErrors {
 }
 impl std::error::Error for OctatoolErrors {}
 
-// TODO: Need other strings for 1.40A and 1.40C
-pub const ALLOWED_OS_VERSIONS: [&str; 3] = ["1.40A", "1.40B", "1.40C"];
+pub const ALLOWED_OS_VERSIONS: [&str; 3] = READ_WRITE_OS_VERSIONS;
 
-/// Check the project OS version indicator is valid for the current version of ot-tools.
-/// NOTE: this does not check the 'release' identifier string
-/// (at least i *think* it's a release ident: `R0177`).
+/// Check ot-tools can change the project's files, based on the OS version which last wrote it
+/// (see `ot_tools_io::versions`).
 pub fn validate_project_version(project: &Project) -> bool {
-    let re = Regex::new(r"\s+").unwrap();
-    let split = re.split(&*project.metadata.os_version).collect_vec();
-    ALLOWED_OS_VERSIONS.contains(&split[1])
+    get_project_version_support(project) == VersionSupport::ReadWrite
+}
+
+/// Check ot-tools can read the project's files (inspect, list, export etc.), based on the OS
+/// version which last wrote it. Projects from OS versions which can't be written are read-only.
+pub fn validate_project_version_readable(project: &Project) -> bool {
+    get_project_version_support(project) != VersionSupport::Unsupported
 }
 
 #[cfg(test)]
 mod test_proj_version {
-    use crate::validate_project_version;
+    use crate::{validate_project_version, validate_project_version_readable};
     use ot_tools_io::projects::Project;
 
     #[test]
@@ -128,4 +135,18 @@ mod test_proj_version {
         proj.metadata.os_version = "R0999     1.00A".to_string();
         assert!(!validate_project_version(&proj))
     }
+
+    #[test]
+    fn readable_139z() {
+        let mut proj = Project::default();
+        proj.metadata.os_version = "R0999     1.39Z".to_string();
+        assert!(validate_project_version_readable(&proj))
+    }
+
+    #[test]
+    fn not_readable_missing_release_id() {
+        let mut proj = Project::default();
+        proj.metadata.os_version = "1.40B".to_string();
+        assert!(!validate_project_version_readable(&proj))
+    }
 }
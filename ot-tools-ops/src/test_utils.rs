@@ -0,0 +1,18 @@
+use std::f64::consts::PI;
+use std::path::PathBuf;
+
+/// Empty directory for a test's files at `<temp dir>/<group>/<name>`, removing anything left
+/// over from a previous run.
+pub(crate) fn test_dir(group: &str, name: &str) -> PathBuf {
+    let dirpath = std::env::temp_dir().join(group).join(name);
+    let _ = std::fs::remove_dir_all(&dirpath);
+    std::fs::create_dir_all(&dirpath).unwrap();
+    dirpath
+}
+
+/// Half amplitude mono sine.
+pub(crate) fn sine(freq: f64, rate: u32, n_frames: usize) -> Vec<f32> {
+    (0..n_frames)
+        .map(|i| (2.0 * PI * freq * i as f64 / rate as f64).sin() as f32 * 0.5)
+        .collect()
+}
@@ -0,0 +1,878 @@
+//! Transactional file changes for operations which modify sets / projects.
+//!
+//! Changes are staged in memory, so nothing in the set or project is touched until
+//! `Transaction::commit` is called. On commit, new file contents are written to temporary files
+//! next to their destination, any files about to be replaced / removed are backed up into a
+//! timestamped directory under `.ot-tools-backups/`, then the temporary files are renamed into
+//! place. If anything fails part way through a commit, all changes made so far are rolled back from
+//! the backups.
+//!
+//! Staged changes can also be turned into an `OperationPlan` for dry runs. Plans can be saved as
+//! JSON and applied later on, as long as none of the files involved have changed in the meantime.
+//!
+//! The most recent committed transaction can be reverted with `undo_last_transaction`.
+
+use crate::utils::get_md5_hash_from_path;
+use crate::RBoxErr;
+use base64ct::{Base64, Encoding};
+use ot_tools_io::projects::{options::ProjectSampleSlotType, slots::ProjectSampleSlot};
+use ot_tools_io::{
+    json_file_to_type, serialize_bin_from_type, type_to_json_file, type_to_yaml_file,
+    write_bin_file, yaml_file_to_type, Encode,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the directory where backups are kept for each committed transaction.
+pub const BACKUPS_DIRNAME: &str = ".ot-tools-backups";
+
+/// Name of the file within each transaction's backup directory describing the changes made.
+const MANIFEST_FNAME: &str = "manifest.yaml";
+
+/// Extension suffix added to staged files.
+const STAGED_FILE_EXT: &str = "ot-tools-staged";
+
+#[derive(Debug)]
+pub enum TransactionErrors {
+    /// No backups found to restore from
+    NoBackupsFound,
+    /// A destination file path has no parent directory / file name
+    InvalidTargetPath,
+    /// Files used by a plan have changed since the plan was created
+    PlanOutOfDate,
+    /// File contents in a plan could not be decoded
+    InvalidPlanContents,
+}
+impl std::fmt::Display for TransactionErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBackupsFound => write!(
+                f,
+                "no backups found, there are no operations which can be undone"
+            ),
+            Self::InvalidTargetPath => write!(
+                f,
+                "destination file path must have a parent directory and a file name"
+            ),
+            Self::PlanOutOfDate => write!(
+                f,
+                "files have changed since the plan was created, create a new plan"
+            ),
+            Self::InvalidPlanContents => write!(f, "could not decode file contents in plan"),
+        }
+    }
+}
+impl std::error::Error for TransactionErrors {}
+
+/// A file changed by a committed transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackupManifestEntry {
+    /// File which was changed
+    pub target: PathBuf,
+    /// File name of the backup copy (relative to the transaction's backup directory).
+    /// `None` when the file did not exist before the transaction.
+    pub backup: Option<PathBuf>,
+}
+
+/// Details of a committed transaction, written to the transaction's backup directory.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackupManifest {
+    /// What the operation was
+    pub description: String,
+    /// Files changed by the operation, in the order the changes were made
+    pub files: Vec<BackupManifestEntry>,
+}
+
+/// A change to a single file, which will be made when a transaction / plan is committed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum PlannedFileChange {
+    /// Write new contents to a file (replacing the file if it exists)
+    Write {
+        target: PathBuf,
+        /// File contents, base64 encoded
+        contents: String,
+        n_bytes: u64,
+    },
+    /// Copy a file (replacing the destination file if it exists)
+    Copy {
+        source: PathBuf,
+        target: PathBuf,
+        n_bytes: u64,
+    },
+    /// Delete a file
+    Remove { target: PathBuf, n_bytes: u64 },
+}
+
+impl PlannedFileChange {
+    pub fn target(&self) -> &Path {
+        match self {
+            Self::Write { target, .. } => target,
+            Self::Copy { target, .. } => target,
+            Self::Remove { target, .. } => target,
+        }
+    }
+
+    pub fn n_bytes(&self) -> u64 {
+        match self {
+            Self::Write { n_bytes, .. } => *n_bytes,
+            Self::Copy { n_bytes, .. } => *n_bytes,
+            Self::Remove { n_bytes, .. } => *n_bytes,
+        }
+    }
+}
+
+impl std::fmt::Display for PlannedFileChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Write {
+                target, n_bytes, ..
+            } => write!(f, "WRITE  {target:?} ({n_bytes} bytes)"),
+            Self::Copy {
+                source,
+                target,
+                n_bytes,
+            } => write!(f, "COPY   {source:?} -> {target:?} ({n_bytes} bytes)"),
+            Self::Remove { target, n_bytes } => {
+                write!(f, "DELETE {target:?} ({n_bytes} bytes)")
+            }
+        }
+    }
+}
+
+/// What happens to a project sample slot as part of an operation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SlotChangeType {
+    /// A new slot is added to the project
+    Created,
+    /// An existing slot is used by the copied data instead of adding a new slot
+    Reused,
+    /// An existing slot has its settings / file path changed (or is moved to another slot id)
+    Modified,
+    /// A slot is removed from the project
+    Removed,
+}
+
+/// A change to a project sample slot made as part of an operation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlannedSlotChange {
+    pub project_fpath: PathBuf,
+    pub change: SlotChangeType,
+    pub sample_type: ProjectSampleSlotType,
+    /// 1-indexed slot ID (as seen on the Octatrack)
+    pub slot_id: u8,
+    pub path: PathBuf,
+}
+
+impl std::fmt::Display for PlannedSlotChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} {:?} slot {}: {:?}",
+            self.change, self.sample_type, self.slot_id, self.path
+        )
+    }
+}
+
+/// Totals for all changes in a plan.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PlanSummary {
+    pub slots_created: usize,
+    pub slots_reused: usize,
+    pub slots_modified: usize,
+    pub slots_removed: usize,
+    pub files_written: usize,
+    pub files_copied: usize,
+    pub files_deleted: usize,
+    /// Total size of all files written, copied and deleted
+    pub bytes_changed: u64,
+}
+
+/// State of a file when a plan was created, used to check nothing has changed before applying it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlannedFileState {
+    pub path: PathBuf,
+    /// Base64 encoded md5 hash of the file, `None` when the file does not exist
+    pub md5: Option<String>,
+}
+
+impl PlannedFileState {
+    fn from_path(path: &Path) -> RBoxErr<Self> {
+        let md5 = match path.is_file() {
+            true => Some(get_md5_hash_from_path(path)?),
+            false => None,
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            md5,
+        })
+    }
+}
+
+/// Every change an operation will make, which can be reviewed (e.g. saved as JSON) and then
+/// applied later on with `apply_plan`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OperationPlan {
+    pub description: String,
+    pub backups_root: PathBuf,
+    pub summary: PlanSummary,
+    pub slots: Vec<PlannedSlotChange>,
+    pub files: Vec<PlannedFileChange>,
+    /// State of all files read or changed by the plan at the time the plan was created
+    pub file_states: Vec<PlannedFileState>,
+}
+
+impl OperationPlan {
+    /// Print a human readable version of the plan.
+    pub fn print(&self) {
+        println!("Operation: {}", self.description);
+        if !self.slots.is_empty() {
+            println!("Sample slot changes:");
+            for slot in &self.slots {
+                println!("    {slot}");
+            }
+        }
+        if !self.files.is_empty() {
+            println!("File changes:");
+            for file in &self.files {
+                println!("    {file}");
+            }
+        }
+        let s = &self.summary;
+        println!(
+            "Slots created/reused/modified/removed: {}/{}/{}/{}",
+            s.slots_created, s.slots_reused, s.slots_modified, s.slots_removed
+        );
+        println!(
+            "Files written/copied/deleted: {}/{}/{} ({} bytes)",
+            s.files_written, s.files_copied, s.files_deleted, s.bytes_changed
+        );
+    }
+}
+
+/// A set of file changes which are either all applied, or not applied at all.
+///
+/// Nothing is written until the transaction is committed, so dropping a transaction without
+/// committing it discards all staged changes.
+#[derive(Debug)]
+pub struct Transaction {
+    backups_root: PathBuf,
+    description: String,
+    changes: Vec<PlannedFileChange>,
+    slots: Vec<PlannedSlotChange>,
+}
+
+impl Transaction {
+    /// Start a new transaction, which will keep backups in the `.ot-tools-backups` directory within
+    /// `backups_root` (usually the set directory).
+    pub fn new(backups_root: &Path, description: &str) -> Self {
+        Self {
+            backups_root: backups_root.to_path_buf(),
+            description: description.to_string(),
+            changes: vec![],
+            slots: vec![],
+        }
+    }
+
+    /// Create a transaction from a previously created plan.
+    ///
+    /// Fails if any of the files the plan depends on have changed since the plan was created.
+    pub fn from_plan(plan: &OperationPlan) -> RBoxErr<Self> {
+        for state in &plan.file_states {
+            if PlannedFileState::from_path(&state.path)? != *state {
+                eprintln!("File changed since plan was created: path={:?}", state.path);
+                return Err(TransactionErrors::PlanOutOfDate.into());
+            }
+        }
+
+        Ok(Self {
+            backups_root: plan.backups_root.clone(),
+            description: plan.description.clone(),
+            changes: plan.files.clone(),
+            slots: plan.slots.clone(),
+        })
+    }
+
+    /// Whether there are any changes staged.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Discard any change previously staged for `target` (the latest staged change wins).
+    fn unstage(&mut self, target: &Path) {
+        self.changes.retain(|x| x.target() != target);
+    }
+
+    fn staged_fpath(target: &Path) -> RBoxErr<PathBuf> {
+        let (Some(dirpath), Some(fname)) = (target.parent(), target.file_name()) else {
+            return Err(TransactionErrors::InvalidTargetPath.into());
+        };
+        let mut staged_fname = std::ffi::OsString::from(".");
+        staged_fname.push(fname);
+        staged_fname.push(format!(".{STAGED_FILE_EXT}"));
+        Ok(dirpath.join(staged_fname))
+    }
+
+    /// Stage writing raw `bytes` to the file at `target`.
+    pub fn stage_bytes(&mut self, bytes: &[u8], target: &Path) -> RBoxErr<()> {
+        Self::staged_fpath(target)?;
+        self.unstage(target);
+        self.changes.push(PlannedFileChange::Write {
+            target: target.to_path_buf(),
+            contents: Base64::encode_string(bytes),
+            n_bytes: bytes.len() as u64,
+        });
+        Ok(())
+    }
+
+    /// Stage writing a data type to a binary data file at `target`.
+    pub fn stage_type<T>(&mut self, data: &T, target: &Path) -> RBoxErr<()>
+    where
+        T: Encode,
+        T: Serialize,
+    {
+        let bytes = serialize_bin_from_type::<T>(data)?;
+        self.stage_bytes(&bytes, target)
+    }
+
+    /// Stage copying the file at `src` to `target`.
+    pub fn stage_copy(&mut self, src: &Path, target: &Path) -> RBoxErr<()> {
+        Self::staged_fpath(target)?;
+        self.unstage(target);
+        self.changes.push(PlannedFileChange::Copy {
+            source: src.to_path_buf(),
+            target: target.to_path_buf(),
+            n_bytes: fs::metadata(src)?.len(),
+        });
+        Ok(())
+    }
+
+    /// Stage deleting the file at `target`.
+    pub fn stage_remove(&mut self, target: &Path) -> RBoxErr<()> {
+        self.unstage(target);
+        self.changes.push(PlannedFileChange::Remove {
+            target: target.to_path_buf(),
+            n_bytes: fs::metadata(target).map(|x| x.len()).unwrap_or(0),
+        });
+        Ok(())
+    }
+
+    /// Record a sample slot change for the plan (sample slots are not modified by this).
+    pub fn record_slot_change(&mut self, change: PlannedSlotChange) {
+        self.slots.push(change);
+    }
+
+    /// Record all sample slot changes between two versions of a project's sample slots.
+    /// Slots must be 1-indexed.
+    pub fn record_project_slot_changes(
+        &mut self,
+        project_fpath: &Path,
+        old: &[ProjectSampleSlot],
+        new: &[ProjectSampleSlot],
+    ) {
+        let find = |slots: &[ProjectSampleSlot], x: &ProjectSampleSlot| {
+            slots
+                .iter()
+                .find(|y| y.sample_type == x.sample_type && y.slot_id == x.slot_id)
+                .cloned()
+        };
+        let mut record = |change: SlotChangeType, slot: &ProjectSampleSlot| {
+            self.slots.push(PlannedSlotChange {
+                project_fpath: project_fpath.to_path_buf(),
+                change,
+                sample_type: slot.sample_type.clone(),
+                slot_id: slot.slot_id,
+                path: slot.path.clone(),
+            })
+        };
+
+        for slot in new {
+            match find(old, slot) {
+                None => record(SlotChangeType::Created, slot),
+                Some(x) if x != *slot => record(SlotChangeType::Modified, slot),
+                _ => {}
+            }
+        }
+        for slot in old.iter().filter(|x| find(new, x).is_none()) {
+            record(SlotChangeType::Removed, slot);
+        }
+    }
+
+    /// Get the plan for all staged changes.
+    pub fn plan(&self) -> RBoxErr<OperationPlan> {
+        let mut summary = PlanSummary::default();
+        for slot in &self.slots {
+            match slot.change {
+                SlotChangeType::Created => summary.slots_created += 1,
+                SlotChangeType::Reused => summary.slots_reused += 1,
+                SlotChangeType::Modified => summary.slots_modified += 1,
+                SlotChangeType::Removed => summary.slots_removed += 1,
+            }
+        }
+
+        let mut file_states: Vec<PlannedFileState> = vec![];
+        for change in &self.changes {
+            match change {
+                PlannedFileChange::Write { .. } => summary.files_written += 1,
+                PlannedFileChange::Copy { source, .. } => {
+                    summary.files_copied += 1;
+                    file_states.push(PlannedFileState::from_path(source)?);
+                }
+                PlannedFileChange::Remove { .. } => summary.files_deleted += 1,
+            }
+            summary.bytes_changed += change.n_bytes();
+            file_states.push(PlannedFileState::from_path(change.target())?);
+        }
+
+        Ok(OperationPlan {
+            description: self.description.clone(),
+            backups_root: self.backups_root.clone(),
+            summary,
+            slots: self.slots.clone(),
+            files: self.changes.clone(),
+            file_states,
+        })
+    }
+
+    /// Create a new, empty backup directory for this transaction.
+    fn create_backup_dir(&self) -> RBoxErr<PathBuf> {
+        let timestamp = chrono::Utc::now().format("%Y%m%dT%H%M%S%.6fZ").to_string();
+        let backups_dirpath = self.backups_root.join(BACKUPS_DIRNAME);
+
+        let mut dirpath = backups_dirpath.join(&timestamp);
+        let mut n = 1;
+        while dirpath.exists() {
+            dirpath = backups_dirpath.join(format!("{timestamp}-{n}"));
+            n += 1;
+        }
+        fs::create_dir_all(&dirpath)?;
+        Ok(dirpath)
+    }
+
+    /// Back up all files which will be replaced / removed. Nothing is changed in the set / project.
+    fn create_backups(&self, backup_dirpath: &Path) -> RBoxErr<BackupManifest> {
+        let mut files = vec![];
+        for (idx, change) in self.changes.iter().enumerate() {
+            let target = change.target();
+            let backup = if target.exists() {
+                let fname = target
+                    .file_name()
+                    .ok_or(TransactionErrors::InvalidTargetPath)?;
+                let backup_fname = PathBuf::from(format!("{idx:0>4}-{}", fname.to_string_lossy()));
+                fs::copy(target, backup_dirpath.join(&backup_fname))?;
+                Some(backup_fname)
+            } else {
+                None
+            };
+            files.push(BackupManifestEntry {
+                target: target.to_path_buf(),
+                backup,
+            });
+        }
+
+        Ok(BackupManifest {
+            description: self.description.clone(),
+            files,
+        })
+    }
+
+    /// Write the contents of every new / replaced file next to its destination, creating any
+    /// missing destination directories.
+    fn write_staged_files(&self) -> RBoxErr<Vec<PathBuf>> {
+        let mut staged_fpaths = vec![];
+        for change in &self.changes {
+            let staged = Self::staged_fpath(change.target())?;
+            if let (
+                PlannedFileChange::Write { .. } | PlannedFileChange::Copy { .. },
+                Some(dirpath),
+            ) = (change, staged.parent())
+            {
+                if let Err(e) = fs::create_dir_all(dirpath) {
+                    remove_staged_files(&staged_fpaths);
+                    return Err(e.into());
+                }
+            }
+            let r = match change {
+                PlannedFileChange::Write { contents, .. } => Base64::decode_vec(contents)
+                    .map_err(|_| TransactionErrors::InvalidPlanContents.into())
+                    .and_then(|bytes| write_bin_file(&bytes, &staged)),
+                PlannedFileChange::Copy { source, .. } => {
+                    fs::copy(source, &staged).map(|_| ()).map_err(|e| e.into())
+                }
+                PlannedFileChange::Remove { .. } => continue,
+            };
+            staged_fpaths.push(staged);
+            if let Err(e) = r {
+                remove_staged_files(&staged_fpaths);
+                return Err(e);
+            }
+        }
+        Ok(staged_fpaths)
+    }
+
+    /// Apply all staged changes, returning the directory path where backups were written.
+    ///
+    /// Changes made before a failure are rolled back from the backups.
+    pub fn commit(self) -> RBoxErr<PathBuf> {
+        let staged_fpaths = self.write_staged_files()?;
+
+        let backup_dirpath = self.create_backup_dir()?;
+        let manifest = match self.create_backups(&backup_dirpath) {
+            Ok(m) => m,
+            Err(e) => {
+                remove_staged_files(&staged_fpaths);
+                let _ = fs::remove_dir_all(&backup_dirpath);
+                return Err(e);
+            }
+        };
+        // written before any changes are made, so an interrupted commit can still be undone
+        type_to_yaml_file(&manifest, &backup_dirpath.join(MANIFEST_FNAME))?;
+
+        for (idx, change) in self.changes.iter().enumerate() {
+            let target = change.target();
+            let r = match change {
+                PlannedFileChange::Remove { .. } => match target.exists() {
+                    true => fs::remove_file(target),
+                    false => Ok(()),
+                },
+                _ => fs::rename(Self::staged_fpath(target)?, target),
+            };
+
+            if let Err(e) = r {
+                eprintln!("Failed to apply changes, rolling back: path={target:?}");
+                remove_staged_files(&staged_fpaths);
+                restore_from_backups(&backup_dirpath, &manifest.files[0..idx])?;
+                let _ = fs::remove_dir_all(&backup_dirpath);
+                return Err(e.into());
+            }
+        }
+
+        Ok(backup_dirpath)
+    }
+}
+
+fn remove_staged_files(staged_fpaths: &[PathBuf]) {
+    for fpath in staged_fpaths.iter().filter(|x| x.exists()) {
+        let _ = fs::remove_file(fpath);
+    }
+}
+
+/// Either print the plan for a transaction (`dry_run`), save the plan as a JSON file
+/// (`plan_fpath`), or commit the transaction. Used by operations to handle their dry-run options.
+pub fn finish_transaction(
+    tx: Transaction,
+    dry_run: bool,
+    plan_fpath: Option<&Path>,
+) -> RBoxErr<()> {
+    if dry_run || plan_fpath.is_some() {
+        let plan = tx.plan()?;
+        if dry_run {
+            plan.print();
+        }
+        if let Some(fpath) = plan_fpath {
+            type_to_json_file(&plan, fpath)?;
+            println!("Plan written to: {fpath:?}");
+        }
+        return Ok(());
+    }
+
+    if tx.is_empty() {
+        println!("No changes to make.");
+        return Ok(());
+    }
+    let backup_dirpath = tx.commit()?;
+    println!("Backups of changed files written to: {backup_dirpath:?}");
+    Ok(())
+}
+
+/// Apply a plan previously saved as a JSON file.
+pub fn apply_plan(plan_fpath: &Path) -> RBoxErr<()> {
+    let plan = json_file_to_type::<OperationPlan>(plan_fpath)?;
+    plan.print();
+    let tx = Transaction::from_plan(&plan)?;
+    finish_transaction(tx, false, None)
+}
+
+/// Backups for changes to a project are kept in the project's set directory.
+pub fn get_backups_root_for_project(project_dirpath: &Path) -> RBoxErr<PathBuf> {
+    let abs_dirpath = fs::canonicalize(project_dirpath)?;
+    Ok(abs_dirpath.parent().unwrap_or(&abs_dirpath).to_path_buf())
+}
+
+/// Revert changes made to the files in `entries`, most recent change first.
+fn restore_from_backups(backup_dirpath: &Path, entries: &[BackupManifestEntry]) -> RBoxErr<()> {
+    for entry in entries.iter().rev() {
+        match &entry.backup {
+            Some(backup_fname) => {
+                fs::copy(backup_dirpath.join(backup_fname), &entry.target)?;
+            }
+            None => {
+                if entry.target.exists() {
+                    fs::remove_file(&entry.target)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Get the backup directory for the most recently committed transaction within `backups_root`.
+pub fn find_last_transaction_backup(backups_root: &Path) -> RBoxErr<PathBuf> {
+    let backups_dirpath = backups_root.join(BACKUPS_DIRNAME);
+    if !backups_dirpath.is_dir() {
+        return Err(TransactionErrors::NoBackupsFound.into());
+    }
+
+    // timestamped directory names sort chronologically
+    let mut dirpaths: Vec<PathBuf> = fs::read_dir(&backups_dirpath)?
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.join(MANIFEST_FNAME).exists())
+        .collect();
+    dirpaths.sort();
+
+    dirpaths
+        .pop()
+        .ok_or(TransactionErrors::NoBackupsFound.into())
+}
+
+/// Undo the most recently committed transaction within `backups_root` (usually the set directory),
+/// restoring all changed files from backups.
+///
+/// The transaction's backups are deleted once restored, so running this again will undo the
+/// transaction before that one.
+pub fn undo_last_transaction(backups_root: &Path) -> RBoxErr<()> {
+    let backup_dirpath = find_last_transaction_backup(backups_root)?;
+    let manifest = yaml_file_to_type::<BackupManifest>(&backup_dirpath.join(MANIFEST_FNAME))?;
+
+    println!("Undoing operation: {}", manifest.description);
+    for entry in &manifest.files {
+        match &entry.backup {
+            Some(_) => println!("Restoring file: {:?}", entry.target),
+            None => println!("Removing created file: {:?}", entry.target),
+        }
+    }
+    restore_from_backups(&backup_dirpath, &manifest.files)?;
+    fs::remove_dir_all(&backup_dirpath)?;
+
+    println!("Undo complete.");
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use ot_tools_io::read_bin_file;
+
+    fn mock_dir(name: &str) -> PathBuf {
+        let dirpath = std::env::temp_dir().join(format!("ot-tools-ops-transaction-{name}"));
+        if dirpath.exists() {
+            fs::remove_dir_all(&dirpath).unwrap();
+        }
+        fs::create_dir_all(&dirpath).unwrap();
+        fs::write(dirpath.join("a.work"), b"aaa").unwrap();
+        fs::write(dirpath.join("b.work"), b"bbb").unwrap();
+        dirpath
+    }
+
+    #[test]
+    fn nothing_written_before_commit() {
+        let dirpath = mock_dir("no-commit");
+        {
+            let mut tx = Transaction::new(&dirpath, "test");
+            tx.stage_bytes(b"zzz", &dirpath.join("a.work")).unwrap();
+            tx.stage_remove(&dirpath.join("b.work")).unwrap();
+            assert_eq!(read_bin_file(&dirpath.join("a.work")).unwrap(), b"aaa");
+            assert!(dirpath.join("b.work").exists());
+        }
+        let n_files = fs::read_dir(&dirpath).unwrap().count();
+        let _ = fs::remove_dir_all(&dirpath);
+        // staged files are cleaned up on drop, no backups dir created
+        assert_eq!(n_files, 2);
+    }
+
+    #[test]
+    fn commit_applies_changes() {
+        let dirpath = mock_dir("commit");
+        let mut tx = Transaction::new(&dirpath, "test");
+        tx.stage_bytes(b"zzz", &dirpath.join("a.work")).unwrap();
+        tx.stage_remove(&dirpath.join("b.work")).unwrap();
+        tx.stage_copy(&dirpath.join("b.work"), &dirpath.join("c.work"))
+            .unwrap();
+        let backup_dirpath = tx.commit().unwrap();
+
+        let a = read_bin_file(&dirpath.join("a.work")).unwrap();
+        let b_exists = dirpath.join("b.work").exists();
+        let c = read_bin_file(&dirpath.join("c.work")).unwrap();
+        let manifest =
+            yaml_file_to_type::<BackupManifest>(&backup_dirpath.join(MANIFEST_FNAME)).unwrap();
+        let _ = fs::remove_dir_all(&dirpath);
+
+        assert_eq!(a, b"zzz");
+        assert!(!b_exists);
+        assert_eq!(c, b"bbb");
+        assert_eq!(manifest.files.len(), 3);
+        assert_eq!(manifest.files[2].backup, None);
+    }
+
+    #[test]
+    fn commit_creates_missing_dirs() {
+        let dirpath = mock_dir("new-dirs");
+        let mut tx = Transaction::new(&dirpath, "test");
+        tx.stage_bytes(b"zzz", &dirpath.join("NEW/a.work")).unwrap();
+        tx.stage_copy(&dirpath.join("b.work"), &dirpath.join("NEW/b.work"))
+            .unwrap();
+        tx.commit().unwrap();
+
+        let a = read_bin_file(&dirpath.join("NEW/a.work")).unwrap();
+        let b = read_bin_file(&dirpath.join("NEW/b.work")).unwrap();
+        let _ = fs::remove_dir_all(&dirpath);
+
+        assert_eq!(a, b"zzz");
+        assert_eq!(b, b"bbb");
+    }
+
+    #[test]
+    fn failed_commit_changes_nothing() {
+        let dirpath = mock_dir("failed-commit");
+        fs::write(dirpath.join("c.work"), b"ccc").unwrap();
+
+        let mut tx = Transaction::new(&dirpath, "test");
+        tx.stage_bytes(b"zzz", &dirpath.join("a.work")).unwrap();
+        tx.stage_copy(&dirpath.join("c.work"), &dirpath.join("b.work"))
+            .unwrap();
+        // source file vanishes before commit
+        fs::remove_file(dirpath.join("c.work")).unwrap();
+
+        let r = tx.commit();
+        let a = read_bin_file(&dirpath.join("a.work")).unwrap();
+        let b = read_bin_file(&dirpath.join("b.work")).unwrap();
+        let n_files = fs::read_dir(&dirpath).unwrap().count();
+        let _ = fs::remove_dir_all(&dirpath);
+
+        assert!(r.is_err());
+        assert_eq!(a, b"aaa");
+        assert_eq!(b, b"bbb");
+        // no staged files or backups left behind
+        assert_eq!(n_files, 2);
+    }
+
+    #[test]
+    fn restore_from_backups_ok() {
+        let dirpath = mock_dir("restore");
+        let mut tx = Transaction::new(&dirpath, "test");
+        tx.stage_bytes(b"zzz", &dirpath.join("a.work")).unwrap();
+        tx.stage_bytes(b"ccc", &dirpath.join("c.work")).unwrap();
+        let backup_dirpath = tx.commit().unwrap();
+
+        let manifest =
+            yaml_file_to_type::<BackupManifest>(&backup_dirpath.join(MANIFEST_FNAME)).unwrap();
+        restore_from_backups(&backup_dirpath, &manifest.files).unwrap();
+        let a = read_bin_file(&dirpath.join("a.work")).unwrap();
+        let c_exists = dirpath.join("c.work").exists();
+        let _ = fs::remove_dir_all(&dirpath);
+
+        assert_eq!(a, b"aaa");
+        assert!(!c_exists);
+    }
+
+    #[test]
+    fn plan_summary() {
+        let dirpath = mock_dir("plan-summary");
+        let mut tx = Transaction::new(&dirpath, "test");
+        tx.stage_bytes(b"zzzz", &dirpath.join("a.work")).unwrap();
+        tx.stage_copy(&dirpath.join("a.work"), &dirpath.join("c.work"))
+            .unwrap();
+        tx.stage_remove(&dirpath.join("b.work")).unwrap();
+        let plan = tx.plan().unwrap();
+        let _ = fs::remove_dir_all(&dirpath);
+
+        assert_eq!(plan.summary.files_written, 1);
+        assert_eq!(plan.summary.files_copied, 1);
+        assert_eq!(plan.summary.files_deleted, 1);
+        assert_eq!(plan.summary.bytes_changed, 4 + 3 + 3);
+    }
+
+    #[test]
+    fn plan_slot_changes() {
+        let old = ProjectSampleSlot::defaults();
+        let mut new = old.clone();
+        new[0].path = PathBuf::from("recording.wav");
+        new.remove(1);
+        let mut slot = old[2].clone();
+        slot.sample_type = ProjectSampleSlotType::Static;
+        slot.slot_id = 1;
+        new.push(slot);
+
+        let mut tx = Transaction::new(Path::new("."), "test");
+        tx.record_project_slot_changes(Path::new("project.work"), &old, &new);
+        let plan = tx.plan().unwrap();
+
+        assert_eq!(plan.summary.slots_created, 1);
+        assert_eq!(plan.summary.slots_modified, 1);
+        assert_eq!(plan.summary.slots_removed, 1);
+    }
+
+    #[test]
+    fn apply_saved_plan() {
+        let dirpath = mock_dir("apply-plan");
+        let plan_fpath = dirpath.join("plan.json");
+        let mut tx = Transaction::new(&dirpath, "test");
+        tx.stage_bytes(b"zzz", &dirpath.join("a.work")).unwrap();
+        finish_transaction(tx, true, Some(&plan_fpath)).unwrap();
+        let a_after_plan = read_bin_file(&dirpath.join("a.work")).unwrap();
+
+        apply_plan(&plan_fpath).unwrap();
+        let a_after_apply = read_bin_file(&dirpath.join("a.work")).unwrap();
+        let _ = fs::remove_dir_all(&dirpath);
+
+        assert_eq!(a_after_plan, b"aaa");
+        assert_eq!(a_after_apply, b"zzz");
+    }
+
+    #[test]
+    fn apply_out_of_date_plan_err() {
+        let dirpath = mock_dir("apply-plan-out-of-date");
+        let plan_fpath = dirpath.join("plan.json");
+        let mut tx = Transaction::new(&dirpath, "test");
+        tx.stage_bytes(b"zzz", &dirpath.join("a.work")).unwrap();
+        finish_transaction(tx, false, Some(&plan_fpath)).unwrap();
+        fs::write(dirpath.join("a.work"), b"changed").unwrap();
+
+        let r = apply_plan(&plan_fpath);
+        let a = read_bin_file(&dirpath.join("a.work")).unwrap();
+        let _ = fs::remove_dir_all(&dirpath);
+
+        assert!(r.is_err());
+        assert_eq!(a, b"changed");
+    }
+
+    #[test]
+    fn undo_restores_last_transaction() {
+        let dirpath = mock_dir("undo");
+
+        let mut tx = Transaction::new(&dirpath, "first");
+        tx.stage_bytes(b"111", &dirpath.join("a.work")).unwrap();
+        tx.commit().unwrap();
+
+        let mut tx = Transaction::new(&dirpath, "second");
+        tx.stage_bytes(b"222", &dirpath.join("a.work")).unwrap();
+        tx.stage_bytes(b"ccc", &dirpath.join("c.work")).unwrap();
+        tx.commit().unwrap();
+
+        undo_last_transaction(&dirpath).unwrap();
+        let a_first_undo = read_bin_file(&dirpath.join("a.work")).unwrap();
+        let c_exists = dirpath.join("c.work").exists();
+
+        undo_last_transaction(&dirpath).unwrap();
+        let a_second_undo = read_bin_file(&dirpath.join("a.work")).unwrap();
+        let third_undo = undo_last_transaction(&dirpath);
+        let _ = fs::remove_dir_all(&dirpath);
+
+        assert_eq!(a_first_undo, b"111");
+        assert!(!c_exists);
+        assert_eq!(a_second_undo, b"aaa");
+        assert!(third_undo.is_err());
+    }
+}
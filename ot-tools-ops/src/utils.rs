@@ -1,32 +1,114 @@
 //! Module for various utility functions and structs.
 
-use crate::audio::wav::WavFile;
+use crate::audio::{file::AudioFile, wav::WavFile};
 use crate::RBoxErr;
+use base64ct::{Base64, Encoding};
+use log::debug;
+use md5::Digest;
 use ot_tools_io::{constants::DEFAULT_SAMPLE_RATE, projects::slots::ProjectSampleSlot};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::{fs::File, io::Read};
 
-/// Calculate the effective number of bars for a single wav file.
+/// Calculate the effective number of bars for a single audio file.
 /// Assumes four beats per bar.
+pub fn get_otsample_nbars_from_audio_file(wav: &AudioFile, tempo_bpm: &f32) -> RBoxErr<u32> {
+    get_otsample_nbars_from_len(wav.len, tempo_bpm)
+}
+
+/// Calculate the effective number of bars for a single WAV file.
+/// Assumes four beats per bar.
+#[deprecated(note = "use `get_otsample_nbars_from_audio_file`")]
 pub fn get_otsample_nbars_from_wavfile(wav: &WavFile, tempo_bpm: &f32) -> RBoxErr<u32> {
-    let beats = wav.len as f32 / (DEFAULT_SAMPLE_RATE as f32 * 60.0 * 4.0);
+    get_otsample_nbars_from_len(wav.len, tempo_bpm)
+}
+
+/// Calculate the effective number of bars for a length of audio (in samples).
+/// Assumes four beats per bar.
+pub fn get_otsample_nbars_from_len(len: u32, tempo_bpm: &f32) -> RBoxErr<u32> {
+    let beats = len as f32 / (DEFAULT_SAMPLE_RATE as f32 * 60.0 * 4.0);
     let mut bars = ((tempo_bpm * 4.0 * beats) + 0.5) * 0.25;
     bars -= bars % 0.25;
     Ok((bars * 100.0) as u32)
 }
 
-/// Calculate the effective number of bars for a vec of wav files.
+/// Calculate the effective number of bars for a vec of audio files (played one after another).
 /// Assumes four beats per bar.
+pub fn get_bin_nbars_ileaved_audio_files(wavs: &[AudioFile], tempo_bpm: &f32) -> RBoxErr<u32> {
+    // lengths are in frames, so don't depend on the number of channels
+    let total_frames: u32 = wavs.iter().map(|x| x.len).sum();
+    let beats = total_frames as f32 / (DEFAULT_SAMPLE_RATE as f32 * 60.0 * 4.0);
+    let bars = (tempo_bpm * beats * 100.0).round();
+    Ok(bars as u32)
+}
+
+/// Calculate the effective number of bars for a vec of WAV files.
+/// Assumes four beats per bar. `n_channels` is unused, WAV file lengths are already in frames.
+#[deprecated(note = "use `get_bin_nbars_ileaved_audio_files`")]
 pub fn get_bin_nbars_ileaved_wavfiles(
     wavs: &[WavFile],
     tempo_bpm: &f32,
-    n_channels: u16,
+    _n_channels: u16,
 ) -> RBoxErr<u32> {
-    let total_samples_interleaved: u32 = wavs.iter().map(|x| x.len).sum();
-    let real_sample_length = total_samples_interleaved / n_channels as u32;
-    let beats = real_sample_length as f32 / (DEFAULT_SAMPLE_RATE as f32 * 60.0 * 4.0);
-    let bars = (tempo_bpm * beats * 100.0).round();
-    Ok(bars as u32)
+    let audio_files = wavs
+        .iter()
+        .cloned()
+        .map(AudioFile::from)
+        .collect::<Vec<_>>();
+    get_bin_nbars_ileaved_audio_files(&audio_files, tempo_bpm)
+}
+
+/// Base64 encoded md5 hash of a file's contents.
+pub fn get_md5_hash_from_path(pathbuf: &Path) -> RBoxErr<String> {
+    debug!("Getting md5 hash: file={pathbuf:#?}");
+
+    let mut f: File = File::open(pathbuf)?;
+    let mut buf: Vec<u8> = vec![];
+    let _: usize = f.read_to_end(&mut buf)?;
+    let md5_hash: Digest = md5::compute(buf);
+
+    let mut buf = [0u8; 32];
+
+    // TODO: hard exit on error
+    /*
+    the trait bound `InvalidLengthError: StdError` is not satisfied
+
+    the following other types implement trait `FromResidual<R>`:
+    `Result<T, F>` implements `FromResidual<Result<Infallible, E>>`
+    `Result<T, F>` implements `FromResidual<Yeet<E>>`
+
+    required for `Box<dyn StdError>` to implement `From<InvalidLengthError>`
+    required for `Result<std::string::String, Box<dyn StdError>>` to implement `FromResidual<Result<Infallible, InvalidLengthError>>`rustc(Click for full compiler diagnostic)
+    */
+
+    let md5_hash_string = Base64::encode(&md5_hash[..], &mut buf).unwrap().to_string();
+
+    debug!("Got md5 hash: file={pathbuf:#?} md5={md5_hash_string:#?}");
+
+    Ok(md5_hash_string)
+}
+
+/// Get a path to `target` relative to the directory `base`, e.g. `../AUDIO/sample.wav` for a
+/// sample in the set audio pool relative to a project directory.
+///
+/// Both paths should be absolute (e.g. canonicalized), no file system access is done here.
+pub fn get_relative_path(base: &Path, target: &Path) -> PathBuf {
+    let base_components = base.components().collect::<Vec<_>>();
+    let target_components = target.components().collect::<Vec<_>>();
+    let n_common = base_components
+        .iter()
+        .zip(&target_components)
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in n_common..base_components.len() {
+        relative.push("..");
+    }
+    for component in &target_components[n_common..] {
+        relative.push(component);
+    }
+    relative
 }
 
 /// Each 'sample' can have two files present on an Octatrack:
@@ -93,78 +175,101 @@ pub struct ProjectSamples {
 #[cfg(test)]
 #[allow(unused_imports)]
 mod test {
+    mod relative_path {
+        use crate::utils::get_relative_path;
+        use std::path::{Path, PathBuf};
+
+        #[test]
+        fn set_audio_pool() {
+            let r = get_relative_path(
+                Path::new("/card/SET/PROJECT"),
+                Path::new("/card/SET/AUDIO/drums/kick.wav"),
+            );
+            assert_eq!(r, PathBuf::from("../AUDIO/drums/kick.wav"));
+        }
+
+        #[test]
+        fn project_dir() {
+            let r = get_relative_path(
+                Path::new("/card/SET/PROJECT"),
+                Path::new("/card/SET/PROJECT/kick.wav"),
+            );
+            assert_eq!(r, PathBuf::from("kick.wav"));
+        }
+    }
+
     mod nbars_from_wav {
 
-        use crate::audio::wav::WavFile;
-        use crate::utils::get_otsample_nbars_from_wavfile;
+        use crate::audio::{file::AudioFile, wav::WavFile};
+        use crate::utils::get_otsample_nbars_from_audio_file;
         use ot_tools_io::samples::slices::Slice;
         use std::path::PathBuf;
 
         #[test]
         fn simple_ok() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
 
-            assert!(get_otsample_nbars_from_wavfile(&wav, &120.0).is_ok())
+            assert!(get_otsample_nbars_from_audio_file(&wav, &120.0).is_ok())
         }
 
         #[test]
         fn simple_120bpm_valid() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
 
-            let nbarsx100 = get_otsample_nbars_from_wavfile(&wav, &120.0).unwrap();
+            let nbarsx100 = get_otsample_nbars_from_audio_file(&wav, &120.0).unwrap();
             assert_eq!(nbarsx100, 75)
         }
 
         #[test]
         fn simple_300bpm_valid() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
 
-            let nbarsx100 = get_otsample_nbars_from_wavfile(&wav, &300.0).unwrap();
+            let nbarsx100 = get_otsample_nbars_from_audio_file(&wav, &300.0).unwrap();
             assert_eq!(nbarsx100, 150)
         }
 
         #[test]
         fn simple_150bpm_valid() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
 
-            let nbarsx100 = get_otsample_nbars_from_wavfile(&wav, &150.0).unwrap();
+            let nbarsx100 = get_otsample_nbars_from_audio_file(&wav, &150.0).unwrap();
             assert_eq!(nbarsx100, 75)
         }
 
         #[test]
         fn simple_200bpm_valid() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
 
-            let nbarsx100 = get_otsample_nbars_from_wavfile(&wav, &200.0).unwrap();
+            let nbarsx100 = get_otsample_nbars_from_audio_file(&wav, &200.0).unwrap();
             assert_eq!(nbarsx100, 100)
         }
 
         #[test]
         fn simple_30bpm_valid() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
 
-            let nbarsx100 = get_otsample_nbars_from_wavfile(&wav, &30.0).unwrap();
+            let nbarsx100 = get_otsample_nbars_from_audio_file(&wav, &30.0).unwrap();
             assert_eq!(nbarsx100, 25)
         }
     }
 
     mod nbars_from_wavs {
 
-        use crate::audio::wav::WavFile;
-        use crate::utils::get_bin_nbars_ileaved_wavfiles;
+        use crate::audio::{file::AudioFile, wav::WavFile};
+        use crate::utils::get_bin_nbars_ileaved_audio_files;
         use ot_tools_io::samples::slices::Slice;
         use std::path::PathBuf;
 
         #[test]
         fn simple_ok() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
             let wavs = [
                 wav.clone(),
                 wav.clone(),
@@ -174,13 +279,13 @@ mod test {
             ]
             .to_vec();
 
-            assert!(get_bin_nbars_ileaved_wavfiles(&wavs, &120.0, 2).is_ok())
+            assert!(get_bin_nbars_ileaved_audio_files(&wavs, &120.0).is_ok())
         }
 
         #[test]
         fn simple_120bpm_valid() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
             let wavs = [
                 wav.clone(),
                 wav.clone(),
@@ -190,14 +295,14 @@ mod test {
             ]
             .to_vec();
 
-            let nbarsx100 = get_bin_nbars_ileaved_wavfiles(&wavs, &120.0, 2).unwrap();
-            assert_eq!(nbarsx100, 158)
+            let nbarsx100 = get_bin_nbars_ileaved_audio_files(&wavs, &120.0).unwrap();
+            assert_eq!(nbarsx100, 317)
         }
 
         #[test]
         fn simple_150bpm_valid() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
             let wavs = [
                 wav.clone(),
                 wav.clone(),
@@ -207,14 +312,14 @@ mod test {
             ]
             .to_vec();
 
-            let nbarsx100 = get_bin_nbars_ileaved_wavfiles(&wavs, &150.0, 2).unwrap();
-            assert_eq!(nbarsx100, 198)
+            let nbarsx100 = get_bin_nbars_ileaved_audio_files(&wavs, &150.0).unwrap();
+            assert_eq!(nbarsx100, 396)
         }
 
         #[test]
         fn simple_200bpm_valid() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
             let wavs = [
                 wav.clone(),
                 wav.clone(),
@@ -224,14 +329,14 @@ mod test {
             ]
             .to_vec();
 
-            let nbarsx100 = get_bin_nbars_ileaved_wavfiles(&wavs, &200.0, 2).unwrap();
-            assert_eq!(nbarsx100, 264)
+            let nbarsx100 = get_bin_nbars_ileaved_audio_files(&wavs, &200.0).unwrap();
+            assert_eq!(nbarsx100, 528)
         }
 
         #[test]
         fn simple_300bpm_valid() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
             let wavs = [
                 wav.clone(),
                 wav.clone(),
@@ -241,14 +346,14 @@ mod test {
             ]
             .to_vec();
 
-            let nbarsx100 = get_bin_nbars_ileaved_wavfiles(&wavs, &300.0, 2).unwrap();
-            assert_eq!(nbarsx100, 396)
+            let nbarsx100 = get_bin_nbars_ileaved_audio_files(&wavs, &300.0).unwrap();
+            assert_eq!(nbarsx100, 792)
         }
 
         #[test]
         fn simple_30bpm_valid() {
             let fp = PathBuf::from("../data/tests/misc/test.wav");
-            let wav = WavFile::from_path(&fp).unwrap();
+            let wav = AudioFile::from_path(&fp).unwrap();
             let wavs = [
                 wav.clone(),
                 wav.clone(),
@@ -258,8 +363,8 @@ mod test {
             ]
             .to_vec();
 
-            let nbarsx100 = get_bin_nbars_ileaved_wavfiles(&wavs, &30.0, 2).unwrap();
-            assert_eq!(nbarsx100, 40)
+            let nbarsx100 = get_bin_nbars_ileaved_audio_files(&wavs, &30.0).unwrap();
+            assert_eq!(nbarsx100, 79)
         }
     }
 
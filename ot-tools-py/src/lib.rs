@@ -236,9 +236,16 @@ pub fn copy_bank_yaml(yaml_conf_fpath: PathBuf) -> PyResult<()> {
 }
 
 #[pyfunction]
-pub fn project_samples_consolidate(project_dirpath: PathBuf) -> PyResult<()> {
-    consolidate_sample_slots_to_project_pool(&project_dirpath)
-        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+#[pyo3(signature = (project_dirpath, delete_unused_pool_files=false))]
+pub fn project_samples_consolidate(
+    project_dirpath: PathBuf,
+    delete_unused_pool_files: bool,
+) -> PyResult<()> {
+    consolidate_sample_slots_to_project_pool(
+        &project_dirpath.join("project.work"),
+        delete_unused_pool_files,
+    )
+    .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
     Ok(())
 }
 
@@ -284,21 +291,21 @@ pub fn split_sample_by_slices(
     ot_fp: PathBuf,
     out_dirpath: PathBuf,
 ) -> PyResult<()> {
-    deconstruct_samplechain_from_paths(&wav_fp, &ot_fp, &out_dirpath)
+    deconstruct_samplechain_from_paths(&wav_fp, &ot_fp, &out_dirpath, None)
         .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
     Ok(())
 }
 
 #[pyfunction]
 pub fn randomly_slice_sample(wav_fp: PathBuf, n_slices: usize) -> PyResult<()> {
-    create_randomly_sliced_sample(&wav_fp, n_slices)
+    create_randomly_sliced_sample(&wav_fp, n_slices, None)
         .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
     Ok(())
 }
 
 #[pyfunction]
 pub fn linearly_slice_sample(wav_fp: PathBuf, n_slices: usize) -> PyResult<()> {
-    create_equally_sliced_sample(&wav_fp, n_slices)
+    create_equally_sliced_sample(&wav_fp, n_slices, None)
         .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
     Ok(())
 }